@@ -0,0 +1,269 @@
+//! Checking anchors, and propagating them from components into composites.
+
+use std::collections::BTreeSet;
+
+use kurbo::Point;
+
+use crate::font::{Font, Glyph, Layer};
+
+/// Something wrong with the anchors of a glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnchorIssue {
+    /// The layer's anchors differ from those of the first master.
+    Inconsistent {
+        layer_id: String,
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// A mark component has none of the anchors it attaches with (`_top`
+    /// and so on) matched by an anchor of the components before it.
+    Unattached {
+        layer_id: String,
+        mark: String,
+        needs: Vec<String>,
+    },
+    /// The anchor lies outside the advance width.
+    OutsideBox {
+        layer_id: String,
+        anchor: String,
+        position: Point,
+    },
+}
+
+fn anchor_names(layer: &Layer) -> BTreeSet<String> {
+    layer
+        .anchors
+        .iter()
+        .flatten()
+        .map(|a| a.name.clone())
+        .collect()
+}
+
+fn master_layers<'a>(font: &'a Font, glyph: &'a Glyph) -> impl Iterator<Item = &'a Layer> {
+    font.font_master
+        .iter()
+        .filter_map(move |m| glyph.get_layer(&m.id))
+}
+
+fn check_glyph(font: &Font, glyph: &Glyph) -> Vec<AnchorIssue> {
+    let mut issues = Vec::new();
+    let mut layers = master_layers(font, glyph);
+    let first = match layers.next() {
+        Some(layer) => anchor_names(layer),
+        None => return issues,
+    };
+    for layer in layers {
+        let names = anchor_names(layer);
+        if names != first {
+            issues.push(AnchorIssue::Inconsistent {
+                layer_id: layer.layer_id.clone(),
+                missing: first.difference(&names).cloned().collect(),
+                extra: names.difference(&first).cloned().collect(),
+            });
+        }
+    }
+    for layer in master_layers(font, glyph) {
+        let components = layer.components.as_deref().unwrap_or(&[]);
+        for (i, component) in components.iter().enumerate().skip(1) {
+            let mark = match font
+                .get_glyph(&component.name)
+                .and_then(|g| g.get_layer(&layer.layer_id))
+            {
+                Some(mark) => mark,
+                None => continue,
+            };
+            let needs: Vec<String> = font
+                .anchors(mark)
+                .into_iter()
+                .filter_map(|a| a.name.strip_prefix('_').map(str::to_string))
+                .collect();
+            if needs.is_empty() {
+                continue;
+            }
+            // The anchors of the glyph as built up to this component.
+            let mut partial = layer.clone();
+            partial.components = Some(components[..i].to_vec());
+            partial.anchors = None;
+            let available = font.anchors(&partial);
+            if !needs.iter().any(|n| available.iter().any(|a| &a.name == n)) {
+                issues.push(AnchorIssue::Unattached {
+                    layer_id: layer.layer_id.clone(),
+                    mark: component.name.clone(),
+                    needs,
+                });
+            }
+        }
+        // Marks stack above and below the line, so only the horizontal
+        // position is checked. Nonspacing marks have no box to be in.
+        if layer.width == 0.0 {
+            continue;
+        }
+        for anchor in layer.anchors.iter().flatten() {
+            let p = anchor.position;
+            if p.x < 0.0 || p.x > layer.width {
+                issues.push(AnchorIssue::OutsideBox {
+                    layer_id: layer.layer_id.clone(),
+                    anchor: anchor.name.clone(),
+                    position: p,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Check the anchors of every glyph in the master layers: that the same
+/// anchors are present in all masters, that the marks of composites can
+/// attach to their bases, and that anchors lie within the advance width,
+/// which transforms like stretching can break, unless there is none.
+pub fn check_anchors(font: &Font) -> Vec<(String, Vec<AnchorIssue>)> {
+    let mut result = Vec::new();
+    for glyph in &font.glyphs {
+        let issues = check_glyph(font, glyph);
+        if !issues.is_empty() {
+            result.push((glyph.glyphname.clone(), issues));
+        }
+    }
+    result
+}
+
+/// Copy anchors from the components of composite glyphs into the
+/// composites, as Glyphs does on export, so that they take part in mark
+/// positioning. Anchors the composites already have are kept.
+///
+/// Returns the names of the glyphs that gained anchors.
+pub fn propagate_anchors(font: &mut Font) -> Vec<String> {
+    let mut updates = Vec::new();
+    for (i, glyph) in font.glyphs.iter().enumerate() {
+        for (j, layer) in glyph.layers.iter().enumerate() {
+            let is_composite = layer.components.as_ref().is_some_and(|c| !c.is_empty());
+            if !is_composite || font.get_master(&layer.layer_id).is_none() {
+                continue;
+            }
+            let anchors = font.anchors(layer);
+            if anchors.len() > layer.anchors.as_ref().map_or(0, |a| a.len()) {
+                updates.push((i, j, anchors));
+            }
+        }
+    }
+    let mut result = Vec::new();
+    for (i, j, anchors) in updates {
+        let glyph = &mut font.glyphs[i];
+        glyph.layers[j].anchors = Some(anchors);
+        if result.last() != Some(&glyph.glyphname) {
+            result.push(glyph.glyphname.clone());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Anchor;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn font(glyphs: &str) -> Font {
+        let source = format!(
+            "{{fontMaster = ({{id = m; weightValue = 400;}}, {{id = n; weightValue = 700;}});\nglyphs = ({});}}",
+            glyphs
+        );
+        Font::from_plist(Plist::parse(&source).unwrap())
+    }
+
+    const MARKS: &str = r#"
+{glyphname = a; layers = (
+{layerId = m; width = 500; anchors = ({name = top; position = "{250, 500}";});},
+{layerId = n; width = 500; anchors = ({name = top; position = "{260, 500}";});}
+);},
+{glyphname = acutecomb; layers = (
+{layerId = m; width = 0; anchors = ({name = _top; position = "{100, 450}";}, {name = top; position = "{100, 650}";});},
+{layerId = n; width = 0; anchors = ({name = _top; position = "{100, 450}";}, {name = top; position = "{100, 650}";});}
+);},
+{glyphname = dotbelowcomb; layers = (
+{layerId = m; width = 0; anchors = ({name = _bottom; position = "{50, 0}";});},
+{layerId = n; width = 0; anchors = ({name = _bottom; position = "{50, 0}";});}
+);}"#;
+
+    #[test]
+    fn reports_inconsistent_unattached_and_outside_anchors() {
+        let font = font(&format!(
+            r#"{},
+{{glyphname = b; layers = (
+{{layerId = m; width = 500; anchors = ({{name = top; position = "{{250, 700}}";}});}},
+{{layerId = n; width = 500; anchors = ({{name = ogonek; position = "{{-10, 0}}";}});}}
+);}},
+{{glyphname = adotbelow; layers = (
+{{layerId = m; width = 500; components = ({{name = a;}}, {{name = dotbelowcomb;}});}},
+{{layerId = n; width = 500; components = ({{name = a;}}, {{name = acutecomb;}});}}
+);}}"#,
+            MARKS
+        ));
+        let issues = check_anchors(&font);
+        assert_eq!(
+            issues,
+            [
+                (
+                    "b".to_string(),
+                    vec![
+                        AnchorIssue::Inconsistent {
+                            layer_id: "n".to_string(),
+                            missing: vec!["top".to_string()],
+                            extra: vec!["ogonek".to_string()],
+                        },
+                        AnchorIssue::OutsideBox {
+                            layer_id: "n".to_string(),
+                            anchor: "ogonek".to_string(),
+                            position: Point::new(-10.0, 0.0),
+                        }
+                    ]
+                ),
+                (
+                    "adotbelow".to_string(),
+                    vec![AnchorIssue::Unattached {
+                        layer_id: "m".to_string(),
+                        mark: "dotbelowcomb".to_string(),
+                        needs: vec!["bottom".to_string()],
+                    }]
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn composites_gain_the_anchors_of_their_components() {
+        let mut font = font(&format!(
+            r#"{},
+{{glyphname = aacute; layers = (
+{{layerId = m; width = 500; components = ({{name = a;}}, {{name = acutecomb; transform = "{{1, 0, 0, 1, 150, 50}}";}});}},
+{{layerId = n; width = 500; components = ({{name = a;}}, {{name = acutecomb; transform = "{{1, 0, 0, 1, 160, 50}}";}});
+anchors = ({{name = top; position = "{{300, 800}}";}});}}
+);}},
+{{glyphname = aa; layers = (
+{{layerId = m; width = 500; components = ({{name = a;}}); anchors = ({{name = top; position = "{{0, 0}}";}});}}
+);}}"#,
+            MARKS
+        ));
+        assert_eq!(propagate_anchors(&mut font), ["aacute"]);
+        let anchors = |id| {
+            let layer = font.get_glyph("aacute").unwrap().get_layer(id).unwrap();
+            let anchors: &[Anchor] = layer.anchors.as_ref().unwrap();
+            anchors
+                .iter()
+                .map(|a| (a.name.clone(), a.position))
+                .collect::<Vec<_>>()
+        };
+        // The top anchor moves up to the acute's, unless the composite has
+        // its own.
+        assert_eq!(
+            anchors("m"),
+            [("top".to_string(), Point::new(250.0, 700.0))]
+        );
+        assert_eq!(
+            anchors("n"),
+            [("top".to_string(), Point::new(300.0, 800.0))]
+        );
+        assert!(propagate_anchors(&mut font).is_empty());
+    }
+}
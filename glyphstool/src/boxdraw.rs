@@ -0,0 +1,737 @@
+//! Box drawing and block elements (U+2500–U+259F).
+//!
+//! Each code point is described by data: the four arms of a box drawing
+//! character, the count of dashes, the corner of an arc, and so on. Stroke
+//! widths are functions of the master's weight and width, so any master
+//! location works, and all positions come from the cell metrics.
+//!
+//! The drawing logic was originally inspired by the box drawing in Source
+//! Code Pro, but supports a wide range of widths and weights.
+
+use kurbo::{Affine, BezPath, Point, Rect};
+
+use crate::cell::CellMetrics;
+use crate::font::{Font, Path};
+use crate::interpolate::{master_location, weight_width};
+use crate::param::Value;
+use crate::region::Region;
+use crate::round::{round_path, RoundIssue};
+use crate::shade::{shade_paths, DotShape, ShadePattern};
+use crate::stroke::{stroke, StrokeStyle, KAPPA};
+use crate::symbols::clip_to_rect;
+
+/// A rectangle in eighths of the cell, as (x0, x1, y0, y1).
+pub type Eighths = (u8, u8, u8, u8);
+
+/// The weight of a box drawing line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoxType {
+    Empty,
+    Light,
+    Double,
+    Heavy,
+}
+
+/// How a code point in the block is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoxGlyph {
+    /// Lines from the center to the edges: up, left, right, down.
+    Arms([BoxType; 4]),
+    /// A line broken into `count` dashes.
+    Dashed {
+        vertical: bool,
+        heavy: bool,
+        count: usize,
+    },
+    /// A rounded corner joining the arms in the given directions.
+    Arc {
+        right: bool,
+        up: bool,
+        weight: BoxType,
+    },
+    /// Diagonals across the cell.
+    Diagonal { rising: bool, falling: bool },
+    /// Rectangles in a grid of eighths of the cell.
+    Blocks(&'static [Eighths]),
+    /// A shade pattern; 0 is light, 2 is dark.
+    Shade(usize),
+}
+
+/// Parameters of the drawing, as functions of weight and width.
+#[derive(Clone, Debug)]
+pub struct BoxStyle {
+    /// Thickness of light lines.
+    pub light: Value,
+    /// Thickness of heavy lines relative to light.
+    pub heavy_ratio: f64,
+    /// Distance of each line of a double line from the center, relative to
+    /// the advance.
+    pub double_spacing: f64,
+    /// Limit on the half-thickness of double lines, relative to their
+    /// spacing, so that narrow heavy masters keep a gap between them.
+    pub double_limit: f64,
+    /// The half-thickness of double lines, relative to their spacing, past
+    /// which the limit applies. Up to it, double lines are as thick as
+    /// light ones.
+    pub double_threshold: f64,
+    /// Patterns of the light, medium and dark shades.
+    pub shades: [ShadePattern; 3],
+    /// Diameter of braille dots.
+    pub dot: Value,
+    /// Vertical extent of the cell as (bottom, top), overriding the line
+    /// height of the masters.
+    pub vertical: Option<(f64, f64)>,
+    /// Overshoot of horizontal lines, relative to the advance.
+    pub overshoot: f64,
+}
+
+impl Default for BoxStyle {
+    /// The parameters used for Inconsolata.
+    fn default() -> BoxStyle {
+        let wght = |a, b, c| vec![[200.0, a], [400.0, b], [900.0, c]];
+        BoxStyle {
+            light: Value::product(
+                &wght(60.0, 120.0, 180.0),
+                &[[50.0, 0.6], [100.0, 1.0], [200.0, 1.1]],
+            ),
+            heavy_ratio: 2.0,
+            double_spacing: 0.24,
+            double_limit: 0.72,
+            double_threshold: 0.75,
+            shades: [
+                ShadePattern::new(DotShape::Square, Value::product(&wght(0.1, 0.15, 0.3), &[])),
+                ShadePattern::new(DotShape::Square, Value::product(&wght(0.3, 0.4, 0.5), &[])),
+                ShadePattern::new(DotShape::Square, Value::product(&wght(0.7, 0.8, 0.85), &[])),
+            ],
+            dot: Value::product(
+                &wght(70.0, 90.0, 130.0),
+                &[[50.0, 0.6], [100.0, 1.0], [200.0, 1.2]],
+            ),
+            vertical: Some((-400.0, 1000.0)),
+            overshoot: 0.16,
+        }
+    }
+}
+
+impl BoxStyle {
+    /// The cell of a master, with the style's adjustments applied.
+    pub fn cell(&self, font: &Font, master_id: &str) -> Option<CellMetrics> {
+        let mut cell = CellMetrics::from_master(font, master_id)?;
+        if let Some((bottom, top)) = self.vertical {
+            cell = CellMetrics::new(cell.advance, bottom, top);
+        }
+        cell.overshoot = self.overshoot * cell.advance;
+        Some(cell)
+    }
+}
+
+/// The arms of the box drawing characters, as up, left, right, down with
+/// `.` for none, `l` light, `h` heavy and `d` double.
+const ARMS: &[(u32, &str)] = &[
+    (0x2500, ".ll."),
+    (0x2501, ".hh."),
+    (0x2502, "l..l"),
+    (0x2503, "h..h"),
+    (0x250C, "..ll"),
+    (0x250D, "..hl"),
+    (0x250E, "..lh"),
+    (0x250F, "..hh"),
+    (0x2510, ".l.l"),
+    (0x2511, ".h.l"),
+    (0x2512, ".l.h"),
+    (0x2513, ".h.h"),
+    (0x2514, "l.l."),
+    (0x2515, "l.h."),
+    (0x2516, "h.l."),
+    (0x2517, "h.h."),
+    (0x2518, "ll.."),
+    (0x2519, "lh.."),
+    (0x251A, "hl.."),
+    (0x251B, "hh.."),
+    (0x251C, "l.ll"),
+    (0x251D, "l.hl"),
+    (0x251E, "h.ll"),
+    (0x251F, "l.lh"),
+    (0x2520, "h.lh"),
+    (0x2521, "h.hl"),
+    (0x2522, "l.hh"),
+    (0x2523, "h.hh"),
+    (0x2524, "ll.l"),
+    (0x2525, "lh.l"),
+    (0x2526, "hl.l"),
+    (0x2527, "ll.h"),
+    (0x2528, "hl.h"),
+    (0x2529, "hh.l"),
+    (0x252A, "lh.h"),
+    (0x252B, "hh.h"),
+    (0x252C, ".lll"),
+    (0x252D, ".hll"),
+    (0x252E, ".lhl"),
+    (0x252F, ".hhl"),
+    (0x2530, ".llh"),
+    (0x2531, ".hlh"),
+    (0x2532, ".lhh"),
+    (0x2533, ".hhh"),
+    (0x2534, "lll."),
+    (0x2535, "lhl."),
+    (0x2536, "llh."),
+    (0x2537, "lhh."),
+    (0x2538, "hll."),
+    (0x2539, "hhl."),
+    (0x253A, "hlh."),
+    (0x253B, "hhh."),
+    (0x253C, "llll"),
+    (0x253D, "lhll"),
+    (0x253E, "llhl"),
+    (0x253F, "lhhl"),
+    (0x2540, "hlll"),
+    (0x2541, "lllh"),
+    (0x2542, "hllh"),
+    (0x2543, "hhll"),
+    (0x2544, "hlhl"),
+    (0x2545, "lhlh"),
+    (0x2546, "llhh"),
+    (0x2547, "hhhl"),
+    (0x2548, "lhhh"),
+    (0x2549, "hhlh"),
+    (0x254A, "hlhh"),
+    (0x254B, "hhhh"),
+    (0x2550, ".dd."),
+    (0x2551, "d..d"),
+    (0x2552, "..dl"),
+    (0x2553, "..ld"),
+    (0x2554, "..dd"),
+    (0x2555, ".d.l"),
+    (0x2556, ".l.d"),
+    (0x2557, ".d.d"),
+    (0x2558, "l.d."),
+    (0x2559, "d.l."),
+    (0x255A, "d.d."),
+    (0x255B, "ld.."),
+    (0x255C, "dl.."),
+    (0x255D, "dd.."),
+    (0x255E, "l.dl"),
+    (0x255F, "d.ld"),
+    (0x2560, "d.dd"),
+    (0x2561, "ld.l"),
+    (0x2562, "dl.d"),
+    (0x2563, "dd.d"),
+    (0x2564, ".ddl"),
+    (0x2565, ".lld"),
+    (0x2566, ".ddd"),
+    (0x2567, "ldd."),
+    (0x2568, "dll."),
+    (0x2569, "ddd."),
+    (0x256A, "lddl"),
+    (0x256B, "dlld"),
+    (0x256C, "dddd"),
+    (0x2574, ".l.."),
+    (0x2575, "l..."),
+    (0x2576, "..l."),
+    (0x2577, "...l"),
+    (0x2578, ".h.."),
+    (0x2579, "h..."),
+    (0x257A, "..h."),
+    (0x257B, "...h"),
+    (0x257C, ".lh."),
+    (0x257D, "l..h"),
+    (0x257E, ".hl."),
+    (0x257F, "h..l"),
+];
+
+/// Block elements, as rectangles in eighths of the cell.
+const BLOCKS: &[(u32, &[Eighths])] = &[
+    (0x2580, &[(0, 8, 4, 8)]),
+    (0x2581, &[(0, 8, 0, 1)]),
+    (0x2582, &[(0, 8, 0, 2)]),
+    (0x2583, &[(0, 8, 0, 3)]),
+    (0x2584, &[(0, 8, 0, 4)]),
+    (0x2585, &[(0, 8, 0, 5)]),
+    (0x2586, &[(0, 8, 0, 6)]),
+    (0x2587, &[(0, 8, 0, 7)]),
+    (0x2588, &[(0, 8, 0, 8)]),
+    (0x2589, &[(0, 7, 0, 8)]),
+    (0x258A, &[(0, 6, 0, 8)]),
+    (0x258B, &[(0, 5, 0, 8)]),
+    (0x258C, &[(0, 4, 0, 8)]),
+    (0x258D, &[(0, 3, 0, 8)]),
+    (0x258E, &[(0, 2, 0, 8)]),
+    (0x258F, &[(0, 1, 0, 8)]),
+    (0x2590, &[(4, 8, 0, 8)]),
+    (0x2594, &[(0, 8, 7, 8)]),
+    (0x2595, &[(7, 8, 0, 8)]),
+    (0x2596, &[(0, 4, 0, 4)]),
+    (0x2597, &[(4, 8, 0, 4)]),
+    (0x2598, &[(0, 4, 4, 8)]),
+    (0x2599, &[(0, 4, 4, 8), (0, 4, 0, 4), (4, 8, 0, 4)]),
+    (0x259A, &[(0, 4, 4, 8), (4, 8, 0, 4)]),
+    (0x259B, &[(0, 4, 4, 8), (4, 8, 4, 8), (0, 4, 0, 4)]),
+    (0x259C, &[(0, 4, 4, 8), (4, 8, 4, 8), (4, 8, 0, 4)]),
+    (0x259D, &[(4, 8, 4, 8)]),
+    (0x259E, &[(4, 8, 4, 8), (0, 4, 0, 4)]),
+    (0x259F, &[(4, 8, 4, 8), (0, 4, 0, 4), (4, 8, 0, 4)]),
+];
+
+fn box_type(c: u8) -> BoxType {
+    match c {
+        b'l' => BoxType::Light,
+        b'h' => BoxType::Heavy,
+        b'd' => BoxType::Double,
+        _ => BoxType::Empty,
+    }
+}
+
+/// How to draw a code point, or `None` if it's not in the block.
+pub fn box_glyph(codepoint: u32) -> Option<BoxGlyph> {
+    if let Some(&(_, arms)) = ARMS.iter().find(|(cp, _)| *cp == codepoint) {
+        let a = arms.as_bytes();
+        let arms = [
+            box_type(a[0]),
+            box_type(a[1]),
+            box_type(a[2]),
+            box_type(a[3]),
+        ];
+        return Some(BoxGlyph::Arms(arms));
+    }
+    if let Some(&(_, rects)) = BLOCKS.iter().find(|(cp, _)| *cp == codepoint) {
+        return Some(BoxGlyph::Blocks(rects));
+    }
+    let dashed = |vertical, heavy, count| BoxGlyph::Dashed {
+        vertical,
+        heavy,
+        count,
+    };
+    let arc = |right, up| BoxGlyph::Arc {
+        right,
+        up,
+        weight: BoxType::Light,
+    };
+    let diagonal = |rising, falling| BoxGlyph::Diagonal { rising, falling };
+    let glyph = match codepoint {
+        0x2504 => dashed(false, false, 3),
+        0x2505 => dashed(false, true, 3),
+        0x2506 => dashed(true, false, 3),
+        0x2507 => dashed(true, true, 3),
+        0x2508 => dashed(false, false, 4),
+        0x2509 => dashed(false, true, 4),
+        0x250A => dashed(true, false, 4),
+        0x250B => dashed(true, true, 4),
+        0x254C => dashed(false, false, 2),
+        0x254D => dashed(false, true, 2),
+        0x254E => dashed(true, false, 2),
+        0x254F => dashed(true, true, 2),
+        0x256D => arc(true, false),
+        0x256E => arc(false, false),
+        0x256F => arc(false, true),
+        0x2570 => arc(true, true),
+        0x2571 => diagonal(true, false),
+        0x2572 => diagonal(false, true),
+        0x2573 => diagonal(true, true),
+        0x2591 => BoxGlyph::Shade(0),
+        0x2592 => BoxGlyph::Shade(1),
+        0x2593 => BoxGlyph::Shade(2),
+        _ => return None,
+    };
+    Some(glyph)
+}
+
+/// State for drawing one glyph at one master location.
+pub struct BoxDraw<'a> {
+    wght: f64,
+    wdth: f64,
+    cell: CellMetrics,
+    style: &'a BoxStyle,
+    region: Region,
+    issues: Vec<RoundIssue>,
+}
+
+impl<'a> BoxDraw<'a> {
+    pub fn new(wght: f64, wdth: f64, cell: CellMetrics, style: &'a BoxStyle) -> BoxDraw<'a> {
+        let region = Default::default();
+        BoxDraw {
+            wght,
+            wdth,
+            cell,
+            style,
+            region,
+            issues: Vec::new(),
+        }
+    }
+
+    // Line width of light line
+    fn light(&self) -> f64 {
+        self.style.light.eval(self.wght, self.wdth)
+    }
+
+    fn heavy(&self) -> f64 {
+        self.light() * self.style.heavy_ratio
+    }
+
+    /// Distance of each line of a double line from the center.
+    fn double_spacing(&self) -> f64 {
+        self.cell.advance * self.style.double_spacing
+    }
+
+    // Based on Source Code Pro box drawing logic
+    fn dashed_hline(&mut self, step: usize, thickness: f64) {
+        let step_length = self.cell.advance / (step as f64);
+        let gap = step_length / (step as f64);
+        let yc = self.cell.center;
+        let y0 = (yc - 0.5 * thickness).round();
+        let y1 = (yc + 0.5 * thickness).round();
+        for i in 0..step {
+            let x0 = i as f64 * step_length + gap / 2.0;
+            let x1 = x0 + step_length - gap;
+            self.rect(x0, y0, x1, y1);
+        }
+    }
+
+    fn dashed_vline(&mut self, step: usize, thickness: f64) {
+        let step_length = self.cell.height() / (step as f64);
+        let gap = step_length / (step as f64);
+        let xc = self.cell.advance * 0.5;
+        let x0 = (xc - 0.5 * thickness).round();
+        let x1 = (xc + 0.5 * thickness).round();
+        for i in 0..step {
+            let y0 = self.cell.bottom + i as f64 * step_length + gap / 2.0;
+            let y1 = y0 + step_length - gap;
+            self.rect(x0, y0, x1, y1);
+        }
+    }
+
+    /// A diagonal from one corner of a rectangle to the opposite one, cut
+    /// off by the rectangle's sides so that diagonals join up across cells.
+    fn diagonal(&mut self, start: Point, end: Point, width: f64) -> Path {
+        // Extend the centerline so that its ends are clipped away.
+        let extend = (end - start).normalize() * width;
+        let mut centerline = BezPath::new();
+        centerline.move_to(start - extend);
+        centerline.line_to(end + extend);
+        let stroked = stroke(&centerline, &StrokeStyle::new(width));
+        let rect = Rect::from_points(start, end);
+        let mut path = clip_to_rect(rect, &stroked[0]);
+        // Rounding around the center keeps the two sides parallel, once
+        // the noise that would tip halves one way or the other is gone.
+        let center = rect.center().round().to_vec2();
+        path.apply_affine(Affine::translate(-center));
+        for node in &mut path.nodes {
+            let snap = |v: f64| (v * 1e6).round() / 1e6;
+            node.pt = Point::new(snap(node.pt.x), snap(node.pt.y));
+        }
+        self.issues.extend(round_path(&mut path, 1.0, 0));
+        path.apply_affine(Affine::translate(center));
+        path
+    }
+
+    /// A rounded corner. The centerline is offset by `d` from the middle of
+    /// the cell, which is how the two lines of a double arc are made.
+    fn arc_centerline(&self, flip_x: bool, flip_y: bool, d: f64) -> BezPath {
+        let cell = &self.cell;
+        let r = (cell.advance * 0.5).round() + d;
+        // The vertical arm reaches the bottom, or the top once flipped.
+        let y0 = if flip_y {
+            2.0 * cell.center - cell.top
+        } else {
+            cell.bottom
+        };
+        let yc = cell.center + d;
+        let x0 = -cell.overshoot;
+        let xc = cell.advance * 0.5 + d;
+        let p = |x: f64, y: f64| {
+            let x = if flip_x { 2.0 * (xc - d) - x } else { x };
+            let y = if flip_y { 2.0 * (yc - d) - y } else { y };
+            Point::new(x, y)
+        };
+        let mut centerline = BezPath::new();
+        centerline.move_to(p(xc, y0));
+        centerline.line_to(p(xc, yc - r));
+        centerline.curve_to(
+            p(xc, yc - r + KAPPA * r),
+            p(xc - r + KAPPA * r, yc),
+            p(xc - r, yc),
+        );
+        centerline.line_to(p(x0, yc));
+        centerline
+    }
+
+    fn arc(&mut self, flip_x: bool, flip_y: bool, weight: BoxType) -> Vec<Path> {
+        let (width, offsets) = match weight {
+            BoxType::Empty => return Vec::new(),
+            BoxType::Light => (self.light(), vec![0.0]),
+            BoxType::Heavy => (self.heavy(), vec![0.0]),
+            BoxType::Double => {
+                let sp = self.double_spacing();
+                (self.double_half() * 2.0, vec![-sp, sp])
+            }
+        };
+        let style = StrokeStyle::new(2.0 * (width * 0.5).round());
+        let mut paths = Vec::new();
+        for d in offsets {
+            paths.extend(stroke(&self.arc_centerline(flip_x, flip_y, d), &style));
+        }
+        for (i, path) in paths.iter_mut().enumerate() {
+            self.issues.extend(round_path(path, 1.0, i));
+        }
+        paths
+    }
+
+    /// The problems rounding introduced in what has been drawn.
+    pub fn round_issues(&self) -> &[RoundIssue] {
+        &self.issues
+    }
+
+    fn quadrant(&mut self, x0: u8, x1: u8, y0: u8, y1: u8) {
+        let p0 = self.cell.pt(x0 as f64 / 8.0, y0 as f64 / 8.0);
+        let p1 = self.cell.pt(x1 as f64 / 8.0, y1 as f64 / 8.0);
+        self.rect(p0.x.round(), p0.y.round(), p1.x.round(), p1.y.round());
+    }
+
+    fn rect(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        self.region = self.region.add(Rect::new(x0, y0, x1, y1).round());
+    }
+
+    /// Half the thickness of each line of a double line.
+    fn double_half(&self) -> f64 {
+        let half = self.light() * 0.5;
+        if half > self.double_spacing() * self.style.double_threshold {
+            half.min(self.double_spacing() * self.style.double_limit)
+        } else {
+            half
+        }
+    }
+
+    /// The general function for drawing most boxes
+    fn bxd(&mut self, top: BoxType, left: BoxType, right: BoxType, bot: BoxType) {
+        use BoxType::*;
+        let light = self.light() * 0.5; // half-width!
+        let heavy = self.heavy() * 0.5;
+        let dbly = self.double_half();
+        let dblx = dbly;
+        let cell = self.cell;
+        let xc = cell.advance * 0.5;
+        let yc = cell.center;
+        let yb = cell.bottom;
+        let yt = cell.top;
+        let xl = -cell.overshoot;
+        let xr = cell.advance + cell.overshoot;
+        let dblspy = self.double_spacing();
+        let dblspx = dblspy;
+        // counterclockwise starting from this arm
+        let yoff = |a, b, c, d| match (a, b, c, d) {
+            (Double, Double, _, _) => dbly - dblspy,
+            (Double, Empty, Empty, Double) => dbly + dblspy,
+            (Light, Double, Empty, Empty) => dbly + dblspy,
+            (Light, Empty, Empty, Double) => dbly + dblspy,
+            (Light, Double, Empty, Double) => dbly - dblspy,
+            (Light, _, _, _) => light,
+            (Heavy, Heavy, _, _) => heavy,
+            (Heavy, _, _, Heavy) => heavy,
+            (Heavy, _, _, _) => light,
+            _ => 0.0,
+        };
+        let xoff = |a, b, c, d| match (a, b, c, d) {
+            (Double, Double, _, _) => dblx - dblspx,
+            (Double, Empty, Empty, Double) => dblx + dblspx,
+            (Light, Double, Empty, Empty) => dblx + dblspx,
+            (Light, Empty, Empty, Double) => dblx + dblspx,
+            (Light, Double, Empty, Double) => dblx - dblspx,
+            (Light, _, _, _) => light,
+            (Heavy, Heavy, _, _) => heavy,
+            (Heavy, _, _, Heavy) => heavy,
+            (Heavy, _, _, _) => light,
+            _ => 0.0,
+        };
+        match top {
+            Empty => (),
+            Light => {
+                let y = yc - yoff(top, left, bot, right);
+                self.rect(xc - light, y, xc + light, yt);
+            }
+            Heavy => {
+                let y = yc - yoff(top, left, bot, right);
+                self.rect(xc - heavy, y, xc + heavy, yt);
+            }
+            Double => {
+                let y = yc - yoff(top, left, bot, right);
+                self.rect(xc - dblx - dblspx, y, xc + dblx - dblspx, yt);
+                let y = yc - yoff(top, right, bot, left);
+                self.rect(xc - dblx + dblspx, y, xc + dblx + dblspx, yt);
+            }
+        }
+        match left {
+            Empty => (),
+            Light => {
+                let x = xc + xoff(left, bot, right, top);
+                self.rect(xl, yc - light, x, yc + light);
+            }
+            Heavy => {
+                let x = xc + xoff(left, bot, right, top);
+                self.rect(xl, yc - heavy, x, yc + heavy);
+            }
+            Double => {
+                let x = xc + xoff(left, bot, right, top);
+                self.rect(xl, yc - dbly - dblspy, x, yc + dbly - dblspy);
+                let x = xc + xoff(left, top, right, bot);
+                self.rect(xl, yc - dbly + dblspy, x, yc + dbly + dblspy);
+            }
+        }
+        match right {
+            Empty => (),
+            Light => {
+                let x = xc - xoff(right, top, left, bot);
+                self.rect(x, yc - light, xr, yc + light);
+            }
+            Heavy => {
+                let x = xc - xoff(right, top, left, bot);
+                self.rect(x, yc - heavy, xr, yc + heavy);
+            }
+            Double => {
+                let x = xc - xoff(right, top, left, bot);
+                self.rect(x, yc - dbly + dblspy, xr, yc + dbly + dblspy);
+                let x = xc - xoff(right, bot, left, top);
+                self.rect(x, yc - dbly - dblspy, xr, yc + dbly - dblspy);
+            }
+        }
+        match bot {
+            Empty => (),
+            Light => {
+                let y = yc + yoff(bot, right, top, left);
+                self.rect(xc - light, yb, xc + light, y);
+            }
+            Heavy => {
+                let y = yc + yoff(bot, right, top, left);
+                self.rect(xc - heavy, yb, xc + heavy, y);
+            }
+            Double => {
+                let y = yc + yoff(bot, right, top, left);
+                self.rect(xc - dblx + dblspx, yb, xc + dblx + dblspx, y);
+                let y = yc + yoff(bot, left, top, right);
+                self.rect(xc - dblx - dblspx, yb, xc + dblx - dblspx, y);
+            }
+        }
+    }
+
+    pub fn draw(&mut self, glyph: BoxGlyph) -> Vec<Path> {
+        match glyph {
+            BoxGlyph::Arms([top, left, right, bot]) => self.bxd(top, left, right, bot),
+            BoxGlyph::Dashed {
+                vertical,
+                heavy,
+                count,
+            } => {
+                let thickness = if heavy { self.heavy() } else { self.light() };
+                if vertical {
+                    self.dashed_vline(count, thickness);
+                } else {
+                    self.dashed_hline(count, thickness);
+                }
+            }
+            BoxGlyph::Arc { right, up, weight } => return self.arc(right, up, weight),
+            BoxGlyph::Diagonal { rising, falling } => {
+                // The diagonals stop a little short of the top and bottom.
+                let low = self.cell.pt(0.0, 1.0 / 14.0).y;
+                let high = self.cell.pt(0.0, 13.0 / 14.0).y;
+                let width = self.cell.advance;
+                let mut paths = Vec::new();
+                if rising {
+                    let start = Point::new(0.0, low);
+                    let end = Point::new(width, high);
+                    paths.push(self.diagonal(start, end, self.light()));
+                }
+                if falling {
+                    let start = Point::new(0.0, high);
+                    let end = Point::new(width, low);
+                    paths.push(self.diagonal(start, end, self.light()));
+                }
+                return paths;
+            }
+            BoxGlyph::Blocks(rects) => {
+                for &(x0, x1, y0, y1) in rects {
+                    self.quadrant(x0, x1, y0, y1);
+                }
+            }
+            BoxGlyph::Shade(level) => {
+                let pattern = &self.style.shades[level];
+                return shade_paths(pattern, self.wght, self.wdth, &self.cell);
+            }
+        }
+        self.region.to_paths()
+    }
+}
+
+/// Draw all the glyphs in the font whose code points are in the box drawing
+/// and block elements block, in every master. Returns the names of the
+/// glyphs drawn, each with the problems rounding introduced, by layer.
+pub fn draw_box_glyphs(
+    font: &mut Font,
+    style: &BoxStyle,
+) -> Vec<(String, Vec<(String, RoundIssue)>)> {
+    let masters: Vec<(String, (f64, f64), CellMetrics)> = font
+        .font_master
+        .iter()
+        .filter_map(|m| {
+            let location = weight_width(&master_location(font, &m.id)?);
+            Some((m.id.clone(), location, style.cell(font, &m.id)?))
+        })
+        .collect();
+    let mut result = Vec::new();
+    for glyph in &mut font.glyphs {
+        let box_glyph = match glyph.unicodes().into_iter().find_map(box_glyph) {
+            Some(box_glyph) => box_glyph,
+            None => continue,
+        };
+        let mut issues = Vec::new();
+        for (layer_id, (wght, wdth), cell) in &masters {
+            if let Some(layer) = glyph.get_layer_mut(layer_id) {
+                let mut draw = BoxDraw::new(*wght, *wdth, *cell, style);
+                layer.paths = Some(draw.draw(box_glyph));
+                let layer_issues = draw.round_issues().iter().cloned();
+                issues.extend(layer_issues.map(|issue| (layer_id.clone(), issue)));
+            }
+        }
+        result.push((glyph.glyphname.clone(), issues));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn double_half(wght: f64, wdth: f64, advance: f64) -> f64 {
+        let style = BoxStyle::default();
+        let cell = CellMetrics::new(advance, -400.0, 1000.0);
+        BoxDraw::new(wght, wdth, cell, &style).double_half()
+    }
+
+    #[test]
+    fn double_lines_thin_only_in_narrow_heavy_masters() {
+        // As Inconsolata had it: thinned by a fifth at UltraCondensed Black.
+        assert!((double_half(900.0, 50.0, 250.0) - 43.2).abs() < 1e-9);
+        assert!((double_half(900.0, 100.0, 500.0) - 90.0).abs() < 1e-9);
+        assert!((double_half(400.0, 50.0, 250.0) - 36.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn diagonals_are_strokes_cut_by_the_cell() {
+        let style = BoxStyle::default();
+        let cell = CellMetrics::new(500.0, -300.0, 900.0);
+        let mut draw = BoxDraw::new(400.0, 100.0, cell, &style);
+        let (start, end) = (Point::new(0.0, -300.0), Point::new(500.0, 900.0));
+        let path = draw.diagonal(start, end, 90.0);
+        let points: Vec<Point> = path.nodes.iter().map(|n| n.pt).collect();
+        assert_eq!(points.len(), 6);
+        assert!(points.contains(&start) && points.contains(&end));
+        // The same width at the bottom and the top, where it is cut.
+        let width = |y: f64| {
+            let xs = points.iter().filter(|p| p.y == y).map(|p| p.x);
+            xs.clone().fold(f64::NEG_INFINITY, f64::max) - xs.fold(f64::INFINITY, f64::min)
+        };
+        assert_eq!(width(-300.0), width(900.0));
+        // The centerline ends at the corners, so half the stroke is left,
+        // measured along the horizontal.
+        let expected = 45.0 * (end - start).hypot() / 1200.0;
+        assert!((width(-300.0) - expected).abs() <= 1.0);
+        assert!(draw.round_issues().is_empty());
+    }
+}
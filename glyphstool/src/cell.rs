@@ -0,0 +1,74 @@
+//! The character cell of a monospace font, which box drawing and other
+//! cell-filling symbols are drawn to.
+
+use std::collections::HashMap;
+
+use kurbo::{Point, Rect};
+
+use crate::font::Font;
+
+/// The metrics of the character cell of one master.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellMetrics {
+    pub advance: f64,
+    pub bottom: f64,
+    pub top: f64,
+    /// The line through which horizontal box drawing lines run.
+    pub center: f64,
+    /// How far horizontal lines extend past the sides of the cell, so that
+    /// they overlap with their neighbors.
+    pub overshoot: f64,
+}
+
+impl CellMetrics {
+    /// A cell with the center line halfway up and no overshoot.
+    pub fn new(advance: f64, bottom: f64, top: f64) -> CellMetrics {
+        CellMetrics {
+            advance,
+            bottom,
+            top,
+            center: 0.5 * (bottom + top),
+            overshoot: 0.0,
+        }
+    }
+
+    /// The cell of a master: the line from the hhea (or typo) ascender and
+    /// descender, falling back to the master's ascender and descender, and
+    /// the most common advance width among the glyphs.
+    pub fn from_master(font: &Font, master_id: &str) -> Option<CellMetrics> {
+        let master = font.get_master(master_id)?;
+        let metric = |params: &[&str], key: &str| {
+            params
+                .iter()
+                .find_map(|name| master.custom_parameter(name))
+                .or_else(|| master.other_stuff.get(key))
+                .and_then(|v| v.as_f64())
+        };
+        let top = metric(&["hheaAscender", "typoAscender"], "ascender")?;
+        let bottom = metric(&["hheaDescender", "typoDescender"], "descender")?;
+        let mut counts: HashMap<i64, usize> = HashMap::new();
+        for glyph in &font.glyphs {
+            if let Some(layer) = glyph.get_layer(master_id) {
+                *counts.entry(layer.width.round() as i64).or_default() += 1;
+            }
+        }
+        let (advance, _) = counts
+            .into_iter()
+            .filter(|&(width, _)| width > 0)
+            .max_by_key(|&(width, count)| (count, width))?;
+        Some(CellMetrics::new(advance as f64, bottom, top))
+    }
+
+    pub fn height(&self) -> f64 {
+        self.top - self.bottom
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(0.0, self.bottom, self.advance, self.top)
+    }
+
+    /// A point given as fractions of the width and height of the cell.
+    pub fn pt(&self, x: f64, y: f64) -> Point {
+        Point::new(x * self.advance, self.bottom + y * self.height())
+    }
+}
@@ -0,0 +1,327 @@
+//! PostScript outlines: the `CFF ` table of an OpenType font.
+//!
+//! Glyphs are written as unhinted Type 2 charstrings, without
+//! subroutines. Glyph names all go in the string index, rather than
+//! referring to the standard strings.
+
+use kurbo::{PathEl, Point};
+
+use crate::font::Path;
+use crate::sfnt::Writer;
+
+/// The first string ID after the standard strings.
+const FIRST_CUSTOM_SID: usize = 391;
+
+/// Font-wide values for the CFF table.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CffInfo {
+    pub postscript_name: String,
+    pub full_name: String,
+    pub family_name: String,
+    pub weight: String,
+    pub copyright: String,
+    pub version: String,
+    pub italic_angle: f64,
+    pub underline_position: f64,
+    pub underline_thickness: f64,
+    pub fixed_pitch: bool,
+    pub bbox: [i16; 4],
+    pub blue_values: Vec<f64>,
+    pub other_blues: Vec<f64>,
+    pub std_hw: Option<f64>,
+    pub std_vw: Option<f64>,
+}
+
+/// A number in a charstring.
+fn cs_number(w: &mut Writer, v: i32) {
+    match v {
+        -107..=107 => {
+            w.u8((v + 139) as u8);
+        }
+        108..=1131 => {
+            let v = v - 108;
+            w.u8((v / 256 + 247) as u8).u8((v % 256) as u8);
+        }
+        -1131..=-108 => {
+            let v = -v - 108;
+            w.u8((v / 256 + 251) as u8).u8((v % 256) as u8);
+        }
+        _ => {
+            w.u8(28).i16(v as i16);
+        }
+    }
+}
+
+/// A number in a DICT.
+fn dict_int(w: &mut Writer, v: i32) {
+    match v {
+        -107..=107 => {
+            w.u8((v + 139) as u8);
+        }
+        108..=1131 => {
+            let v = v - 108;
+            w.u8((v / 256 + 247) as u8).u8((v % 256) as u8);
+        }
+        -1131..=-108 => {
+            let v = -v - 108;
+            w.u8((v / 256 + 251) as u8).u8((v % 256) as u8);
+        }
+        -32768..=32767 => {
+            w.u8(28).i16(v as i16);
+        }
+        _ => {
+            w.u8(29).i32(v);
+        }
+    }
+}
+
+/// A number in a DICT, as an integer if it is one, or else as a real in
+/// packed decimal.
+fn dict_number(w: &mut Writer, v: f64) {
+    if v.fract() == 0.0 && v.abs() < 1e9 {
+        dict_int(w, v as i32);
+        return;
+    }
+    let s = format!("{}", (v * 1e6).round() / 1e6);
+    let mut nibbles: Vec<u8> = s
+        .chars()
+        .map(|c| match c {
+            '0'..='9' => c as u8 - b'0',
+            '.' => 0xa,
+            '-' => 0xe,
+            _ => 0xf,
+        })
+        .collect();
+    nibbles.push(0xf);
+    if nibbles.len() % 2 == 1 {
+        nibbles.push(0xf);
+    }
+    w.u8(30);
+    for pair in nibbles.chunks(2) {
+        w.u8(pair[0] << 4 | pair[1]);
+    }
+}
+
+fn dict_op(w: &mut Writer, op: u16) {
+    if op >= 1200 {
+        w.u8(12).u8((op - 1200) as u8);
+    } else {
+        w.u8(op as u8);
+    }
+}
+
+/// An INDEX structure holding the given items.
+fn index(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.u16(items.len() as u16);
+    if items.is_empty() {
+        return w.data;
+    }
+    let total: usize = items.iter().map(Vec::len).sum::<usize>() + 1;
+    let off_size = match total {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x1_0000..=0xff_ffff => 3,
+        _ => 4,
+    };
+    w.u8(off_size);
+    let mut offset = 1;
+    let write_offset = |w: &mut Writer, offset: usize| {
+        let bytes = (offset as u32).to_be_bytes();
+        w.bytes(&bytes[4 - off_size as usize..]);
+    };
+    write_offset(&mut w, offset);
+    for item in items {
+        offset += item.len();
+        write_offset(&mut w, offset);
+    }
+    for item in items {
+        w.bytes(item);
+    }
+    w.data
+}
+
+/// Write a point relative to the current point, both rounded, and make it
+/// current.
+fn delta(w: &mut Writer, current: &mut Point, p: Point) {
+    let p = Point::new(p.x.round(), p.y.round());
+    cs_number(w, (p.x - current.x) as i32);
+    cs_number(w, (p.y - current.y) as i32);
+    *current = p;
+}
+
+/// The charstring of a glyph. The coordinates are rounded; the width is
+/// only written when it differs from the default.
+pub(crate) fn charstring(paths: &[Path], width: f64, default_width: f64) -> Vec<u8> {
+    let mut w = Writer::new();
+    let width = width.round();
+    if width != default_width {
+        cs_number(&mut w, (width - default_width) as i32);
+    }
+    let mut current = Point::ORIGIN;
+    for path in paths.iter().filter(|p| p.closed) {
+        let mut start = Point::ORIGIN;
+        for el in path.to_bez_path().elements() {
+            match *el {
+                PathEl::MoveTo(p) => {
+                    delta(&mut w, &mut current, p);
+                    start = p;
+                    w.u8(21);
+                }
+                PathEl::LineTo(p) => {
+                    delta(&mut w, &mut current, p);
+                    start = p;
+                    w.u8(5);
+                }
+                PathEl::QuadTo(p1, p) => {
+                    // Raise to a cubic.
+                    let c1 = start + (p1 - start) * (2.0 / 3.0);
+                    delta(&mut w, &mut current, c1);
+                    delta(&mut w, &mut current, p + (p1 - p) * (2.0 / 3.0));
+                    delta(&mut w, &mut current, p);
+                    start = p;
+                    w.u8(8);
+                }
+                PathEl::CurveTo(p1, p2, p) => {
+                    delta(&mut w, &mut current, p1);
+                    delta(&mut w, &mut current, p2);
+                    delta(&mut w, &mut current, p);
+                    start = p;
+                    w.u8(8);
+                }
+                PathEl::ClosePath => (),
+            }
+        }
+    }
+    w.u8(14);
+    w.data
+}
+
+/// Blue zones as deltas from the previous value, as DICT arrays are.
+fn deltas(values: &[f64]) -> Vec<f64> {
+    let mut prev = 0.0;
+    values
+        .iter()
+        .map(|&v| {
+            let d = v - prev;
+            prev = v;
+            d
+        })
+        .collect()
+}
+
+fn private_dict(info: &CffInfo, width: f64) -> Vec<u8> {
+    let mut w = Writer::new();
+    for &(op, values) in &[(6, &info.blue_values), (7, &info.other_blues)] {
+        if !values.is_empty() {
+            for d in deltas(values) {
+                dict_number(&mut w, d);
+            }
+            dict_op(&mut w, op);
+        }
+    }
+    if let Some(hw) = info.std_hw {
+        dict_number(&mut w, hw);
+        dict_op(&mut w, 10);
+    }
+    if let Some(vw) = info.std_vw {
+        dict_number(&mut w, vw);
+        dict_op(&mut w, 11);
+    }
+    // defaultWidthX and nominalWidthX.
+    dict_number(&mut w, width);
+    dict_op(&mut w, 20);
+    dict_number(&mut w, width);
+    dict_op(&mut w, 21);
+    w.data
+}
+
+/// Build the CFF table from glyph names and charstrings. The first glyph
+/// must be `.notdef`.
+pub(crate) fn build_cff(
+    info: &CffInfo,
+    names: &[String],
+    charstrings: Vec<Vec<u8>>,
+    default_width: f64,
+) -> Vec<u8> {
+    let mut strings: Vec<Vec<u8>> = Vec::new();
+    let mut sid = |s: &str| {
+        strings.push(s.as_bytes().to_vec());
+        (FIRST_CUSTOM_SID + strings.len() - 1) as i32
+    };
+
+    let mut top = Writer::new();
+    for &(op, value) in &[
+        (0, &info.version),
+        (1200, &info.copyright),
+        (2, &info.full_name),
+        (3, &info.family_name),
+        (4, &info.weight),
+    ] {
+        if !value.is_empty() {
+            dict_int(&mut top, sid(value));
+            dict_op(&mut top, op);
+        }
+    }
+    if info.fixed_pitch {
+        dict_int(&mut top, 1);
+        dict_op(&mut top, 1201);
+    }
+    if info.italic_angle != 0.0 {
+        dict_number(&mut top, info.italic_angle);
+        dict_op(&mut top, 1202);
+    }
+    dict_number(&mut top, info.underline_position);
+    dict_op(&mut top, 1203);
+    dict_number(&mut top, info.underline_thickness);
+    dict_op(&mut top, 1204);
+    for &v in &info.bbox {
+        dict_int(&mut top, v as i32);
+    }
+    dict_op(&mut top, 5);
+    let charset_sids: Vec<i32> = names.iter().skip(1).map(|n| sid(n)).collect();
+
+    let private = private_dict(info, default_width);
+    // The offsets are written as five-byte integers, so the size of the top
+    // DICT is known before they are.
+    let fixed_int = |w: &mut Writer, v: usize| {
+        w.u8(29).i32(v as i32);
+    };
+    let top_len = top.len() + 4 * 5 + 3;
+
+    let name_index = index(&[info.postscript_name.as_bytes().to_vec()]);
+    let string_index = index(&strings);
+    let global_subrs = index(&[]);
+    let header_len = 4;
+    let top_index_len = index(&[vec![0; top_len]]).len();
+    let charset_offset =
+        header_len + name_index.len() + top_index_len + string_index.len() + global_subrs.len();
+    let mut charset = Writer::new();
+    charset.u8(0);
+    for sid in &charset_sids {
+        charset.u16(*sid as u16);
+    }
+    let charstrings_offset = charset_offset + charset.len();
+    let charstrings_index = index(&charstrings);
+    let private_offset = charstrings_offset + charstrings_index.len();
+
+    fixed_int(&mut top, charset_offset);
+    dict_op(&mut top, 15);
+    fixed_int(&mut top, charstrings_offset);
+    dict_op(&mut top, 17);
+    fixed_int(&mut top, private.len());
+    fixed_int(&mut top, private_offset);
+    dict_op(&mut top, 18);
+    debug_assert_eq!(top.len(), top_len);
+
+    let mut w = Writer::new();
+    w.u8(1).u8(0).u8(header_len as u8).u8(4);
+    w.bytes(&name_index);
+    w.bytes(&index(&[top.data]));
+    w.bytes(&string_index);
+    w.bytes(&global_subrs);
+    w.bytes(&charset.data);
+    w.bytes(&charstrings_index);
+    w.bytes(&private);
+    w.data
+}
@@ -0,0 +1,192 @@
+//! Coverage of a character set, from a namelist.
+//!
+//! A code point is missing when no glyph has it. If there is a glyph named
+//! for it, like `uni0122`, it's reported as unencoded instead. The glyphs
+//! that are there are checked in each master for layers with no paths or
+//! components, which is expected only of spaces and other invisible
+//! characters.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::font::{Font, Glyph};
+use crate::nam::NameList;
+use crate::symbols::glyph_name;
+
+/// How a font covers a namelist.
+#[derive(Clone, Debug, Default)]
+pub struct CharsetCoverage {
+    /// What the namelist is called in reports, usually its file name.
+    pub name: String,
+    /// The number of code points in the namelist.
+    pub codepoints: usize,
+    /// The number of unencoded glyph names in the namelist.
+    pub glyph_names: usize,
+    /// Code points no glyph has.
+    pub missing: Vec<u32>,
+    /// Code points no glyph has, with the glyph named for them.
+    pub unencoded: Vec<(u32, String)>,
+    /// Glyph names in the namelist that the font doesn't have.
+    pub missing_glyphs: Vec<String>,
+    /// For each master, by name, the glyphs of the namelist whose layer is
+    /// empty or absent.
+    pub empty: Vec<(String, Vec<String>)>,
+}
+
+impl CharsetCoverage {
+    /// Whether every code point and glyph is there, and drawn in every
+    /// master.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+            && self.unencoded.is_empty()
+            && self.missing_glyphs.is_empty()
+            && self.empty.iter().all(|(_, glyphs)| glyphs.is_empty())
+    }
+}
+
+/// The namelists "coverage" requires by default: Nigel's list and the
+/// Google Fonts Latin Core and Plus sets.
+pub const DEFAULT_NAMELISTS: &[&str] =
+    &["nigels-list.nam", "GF_Latin_Core.nam", "GF_Latin_Plus.nam"];
+
+/// The default namelists in the `charset` directory next to the font, split
+/// into those that are there and those that aren't.
+pub fn default_namelists(font: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let dir = font
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join("charset");
+    DEFAULT_NAMELISTS
+        .iter()
+        .map(|n| dir.join(n))
+        .partition(|path| path.exists())
+}
+
+/// Characters that are drawn as nothing: spaces, controls, and format
+/// characters like joiners and marks of direction.
+fn is_blank(cp: u32) -> bool {
+    let format = [0x034F, 0x061C, 0x180E, 0xFEFF];
+    match std::char::from_u32(cp) {
+        Some(c) if c.is_whitespace() || c.is_control() => true,
+        _ => {
+            format.contains(&cp)
+                || (0x200B..=0x200F).contains(&cp)
+                || (0x202A..=0x202E).contains(&cp)
+                || (0x2060..=0x206F).contains(&cp)
+        }
+    }
+}
+
+/// Check the coverage of a namelist by a font.
+pub fn charset_coverage(font: &Font, name: &str, list: &NameList) -> CharsetCoverage {
+    let mut coverage = CharsetCoverage {
+        name: name.to_string(),
+        codepoints: list.codepoints.len(),
+        glyph_names: list.glyph_names.len(),
+        ..Default::default()
+    };
+    // The glyphs to check for drawings, and whether they may be blank.
+    let mut present: Vec<(&Glyph, bool)> = Vec::new();
+    for &cp in &list.codepoints {
+        if let Some(glyph) = font.glyphs.iter().find(|g| g.unicodes().contains(&cp)) {
+            present.push((glyph, is_blank(cp)));
+        } else if let Some(glyph) = font.get_glyph(&glyph_name(cp)) {
+            coverage.unencoded.push((cp, glyph.glyphname.clone()));
+        } else {
+            coverage.missing.push(cp);
+        }
+    }
+    for glyphname in &list.glyph_names {
+        match font.get_glyph(glyphname) {
+            Some(glyph) => present.push((glyph, false)),
+            None => coverage.missing_glyphs.push(glyphname.clone()),
+        }
+    }
+    for master in &font.font_master {
+        let mut empty = Vec::new();
+        for (glyph, blank) in &present {
+            let drawn = glyph.get_layer(&master.id).is_some_and(|layer| {
+                layer.paths.iter().flatten().next().is_some()
+                    || layer.components.iter().flatten().next().is_some()
+            });
+            if !drawn && !blank && !empty.contains(&glyph.glyphname) {
+                empty.push(glyph.glyphname.clone());
+            }
+        }
+        coverage.empty.push((master.name().to_string(), empty));
+    }
+    coverage
+}
+
+/// Coverage reports as a JSON array, with code points as `U+XXXX`.
+pub fn coverage_json(reports: &[CharsetCoverage]) -> String {
+    let codepoint = |cp: &u32| json_string(&format!("U+{:04X}", cp));
+    let list = |items: Vec<String>| format!("[{}]", items.join(", "));
+    let mut s = String::from("[\n");
+    for (i, report) in reports.iter().enumerate() {
+        let unencoded = report.unencoded.iter().map(|(cp, glyph)| {
+            format!(
+                "{{\"codepoint\": {}, \"glyph\": {}}}",
+                codepoint(cp),
+                json_string(glyph)
+            )
+        });
+        let empty = report.empty.iter().map(|(master, glyphs)| {
+            let glyphs = glyphs.iter().map(|g| json_string(g)).collect();
+            format!("{}: {}", json_string(master), list(glyphs))
+        });
+        let empty: Vec<String> = empty.collect();
+        writeln!(s, "  {{").unwrap();
+        writeln!(s, "    \"namelist\": {},", json_string(&report.name)).unwrap();
+        writeln!(s, "    \"codepoints\": {},", report.codepoints).unwrap();
+        writeln!(s, "    \"glyph_names\": {},", report.glyph_names).unwrap();
+        writeln!(s, "    \"complete\": {},", report.is_complete()).unwrap();
+        let missing = report.missing.iter().map(codepoint).collect();
+        writeln!(s, "    \"missing\": {},", list(missing)).unwrap();
+        writeln!(s, "    \"unencoded\": {},", list(unencoded.collect())).unwrap();
+        let missing_glyphs = report.missing_glyphs.iter().map(|g| json_string(g));
+        writeln!(
+            s,
+            "    \"missing_glyphs\": {},",
+            list(missing_glyphs.collect())
+        )
+        .unwrap();
+        writeln!(s, "    \"empty\": {{{}}}", empty.join(", ")).unwrap();
+        let comma = if i + 1 < reports.len() { "," } else { "" };
+        writeln!(s, "  }}{}", comma).unwrap();
+    }
+    s.push(']');
+    s
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(result, "\\u{:04x}", c as u32).unwrap(),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn default_namelists_that_are_missing_are_set_apart() {
+        let dir = std::env::temp_dir().join(format!("charset-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("charset")).unwrap();
+        fs::write(dir.join("charset").join("nigels-list.nam"), "0x0041\n").unwrap();
+        let (found, missing) = default_namelists(&dir.join("Font.glyphs"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(found, [dir.join("charset").join("nigels-list.nam")]);
+        let missing: Vec<_> = missing.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(missing, ["GF_Latin_Core.nam", "GF_Latin_Plus.nam"]);
+    }
+}
@@ -0,0 +1,359 @@
+//! Outline cleanup: adding points at extrema and removing redundant nodes.
+
+use kurbo::{CubicBez, ParamCurve, ParamCurveDeriv, Point};
+
+use crate::font::{cubic_extrema, Font, Layer, Node, NodeType, Path};
+
+/// Extrema that overshoot the segment's end points by less than this (in the
+/// direction of the extremum) are considered already covered.
+const EXTREMA_TOLERANCE: f64 = 1.0;
+
+fn is_on_curve(node: &Node) -> bool {
+    node.node_type != NodeType::OffCurve
+}
+
+/// Indices of the on-curve nodes of a path, or `None` if there are none.
+fn on_curve_ixs(path: &Path) -> Option<Vec<usize>> {
+    let ixs: Vec<usize> = (0..path.nodes.len())
+        .filter(|&i| is_on_curve(&path.nodes[i]))
+        .collect();
+    if ixs.is_empty() {
+        None
+    } else {
+        Some(ixs)
+    }
+}
+
+/// Indices of the off-curve nodes between two on-curve nodes.
+fn offs_between(n: usize, prev: usize, i: usize) -> Vec<usize> {
+    let mut d = (i + n - prev) % n;
+    if d == 0 {
+        d = n;
+    }
+    (1..d).map(|d| (prev + d) % n).collect()
+}
+
+/// Whether the extremum of the cubic at `t` overshoots the segment's end
+/// points, in the direction of the extremum, by at least the tolerance.
+fn overshoots(c: &CubicBez, t: f64) -> bool {
+    let (p, d) = (c.eval(t), c.deriv().eval(t));
+    let (v, v0, v3) = if d.x.abs() <= d.y.abs() {
+        (p.x, c.p0.x, c.p3.x)
+    } else {
+        (p.y, c.p0.y, c.p3.y)
+    };
+    (v - v0).abs().min((v - v3).abs()) >= EXTREMA_TOLERANCE
+}
+
+/// Rotate a closed path so that it ends with an on-curve node, as Glyphs
+/// writes them.
+fn normalize_start(path: &mut Path) {
+    if path.closed {
+        let n = path.nodes.len();
+        if let Some(ix) = path.nodes.iter().rposition(is_on_curve) {
+            path.nodes.rotate_left((ix + 1) % n);
+        }
+    }
+}
+
+impl Path {
+    /// Insert on-curve nodes at the horizontal and vertical extrema of cubic
+    /// segments, without changing the shape.
+    ///
+    /// The new nodes are not rounded.
+    pub fn add_extremes(&mut self) {
+        let ons = match on_curve_ixs(self) {
+            Some(ons) => ons,
+            None => return,
+        };
+        let n = self.nodes.len();
+        let mut nodes = Vec::new();
+        if !self.closed {
+            nodes.push(self.nodes[0].clone());
+        }
+        for (k, &i) in ons.iter().enumerate() {
+            if k == 0 && !self.closed {
+                continue;
+            }
+            let prev = if k == 0 {
+                ons[ons.len() - 1]
+            } else {
+                ons[k - 1]
+            };
+            let offs = offs_between(n, prev, i);
+            if offs.len() == 2 {
+                let c = CubicBez::new(
+                    self.nodes[prev].pt,
+                    self.nodes[offs[0]].pt,
+                    self.nodes[offs[1]].pt,
+                    self.nodes[i].pt,
+                );
+                let mut ts = cubic_extrema(&c);
+                ts.retain(|&t| overshoots(&c, t));
+                ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                ts.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+                let mut t0 = 0.0;
+                for &t in ts.iter().chain(std::iter::once(&1.0)) {
+                    let piece = c.subsegment(t0..t);
+                    nodes.push(Node {
+                        pt: piece.p1,
+                        node_type: NodeType::OffCurve,
+                    });
+                    nodes.push(Node {
+                        pt: piece.p2,
+                        node_type: NodeType::OffCurve,
+                    });
+                    if t < 1.0 {
+                        nodes.push(Node {
+                            pt: piece.p3,
+                            node_type: NodeType::CurveSmooth,
+                        });
+                    }
+                    t0 = t;
+                }
+            } else {
+                nodes.extend(offs.iter().map(|&j| self.nodes[j].clone()));
+            }
+            nodes.push(self.nodes[i].clone());
+        }
+        self.nodes = nodes;
+        normalize_start(self);
+    }
+
+    /// Remove duplicated consecutive points, zero-length segments and
+    /// on-curve nodes in the middle of straight lines.
+    ///
+    /// Curves whose handles are both retracted are turned into lines.
+    pub fn cleanup(&mut self) {
+        while self.cleanup_step() {}
+        normalize_start(self);
+    }
+
+    /// Perform one simplification, returning whether anything changed.
+    fn cleanup_step(&mut self) -> bool {
+        let ons = match on_curve_ixs(self) {
+            Some(ons) => ons,
+            None => return false,
+        };
+        let n = self.nodes.len();
+        let min_ons = if self.closed { 2 } else { 1 };
+        for (k, &i) in ons.iter().enumerate() {
+            if k == 0 && !self.closed {
+                continue;
+            }
+            let prev = if k == 0 {
+                ons[ons.len() - 1]
+            } else {
+                ons[k - 1]
+            };
+            let offs = offs_between(n, prev, i);
+            let p0 = self.nodes[prev].pt;
+            let p1 = self.nodes[i].pt;
+            let retracted = offs.iter().all(|&j| {
+                let pt = self.nodes[j].pt;
+                pt == p0 || pt == p1
+            });
+            if p0 == p1 && retracted && ons.len() > min_ons {
+                // Zero-length segment: drop it along with its end point.
+                let mut remove = offs.clone();
+                remove.push(i);
+                self.remove_nodes(&remove);
+                return true;
+            }
+            if !offs.is_empty() && retracted {
+                self.nodes[i].node_type = NodeType::Line;
+                self.remove_nodes(&offs);
+                return true;
+            }
+        }
+        // Collinear on-curve nodes between two lines.
+        let count = ons.len();
+        if count > min_ons + 1 {
+            for k in 0..count {
+                if !self.closed && (k == 0 || k == count - 1) {
+                    continue;
+                }
+                let i = ons[k];
+                let prev = ons[(k + count - 1) % count];
+                let next = ons[(k + 1) % count];
+                let is_line_in = (prev + 1) % n == i;
+                let is_line_out = (i + 1) % n == next;
+                if is_line_in
+                    && is_line_out
+                    && is_between(self.nodes[prev].pt, self.nodes[i].pt, self.nodes[next].pt)
+                {
+                    self.remove_nodes(&[i]);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn remove_nodes(&mut self, ixs: &[usize]) {
+        let mut ix = 0;
+        self.nodes.retain(|_| {
+            let retain = !ixs.contains(&ix);
+            ix += 1;
+            retain
+        });
+    }
+}
+
+/// Whether `p` lies on the line segment from `p0` to `p1`.
+fn is_between(p0: Point, p: Point, p1: Point) -> bool {
+    let a = p - p0;
+    let b = p1 - p;
+    let len = a.hypot() * b.hypot();
+    len > 0.0 && (a.cross(b) / len).abs() < 1e-9 && a.dot(b) > 0.0
+}
+
+impl Layer {
+    /// Insert on-curve nodes at the extrema of all curves.
+    pub fn add_extremes(&mut self) {
+        if let Some(ref mut paths) = self.paths {
+            for path in paths {
+                path.add_extremes();
+            }
+        }
+    }
+
+    /// Remove redundant nodes from all paths.
+    pub fn cleanup(&mut self) {
+        if let Some(ref mut paths) = self.paths {
+            for path in paths.iter_mut() {
+                path.cleanup();
+            }
+            paths.retain(|path| path.nodes.len() > 1);
+        }
+    }
+}
+
+/// Whether two layers have the same outline structure, so that they can be
+/// interpolated.
+fn is_compatible(a: &Layer, b: &Layer) -> bool {
+    let empty = Vec::new();
+    let paths_a = a.paths.as_ref().unwrap_or(&empty);
+    let paths_b = b.paths.as_ref().unwrap_or(&empty);
+    paths_a.len() == paths_b.len()
+        && paths_a.iter().zip(paths_b).all(|(pa, pb)| {
+            pa.closed == pb.closed
+                && pa.nodes.len() == pb.nodes.len()
+                && pa
+                    .nodes
+                    .iter()
+                    .zip(&pb.nodes)
+                    .all(|(na, nb)| is_on_curve(na) == is_on_curve(nb))
+        })
+}
+
+fn compatible_pairs(layers: &[Layer]) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    for i in 0..layers.len() {
+        for j in i + 1..layers.len() {
+            if is_compatible(&layers[i], &layers[j]) {
+                result.push((i, j));
+            }
+        }
+    }
+    result
+}
+
+/// Clean up all glyphs in the given layer, or in all layers if `None`.
+///
+/// Adding extrema or removing nodes in one master but not another breaks
+/// interpolation, so glyphs whose layers were compatible before but no
+/// longer are keep their original layers. Returns the names of those
+/// glyphs. Extrema rarely fall at the same nodes in every master, so they
+/// should be added to all layers at once.
+pub fn clean(font: &mut Font, layer_id: Option<&str>, add_extremes: bool) -> Vec<String> {
+    let mut skipped = Vec::new();
+    for glyph in &mut font.glyphs {
+        let original = glyph.layers.clone();
+        let before = compatible_pairs(&glyph.layers);
+        for layer in &mut glyph.layers {
+            if layer_id.map(|id| id == layer.layer_id).unwrap_or(true) {
+                if add_extremes {
+                    layer.add_extremes();
+                }
+                layer.cleanup();
+            }
+        }
+        let layers = &glyph.layers;
+        if before
+            .iter()
+            .any(|&(i, j)| !is_compatible(&layers[i], &layers[j]))
+        {
+            glyph.layers = original;
+            skipped.push(glyph.glyphname.clone());
+        }
+    }
+    skipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    #[test]
+    fn incompatible_result_keeps_original_layers() {
+        // The curve has a vertical extremum in the first layer only.
+        let font = Font::from_plist(
+            Plist::parse(
+                r#"{
+fontMaster = ({id = a; weightValue = 400;}, {id = b; weightValue = 700;});
+glyphs = (
+{
+glyphname = test;
+layers = (
+{
+layerId = a;
+width = 500;
+paths = ({closed = 0; nodes = ("0 0 LINE", "100 100 OFFCURVE", "100 200 OFFCURVE", "0 300 CURVE");});
+},
+{
+layerId = b;
+width = 500;
+paths = ({closed = 0; nodes = ("0 0 LINE", "0 100 OFFCURVE", "100 200 OFFCURVE", "100 300 CURVE");});
+}
+);
+}
+);
+}"#,
+            )
+            .unwrap(),
+        );
+        let mut cleaned = font.clone();
+        assert_eq!(clean(&mut cleaned, None, true), vec!["test".to_string()]);
+        for (layer, original) in cleaned.glyphs[0].layers.iter().zip(&font.glyphs[0].layers) {
+            let path = &layer.paths.as_ref().unwrap()[0];
+            let original = &original.paths.as_ref().unwrap()[0];
+            assert_eq!(path.nodes.len(), original.nodes.len());
+        }
+    }
+
+    #[test]
+    fn extrema_within_tolerance_are_skipped() {
+        let node = |x, y, node_type| Node {
+            pt: Point::new(x, y),
+            node_type,
+        };
+        let path = |x: f64| Path {
+            closed: false,
+            nodes: vec![
+                node(0.0, 0.0, NodeType::Line),
+                node(x, 100.0, NodeType::OffCurve),
+                node(x, 200.0, NodeType::OffCurve),
+                node(0.0, 300.0, NodeType::Curve),
+            ],
+        };
+        let (mut barely, mut far) = (path(1.0), path(100.0));
+        barely.add_extremes();
+        far.add_extremes();
+        assert_eq!(barely.nodes.len(), 4);
+        assert_eq!(far.nodes.len(), 7);
+        assert_eq!(far.nodes[3].pt, Point::new(75.0, 150.0));
+    }
+}
@@ -0,0 +1,1252 @@
+//! Compilation of static instances to OpenType fonts.
+//!
+//! An instance is interpolated from the masters, and its glyphs are
+//! written either as TrueType (quadratic) outlines or as CFF. The output
+//! has the tables needed for a working font: `head`, `hhea`, `maxp`,
+//! `OS/2`, `hmtx`, `cmap`, `name` and `post`, plus `glyf` and `loca` or
+//! `CFF `. It is unhinted. Layout tables come from the feature code and
+//! the anchors, and nonspacing marks have no advance width.
+//!
+//! Glyphs marked as not exported are left out, and components referring
+//! to them are decomposed. TrueType composites are kept where the
+//! transforms allow; CFF glyphs are always decomposed.
+
+use std::collections::HashMap;
+
+use kurbo::Rect;
+
+use crate::cff::{build_cff, charstring, CffInfo};
+use crate::fea::update_automatic_classes;
+use crate::font::{Font, FontMaster, Glyph, Layer};
+use crate::glyf::{build_glyf, component_ok, quad_contours, GlyphOutline, MaxStats};
+use crate::interpolate::{instance_font, instance_location, Location};
+use crate::layout::{layout_tables, MarkAnchors};
+use crate::plist::Plist;
+use crate::sfnt::{font_file, search_params, to_i16, to_u16, Writer};
+
+/// The kind of outlines to compile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outlines {
+    /// Quadratic outlines in `glyf`, for a `.ttf`.
+    TrueType,
+    /// Cubic outlines in `CFF `, for an `.otf`.
+    Cff,
+}
+
+impl Outlines {
+    /// The usual file extension for fonts with these outlines.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Outlines::TrueType => "ttf",
+            Outlines::Cff => "otf",
+        }
+    }
+}
+
+/// A static instance, as defined in the font's `instances`.
+#[derive(Clone, Debug)]
+pub struct Instance {
+    /// The style name, like `SemiCondensed Bold`.
+    pub name: String,
+    pub location: Location,
+    /// The `usWeightClass` of OS/2, from 100 to 900.
+    pub weight_class: u16,
+    /// The `usWidthClass` of OS/2, from 1 to 9.
+    pub width_class: u16,
+}
+
+const WEIGHT_CLASSES: &[(&str, u16)] = &[
+    ("Thin", 100),
+    ("ExtraLight", 200),
+    ("UltraLight", 200),
+    ("Light", 300),
+    ("Normal", 400),
+    ("Regular", 400),
+    ("Medium", 500),
+    ("SemiBold", 600),
+    ("DemiBold", 600),
+    ("Bold", 700),
+    ("ExtraBold", 800),
+    ("UltraBold", 800),
+    ("Black", 900),
+    ("Heavy", 900),
+];
+
+const WIDTH_CLASSES: &[(&str, u16)] = &[
+    ("Ultra Condensed", 1),
+    ("Extra Condensed", 2),
+    ("Condensed", 3),
+    ("SemiCondensed", 4),
+    ("Semi Condensed", 4),
+    ("Medium (normal)", 5),
+    ("Normal", 5),
+    ("Semi Expanded", 6),
+    ("SemiExpanded", 6),
+    ("Expanded", 7),
+    ("Extra Expanded", 8),
+    ("Ultra Expanded", 9),
+];
+
+/// A weight or width class, given by name or number.
+fn class_value(plist: Option<&Plist>, names: &[(&str, u16)], default: u16) -> u16 {
+    match plist {
+        Some(Plist::String(s)) => names
+            .iter()
+            .find(|(name, _)| name == s)
+            .map(|&(_, v)| v)
+            .or_else(|| s.parse().ok())
+            .unwrap_or(default),
+        Some(p) => p.as_i64().map(|v| v as u16).unwrap_or(default),
+        None => default,
+    }
+}
+
+/// The exported instances of the font. Glyphs leaves out the weight and
+/// width classes when they are Regular and Medium (normal).
+pub fn instances(font: &Font) -> Vec<Instance> {
+    font.other_stuff
+        .get("instances")
+        .and_then(Plist::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .filter(|i| i.get("exports").and_then(Plist::as_i64) != Some(0))
+        .map(|i| Instance {
+            name: i
+                .get("name")
+                .and_then(Plist::as_str)
+                .unwrap_or("Regular")
+                .to_string(),
+            location: instance_location(font, i),
+            weight_class: class_value(i.get("weightClass"), WEIGHT_CLASSES, 400),
+            width_class: class_value(i.get("widthClass"), WIDTH_CLASSES, 5),
+        })
+        .collect()
+}
+
+/// The names of an instance, following the Glyphs conventions: Regular,
+/// Bold, Italic and Bold Italic are styles of the family itself, and other
+/// styles get a family of their own, with typographic names to group them.
+struct Names {
+    family: String,
+    style: String,
+    /// The style name in the typographic family.
+    full_style: String,
+    bold: bool,
+    italic: bool,
+}
+
+impl Names {
+    fn new(family: &str, style: &str) -> Names {
+        let mut words: Vec<&str> = style.split_whitespace().collect();
+        let italic = words.last() == Some(&"Italic");
+        if italic {
+            words.pop();
+        }
+        let base = words.join(" ");
+        let (family, bold) = match base.as_str() {
+            "" | "Regular" => (family.to_string(), false),
+            "Bold" => (family.to_string(), true),
+            _ => (format!("{} {}", family, base), false),
+        };
+        let style = match (bold, italic) {
+            (false, false) => "Regular",
+            (true, false) => "Bold",
+            (false, true) => "Italic",
+            (true, true) => "Bold Italic",
+        };
+        Names {
+            family,
+            style: style.to_string(),
+            full_style: style_name(style, &base, italic),
+            bold,
+            italic,
+        }
+    }
+
+    fn is_ribbi(&self, family: &str) -> bool {
+        self.family == family
+    }
+}
+
+fn style_name(linked: &str, base: &str, italic: bool) -> String {
+    match (base, italic) {
+        ("", _) => linked.to_string(),
+        (_, true) => format!("{} Italic", base),
+        (_, false) => base.to_string(),
+    }
+}
+
+/// Seconds from 1904-01-01 to a Glyphs date like `2019-12-11 20:47:13 +0000`.
+fn timestamp(date: &str) -> Option<i64> {
+    let number = |range: std::ops::Range<usize>| date.get(range)?.parse::<i64>().ok();
+    let (y, m, d) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hh, mm, ss) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    // Days from the civil calendar, counting years from March.
+    let (y, m) = if m <= 2 { (y - 1, m + 9) } else { (y, m - 3) };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days_since_1970 = era * 146_097 + doe - 719_468;
+    let days = days_since_1970 + 24_107;
+    Some(days * 86400 + hh * 3600 + mm * 60 + ss)
+}
+
+/// Whether a glyph is included in the font.
+pub(crate) fn exported(glyph: &Glyph) -> bool {
+    glyph.other_stuff.get("export").and_then(Plist::as_i64) != Some(0)
+}
+
+/// Glyph metrics after compilation.
+struct Metrics {
+    advances: Vec<u16>,
+    bounds: Vec<Option<[i16; 4]>>,
+}
+
+fn rect_bounds(rect: Option<Rect>) -> Option<[i16; 4]> {
+    rect.map(|r| [to_i16(r.x0), to_i16(r.y0), to_i16(r.x1), to_i16(r.y1)])
+}
+
+fn font_bounds(bounds: &[Option<[i16; 4]>]) -> [i16; 4] {
+    let mut result: Option<[i16; 4]> = None;
+    for b in bounds.iter().flatten() {
+        result = Some(match result {
+            None => *b,
+            Some(r) => [
+                r[0].min(b[0]),
+                r[1].min(b[1]),
+                r[2].max(b[2]),
+                r[3].max(b[3]),
+            ],
+        });
+    }
+    result.unwrap_or_default()
+}
+
+/// Everything the tables are made from.
+pub(crate) struct Context<'a> {
+    font: &'a Font,
+    master: &'a FontMaster,
+    instance: &'a Instance,
+    names: Names,
+    glyphs: Vec<&'a Glyph>,
+    metrics: Metrics,
+    cmap: Vec<(u32, u16)>,
+}
+
+impl<'a> Context<'a> {
+    /// The context for compiling the glyphs of one master, named as the
+    /// instance, given the bounds of the compiled glyphs.
+    pub(crate) fn new(
+        font: &'a Font,
+        master: &'a FontMaster,
+        instance: &'a Instance,
+        glyphs: Vec<&'a Glyph>,
+        bounds: Vec<Option<[i16; 4]>>,
+    ) -> Context<'a> {
+        let mut cmap = Vec::new();
+        for (i, glyph) in glyphs.iter().enumerate() {
+            for cp in glyph.unicodes() {
+                cmap.push((cp, i as u16));
+            }
+        }
+        cmap.sort();
+        cmap.dedup_by_key(|&mut (cp, _)| cp);
+        let advances = glyphs
+            .iter()
+            .map(|g| match g.get_layer(&master.id) {
+                Some(layer) if !g.is_nonspacing_mark() => to_u16(layer.width),
+                _ => 0,
+            })
+            .collect();
+        let family = font
+            .other_stuff
+            .get("familyName")
+            .and_then(Plist::as_str)
+            .unwrap_or("Untitled");
+        Context {
+            font,
+            master,
+            instance,
+            names: Names::new(family, &instance.name),
+            glyphs,
+            metrics: Metrics { advances, bounds },
+            cmap,
+        }
+    }
+
+    fn font_str(&self, key: &str) -> Option<&'a str> {
+        self.font.other_stuff.get(key).and_then(Plist::as_str)
+    }
+
+    fn font_number(&self, key: &str) -> Option<f64> {
+        self.font.other_stuff.get(key).and_then(Plist::as_f64)
+    }
+
+    fn font_param(&self, name: &str) -> Option<&'a Plist> {
+        self.font.custom_parameter(name)
+    }
+
+    fn master_number(&self, key: &str) -> Option<f64> {
+        self.master.other_stuff.get(key).and_then(Plist::as_f64)
+    }
+
+    fn master_param(&self, name: &str) -> Option<f64> {
+        self.master.custom_parameter(name).and_then(Plist::as_f64)
+    }
+
+    fn upm(&self) -> f64 {
+        self.font_number("unitsPerEm").unwrap_or(1000.0)
+    }
+
+    fn ascender(&self) -> f64 {
+        self.master_number("ascender").unwrap_or(800.0)
+    }
+
+    fn descender(&self) -> f64 {
+        self.master_number("descender").unwrap_or(-200.0)
+    }
+
+    fn family_name(&self) -> &'a str {
+        self.font_str("familyName").unwrap_or("Untitled")
+    }
+
+    fn postscript_name(&self) -> String {
+        format!("{}-{}", self.family_name(), self.instance.name).replace(' ', "")
+    }
+
+    fn full_name(&self) -> String {
+        format!("{} {}", self.family_name(), self.instance.name)
+    }
+
+    fn version(&self) -> (i64, i64) {
+        let number = |key| self.font_number(key).unwrap_or(0.0) as i64;
+        (number("versionMajor").max(1), number("versionMinor"))
+    }
+
+    fn italic_angle(&self) -> f64 {
+        // Glyphs measures the angle clockwise, OpenType counterclockwise.
+        -self.master.italic_angle.unwrap_or(0.0)
+    }
+
+    fn underline(&self) -> (f64, f64) {
+        let thickness = self.master_param("underlineThickness").unwrap_or(50.0);
+        let position = self.master_param("underlinePosition").unwrap_or(-100.0);
+        (position, thickness)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.font_param(name).and_then(Plist::as_i64) == Some(1)
+    }
+
+    fn bbox(&self) -> [i16; 4] {
+        font_bounds(&self.metrics.bounds)
+    }
+}
+
+fn head(cx: &Context, outlines: Outlines) -> Vec<u8> {
+    let (major, minor) = cx.version();
+    let revision = major as f64 + minor as f64 / 1000.0;
+    let created = cx.font_str("date").and_then(timestamp).unwrap_or(0);
+    let bbox = cx.bbox();
+    let mac_style = (cx.names.bold as u16) | (cx.names.italic as u16) << 1;
+    let mut w = Writer::new();
+    w.u32(0x0001_0000)
+        .fixed(revision)
+        .u32(0)
+        .u32(0x5F0F_3CF5)
+        // Baseline and left sidebearing point at zero.
+        .u16(3)
+        .u16(to_u16(cx.upm()))
+        .i64(created)
+        .i64(created);
+    for &v in &bbox {
+        w.i16(v);
+    }
+    w.u16(mac_style).u16(6).i16(2);
+    // Long loca offsets; unused for CFF.
+    w.i16((outlines == Outlines::TrueType) as i16).i16(0);
+    w.data
+}
+
+/// The number of metrics written in full in `hmtx`: a run of glyphs with
+/// the same advance at the end only needs the first.
+fn num_h_metrics(advances: &[u16]) -> usize {
+    let mut n = advances.len();
+    while n > 1 && advances[n - 2] == advances[n - 1] {
+        n -= 1;
+    }
+    n
+}
+
+fn hhea(cx: &Context) -> Vec<u8> {
+    let advances = &cx.metrics.advances;
+    let bounds = &cx.metrics.bounds;
+    let ascender = cx.master_param("hheaAscender").unwrap_or(cx.ascender());
+    let descender = cx.master_param("hheaDescender").unwrap_or(cx.descender());
+    let line_gap = cx.master_param("hheaLineGap").unwrap_or(0.0);
+    let mut min_lsb = i16::MAX;
+    let mut min_rsb = i16::MAX;
+    let mut max_extent = i16::MIN;
+    for (advance, b) in advances.iter().zip(bounds) {
+        if let Some(b) = b {
+            min_lsb = min_lsb.min(b[0]);
+            min_rsb = min_rsb.min((*advance as i32 - b[2] as i32) as i16);
+            max_extent = max_extent.max(b[2]);
+        }
+    }
+    if max_extent == i16::MIN {
+        min_lsb = 0;
+        min_rsb = 0;
+        max_extent = 0;
+    }
+    let angle = cx.italic_angle().to_radians();
+    let (rise, run) = if angle == 0.0 {
+        (1, 0)
+    } else {
+        let rise = cx.upm();
+        (to_i16(rise), to_i16(-rise * angle.tan()))
+    };
+    let mut w = Writer::new();
+    w.u32(0x0001_0000)
+        .i16(to_i16(ascender))
+        .i16(to_i16(descender))
+        .i16(to_i16(line_gap))
+        .u16(advances.iter().copied().max().unwrap_or(0))
+        .i16(min_lsb)
+        .i16(min_rsb)
+        .i16(max_extent)
+        .i16(rise)
+        .i16(run)
+        .i16(0);
+    for _ in 0..4 {
+        w.i16(0);
+    }
+    w.i16(0).u16(num_h_metrics(advances) as u16);
+    w.data
+}
+
+fn hmtx(cx: &Context) -> Vec<u8> {
+    let advances = &cx.metrics.advances;
+    let n = num_h_metrics(advances);
+    let mut w = Writer::new();
+    for (i, b) in cx.metrics.bounds.iter().enumerate() {
+        let lsb = b.map(|b| b[0]).unwrap_or(0);
+        if i < n {
+            w.u16(advances[i]);
+        }
+        w.i16(lsb);
+    }
+    w.data
+}
+
+/// OS/2 Unicode range bits, with the blocks that set them.
+const UNICODE_RANGES: &[(u32, u32, u8)] = &[
+    (0x0000, 0x007F, 0),
+    (0x0080, 0x00FF, 1),
+    (0x0100, 0x017F, 2),
+    (0x0180, 0x024F, 3),
+    (0x0250, 0x02AF, 4),
+    (0x1D00, 0x1DBF, 4),
+    (0x02B0, 0x02FF, 5),
+    (0xA700, 0xA71F, 5),
+    (0x0300, 0x036F, 6),
+    (0x1DC0, 0x1DFF, 6),
+    (0x0370, 0x03FF, 7),
+    (0x2C80, 0x2CFF, 8),
+    (0x0400, 0x052F, 9),
+    (0x2DE0, 0x2DFF, 9),
+    (0xA640, 0xA69F, 9),
+    (0x0530, 0x058F, 10),
+    (0x0590, 0x05FF, 11),
+    (0xA500, 0xA63F, 12),
+    (0x0600, 0x06FF, 13),
+    (0x0750, 0x077F, 13),
+    (0x07C0, 0x07FF, 14),
+    (0x0900, 0x097F, 15),
+    (0x0980, 0x09FF, 16),
+    (0x0A00, 0x0A7F, 17),
+    (0x0A80, 0x0AFF, 18),
+    (0x0B00, 0x0B7F, 19),
+    (0x0B80, 0x0BFF, 20),
+    (0x0C00, 0x0C7F, 21),
+    (0x0C80, 0x0CFF, 22),
+    (0x0D00, 0x0D7F, 23),
+    (0x0E00, 0x0E7F, 24),
+    (0x0E80, 0x0EFF, 25),
+    (0x10A0, 0x10FF, 26),
+    (0x2D00, 0x2D2F, 26),
+    (0x1B00, 0x1B7F, 27),
+    (0x1100, 0x11FF, 28),
+    (0x1E00, 0x1EFF, 29),
+    (0x2C60, 0x2C7F, 29),
+    (0xA720, 0xA7FF, 29),
+    (0x1F00, 0x1FFF, 30),
+    (0x2000, 0x206F, 31),
+    (0x2E00, 0x2E7F, 31),
+    (0x2070, 0x209F, 32),
+    (0x20A0, 0x20CF, 33),
+    (0x20D0, 0x20FF, 34),
+    (0x2100, 0x214F, 35),
+    (0x2150, 0x218F, 36),
+    (0x2190, 0x21FF, 37),
+    (0x27F0, 0x27FF, 37),
+    (0x2900, 0x297F, 37),
+    (0x2B00, 0x2BFF, 37),
+    (0x2200, 0x22FF, 38),
+    (0x27C0, 0x27EF, 38),
+    (0x2980, 0x2AFF, 38),
+    (0x2300, 0x23FF, 39),
+    (0x2400, 0x243F, 40),
+    (0x2440, 0x245F, 41),
+    (0x2460, 0x24FF, 42),
+    (0x2500, 0x257F, 43),
+    (0x2580, 0x259F, 44),
+    (0x25A0, 0x25FF, 45),
+    (0x2600, 0x26FF, 46),
+    (0x2700, 0x27BF, 47),
+    (0x3000, 0x303F, 48),
+    (0x3040, 0x309F, 49),
+    (0x30A0, 0x30FF, 50),
+    (0x31F0, 0x31FF, 50),
+    (0x3100, 0x312F, 51),
+    (0x31A0, 0x31BF, 51),
+    (0x3130, 0x318F, 52),
+    (0xA840, 0xA87F, 53),
+    (0x3200, 0x32FF, 54),
+    (0x3300, 0x33FF, 55),
+    (0xAC00, 0xD7AF, 56),
+    (0x10000, 0x10FFFF, 57),
+    (0x10900, 0x1091F, 58),
+    (0x2E80, 0x2FDF, 59),
+    (0x2FF0, 0x2FFF, 59),
+    (0x3190, 0x319F, 59),
+    (0x3400, 0x4DBF, 59),
+    (0x4E00, 0x9FFF, 59),
+    (0x20000, 0x2A6DF, 59),
+    (0xE000, 0xF8FF, 60),
+    (0x31C0, 0x31EF, 61),
+    (0xF900, 0xFAFF, 61),
+    (0x2F800, 0x2FA1F, 61),
+    (0xFB00, 0xFB4F, 62),
+    (0xFB50, 0xFDFF, 63),
+    (0xFE20, 0xFE2F, 64),
+    (0xFE10, 0xFE1F, 65),
+    (0xFE30, 0xFE4F, 65),
+    (0xFE50, 0xFE6F, 66),
+    (0xFE70, 0xFEFF, 67),
+    (0xFF00, 0xFFEF, 68),
+    (0xFFF0, 0xFFFF, 69),
+    (0x0F00, 0x0FFF, 70),
+    (0x0700, 0x074F, 71),
+    (0x0780, 0x07BF, 72),
+    (0x0D80, 0x0DFF, 73),
+    (0x1000, 0x109F, 74),
+    (0x1200, 0x139F, 75),
+    (0x2D80, 0x2DDF, 75),
+    (0x13A0, 0x13FF, 76),
+    (0x1400, 0x167F, 77),
+    (0x1680, 0x169F, 78),
+    (0x16A0, 0x16FF, 79),
+    (0x1780, 0x17FF, 80),
+    (0x19E0, 0x19FF, 80),
+    (0x1800, 0x18AF, 81),
+    (0x2800, 0x28FF, 82),
+    (0xA000, 0xA4CF, 83),
+    (0x1700, 0x177F, 84),
+    (0x10300, 0x1032F, 85),
+    (0x10330, 0x1034F, 86),
+    (0x10400, 0x1044F, 87),
+    (0x1D000, 0x1D24F, 88),
+    (0x1D400, 0x1D7FF, 89),
+    (0xF0000, 0x10FFFD, 90),
+    (0xFE00, 0xFE0F, 91),
+    (0xE0100, 0xE01EF, 91),
+    (0xE0000, 0xE007F, 92),
+    (0x1900, 0x194F, 93),
+    (0x1950, 0x197F, 94),
+    (0x1980, 0x19DF, 95),
+    (0x1A00, 0x1A1F, 96),
+    (0x2C00, 0x2C5F, 97),
+    (0x2D30, 0x2D7F, 98),
+    (0x4DC0, 0x4DFF, 99),
+    (0xA800, 0xA82F, 100),
+    (0x10000, 0x1013F, 101),
+    (0x10140, 0x1018F, 102),
+    (0x10380, 0x1039F, 103),
+    (0x103A0, 0x103DF, 104),
+    (0x10450, 0x1047F, 105),
+    (0x10480, 0x104AF, 106),
+    (0x10800, 0x1083F, 107),
+    (0x10A00, 0x10A5F, 108),
+    (0x1D300, 0x1D35F, 109),
+    (0x12000, 0x1247F, 110),
+    (0x1D360, 0x1D37F, 111),
+    (0x1B80, 0x1BBF, 112),
+    (0x1C00, 0x1C4F, 113),
+    (0x1C50, 0x1C7F, 114),
+    (0xA880, 0xA8DF, 115),
+    (0xA900, 0xA92F, 116),
+    (0xA930, 0xA95F, 117),
+    (0xAA00, 0xAA5F, 118),
+    (0x10190, 0x101CF, 119),
+    (0x101D0, 0x101FF, 120),
+    (0x102A0, 0x102DF, 121),
+    (0x10280, 0x1029F, 121),
+    (0x10920, 0x1093F, 121),
+    (0x1F000, 0x1F09F, 122),
+];
+
+fn unicode_ranges(cmap: &[(u32, u16)]) -> [u32; 4] {
+    let mut ranges = [0u32; 4];
+    for &(cp, _) in cmap {
+        for &(start, end, bit) in UNICODE_RANGES {
+            if (start..=end).contains(&cp) {
+                ranges[bit as usize / 32] |= 1 << (bit % 32);
+            }
+        }
+    }
+    ranges
+}
+
+/// OS/2 code page bits, with the characters that are taken as evidence
+/// of support, and whether ASCII and box drawing are also needed.
+const CODE_PAGES: &[(u8, &str, bool, bool)] = &[
+    (0, "Þ", true, false),
+    (1, "Ľ", true, false),
+    (58, "Ľ", true, true),
+    (2, "Б", false, false),
+    (57, "БЅ", false, true),
+    (49, "Б╜", false, true),
+    (3, "Ά", false, false),
+    (48, "Ά½", false, true),
+    (60, "Ά√", false, true),
+    (4, "İ", true, false),
+    (56, "İ", true, true),
+    (5, "א", false, false),
+    (53, "א√", false, true),
+    (6, "ر", false, false),
+    (51, "ر√", false, false),
+    (61, "ر", false, true),
+    (7, "ŗ", true, false),
+    (59, "ŗ", true, true),
+    (8, "₫", true, false),
+    (16, "ๅ", false, false),
+    (17, "エ", false, false),
+    (18, "央", false, false),
+    (19, "ㄱ", false, false),
+    (20, "ㄅ", false, false),
+    (21, "곴", false, false),
+    (29, "‰∑", true, false),
+    (30, "♥", true, false),
+    (50, "Þ√", true, true),
+    (52, "é√", true, true),
+    (54, "þ", true, true),
+    (55, "õ√", true, true),
+    (62, "╚", true, false),
+    (63, "╚", true, false),
+];
+
+fn code_page_ranges(cmap: &[(u32, u16)]) -> [u32; 2] {
+    let has = |c: u32| cmap.binary_search_by_key(&c, |&(cp, _)| cp).is_ok();
+    let ascii = (0x20..0x7F).all(has);
+    let lineart = has('┤' as u32);
+    let mut ranges = [0u32; 2];
+    for &(bit, chars, needs_ascii, needs_lineart) in CODE_PAGES {
+        if chars.chars().all(|c| has(c as u32))
+            && (ascii || !needs_ascii)
+            && (lineart || !needs_lineart)
+        {
+            ranges[bit as usize / 32] |= 1 << (bit % 32);
+        }
+    }
+    if ranges == [0, 0] {
+        ranges[0] = 1;
+    }
+    ranges
+}
+
+fn os2(cx: &Context) -> Vec<u8> {
+    let upm = cx.upm();
+    let advances: Vec<f64> = cx
+        .metrics
+        .advances
+        .iter()
+        .filter(|&&a| a != 0)
+        .map(|&a| a as f64)
+        .collect();
+    let avg_width = if advances.is_empty() {
+        0.0
+    } else {
+        advances.iter().sum::<f64>() / advances.len() as f64
+    };
+    let x_height = cx.master.x_height.unwrap_or(500.0);
+    let cap_height = cx.master_number("capHeight").unwrap_or(700.0);
+    let (_, underline_thickness) = cx.underline();
+    let fs_type = cx
+        .font_param("fsType")
+        .and_then(Plist::as_array)
+        .map(|bits| {
+            bits.iter()
+                .filter_map(Plist::as_i64)
+                .fold(0u16, |t, bit| t | 1 << bit)
+        })
+        .unwrap_or(0);
+    let panose: Vec<u8> = cx
+        .font_param("panose")
+        .and_then(Plist::as_array)
+        .map(|v| {
+            v.iter()
+                .filter_map(Plist::as_i64)
+                .map(|x| x as u8)
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut vendor = *b"NONE";
+    if let Some(id) = cx.font_param("vendorID").and_then(Plist::as_str) {
+        vendor = *b"    ";
+        for (v, b) in vendor.iter_mut().zip(id.bytes()) {
+            *v = b;
+        }
+    }
+    let mut selection = 0u16;
+    if cx.names.italic {
+        selection |= 1;
+    }
+    if cx.names.bold {
+        selection |= 1 << 5;
+    }
+    if !cx.names.bold && !cx.names.italic {
+        selection |= 1 << 6;
+    }
+    if cx.flag("Use Typo Metrics") {
+        selection |= 1 << 7;
+    }
+    let first_char = cx.cmap.first().map(|&(cp, _)| cp).unwrap_or(0);
+    let last_char = cx.cmap.last().map(|&(cp, _)| cp).unwrap_or(0);
+    let typo_ascender = cx.master_param("typoAscender").unwrap_or(cx.ascender());
+    let typo_descender = cx.master_param("typoDescender").unwrap_or(cx.descender());
+    let typo_line_gap = cx.master_param("typoLineGap").unwrap_or(0.0);
+    let bbox = cx.bbox();
+    let win_ascent = cx.master_param("winAscent").unwrap_or(bbox[3] as f64);
+    let win_descent = cx.master_param("winDescent").unwrap_or(-(bbox[1] as f64));
+
+    let mut w = Writer::new();
+    w.u16(4)
+        .i16(to_i16(avg_width))
+        .u16(cx.instance.weight_class)
+        .u16(cx.instance.width_class)
+        .u16(fs_type);
+    // Subscript and superscript size and offsets.
+    for &v in &[0.65, 0.6, 0.0, 0.075, 0.65, 0.6, 0.0, 0.35] {
+        w.i16(to_i16(upm * v));
+    }
+    w.i16(to_i16(underline_thickness))
+        .i16(to_i16(x_height * 0.6))
+        .i16(0);
+    let mut panose_bytes = [0u8; 10];
+    for (p, v) in panose_bytes.iter_mut().zip(panose) {
+        *p = v;
+    }
+    w.bytes(&panose_bytes);
+    for &r in &unicode_ranges(&cx.cmap) {
+        w.u32(r);
+    }
+    w.tag(&vendor)
+        .u16(selection)
+        .u16(first_char.min(0xFFFF) as u16)
+        .u16(last_char.min(0xFFFF) as u16)
+        .i16(to_i16(typo_ascender))
+        .i16(to_i16(typo_descender))
+        .i16(to_i16(typo_line_gap))
+        .u16(to_u16(win_ascent))
+        .u16(to_u16(win_descent));
+    for &r in &code_page_ranges(&cx.cmap) {
+        w.u32(r);
+    }
+    w.i16(to_i16(x_height))
+        .i16(to_i16(cap_height))
+        // Default and break characters, and no layout context.
+        .u16(0)
+        .u16(32)
+        .u16(0);
+    w.data
+}
+
+/// A format 4 subtable, for the Basic Multilingual Plane.
+fn cmap_format4(cmap: &[(u32, u16)]) -> Vec<u8> {
+    // Segments of consecutive code points: (start, end, glyph IDs).
+    let mut segments: Vec<(u16, u16, Vec<u16>)> = Vec::new();
+    for &(cp, gid) in cmap.iter().filter(|&&(cp, _)| cp < 0xFFFF) {
+        let cp = cp as u16;
+        match segments.last_mut() {
+            Some((_, end, gids)) if *end + 1 == cp => {
+                *end = cp;
+                gids.push(gid);
+            }
+            _ => segments.push((cp, cp, vec![gid])),
+        }
+    }
+    segments.push((0xFFFF, 0xFFFF, vec![0]));
+    let n = segments.len() as u16;
+    // Segments whose glyph IDs are consecutive are mapped by a delta, the
+    // rest through the glyph ID array.
+    let consecutive = |gids: &[u16]| gids.windows(2).all(|w| w[1] == w[0].wrapping_add(1));
+    let mut deltas = Vec::new();
+    let mut range_offsets = Vec::new();
+    let mut glyph_ids: Vec<u16> = Vec::new();
+    for (i, (start, _, gids)) in segments.iter().enumerate() {
+        if *start == 0xFFFF {
+            deltas.push(1u16);
+            range_offsets.push(0u16);
+        } else if consecutive(gids) {
+            deltas.push(gids[0].wrapping_sub(*start));
+            range_offsets.push(0);
+        } else {
+            deltas.push(0);
+            range_offsets.push((2 * (n as usize - i + glyph_ids.len())) as u16);
+            glyph_ids.extend(gids);
+        }
+    }
+    let (search_range, entry_selector, range_shift) = search_params(n, 2);
+    let mut w = Writer::new();
+    w.u16(4).u16(0).u16(0);
+    w.u16(n * 2)
+        .u16(search_range)
+        .u16(entry_selector)
+        .u16(range_shift);
+    for (_, end, _) in &segments {
+        w.u16(*end);
+    }
+    w.u16(0);
+    for (start, _, _) in &segments {
+        w.u16(*start);
+    }
+    for d in &deltas {
+        w.u16(*d);
+    }
+    for r in &range_offsets {
+        w.u16(*r);
+    }
+    for g in &glyph_ids {
+        w.u16(*g);
+    }
+    let len = w.len() as u16;
+    w.set_u16(2, len);
+    w.data
+}
+
+/// A format 12 subtable, covering all code points.
+fn cmap_format12(cmap: &[(u32, u16)]) -> Vec<u8> {
+    let mut groups: Vec<(u32, u32, u32)> = Vec::new();
+    for &(cp, gid) in cmap {
+        match groups.last_mut() {
+            Some((start, end, start_gid))
+                if *end + 1 == cp && *start_gid + (cp - *start) == gid as u32 =>
+            {
+                *end = cp;
+            }
+            _ => groups.push((cp, cp, gid as u32)),
+        }
+    }
+    let mut w = Writer::new();
+    w.u16(12)
+        .u16(0)
+        .u32(16 + 12 * groups.len() as u32)
+        .u32(0)
+        .u32(groups.len() as u32);
+    for (start, end, gid) in groups {
+        w.u32(start).u32(end).u32(gid);
+    }
+    w.data
+}
+
+fn cmap(cx: &Context) -> Vec<u8> {
+    let bmp = cmap_format4(&cx.cmap);
+    let full = if cx.cmap.iter().any(|&(cp, _)| cp > 0xFFFF) {
+        Some(cmap_format12(&cx.cmap))
+    } else {
+        None
+    };
+    // Unicode and Windows encodings, with full repertoire ones when needed.
+    let mut records = vec![(0, 3, 0), (3, 1, 0)];
+    if full.is_some() {
+        records.push((0, 4, 1));
+        records.push((3, 10, 1));
+    }
+    records.sort();
+    let header_len = 4 + 8 * records.len();
+    let offsets = [header_len, header_len + bmp.len()];
+    let mut w = Writer::new();
+    w.u16(0).u16(records.len() as u16);
+    for &(platform, encoding, subtable) in &records {
+        w.u16(platform).u16(encoding).u32(offsets[subtable] as u32);
+    }
+    w.bytes(&bmp);
+    if let Some(full) = full {
+        w.bytes(&full);
+    }
+    w.data
+}
+
+/// The `name` table, with any extra records after the standard ones.
+fn name(cx: &Context, extra: &[(u16, String)]) -> Vec<u8> {
+    let (major, minor) = cx.version();
+    let version = format!("{}.{:03}", major, minor);
+    let vendor = cx
+        .font_param("vendorID")
+        .and_then(Plist::as_str)
+        .unwrap_or("NONE");
+    let ps_name = cx.postscript_name();
+    let family = cx.family_name();
+    let param = |name| cx.font_param(name).and_then(Plist::as_str);
+    let mut names: Vec<(u16, String)> = Vec::new();
+    let mut add = |id: u16, value: Option<&str>| {
+        if let Some(value) = value {
+            names.push((id, value.to_string()));
+        }
+    };
+    add(0, cx.font_str("copyright"));
+    add(1, Some(&cx.names.family));
+    add(2, Some(&cx.names.style));
+    add(3, Some(&format!("{};{};{}", version, vendor, ps_name)));
+    add(4, Some(&cx.full_name()));
+    add(5, Some(&format!("Version {}", version)));
+    add(6, Some(&ps_name));
+    add(8, cx.font_str("manufacturer"));
+    add(9, cx.font_str("designer"));
+    add(11, cx.font_str("manufacturerURL"));
+    add(12, cx.font_str("designerURL"));
+    add(13, param("license"));
+    add(14, param("licenseURL"));
+    if !cx.names.is_ribbi(family) {
+        add(16, Some(family));
+        add(17, Some(&cx.names.full_style));
+    }
+    names.extend_from_slice(extra);
+
+    let mut strings = Writer::new();
+    let mut records = Writer::new();
+    for (id, value) in &names {
+        let offset = strings.len();
+        for unit in value.encode_utf16() {
+            strings.u16(unit);
+        }
+        records
+            .u16(3)
+            .u16(1)
+            .u16(0x409)
+            .u16(*id)
+            .u16((strings.len() - offset) as u16)
+            .u16(offset as u16);
+    }
+    let mut w = Writer::new();
+    w.u16(0)
+        .u16(names.len() as u16)
+        .u16((6 + records.len()) as u16)
+        .bytes(&records.data)
+        .bytes(&strings.data);
+    w.data
+}
+
+fn post(cx: &Context, outlines: Outlines) -> Vec<u8> {
+    let (position, thickness) = cx.underline();
+    let mut w = Writer::new();
+    let version = match outlines {
+        Outlines::TrueType => 0x0002_0000,
+        Outlines::Cff => 0x0003_0000,
+    };
+    w.u32(version)
+        .fixed(cx.italic_angle())
+        .i16(to_i16(position))
+        .i16(to_i16(thickness))
+        .u32(cx.flag("isFixedPitch") as u32);
+    for _ in 0..4 {
+        w.u32(0);
+    }
+    if outlines == Outlines::TrueType {
+        // All names are stored, rather than using the standard Macintosh
+        // names.
+        w.u16(cx.glyphs.len() as u16);
+        for i in 0..cx.glyphs.len() {
+            w.u16((258 + i) as u16);
+        }
+        for glyph in &cx.glyphs {
+            let name = &glyph.glyphname.as_bytes()[..glyph.glyphname.len().min(255)];
+            w.u8(name.len() as u8).bytes(name);
+        }
+    }
+    w.data
+}
+
+fn maxp(cx: &Context, stats: Option<MaxStats>) -> Vec<u8> {
+    let mut w = Writer::new();
+    match stats {
+        Some(stats) => {
+            w.u32(0x0001_0000)
+                .u16(cx.glyphs.len() as u16)
+                .u16(stats.points)
+                .u16(stats.contours)
+                .u16(stats.composite_points)
+                .u16(stats.composite_contours)
+                // One zone, and no instructions.
+                .u16(1);
+            for _ in 0..6 {
+                w.u16(0);
+            }
+            w.u16(stats.component_elements).u16(stats.component_depth);
+        }
+        None => {
+            w.u32(0x0000_5000).u16(cx.glyphs.len() as u16);
+        }
+    }
+    w.data
+}
+
+/// The outline of a glyph for TrueType: a composite if the layer only has
+/// components that TrueType can express, and otherwise its decomposed
+/// paths.
+fn truetype_outline(font: &Font, layer: &Layer, index: &HashMap<&str, usize>) -> GlyphOutline {
+    let components = layer.components.as_deref().unwrap_or(&[]);
+    let has_paths = layer.paths.as_ref().is_some_and(|p| !p.is_empty());
+    if !components.is_empty() && !has_paths {
+        let refs: Option<Vec<_>> = components
+            .iter()
+            .map(|c| {
+                let transform = c.transform.unwrap_or_default();
+                let ix = *index.get(c.name.as_str())?;
+                Some((ix, transform)).filter(|_| component_ok(transform))
+            })
+            .collect();
+        if let Some(refs) = refs {
+            return GlyphOutline::Composite(refs);
+        }
+    }
+    GlyphOutline::Simple(quad_contours(&font.decompose(layer)))
+}
+
+/// The most common advance width.
+fn default_width(advances: &[u16]) -> f64 {
+    let mut counts: HashMap<u16, usize> = HashMap::new();
+    for &a in advances {
+        *counts.entry(a).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(a, n)| (n, a))
+        .map(|(a, _)| a as f64)
+        .unwrap_or(0.0)
+}
+
+/// Alignment zones as blue values and other blues: zones below the
+/// baseline are other blues.
+fn blues(master: &FontMaster) -> (Vec<f64>, Vec<f64>) {
+    let mut blue_values = Vec::new();
+    let mut other_blues = Vec::new();
+    let zones = master
+        .other_stuff
+        .get("alignmentZones")
+        .and_then(Plist::as_array)
+        .unwrap_or(&[]);
+    let mut pairs: Vec<(f64, f64, bool)> = zones
+        .iter()
+        .filter_map(|z| {
+            let s = z.as_str()?.trim_matches(|c| c == '{' || c == '}');
+            let mut values = s.split(',').map(|v| v.trim().parse::<f64>().ok());
+            let (position, size) = (values.next()??, values.next()??);
+            let (a, b) = (position, position + size);
+            Some((a.min(b), a.max(b), position < 0.0))
+        })
+        .collect();
+    pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    for (bottom, top, other) in pairs {
+        let values = if other {
+            &mut other_blues
+        } else {
+            &mut blue_values
+        };
+        values.push(bottom);
+        values.push(top);
+    }
+    (blue_values, other_blues)
+}
+
+fn cff(cx: &Context) -> Vec<u8> {
+    let default_width = default_width(&cx.metrics.advances);
+    let (blue_values, other_blues) = blues(cx.master);
+    let (major, minor) = cx.version();
+    let (underline_position, underline_thickness) = cx.underline();
+    let info = CffInfo {
+        postscript_name: cx.postscript_name(),
+        full_name: cx.full_name(),
+        family_name: cx.family_name().to_string(),
+        weight: String::new(),
+        copyright: cx.font_str("copyright").unwrap_or("").to_string(),
+        version: format!("{}.{:03}", major, minor),
+        italic_angle: cx.italic_angle(),
+        underline_position,
+        underline_thickness,
+        fixed_pitch: cx.flag("isFixedPitch"),
+        bbox: cx.bbox(),
+        blue_values,
+        other_blues,
+        std_hw: cx
+            .master
+            .other_stuff
+            .get("horizontalStems")
+            .and_then(Plist::as_array)
+            .and_then(|s| s.first()?.as_f64()),
+        std_vw: cx
+            .master
+            .vertical_stems
+            .as_ref()
+            .and_then(|s| s.first().copied()),
+    };
+    let names: Vec<String> = cx.glyphs.iter().map(|g| g.glyphname.clone()).collect();
+    let charstrings = cx
+        .glyphs
+        .iter()
+        .zip(&cx.metrics.advances)
+        .map(|(glyph, &advance)| {
+            let paths = glyph
+                .get_layer(&cx.master.id)
+                .map(|l| cx.font.decompose(l))
+                .unwrap_or_default();
+            charstring(&paths, advance as f64, default_width)
+        })
+        .collect();
+    build_cff(&info, &names, charstrings, default_width)
+}
+
+/// Move `.notdef` to the start of the glyphs, adding an empty one with a
+/// layer for each master if the font has none.
+pub(crate) fn notdef_first(font: &mut Font) {
+    let notdef = match font.glyphs.iter().position(|g| g.glyphname == ".notdef") {
+        Some(i) => font.glyphs.remove(i),
+        None => Glyph {
+            layers: font
+                .font_master
+                .iter()
+                .map(|m| Layer {
+                    layer_id: m.id.clone(),
+                    width: 0.0,
+                    paths: None,
+                    components: None,
+                    anchors: None,
+                    guide_lines: None,
+                    other_stuff: HashMap::new(),
+                })
+                .collect(),
+            glyphname: ".notdef".to_string(),
+            other_stuff: HashMap::new(),
+        },
+    };
+    font.glyphs.insert(0, notdef);
+}
+
+/// The glyphs of the font in glyph ID order: the exported ones, in the
+/// order of the font.
+pub(crate) fn glyph_order(font: &Font) -> Result<Vec<&Glyph>, String> {
+    let glyphs: Vec<&Glyph> = font.glyphs.iter().filter(|g| exported(g)).collect();
+    if glyphs.len() > 0xFFFF {
+        return Err("too many glyphs".to_string());
+    }
+    Ok(glyphs)
+}
+
+/// Glyph IDs by name.
+pub(crate) fn glyph_index<'a>(glyphs: &[&'a Glyph]) -> HashMap<&'a str, usize> {
+    glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g.glyphname.as_str(), i))
+        .collect()
+}
+
+/// The tables every font has, whatever its outlines. `extra_names` are
+/// added to the `name` table.
+pub(crate) fn common_tables(
+    cx: &Context,
+    outlines: Outlines,
+    stats: Option<MaxStats>,
+    extra_names: &[(u16, String)],
+) -> Vec<([u8; 4], Vec<u8>)> {
+    vec![
+        (*b"head", head(cx, outlines)),
+        (*b"hhea", hhea(cx)),
+        (*b"maxp", maxp(cx, stats)),
+        (*b"OS/2", os2(cx)),
+        (*b"hmtx", hmtx(cx)),
+        (*b"cmap", cmap(cx)),
+        (*b"name", name(cx, extra_names)),
+        (*b"post", post(cx, outlines)),
+    ]
+}
+
+/// Compile a static instance of the font.
+pub fn compile_static(
+    font: &Font,
+    instance: &Instance,
+    outlines: Outlines,
+) -> Result<Vec<u8>, String> {
+    let mut source = instance_font(font, &instance.location)?;
+    notdef_first(&mut source);
+    update_automatic_classes(&mut source);
+    let master = &source.font_master[0];
+    let glyphs = glyph_order(&source)?;
+    let mut tables: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+    let (bounds, stats) = match outlines {
+        Outlines::TrueType => {
+            let index = glyph_index(&glyphs);
+            let outlines: Vec<GlyphOutline> = glyphs
+                .iter()
+                .map(|g| truetype_outline(&source, &g.layers[0], &index))
+                .collect();
+            let glyf = build_glyf(&outlines);
+            tables.push((*b"glyf", glyf.glyf));
+            tables.push((*b"loca", glyf.loca));
+            (glyf.bounds, Some(glyf.stats))
+        }
+        Outlines::Cff => {
+            let bounds = glyphs
+                .iter()
+                .map(|g| {
+                    let paths = source.decompose(&g.layers[0]);
+                    let rect = paths
+                        .iter()
+                        .filter(|p| p.closed)
+                        .filter_map(|p| p.bounds())
+                        .fold(None, |acc: Option<Rect>, r| {
+                            Some(acc.map_or(r, |a| a.union(r)))
+                        });
+                    rect_bounds(rect)
+                })
+                .collect();
+            (bounds, None)
+        }
+    };
+    let layers: Vec<&Layer> = glyphs.iter().map(|g| &g.layers[0]).collect();
+    let anchors = MarkAnchors::of_layers(&source, &layers);
+    tables.extend(layout_tables(&source, &glyphs, &anchors)?);
+    let cx = Context::new(&source, master, instance, glyphs, bounds);
+    if outlines == Outlines::Cff {
+        tables.push((*b"CFF ", cff(&cx)));
+    }
+    tables.extend(common_tables(&cx, outlines, stats, &[]));
+    let version = match outlines {
+        Outlines::TrueType => 0x0001_0000,
+        Outlines::Cff => u32::from_be_bytes(*b"OTTO"),
+    };
+    Ok(font_file(version, tables))
+}
+
+/// The file name for a compiled instance, like `Inconsolata-Bold.ttf`.
+pub fn static_file_name(font: &Font, instance: &Instance, outlines: Outlines) -> String {
+    let family = font
+        .other_stuff
+        .get("familyName")
+        .and_then(Plist::as_str)
+        .unwrap_or("Untitled");
+    let name = format!("{}-{}", family, instance.name).replace(' ', "");
+    format!("{}.{}", name, outlines.extension())
+}
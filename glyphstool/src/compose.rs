@@ -0,0 +1,307 @@
+//! Composite glyphs built by attaching marks to bases with anchors.
+//!
+//! A composite is written as the base followed by its marks, for example
+//! `dcaron = d + caroncomb.alt@topright`. Each mark is attached by its `_`
+//! anchor to the anchor of the same name in the glyph built so far, so
+//! `@topright` lines up `_topright` in `caroncomb.alt` with `topright` in
+//! `d`. Without an explicit anchor, the first of the mark's `_` anchors
+//! with a counterpart in the base is used. Marks stack: a mark attached
+//! with `_top` that has a `top` anchor of its own moves the glyph's `top`
+//! there, for the next mark.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use kurbo::{Affine, Point, Vec2};
+use serde::Deserialize;
+
+use crate::font::{Anchor, Component, Font, Glyph, Layer};
+
+/// A recipe for a composite glyph.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Composite {
+    pub name: String,
+    pub base: String,
+    pub marks: Vec<Mark>,
+}
+
+/// A mark component, with the anchor it attaches to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mark {
+    pub name: String,
+    pub anchor: Option<String>,
+}
+
+impl FromStr for Composite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Composite, String> {
+        let bad = || format!("bad composite recipe {:?}", s);
+        let mut halves = s.splitn(2, '=');
+        let name = halves.next().unwrap().trim();
+        let mut parts = halves.next().ok_or_else(bad)?.split('+').map(str::trim);
+        let base = parts.next().unwrap();
+        if name.is_empty() || base.is_empty() {
+            return Err(bad());
+        }
+        let marks = parts
+            .map(|part| {
+                let mut split = part.splitn(2, '@');
+                let name = split.next().unwrap().to_string();
+                let anchor = split.next().map(|a| a.to_string());
+                if name.is_empty() || anchor.as_deref() == Some("") {
+                    return Err(bad());
+                }
+                Ok(Mark { name, anchor })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Composite {
+            name: name.to_string(),
+            base: base.to_string(),
+            marks,
+        })
+    }
+}
+
+impl TryFrom<String> for Composite {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Composite, String> {
+        s.parse()
+    }
+}
+
+fn find_anchor(anchors: &[Anchor], name: &str) -> Option<Point> {
+    anchors.iter().find(|a| a.name == name).map(|a| a.position)
+}
+
+/// The offset that attaches a mark with the given anchors to a glyph with
+/// `anchors`, and the name of the anchor used.
+pub fn attach(
+    anchors: &[Anchor],
+    mark_anchors: &[Anchor],
+    anchor: Option<&str>,
+) -> Option<(Vec2, String)> {
+    let name = match anchor {
+        Some(name) => name.to_string(),
+        None => mark_anchors
+            .iter()
+            .filter_map(|a| a.name.strip_prefix('_'))
+            .find(|name| find_anchor(anchors, name).is_some())?
+            .to_string(),
+    };
+    let base = find_anchor(anchors, &name)?;
+    let mark = find_anchor(mark_anchors, &format!("_{}", name))?;
+    Some((base - mark, name))
+}
+
+fn translate_component(name: &str, offset: Vec2) -> Component {
+    Component {
+        name: name.to_string(),
+        transform: if offset == Vec2::ZERO {
+            None
+        } else {
+            Some(Affine::translate(offset))
+        },
+        other_stuff: Default::default(),
+    }
+}
+
+/// Build one master layer of a composite.
+fn compose_layer(font: &Font, composite: &Composite, layer_id: &str) -> Result<Layer, String> {
+    let master_layer = |name: &str| {
+        font.get_glyph(name)
+            .ok_or_else(|| format!("glyph {} not found", name))?
+            .get_layer(layer_id)
+            .ok_or_else(|| format!("glyph {} has no layer {}", name, layer_id))
+    };
+    let base = master_layer(&composite.base)?;
+    let mut components = vec![translate_component(&composite.base, Vec2::ZERO)];
+    let mut anchors = font.anchors(base);
+    for mark in &composite.marks {
+        let mark_anchors = font.anchors(master_layer(&mark.name)?);
+        let (offset, _) =
+            attach(&anchors, &mark_anchors, mark.anchor.as_deref()).ok_or_else(|| {
+                format!(
+                    "can't attach {} to {} in layer {}",
+                    mark.name, composite.base, layer_id
+                )
+            })?;
+        components.push(translate_component(&mark.name, offset));
+        let has = |name: &str| mark_anchors.iter().any(|a| a.name == name);
+        for anchor in &mark_anchors {
+            if !anchor.name.starts_with('_') && has(&format!("_{}", anchor.name)) {
+                let position = anchor.position + offset;
+                match anchors.iter_mut().find(|a| a.name == anchor.name) {
+                    Some(a) => a.position = position,
+                    None => anchors.push(Anchor {
+                        name: anchor.name.clone(),
+                        position,
+                    }),
+                }
+            }
+        }
+    }
+    Ok(Layer {
+        layer_id: layer_id.to_string(),
+        width: base.width,
+        paths: None,
+        components: Some(components),
+        anchors: if anchors.is_empty() {
+            None
+        } else {
+            Some(anchors)
+        },
+        guide_lines: None,
+        other_stuff: HashMap::new(),
+    })
+}
+
+/// Build composite glyphs in every master, adding glyphs that don't exist.
+/// The master layers of existing glyphs are replaced, keeping the advance
+/// widths; new ones take the advance width of the base.
+///
+/// The anchors of the base and marks are propagated to the composite.
+pub fn build_composites(font: &mut Font, composites: &[Composite]) -> Result<(), String> {
+    let master_ids: Vec<String> = font.font_master.iter().map(|m| m.id.clone()).collect();
+    for composite in composites {
+        let layers = master_ids
+            .iter()
+            .map(|id| compose_layer(font, composite, id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let glyph = match font.get_glyph_mut(&composite.name) {
+            Some(glyph) => glyph,
+            None => {
+                font.glyphs.push(Glyph {
+                    layers: Vec::new(),
+                    glyphname: composite.name.clone(),
+                    other_stuff: HashMap::new(),
+                });
+                font.glyphs.last_mut().unwrap()
+            }
+        };
+        for layer in layers {
+            match glyph.get_layer_mut(&layer.layer_id) {
+                Some(existing) => {
+                    existing.paths = layer.paths;
+                    existing.components = layer.components;
+                    existing.anchors = layer.anchors;
+                }
+                None => glyph.layers.push(layer),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn font() -> Font {
+        let source = r#"{
+fontMaster = ({id = m; weightValue = 400;});
+glyphs = (
+{glyphname = a; layers = ({layerId = m; width = 500;
+anchors = ({name = top; position = "{250, 500}";}, {name = bottom; position = "{250, 0}";});});},
+{glyphname = acutecomb; layers = ({layerId = m; width = 0;
+anchors = ({name = _top; position = "{100, 450}";}, {name = top; position = "{100, 650}";});});},
+{glyphname = dotbelowcomb; layers = ({layerId = m; width = 0;
+anchors = ({name = _bottom; position = "{50, -10}";});});},
+{glyphname = aacute; layers = ({layerId = m; width = 600; paths = ({closed = 1; nodes = (
+"0 0 LINE", "100 0 LINE", "100 100 LINE");});});}
+);
+}"#;
+        Font::from_plist(Plist::parse(source).unwrap())
+    }
+
+    fn offsets(layer: &Layer) -> Vec<(&str, Option<[f64; 6]>)> {
+        let components = layer.components.as_ref().unwrap();
+        components
+            .iter()
+            .map(|c| (c.name.as_str(), c.transform.map(|t| t.as_coeffs())))
+            .collect()
+    }
+
+    #[test]
+    fn parse_recipes() {
+        let composite: Composite = "dcaron = d + caroncomb.alt@topright".parse().unwrap();
+        assert_eq!(composite.name, "dcaron");
+        assert_eq!(composite.base, "d");
+        assert_eq!(
+            composite.marks,
+            [Mark {
+                name: "caroncomb.alt".to_string(),
+                anchor: Some("topright".to_string())
+            }]
+        );
+        for bad in &["d", "= d", "x = ", "x = d + @top", "x = d + caroncomb@"] {
+            assert!(bad.parse::<Composite>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn attach_by_anchor() {
+        let font = font();
+        let layer = |name| font.get_glyph(name).unwrap().get_layer("m").unwrap();
+        let base = font.anchors(layer("a"));
+        let mark = font.anchors(layer("acutecomb"));
+        let (offset, name) = attach(&base, &mark, None).unwrap();
+        assert_eq!((offset, name.as_str()), (Vec2::new(150.0, 50.0), "top"));
+        assert_eq!(attach(&base, &mark, Some("top")).unwrap().0, offset);
+        assert!(attach(&base, &mark, Some("bottom")).is_none());
+        assert!(attach(&mark, &base, None).is_none());
+    }
+
+    #[test]
+    fn marks_stack_and_existing_glyphs_keep_their_width() {
+        let mut font = font();
+        let composites: Vec<Composite> = [
+            "aacute = a + acutecomb",
+            "adotbelowacuteacute = a + dotbelowcomb + acutecomb + acutecomb",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        build_composites(&mut font, &composites).unwrap();
+
+        let layer = |name| font.get_glyph(name).unwrap().get_layer("m").unwrap();
+        let aacute = layer("aacute");
+        assert_eq!(aacute.width, 600.0);
+        assert!(aacute.paths.is_none());
+        assert_eq!(
+            offsets(aacute),
+            [
+                ("a", None),
+                ("acutecomb", Some([1.0, 0.0, 0.0, 1.0, 150.0, 50.0]))
+            ]
+        );
+
+        // The second acute goes on the top anchor of the first.
+        let stacked = layer("adotbelowacuteacute");
+        assert_eq!(stacked.width, 500.0);
+        assert_eq!(
+            offsets(stacked),
+            [
+                ("a", None),
+                ("dotbelowcomb", Some([1.0, 0.0, 0.0, 1.0, 200.0, 10.0])),
+                ("acutecomb", Some([1.0, 0.0, 0.0, 1.0, 150.0, 50.0])),
+                ("acutecomb", Some([1.0, 0.0, 0.0, 1.0, 150.0, 250.0]))
+            ]
+        );
+        let top = stacked
+            .anchors
+            .as_ref()
+            .unwrap()
+            .iter()
+            .find(|a| a.name == "top");
+        assert_eq!(top.unwrap().position, Point::new(250.0, 900.0));
+
+        let missing = ["x = a + dotbelowcomb@top".parse().unwrap()];
+        assert!(build_composites(&mut font, &missing).is_err());
+    }
+}
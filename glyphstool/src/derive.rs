@@ -0,0 +1,424 @@
+//! Derived glyphs (superiors, ordinals, fractions and the like) built from
+//! other glyphs according to a recipe file.
+//!
+//! A recipe file is TOML, with one `[[family]]` table per group of glyphs
+//! that share a construction. For example, superior figures:
+//!
+//! ```toml
+//! [[family]]
+//! name = "superiors"
+//! weight = { factor = 1.3, max = 1000 }
+//! width = { factor = 1.1 }
+//! scale = 0.62
+//! y = 246
+//! glyphs = [
+//!     { name = "onesuperior", source = "one" },
+//!     { name = "twosuperior", source = "two" },
+//! ]
+//! ```
+//!
+//! For each master, the sources are interpolated at the master's location
+//! with the weight and width adjustments applied, scaled, centered in the
+//! target's advance width, and offset. Horizontal offsets are fractions of
+//! the advance width; vertical offsets are in font units.
+//!
+//! Instead of a fixed weight adjustment, a family can ask for a stem
+//! thickness (`stem`, or `stem_ratio` of the source's own stem), and the
+//! weight is then chosen so the scaled stems match, extrapolating past the
+//! masters if needed.
+//!
+//! Derived glyphs take the anchors of their first source, moved along with
+//! its outline. A family's components can be placed by anchor rather than
+//! by offset: `{ name = "caroncomb.alt", anchor = "topright" }` lines up the
+//! component's `_topright` anchor with the glyph's `topright`.
+//!
+//! Plain composites are listed separately, and are built after the
+//! families, so they can use derived glyphs:
+//!
+//! ```toml
+//! composites = ["lcaron = l + caroncomb.alt@topright"]
+//! ```
+//!
+//! See `compose.rs` for the format.
+
+use kurbo::Affine;
+use serde::Deserialize;
+
+use crate::compose::{attach, build_composites, Composite};
+use crate::font::{Anchor, Component, Font, Layer, NodeType, Path};
+use crate::interpolate::{
+    glyph_stem, interpolate, location_for_stem, master_location, weight_width, Location,
+};
+use crate::param::Value;
+use crate::round::{round_layer, RoundIssue};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Recipes {
+    #[serde(rename = "family", default)]
+    pub families: Vec<Family>,
+    #[serde(default)]
+    pub composites: Vec<Composite>,
+}
+
+/// A group of derived glyphs built the same way.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Family {
+    pub name: String,
+    /// Adjustment of the weight at which sources are interpolated.
+    #[serde(default)]
+    pub weight: AxisAdjust,
+    /// Adjustment of the width at which sources are interpolated.
+    #[serde(default)]
+    pub width: AxisAdjust,
+    /// Target thickness of vertical stems after scaling. If given, the
+    /// weight at which sources are interpolated is chosen to match it, and
+    /// the weight adjustment is ignored.
+    pub stem: Option<Value>,
+    /// Like `stem`, but as a ratio of the source glyph's own stem at the
+    /// master.
+    pub stem_ratio: Option<f64>,
+    /// Default scale of the parts.
+    #[serde(default = "one")]
+    pub scale: f64,
+    /// Default vertical offset of the parts.
+    #[serde(default)]
+    pub y: f64,
+    /// Center each scaled source in the advance width before offsetting.
+    #[serde(default = "yes")]
+    pub center: bool,
+    /// Components added to every glyph, replacing any existing ones.
+    #[serde(default)]
+    pub components: Vec<ComponentSpec>,
+    /// Shapes added to every glyph.
+    #[serde(default)]
+    pub shapes: Vec<Shape>,
+    /// Remove anchors from the derived glyphs.
+    #[serde(default)]
+    pub clear_anchors: bool,
+    pub glyphs: Vec<Target>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AxisAdjust {
+    #[serde(default = "one")]
+    pub factor: f64,
+    pub max: Option<f64>,
+}
+
+impl Default for AxisAdjust {
+    fn default() -> AxisAdjust {
+        AxisAdjust {
+            factor: 1.0,
+            max: None,
+        }
+    }
+}
+
+impl AxisAdjust {
+    fn apply(&self, value: f64) -> f64 {
+        let value = value * self.factor;
+        self.max.map(|max| value.min(max)).unwrap_or(value)
+    }
+}
+
+/// A derived glyph. It has either a single `source`, or a list of `parts`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Target {
+    pub name: String,
+    pub source: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<Part>,
+}
+
+/// A source glyph placed into a derived glyph.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Part {
+    pub source: String,
+    pub scale: Option<f64>,
+    /// Horizontal offset, as a fraction of the advance width.
+    #[serde(default)]
+    pub x: f64,
+    pub y: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ComponentSpec {
+    pub name: String,
+    /// Attach the component by its `_` anchor to this anchor of the glyph.
+    /// The offsets are then added to the attached position.
+    pub anchor: Option<String>,
+    /// Horizontal offset, as a fraction of the advance width.
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    /// A horizontal bar from `x0` to `x1`, with `below` of its thickness
+    /// under `y`.
+    Hline {
+        x0: Value,
+        x1: Value,
+        y: Value,
+        thickness: Value,
+        #[serde(default = "half")]
+        below: f64,
+    },
+    Rect {
+        x0: Value,
+        y0: Value,
+        x1: Value,
+        y1: Value,
+    },
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn half() -> f64 {
+    0.5
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl Recipes {
+    pub fn parse(s: &str) -> Result<Recipes, String> {
+        toml::from_str(s).map_err(|e| e.to_string())
+    }
+}
+
+impl Target {
+    fn parts(&self, family: &Family) -> Vec<Part> {
+        let mut parts = self.parts.clone();
+        if let Some(ref source) = self.source {
+            parts.insert(
+                0,
+                Part {
+                    source: source.clone(),
+                    scale: None,
+                    x: 0.0,
+                    y: None,
+                },
+            );
+        }
+        for part in &mut parts {
+            part.scale = part.scale.or(Some(family.scale));
+            part.y = part.y.or(Some(family.y));
+        }
+        parts
+    }
+}
+
+fn rect_path(x0: f64, y0: f64, x1: f64, y1: f64) -> Path {
+    let mut path = Path::new(true);
+    path.add((x0, y0), NodeType::Line);
+    path.add((x1, y0), NodeType::Line);
+    path.add((x1, y1), NodeType::Line);
+    path.add((x0, y1), NodeType::Line);
+    path
+}
+
+impl Shape {
+    fn to_path(&self, weight: f64, width: f64) -> Path {
+        match self {
+            Shape::Hline {
+                x0,
+                x1,
+                y,
+                thickness,
+                below,
+            } => {
+                let y = y.eval(weight, width);
+                let thickness = thickness.eval(weight, width);
+                let y0 = y - below * thickness;
+                let x0 = x0.eval(weight, width);
+                let x1 = x1.eval(weight, width);
+                rect_path(x0, y0, x1, y0 + thickness)
+            }
+            Shape::Rect { x0, y0, x1, y1 } => rect_path(
+                x0.eval(weight, width),
+                y0.eval(weight, width),
+                x1.eval(weight, width),
+                y1.eval(weight, width),
+            ),
+        }
+    }
+}
+
+/// Something worth knowing about a derived glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeriveIssue {
+    Round(RoundIssue),
+    /// Matching the stem thickness required a weight outside the masters.
+    Extrapolated {
+        layer_id: String,
+        weight: f64,
+    },
+}
+
+/// The location at which to interpolate a part's source: the master's
+/// location with the family's weight and width adjustments, or with the
+/// weight that matches the stem thickness.
+fn source_location(
+    font: &Font,
+    family: &Family,
+    part: &Part,
+    layer_id: &str,
+    issues: &mut Vec<DeriveIssue>,
+) -> Result<Location, String> {
+    let mut location = master_location(font, layer_id).unwrap();
+    let (weight, width) = weight_width(&location);
+    for (tag, value) in &mut location {
+        match tag.as_str() {
+            "wght" => *value = family.weight.apply(*value),
+            "wdth" => *value = family.width.apply(*value),
+            _ => (),
+        }
+    }
+    let glyph = font
+        .get_glyph(&part.source)
+        .ok_or_else(|| format!("source glyph {} not found", part.source))?;
+    let stem = if let Some(ref stem) = family.stem {
+        stem.eval(weight, width)
+    } else if let Some(ratio) = family.stem_ratio {
+        let layer = glyph
+            .get_layer(layer_id)
+            .ok_or_else(|| format!("glyph {} has no layer {}", part.source, layer_id))?;
+        let stem =
+            glyph_stem(font, layer).ok_or_else(|| format!("no stems found in {}", part.source))?;
+        stem * ratio
+    } else {
+        return Ok(location);
+    };
+    let scale = part.scale.unwrap();
+    let stem_match = location_for_stem(font, glyph, "wght", &location, scale, stem)?;
+    if stem_match.extrapolated {
+        issues.push(DeriveIssue::Extrapolated {
+            layer_id: layer_id.to_string(),
+            weight: stem_match.value,
+        });
+    }
+    Ok(stem_match.location)
+}
+
+/// Build the contents of one master layer of a derived glyph.
+fn derive_layer(
+    font: &Font,
+    family: &Family,
+    parts: &[Part],
+    layer: &mut Layer,
+    issues: &mut Vec<DeriveIssue>,
+) -> Result<(), String> {
+    let (weight, width) = match master_location(font, &layer.layer_id) {
+        Some(location) => weight_width(&location),
+        None => return Ok(()),
+    };
+    let advance = layer.width;
+    let mut paths = Vec::new();
+    let mut anchors: Vec<Anchor> = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        let glyph = font
+            .get_glyph(&part.source)
+            .ok_or_else(|| format!("source glyph {} not found", part.source))?;
+        let location = source_location(font, family, part, &layer.layer_id, issues)?;
+        let src = interpolate(font, glyph, &location)?;
+        let scale = part.scale.unwrap();
+        let mut x = part.x * advance;
+        if family.center {
+            x += (advance - src.width * scale) * 0.5;
+        }
+        let a = Affine::new([scale, 0.0, 0.0, scale, x, part.y.unwrap()]);
+        for mut path in src.paths.unwrap_or_default() {
+            path.apply_affine(a);
+            paths.push(path);
+        }
+        if i == 0 {
+            for mut anchor in src.anchors.unwrap_or_default() {
+                anchor.position = a * anchor.position;
+                anchors.push(anchor);
+            }
+        }
+    }
+    for shape in &family.shapes {
+        paths.push(shape.to_path(weight, width));
+    }
+    layer.paths = if paths.is_empty() { None } else { Some(paths) };
+    let components = family
+        .components
+        .iter()
+        .map(|spec| {
+            let (mut x, mut y) = (spec.x * advance, spec.y);
+            if let Some(ref anchor) = spec.anchor {
+                let mark = font
+                    .get_glyph(&spec.name)
+                    .and_then(|g| g.get_layer(&layer.layer_id))
+                    .ok_or_else(|| format!("component glyph {} not found", spec.name))?;
+                let (offset, _) = attach(&anchors, &font.anchors(mark), Some(anchor))
+                    .ok_or_else(|| format!("can't attach {} at {}", spec.name, anchor))?;
+                x += offset.x;
+                y += offset.y;
+            }
+            Ok(Component {
+                name: spec.name.clone(),
+                transform: if x == 0.0 && y == 0.0 {
+                    None
+                } else {
+                    Some(Affine::translate((x, y)))
+                },
+                other_stuff: Default::default(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    layer.components = if components.is_empty() {
+        None
+    } else {
+        Some(components)
+    };
+    layer.anchors = if family.clear_anchors || anchors.is_empty() {
+        None
+    } else {
+        Some(anchors)
+    };
+    Ok(())
+}
+
+/// Build all the derived glyphs described by the recipes, then the
+/// composites.
+///
+/// The target glyphs of families must already exist; their master layers
+/// are replaced, keeping the advance widths. Returns any problems found
+/// along the way.
+pub fn derive(
+    font: &mut Font,
+    recipes: &Recipes,
+) -> Result<Vec<(String, Vec<DeriveIssue>)>, String> {
+    let mut result = Vec::new();
+    for family in &recipes.families {
+        for target in &family.glyphs {
+            let parts = target.parts(family);
+            let mut glyph = font
+                .get_glyph(&target.name)
+                .ok_or_else(|| format!("target glyph {} not found", target.name))?
+                .clone();
+            let mut issues = Vec::new();
+            for layer in &mut glyph.layers {
+                if font.get_master(&layer.layer_id).is_some() {
+                    derive_layer(font, family, &parts, layer, &mut issues)?;
+                    issues.extend(round_layer(layer, 1.0).into_iter().map(DeriveIssue::Round));
+                }
+            }
+            if !issues.is_empty() {
+                result.push((target.name.clone(), issues));
+            }
+            *font.get_glyph_mut(&target.name).unwrap() = glyph;
+        }
+    }
+    build_composites(font, &recipes.composites)?;
+    Ok(result)
+}
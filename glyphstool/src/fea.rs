@@ -0,0 +1,986 @@
+//! Feature code in the AFDKO feature file syntax.
+//!
+//! The prefixes, classes and features of a Glyphs font are parsed into
+//! statements, with glyph classes expanded to glyph names in order, and
+//! every glyph and class name checked against the font. Each feature's code
+//! is the body of a `feature` block, and line numbers in errors count from
+//! the start of that code, as Glyphs shows it.
+//!
+//! This covers the substitutions that Glyphs generates and that we write by
+//! hand, along with single and pair positioning. Reverse chaining, contextual
+//! positioning, anchors and mark classes, `table` blocks, glyph ranges and
+//! `include` are not supported, and are reported as errors.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::compile::exported;
+use crate::font::{Font, Glyph};
+
+/// Glyph names, from a glyph or a class, in order.
+pub type GlyphSet = Vec<String>;
+
+/// Parsed feature code.
+#[derive(Clone, Debug, Default)]
+pub struct FeatureFile {
+    /// The `languagesystem` statements, as script and language tags.
+    pub language_systems: Vec<(String, String)>,
+    /// Named lookups outside features, and features, in order.
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Block {
+    Lookup(LookupBlock),
+    Feature(FeatureBlock),
+}
+
+#[derive(Clone, Debug)]
+pub struct FeatureBlock {
+    pub tag: String,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LookupBlock {
+    pub name: String,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Statement {
+    Script(String),
+    /// A language of the current script; `include_dflt` is false when the
+    /// script's default lookups are excluded.
+    Language {
+        tag: String,
+        include_dflt: bool,
+    },
+    LookupFlag(u16),
+    /// A named lookup defined in a feature.
+    Lookup(LookupBlock),
+    /// A use of a named lookup.
+    LookupRef(String),
+    /// A feature whose substitutions go into `aalt`.
+    FeatureRef(String),
+    Substitute(Substitution),
+    Position(Positioning),
+}
+
+/// A `sub` or `ignore sub` rule. The input is the marked glyphs of a
+/// contextual rule, or all of them otherwise.
+#[derive(Clone, Debug)]
+pub struct Substitution {
+    pub backtrack: Vec<GlyphSet>,
+    pub input: Vec<GlyphSet>,
+    pub lookahead: Vec<GlyphSet>,
+    /// Whether glyphs were marked with `'`.
+    pub contextual: bool,
+    pub action: SubAction,
+}
+
+#[derive(Clone, Debug)]
+pub enum SubAction {
+    /// `ignore sub`: the rule only stops later rules from matching.
+    Ignore,
+    /// One glyph for each input glyph: a single glyph, or a set as long as
+    /// the input set.
+    Single(GlyphSet),
+    /// A sequence of glyphs for one; empty for `by NULL`.
+    Multiple(Vec<String>),
+    /// `from`: alternates for one glyph.
+    Alternate(GlyphSet),
+    /// One glyph for a sequence.
+    Ligature(String),
+    /// The named lookups applied to each input glyph.
+    Lookups(Vec<Option<String>>),
+}
+
+/// A `pos` rule: one glyph set for single positioning, two for a pair.
+#[derive(Clone, Debug)]
+pub struct Positioning {
+    pub glyphs: Vec<GlyphSet>,
+    /// The adjustment of each glyph set.
+    pub values: Vec<Option<ValueRecord>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ValueRecord {
+    pub x_placement: i16,
+    pub y_placement: i16,
+    pub x_advance: i16,
+    pub y_advance: i16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Class(String),
+    Number(i64),
+    Str(String),
+    Punct(char),
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._-*+^|~\\".contains(c)
+}
+
+/// Split feature code into tokens, each with its line number.
+fn tokenize(code: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = code.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c == '\n' {
+            line += 1;
+            chars.next();
+        } else if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            while chars.peek().is_some_and(|&c| c != '\n') {
+                chars.next();
+            }
+        } else if c == '"' {
+            chars.next();
+            let start = line;
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        s.push(c);
+                    }
+                    None => return Err(format!("line {}: unterminated string", start)),
+                }
+            }
+            tokens.push((Token::Str(s), start));
+        } else if c == '@' || is_name_char(c) {
+            chars.next();
+            let mut s = String::new();
+            while let Some(&c) = chars.peek() {
+                if !is_name_char(c) {
+                    break;
+                }
+                s.push(c);
+                chars.next();
+            }
+            let token = if c == '@' {
+                Token::Class(s)
+            } else {
+                let word = format!("{}{}", c, s);
+                match word.parse() {
+                    Ok(n) => Token::Number(n),
+                    Err(_) if c == '-' && s.is_empty() => Token::Punct('-'),
+                    Err(_) => Token::Name(word.trim_start_matches('\\').to_string()),
+                }
+            };
+            tokens.push((token, line));
+        } else if "{}[]<>;,'=()".contains(c) {
+            chars.next();
+            tokens.push((Token::Punct(c), line));
+        } else {
+            return Err(format!("line {}: unexpected character {:?}", line, c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Feature or class code with glyph names replaced. Comments, strings and
+/// class names are left alone.
+pub fn rename_glyphs_in_code(code: &str, renames: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        result.push(c);
+        if c == '#' || c == '"' {
+            let end = if c == '#' { '\n' } else { '"' };
+            for c in chars.by_ref() {
+                result.push(c);
+                if c == end {
+                    break;
+                }
+            }
+        } else if c == '@' || is_name_char(c) {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if !is_name_char(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if c == '@' {
+                result.push_str(&word);
+                continue;
+            }
+            result.pop();
+            let word = format!("{}{}", c, word);
+            let (escape, name) = match word.strip_prefix('\\') {
+                Some(name) => ("\\", name),
+                None => ("", word.as_str()),
+            };
+            match renames.get(name) {
+                Some(new_name) => {
+                    result.push_str(escape);
+                    result.push_str(new_name);
+                }
+                None => result.push_str(&word),
+            }
+        }
+    }
+    result
+}
+
+const LOOKUP_FLAGS: &[(&str, u16)] = &[
+    ("RightToLeft", 1),
+    ("IgnoreBaseGlyphs", 2),
+    ("IgnoreLigatures", 4),
+    ("IgnoreMarks", 8),
+];
+
+const UNSUPPORTED: &[&str] = &[
+    "rsub",
+    "reversesub",
+    "markClass",
+    "anchorDef",
+    "valueRecordDef",
+    "table",
+    "include",
+    "featureNames",
+    "cvParameters",
+    "parameters",
+    "sizemenuname",
+    "anon",
+    "anonymous",
+];
+
+/// Where statements are being parsed, which decides the ones allowed.
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    TopLevel,
+    Feature,
+    Lookup,
+}
+
+struct Parser<'a> {
+    glyphs: HashSet<&'a str>,
+    classes: HashMap<String, GlyphSet>,
+    file: FeatureFile,
+    errors: Vec<String>,
+    /// The block being parsed, for error messages.
+    context: String,
+    /// The feature being parsed, if any.
+    feature: Option<String>,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&mut self, line: usize, msg: String) {
+        self.errors
+            .push(format!("{}, line {}: {}", self.context, line, msg));
+    }
+
+    /// The line of the token just read.
+    fn last_line(&self) -> usize {
+        self.tokens[self.pos.max(1) - 1].1
+    }
+
+    fn line(&self) -> usize {
+        match self.tokens.get(self.pos).or_else(|| self.tokens.last()) {
+            Some((_, line)) => *line,
+            None => 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.0)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.peek().cloned().ok_or("unexpected end of code")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn is_word(&self, words: &[&str]) -> bool {
+        matches!(self.peek(), Some(Token::Name(n)) if words.contains(&n.as_str()))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            t => Err(format!("expected '{}', found {}", c, describe(&t))),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Name(n) => Ok(n),
+            t => Err(format!("expected a name, found {}", describe(&t))),
+        }
+    }
+
+    /// Parse one block of code, recording any errors.
+    fn parse_block(&mut self, context: String, code: &str, feature: Option<&str>) {
+        self.context = context;
+        self.pos = 0;
+        self.tokens = match tokenize(code) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                let context = self.context.clone();
+                self.errors.push(format!("{}, {}", context, e));
+                return;
+            }
+        };
+        let result = match feature {
+            Some(tag) => {
+                self.feature = Some(tag.to_string());
+                let result = self.statements(Scope::Feature);
+                self.feature = None;
+                result.map(|statements| {
+                    self.file.blocks.push(Block::Feature(FeatureBlock {
+                        tag: tag.to_string(),
+                        statements,
+                    }))
+                })
+            }
+            None => self.statements(Scope::TopLevel).map(|_| ()),
+        };
+        let result = result.and_then(|_| match self.peek() {
+            Some(t) => Err(format!("unexpected {}", describe(t))),
+            None => Ok(()),
+        });
+        if let Err(e) = result {
+            let line = self.line();
+            self.error(line, e);
+        }
+    }
+
+    /// Parse statements up to a closing brace or the end of the code.
+    /// Top-level blocks go straight into the file.
+    fn statements(&mut self, scope: Scope) -> Result<Vec<Statement>, String> {
+        let mut statements = Vec::new();
+        while let Some(token) = self.peek().cloned() {
+            let line = self.line();
+            match token {
+                Token::Punct('}') => break,
+                Token::Punct(';') => self.pos += 1,
+                Token::Class(name) => {
+                    self.pos += 1;
+                    self.expect('=')?;
+                    let glyphs = self.glyph_set()?;
+                    self.expect(';')?;
+                    self.classes.insert(name, glyphs);
+                }
+                Token::Name(word) => {
+                    self.pos += 1;
+                    let statement = self.statement(&word, scope, line)?;
+                    self.expect(';')?;
+                    statements.extend(statement);
+                }
+                t => return Err(format!("unexpected {}", describe(&t))),
+            }
+        }
+        Ok(statements)
+    }
+
+    fn statement(
+        &mut self,
+        word: &str,
+        scope: Scope,
+        line: usize,
+    ) -> Result<Vec<Statement>, String> {
+        if UNSUPPORTED.contains(&word) {
+            return Err(format!("'{}' is not supported", word));
+        }
+        let in_rules = scope != Scope::TopLevel;
+        let statement = match word {
+            "languagesystem" if scope == Scope::TopLevel => {
+                let script = self.name()?;
+                let lang = self.name()?;
+                self.file.language_systems.push((script, lang));
+                vec![]
+            }
+            "feature" if scope == Scope::TopLevel => {
+                let tag = self.name()?;
+                self.feature = Some(tag.clone());
+                let statements = self.braced(&tag, Scope::Feature);
+                self.feature = None;
+                let statements = statements?;
+                self.file
+                    .blocks
+                    .push(Block::Feature(FeatureBlock { tag, statements }));
+                vec![]
+            }
+            "feature" if scope == Scope::Feature => {
+                let tag = self.name()?;
+                if self.feature.as_deref() != Some("aalt") {
+                    self.error(line, "features can only be referenced in aalt".to_string());
+                }
+                vec![Statement::FeatureRef(tag)]
+            }
+            "lookup" if scope != Scope::Lookup => {
+                let name = self.name()?;
+                if self.is_word(&["useExtension"]) {
+                    self.pos += 1;
+                }
+                if self.is_punct('{') {
+                    let statements = self.braced(&name, Scope::Lookup)?;
+                    let lookup = LookupBlock { name, statements };
+                    if scope == Scope::TopLevel {
+                        self.file.blocks.push(Block::Lookup(lookup));
+                        vec![]
+                    } else {
+                        vec![Statement::Lookup(lookup)]
+                    }
+                } else if scope == Scope::Feature {
+                    vec![Statement::LookupRef(name)]
+                } else {
+                    return Err(format!("lookup {} has no body", name));
+                }
+            }
+            "script" if in_rules => vec![Statement::Script(self.name()?)],
+            "language" if in_rules => {
+                let tag = self.name()?;
+                let mut include_dflt = true;
+                while self.is_word(&["exclude_dflt", "include_dflt", "exclude", "include"]) {
+                    include_dflt = !self.is_word(&["exclude_dflt", "exclude"]);
+                    self.pos += 1;
+                }
+                if self.is_word(&["required"]) {
+                    return Err("required languages are not supported".to_string());
+                }
+                vec![Statement::Language { tag, include_dflt }]
+            }
+            "lookupflag" if in_rules => {
+                let mut flag = 0;
+                loop {
+                    match self.peek().cloned() {
+                        Some(Token::Number(n)) => {
+                            self.pos += 1;
+                            flag |= n as u16;
+                        }
+                        Some(Token::Name(n)) => {
+                            let bit = LOOKUP_FLAGS.iter().find(|(name, _)| *name == n);
+                            match bit {
+                                Some((_, bit)) => flag |= bit,
+                                None => return Err(format!("lookup flag {} is not supported", n)),
+                            }
+                            self.pos += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                vec![Statement::LookupFlag(flag)]
+            }
+            "subtable" if in_rules => vec![],
+            "sub" | "substitute" if in_rules => {
+                vec![Statement::Substitute(self.substitution(false, line)?)]
+            }
+            "pos" | "position" if in_rules => vec![Statement::Position(self.positioning()?)],
+            "enum" | "enumerate" if in_rules => {
+                let word = self.name()?;
+                if word != "pos" && word != "position" {
+                    return Err(format!("expected pos after {}", word));
+                }
+                vec![Statement::Position(self.positioning()?)]
+            }
+            "ignore" if in_rules => {
+                let word = self.name()?;
+                if word != "sub" && word != "substitute" {
+                    return Err(format!("ignore {} is not supported", word));
+                }
+                // Each context is a rule of its own.
+                let mut rules = vec![Statement::Substitute(self.substitution(true, line)?)];
+                while self.is_punct(',') {
+                    self.pos += 1;
+                    rules.push(Statement::Substitute(self.substitution(true, line)?));
+                }
+                rules
+            }
+            _ => return Err(format!("unexpected '{}'", word)),
+        };
+        Ok(statement)
+    }
+
+    /// Parse `{ statements } name`, the body and end of a feature or
+    /// lookup block.
+    fn braced(&mut self, name: &str, scope: Scope) -> Result<Vec<Statement>, String> {
+        self.expect('{')?;
+        let statements = self.statements(scope)?;
+        self.expect('}')?;
+        let end = self.name()?;
+        if end != name {
+            return Err(format!("block {} ends with {}", name, end));
+        }
+        Ok(statements)
+    }
+
+    /// Check a glyph name against the font.
+    fn glyph(&mut self, name: String) -> String {
+        if !self.glyphs.contains(name.as_str()) {
+            let line = self.last_line();
+            self.error(line, format!("unknown glyph {}", name));
+        }
+        name
+    }
+
+    fn class(&mut self, name: &str) -> GlyphSet {
+        match self.classes.get(name) {
+            Some(glyphs) => glyphs.clone(),
+            None => {
+                let line = self.last_line();
+                self.error(line, format!("unknown class @{}", name));
+                Vec::new()
+            }
+        }
+    }
+
+    /// A glyph, a class name, or a bracketed list of glyphs and classes.
+    fn glyph_set(&mut self) -> Result<GlyphSet, String> {
+        match self.next()? {
+            Token::Name(name) => Ok(vec![self.glyph(name)]),
+            Token::Class(name) => Ok(self.class(&name)),
+            Token::Punct('[') => {
+                let mut glyphs = Vec::new();
+                loop {
+                    match self.next()? {
+                        Token::Punct(']') => break,
+                        Token::Name(name) => glyphs.push(self.glyph(name)),
+                        Token::Class(name) => glyphs.extend(self.class(&name)),
+                        Token::Punct('-') => return Err("glyph ranges are not supported".into()),
+                        t => return Err(format!("unexpected {} in a class", describe(&t))),
+                    }
+                }
+                Ok(glyphs)
+            }
+            t => Err(format!("expected glyphs, found {}", describe(&t))),
+        }
+    }
+
+    fn is_glyph_set(&self) -> bool {
+        match self.peek() {
+            Some(Token::Name(n)) => !["by", "from", "lookup"].contains(&n.as_str()),
+            Some(Token::Class(_)) | Some(Token::Punct('[')) => true,
+            _ => false,
+        }
+    }
+
+    /// The rest of a `sub` rule, or one context of an `ignore sub` rule.
+    fn substitution(&mut self, ignore: bool, line: usize) -> Result<Substitution, String> {
+        // Each glyph set, whether it is marked, and the lookups applied to it.
+        let mut items: Vec<(GlyphSet, bool, Option<String>)> = Vec::new();
+        while self.is_glyph_set() {
+            let glyphs = self.glyph_set()?;
+            let marked = self.is_punct('\'');
+            if marked {
+                self.pos += 1;
+            }
+            let mut lookup = None;
+            if self.is_word(&["lookup"]) {
+                if !marked {
+                    return Err("lookups can only be applied to marked glyphs".to_string());
+                }
+                self.pos += 1;
+                lookup = Some(self.name()?);
+                if self.is_word(&["lookup"]) {
+                    return Err("only one lookup can be applied to a glyph".to_string());
+                }
+            }
+            items.push((glyphs, marked, lookup));
+        }
+        if items.is_empty() {
+            return Err("expected glyphs".to_string());
+        }
+        let contextual = items.iter().any(|i| i.1);
+        let (backtrack, input, lookahead) = if contextual {
+            let first = items.iter().position(|i| i.1).unwrap();
+            let count = items[first..].iter().take_while(|i| i.1).count();
+            if items[first + count..].iter().any(|i| i.1) {
+                return Err("marked glyphs must be consecutive".to_string());
+            }
+            let lookahead = items.split_off(first + count);
+            let input = items.split_off(first);
+            (items, input, lookahead)
+        } else {
+            (Vec::new(), items, Vec::new())
+        };
+        let lookups: Vec<Option<String>> = input.iter().map(|i| i.2.clone()).collect();
+        let sets = |items: Vec<(GlyphSet, bool, Option<String>)>| -> Vec<GlyphSet> {
+            items.into_iter().map(|i| i.0).collect()
+        };
+        let (backtrack, input, lookahead) = (sets(backtrack), sets(input), sets(lookahead));
+        let has_lookups = lookups.iter().any(Option::is_some);
+
+        let action = if ignore {
+            if !contextual {
+                return Err("ignore rules need marked glyphs".to_string());
+            }
+            SubAction::Ignore
+        } else if has_lookups {
+            SubAction::Lookups(lookups)
+        } else if self.is_word(&["from"]) {
+            self.pos += 1;
+            let alternates = self.glyph_set()?;
+            if input.len() != 1 || input[0].len() != 1 {
+                return Err("alternates must be for a single glyph".to_string());
+            }
+            SubAction::Alternate(alternates)
+        } else if self.is_word(&["by"]) {
+            self.pos += 1;
+            let mut replacement = Vec::new();
+            if self.is_word(&["NULL"]) {
+                self.pos += 1;
+            } else {
+                while self.is_glyph_set() {
+                    replacement.push(self.glyph_set()?);
+                }
+            }
+            self.substitution_action(&input, replacement)?
+        } else {
+            return Err("expected by or from".to_string());
+        };
+        if contextual {
+            let mut empty = backtrack.iter().chain(&input).chain(&lookahead);
+            if empty.any(Vec::is_empty) {
+                self.error(line, "empty glyph set in a contextual rule".to_string());
+            }
+        }
+        Ok(Substitution {
+            backtrack,
+            input,
+            lookahead,
+            contextual,
+            action,
+        })
+    }
+
+    /// Decide the kind of substitution from the shapes of the input and
+    /// the replacement.
+    fn substitution_action(
+        &self,
+        input: &[GlyphSet],
+        mut replacement: Vec<GlyphSet>,
+    ) -> Result<SubAction, String> {
+        let single = |set: &GlyphSet| set.len() == 1;
+        if input.len() == 1 && replacement.len() == 1 {
+            let (from, to) = (&input[0], &replacement[0]);
+            if to.len() == 1 || to.len() == from.len() {
+                Ok(SubAction::Single(replacement.pop().unwrap()))
+            } else {
+                Err(format!(
+                    "{} glyphs replaced by {} glyphs",
+                    from.len(),
+                    to.len()
+                ))
+            }
+        } else if input.len() == 1 {
+            if !single(&input[0]) || !replacement.iter().all(single) {
+                return Err("a glyph sequence must replace a single glyph".to_string());
+            }
+            Ok(SubAction::Multiple(
+                replacement
+                    .into_iter()
+                    .map(|mut s| s.pop().unwrap())
+                    .collect(),
+            ))
+        } else if replacement.len() == 1 && single(&replacement[0]) {
+            Ok(SubAction::Ligature(
+                replacement.pop().unwrap().pop().unwrap(),
+            ))
+        } else {
+            Err("a glyph sequence must be replaced by a single glyph".to_string())
+        }
+    }
+
+    /// The rest of a `pos` rule.
+    fn positioning(&mut self) -> Result<Positioning, String> {
+        let mut glyphs = Vec::new();
+        let mut values = Vec::new();
+        while self.is_glyph_set() {
+            glyphs.push(self.glyph_set()?);
+            if self.is_punct('\'') {
+                return Err("contextual positioning is not supported".to_string());
+            }
+            values.push(self.value_record()?);
+        }
+        if self.is_word(&["cursive", "base", "ligature", "mark"]) {
+            return Err("attachment positioning is not supported".to_string());
+        }
+        match (glyphs.len(), values.as_slice()) {
+            (1, [Some(_)]) => {}
+            // `pos a b -10;` adjusts the first glyph.
+            (2, [None, Some(_)]) => values.swap(0, 1),
+            (2, [Some(_), _]) => {}
+            _ => return Err("expected a glyph with a value, or a pair".to_string()),
+        }
+        Ok(Positioning { glyphs, values })
+    }
+
+    /// A value after a glyph: a number for the advance, or four numbers
+    /// in angle brackets.
+    fn value_record(&mut self) -> Result<Option<ValueRecord>, String> {
+        let number = |p: &mut Self| match p.next()? {
+            Token::Number(n) => Ok(n as i16),
+            t => Err(format!("expected a number, found {}", describe(&t))),
+        };
+        match self.peek() {
+            Some(Token::Number(_)) => {
+                let x_advance = number(self)?;
+                Ok(Some(ValueRecord {
+                    x_advance,
+                    ..ValueRecord::default()
+                }))
+            }
+            Some(Token::Punct('<')) => {
+                self.pos += 1;
+                if let Some(Token::Name(n)) = self.peek() {
+                    return Err(format!("named value record {} is not supported", n));
+                }
+                let record = ValueRecord {
+                    x_placement: number(self)?,
+                    y_placement: number(self)?,
+                    x_advance: number(self)?,
+                    y_advance: number(self)?,
+                };
+                self.expect('>')?;
+                Ok(Some(record))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Parse the feature code of the font: its prefixes, classes and enabled
+/// features. Glyph and class names are checked against the font, and all
+/// the problems found are returned.
+pub fn parse_features(font: &Font) -> Result<FeatureFile, Vec<String>> {
+    let mut parser = Parser {
+        glyphs: font.glyphs.iter().map(|g| g.glyphname.as_str()).collect(),
+        classes: HashMap::new(),
+        file: FeatureFile::default(),
+        errors: Vec::new(),
+        context: String::new(),
+        feature: None,
+        tokens: Vec::new(),
+        pos: 0,
+    };
+    for prefix in font.feature_prefixes.iter().flatten() {
+        if !prefix.is_disabled() {
+            parser.parse_block(format!("prefix {}", prefix.name), &prefix.code, None);
+        }
+    }
+    for class in font.classes.iter().flatten() {
+        if class.is_disabled() {
+            continue;
+        }
+        parser.context = format!("class {}", class.name);
+        let mut glyphs = Vec::new();
+        for name in class.glyph_names() {
+            if !parser.glyphs.contains(name) {
+                let context = parser.context.clone();
+                parser
+                    .errors
+                    .push(format!("{}: unknown glyph {}", context, name));
+            }
+            glyphs.push(name.to_string());
+        }
+        parser.classes.insert(class.name.clone(), glyphs);
+    }
+    for feature in font.features.iter().flatten() {
+        if !feature.is_disabled() {
+            let context = format!("feature {}", feature.name);
+            parser.parse_block(context, &feature.code, Some(&feature.name));
+        }
+    }
+    if parser.errors.is_empty() {
+        Ok(parser.file)
+    } else {
+        Err(parser.errors)
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Name(n) => format!("'{}'", n),
+        Token::Class(n) => format!("'@{}'", n),
+        Token::Number(n) => format!("'{}'", n),
+        Token::Str(s) => format!("{:?}", s),
+        Token::Punct(c) => format!("'{}'", c),
+    }
+}
+
+/// Whether a character is an uppercase or titlecase letter.
+fn is_upper(c: char) -> bool {
+    c.is_uppercase() || (c.is_alphabetic() && !c.is_lowercase() && c.to_lowercase().ne([c]))
+}
+
+/// The glyphs Glyphs puts in an automatic class, in font order. For
+/// `Uppercase` and `Lowercase`, these are the exported letters of that case,
+/// judged by the code point of the glyph or, for a suffixed glyph like
+/// `U.i`, of the glyph named before the first dot. For any other name, they
+/// are the exported glyphs with that suffix, so `ss01` gets `a.ss01` and
+/// `zero.ss01.tf`. `None` if nothing in the font defines the class.
+pub fn automatic_class(font: &Font, name: &str) -> Option<Vec<String>> {
+    let case: Option<fn(char) -> bool> = match name {
+        "Uppercase" => Some(is_upper),
+        "Lowercase" => Some(char::is_lowercase),
+        _ => None,
+    };
+    let exported = font.glyphs.iter().filter(|g| exported(g));
+    let glyphs: Vec<String> = match case {
+        Some(case) => {
+            let chars: HashMap<&str, char> = font
+                .glyphs
+                .iter()
+                .filter_map(|g| Some((g.glyphname.as_str(), first_char(g)?)))
+                .collect();
+            exported
+                .filter(|g| {
+                    let base = g.glyphname.split('.').next().unwrap_or("");
+                    let c = first_char(g).or_else(|| chars.get(base).copied());
+                    c.is_some_and(|c| c.is_alphabetic() && case(c))
+                })
+                .map(|g| g.glyphname.clone())
+                .collect()
+        }
+        None => exported
+            .filter(|g| g.glyphname.split('.').skip(1).any(|s| s == name))
+            .map(|g| g.glyphname.clone())
+            .collect(),
+    };
+    if glyphs.is_empty() && case.is_none() {
+        None
+    } else {
+        Some(glyphs)
+    }
+}
+
+fn first_char(glyph: &Glyph) -> Option<char> {
+    glyph.unicodes().first().and_then(|&u| char::from_u32(u))
+}
+
+/// Regenerate the code of the automatic classes, returning the names of
+/// the classes that changed.
+pub fn update_automatic_classes(font: &mut Font) -> Vec<String> {
+    let mut changed = Vec::new();
+    let updates: Vec<(usize, String)> = font
+        .classes
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter(|(_, c)| c.is_automatic())
+        .filter_map(|(i, c)| Some((i, automatic_class(font, &c.name)?.join(" "))))
+        .collect();
+    if let Some(classes) = &mut font.classes {
+        for (i, code) in updates {
+            let class = &mut classes[i];
+            if class.glyph_names().ne(code.split_whitespace()) {
+                changed.push(class.name.clone());
+            }
+            class.code = code;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn font(prefix: &str, features: &str) -> Font {
+        let source = format!(
+            "{{fontMaster = ({{id = m; weightValue = 400;}});
+glyphs = ({{glyphname = a; layers = ();}}, {{glyphname = a.alt; layers = ();}},
+{{glyphname = f; layers = ();}}, {{glyphname = i; layers = ();}},
+{{glyphname = f_i; layers = ();}});
+classes = ({{name = Alt; code = \"a.alt\";}});
+featurePrefixes = ({{name = Languagesystems; code = \"{}\";}});
+features = ({});}}",
+            prefix, features
+        );
+        Font::from_plist(Plist::parse(&source).unwrap())
+    }
+
+    #[test]
+    fn parses_rules_with_classes_expanded() {
+        let fea = parse_features(&font(
+            "languagesystem DFLT dflt; languagesystem latn dflt;",
+            "{name = salt; code = \"sub [a] by @Alt;\";},
+{name = liga; code = \"sub f i by f_i;\";},
+{name = kern; code = \"pos a f -20;\";}",
+        ))
+        .unwrap();
+        assert_eq!(fea.language_systems.len(), 2);
+        assert_eq!(
+            fea.language_systems[1],
+            ("latn".to_string(), "dflt".to_string())
+        );
+        let statements = |i: usize| match &fea.blocks[i] {
+            Block::Feature(f) => f.statements.clone(),
+            Block::Lookup(_) => panic!("expected a feature"),
+        };
+        match &statements(0)[..] {
+            [Statement::Substitute(s)] => {
+                assert_eq!(s.input, [vec!["a".to_string()]]);
+                match &s.action {
+                    SubAction::Single(glyphs) => assert_eq!(glyphs, &["a.alt"]),
+                    action => panic!("unexpected {:?}", action),
+                }
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &statements(1)[..] {
+            [Statement::Substitute(s)] => {
+                assert_eq!(s.input.len(), 2);
+                assert!(matches!(&s.action, SubAction::Ligature(g) if g == "f_i"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &statements(2)[..] {
+            [Statement::Position(p)] => {
+                assert_eq!(p.glyphs.len(), 2);
+                assert_eq!(p.values[0].unwrap().x_advance, -20);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_unknown_names_with_their_line() {
+        let errors = parse_features(&font(
+            "",
+            "{name = liga; code = \"sub f i by f_i;\nsub f l by f_l;\nsub @Missing by a;\";}",
+        ))
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "feature liga, line 2: unknown glyph l",
+                "feature liga, line 2: unknown glyph f_l",
+                "feature liga, line 3: unknown class @Missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn renames_glyphs_but_not_comments_or_classes() {
+        let renames: HashMap<String, String> = vec![("a".to_string(), "b".to_string())]
+            .into_iter()
+            .collect();
+        let code = "sub a by a.alt; # a\n@a = [a];";
+        assert_eq!(
+            rename_glyphs_in_code(code, &renames),
+            "sub b by a.alt; # a\n@a = [b];"
+        );
+    }
+}
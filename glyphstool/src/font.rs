@@ -6,16 +6,19 @@
 
 use std::collections::HashMap;
 
-use kurbo::{Affine, Point};
+use kurbo::{Affine, BezPath, CubicBez, ParamCurve, Point, Rect};
 
 use crate::from_plist::FromPlist;
 use crate::plist::Plist;
 use crate::to_plist::ToPlist;
 
-#[derive(Debug, FromPlist, ToPlist)]
+#[derive(Clone, Debug, FromPlist, ToPlist)]
 pub struct Font {
     pub glyphs: Vec<Glyph>,
     pub font_master: Vec<FontMaster>,
+    pub classes: Option<Vec<FontClass>>,
+    pub feature_prefixes: Option<Vec<FeatureCode>>,
+    pub features: Option<Vec<FeatureCode>>,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
@@ -81,15 +84,68 @@ pub struct GuideLine {
     pub position: Point,
 }
 
-#[derive(Debug, FromPlist, ToPlist)]
+#[derive(Clone, Debug, FromPlist, ToPlist)]
 pub struct FontMaster {
     pub id: String,
     pub weight_value: i64,
     pub width_value: Option<i64>,
+    pub x_height: Option<f64>,
+    pub italic_angle: Option<f64>,
+    pub vertical_stems: Option<Vec<f64>>,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// A glyph class, as used in feature code.
+#[derive(Clone, Debug, FromPlist, ToPlist)]
+pub struct FontClass {
+    pub name: String,
+    /// Space-separated glyph names.
+    pub code: String,
+    #[rest]
+    pub other_stuff: HashMap<String, Plist>,
+}
+
+/// A block of feature code: a feature, whose name is its tag, or a prefix
+/// that goes before all features.
+#[derive(Clone, Debug, FromPlist, ToPlist)]
+pub struct FeatureCode {
+    pub name: String,
+    pub code: String,
     #[rest]
     pub other_stuff: HashMap<String, Plist>,
 }
 
+impl FontClass {
+    pub fn glyph_names(&self) -> impl Iterator<Item = &str> {
+        self.code.split_whitespace()
+    }
+
+    /// Whether Glyphs generates the code of the class.
+    pub fn is_automatic(&self) -> bool {
+        flag(&self.other_stuff, "automatic")
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        flag(&self.other_stuff, "disabled")
+    }
+}
+
+impl FeatureCode {
+    /// Whether Glyphs generates the code of the feature.
+    pub fn is_automatic(&self) -> bool {
+        flag(&self.other_stuff, "automatic")
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        flag(&self.other_stuff, "disabled")
+    }
+}
+
+fn flag(stuff: &HashMap<String, Plist>, key: &str) -> bool {
+    stuff.get(key).and_then(Plist::as_i64) == Some(1)
+}
+
 impl Font {
     pub fn load(path: &std::path::Path) -> Result<Font, String> {
         let contents = std::fs::read_to_string(path).map_err(|e| format!("{:?}", e))?;
@@ -104,12 +160,191 @@ impl Font {
     pub fn get_glyph_mut(&mut self, glyphname: &str) -> Option<&mut Glyph> {
         self.glyphs.iter_mut().find(|g| g.glyphname == glyphname)
     }
+
+    pub fn get_class(&self, name: &str) -> Option<&FontClass> {
+        self.classes.as_ref()?.iter().find(|c| c.name == name)
+    }
+
+    pub fn get_master(&self, id: &str) -> Option<&FontMaster> {
+        self.font_master.iter().find(|m| m.id == id)
+    }
+
+    pub fn get_master_mut(&mut self, id: &str) -> Option<&mut FontMaster> {
+        self.font_master.iter_mut().find(|m| m.id == id)
+    }
+
+    /// The value of a custom parameter of the font.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        custom_parameter(&self.other_stuff, name)
+    }
+
+    /// The paths of a layer with all components recursively flattened in.
+    ///
+    /// Components are resolved against the layer with the same id in the
+    /// referenced glyph; missing glyphs or layers are skipped. Paths of
+    /// mirrored components are reversed, so they keep their direction.
+    pub fn decompose(&self, layer: &Layer) -> Vec<Path> {
+        let mut result = Vec::new();
+        self.decompose_rec(layer, Affine::default(), 0, &mut result);
+        result
+    }
+
+    fn decompose_rec(&self, layer: &Layer, a: Affine, depth: usize, result: &mut Vec<Path>) {
+        if let Some(ref paths) = layer.paths {
+            for path in paths {
+                let mut path = path.clone();
+                path.apply_affine(a);
+                let c = a.as_coeffs();
+                if c[0] * c[3] - c[1] * c[2] < 0.0 {
+                    path.reverse();
+                }
+                result.push(path);
+            }
+        }
+        // Guard against reference cycles.
+        if depth > 16 {
+            return;
+        }
+        if let Some(ref components) = layer.components {
+            for component in components {
+                let base = self
+                    .get_glyph(&component.name)
+                    .and_then(|g| g.get_layer(&layer.layer_id));
+                if let Some(base) = base {
+                    let t = component.transform.unwrap_or_default();
+                    self.decompose_rec(base, a * t, depth + 1, result);
+                }
+            }
+        }
+    }
+
+    /// The anchors of a layer, including those propagated from its
+    /// components, as Glyphs does.
+    ///
+    /// The first component contributes all its anchors, except for `_`
+    /// attachment anchors if the layer has its own. Later components
+    /// (usually marks) contribute the anchors for stacking further marks,
+    /// that is `top` if they attach with `_top`, replacing the anchor of the
+    /// same name. The layer's own anchors take precedence.
+    pub fn anchors(&self, layer: &Layer) -> Vec<Anchor> {
+        self.anchors_rec(layer, 0)
+    }
+
+    fn anchors_rec(&self, layer: &Layer, depth: usize) -> Vec<Anchor> {
+        let mut result: Vec<Anchor> = Vec::new();
+        let mut set = |anchor: Anchor| match result.iter_mut().find(|a| a.name == anchor.name) {
+            Some(a) => a.position = anchor.position,
+            None => result.push(anchor),
+        };
+        let own = layer.anchors.as_deref().unwrap_or(&[]);
+        let own_attachment = own.iter().any(|a| a.name.starts_with('_'));
+        if depth <= 16 {
+            for (i, component) in layer.components.iter().flatten().enumerate() {
+                let base = self
+                    .get_glyph(&component.name)
+                    .and_then(|g| g.get_layer(&layer.layer_id));
+                if let Some(base) = base {
+                    let t = component.transform.unwrap_or_default();
+                    let anchors = self.anchors_rec(base, depth + 1);
+                    let has = |name: &str| anchors.iter().any(|a| a.name == name);
+                    for anchor in &anchors {
+                        let keep = match anchor.name.strip_prefix('_') {
+                            Some(_) => i == 0 && !own_attachment,
+                            None => i == 0 || has(&format!("_{}", anchor.name)),
+                        };
+                        if keep {
+                            set(Anchor {
+                                name: anchor.name.clone(),
+                                position: t * anchor.position,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        for anchor in own {
+            set(anchor.clone());
+        }
+        result
+    }
+}
+
+impl FontMaster {
+    /// The value of a custom parameter of the master.
+    pub fn custom_parameter(&self, name: &str) -> Option<&Plist> {
+        custom_parameter(&self.other_stuff, name)
+    }
+
+    /// The name of the master, as shown in Glyphs.
+    pub fn name(&self) -> &str {
+        ["name", "custom"]
+            .iter()
+            .filter_map(|key| self.other_stuff.get(*key)?.as_str())
+            .find(|name| !name.is_empty())
+            .unwrap_or("Regular")
+    }
+}
+
+fn custom_parameter<'a>(stuff: &'a HashMap<String, Plist>, name: &str) -> Option<&'a Plist> {
+    stuff
+        .get("customParameters")?
+        .as_array()?
+        .iter()
+        .find(|p| p.get("name").and_then(Plist::as_str) == Some(name))?
+        .get("value")
 }
 
 impl Glyph {
     pub fn get_layer(&self, layer_id: &str) -> Option<&Layer> {
         self.layers.iter().find(|l| l.layer_id == layer_id)
     }
+
+    pub fn get_layer_mut(&mut self, layer_id: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.layer_id == layer_id)
+    }
+
+    /// The code points of the glyph.
+    ///
+    /// Glyphs stores these as comma-separated hex, which the plist parser
+    /// reads as an integer when there are no letters in it.
+    pub fn unicodes(&self) -> Vec<u32> {
+        let s = match self.other_stuff.get("unicode") {
+            Some(Plist::String(s)) => s.clone(),
+            Some(Plist::Integer(i)) => i.to_string(),
+            _ => return Vec::new(),
+        };
+        s.split(',')
+            .filter_map(|u| u32::from_str_radix(u.trim(), 16).ok())
+            .collect()
+    }
+
+    /// Whether the glyph is a nonspacing mark, which has no advance width.
+    ///
+    /// This is the category Mark and subcategory Nonspacing of Glyphs: as
+    /// set on the glyph, or else as GlyphData has it for the combining
+    /// diacritical mark blocks, and for unencoded glyphs named like
+    /// `acutecomb.case` or `brevecomb_acutecomb`.
+    pub fn is_nonspacing_mark(&self) -> bool {
+        let category = self.other_stuff.get("category").and_then(Plist::as_str);
+        let sub_category = self.other_stuff.get("subCategory").and_then(Plist::as_str);
+        if let Some(category) = category {
+            return category == "Mark" && sub_category == Some("Nonspacing");
+        }
+        let blocks = [
+            0x0300..=0x036F,
+            0x1AB0..=0x1AFF,
+            0x1DC0..=0x1DFF,
+            0x20D0..=0x20FF,
+            0xFE20..=0xFE2F,
+        ];
+        match self.unicodes().first() {
+            Some(cp) => blocks.iter().any(|b| b.contains(cp)),
+            None => {
+                let base = self.glyphname.split('.').next().unwrap_or("");
+                base.split('_').all(|part| part.ends_with("comb"))
+            }
+        }
+    }
 }
 
 impl FromPlist for Node {
@@ -217,7 +452,193 @@ impl Path {
         self.nodes.rotate_left(delta);
     }
 
+    /// Reverse the direction of the path. On-curve nodes are retyped for
+    /// the segments that now end at them, keeping their smoothness.
     pub fn reverse(&mut self) {
         self.nodes.reverse();
+        let n = self.nodes.len();
+        let curves: Vec<bool> = (0..n)
+            .map(|i| {
+                let prev = match i {
+                    0 if self.closed => n - 1,
+                    0 => return false,
+                    _ => i - 1,
+                };
+                self.nodes[prev].node_type == NodeType::OffCurve
+            })
+            .collect();
+        for (node, curve) in self.nodes.iter_mut().zip(curves) {
+            let smooth = matches!(node.node_type, NodeType::LineSmooth | NodeType::CurveSmooth);
+            node.node_type = match (node.node_type, curve, smooth) {
+                (NodeType::OffCurve, _, _) => NodeType::OffCurve,
+                (_, true, false) => NodeType::Curve,
+                (_, true, true) => NodeType::CurveSmooth,
+                (_, false, false) => NodeType::Line,
+                (_, false, true) => NodeType::LineSmooth,
+            };
+        }
+    }
+
+    pub fn apply_affine(&mut self, a: Affine) {
+        for node in &mut self.nodes {
+            node.pt = a * node.pt;
+        }
+    }
+
+    /// The exact bounding box of the outline, or `None` for an empty path.
+    pub fn bounds(&self) -> Option<Rect> {
+        let mut result: Option<Rect> = None;
+        let mut add = |pt: Point| {
+            result = Some(match result {
+                Some(r) => r.union_pt(pt),
+                None => Rect::from_points(pt, pt),
+            });
+        };
+        for seg in self.to_bez_path().segments() {
+            let c = seg.to_cubic();
+            add(c.p0);
+            add(c.p3);
+            for t in cubic_extrema(&c) {
+                add(c.eval(t));
+            }
+        }
+        if self.nodes.len() == 1 {
+            add(self.nodes[0].pt);
+        }
+        result
+    }
+
+    /// Convert to a kurbo path.
+    ///
+    /// A single off-curve point between on-curves is treated as a quadratic.
+    pub fn to_bez_path(&self) -> BezPath {
+        let mut bez = BezPath::new();
+        let n = self.nodes.len();
+        // Closed paths in Glyphs start at the last on-curve node.
+        let start = if self.closed {
+            match self
+                .nodes
+                .iter()
+                .rposition(|node| node.node_type != NodeType::OffCurve)
+            {
+                Some(ix) => ix,
+                None => return bez,
+            }
+        } else if n == 0 {
+            return bez;
+        } else {
+            0
+        };
+        bez.move_to(self.nodes[start].pt);
+        let count = if self.closed { n } else { n - 1 };
+        let mut offs = Vec::new();
+        for i in 1..=count {
+            let node = &self.nodes[(start + i) % n];
+            match node.node_type {
+                NodeType::OffCurve => offs.push(node.pt),
+                _ => {
+                    match offs.len() {
+                        0 => bez.line_to(node.pt),
+                        1 => bez.quad_to(offs[0], node.pt),
+                        _ => bez.curve_to(offs[0], offs[offs.len() - 1], node.pt),
+                    }
+                    offs.clear();
+                }
+            }
+        }
+        if self.closed {
+            bez.close_path();
+        }
+        bez
+    }
+}
+
+/// Parameter values in (0, 1) where a cubic has a horizontal or vertical
+/// tangent.
+pub(crate) fn cubic_extrema(c: &CubicBez) -> Vec<f64> {
+    let mut result = Vec::new();
+    for &(x0, x1, x2, x3) in &[
+        (c.p0.x, c.p1.x, c.p2.x, c.p3.x),
+        (c.p0.y, c.p1.y, c.p2.y, c.p3.y),
+    ] {
+        let (d0, d1, d2) = (x1 - x0, x2 - x1, x3 - x2);
+        let a = d0 - 2.0 * d1 + d2;
+        let b = 2.0 * (d1 - d0);
+        if a.abs() < 1e-12 {
+            if b.abs() > 1e-12 {
+                result.push(-d0 / b);
+            }
+        } else {
+            let disc = b * b - 4.0 * a * d0;
+            if disc >= 0.0 {
+                let sq = disc.sqrt();
+                result.push((-b - sq) / (2.0 * a));
+                result.push((-b + sq) / (2.0 * a));
+            }
+        }
+    }
+    result.retain(|&t| t > 0.0 && t < 1.0);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurbo::Shape;
+
+    #[test]
+    fn decompose_keeps_the_direction_of_mirrored_components() {
+        let source = r#"{
+fontMaster = ({id = m; weightValue = 400;});
+glyphs = (
+{glyphname = a; layers = ({layerId = m; width = 500; paths = ({closed = 1; nodes = (
+"100 0 LINE", "100 300 OFFCURVE", "200 400 OFFCURVE", "300 400 CURVE SMOOTH", "400 0 LINE");});});},
+{glyphname = b; layers = ({layerId = m; width = 500; components = ({name = a; transform = "{-1, 0, 0, 1, 500, 0}";});});}
+);
+}"#;
+        let font = Font::from_plist(Plist::parse(source).unwrap());
+        let layer = |name| font.get_glyph(name).unwrap().get_layer("m").unwrap();
+        let original = &font.decompose(layer("a"))[0];
+        let mirrored = &font.decompose(layer("b"))[0];
+        let area = |p: &Path| p.to_bez_path().area();
+        assert!((area(original) - area(mirrored)).abs() < 1e-6);
+        let types: Vec<NodeType> = mirrored.nodes.iter().map(|n| n.node_type).collect();
+        assert_eq!(
+            types,
+            [
+                NodeType::Line,
+                NodeType::LineSmooth,
+                NodeType::OffCurve,
+                NodeType::OffCurve,
+                NodeType::Curve
+            ]
+        );
+    }
+
+    #[test]
+    fn nonspacing_marks_by_category_code_point_or_name() {
+        let source = r#"{
+fontMaster = ({id = m; weightValue = 400;});
+glyphs = (
+{glyphname = acutecomb; unicode = 0301; layers = ();},
+{glyphname = acute; unicode = 00B4; layers = ();},
+{glyphname = acutecomb.case; layers = ();},
+{glyphname = brevecomb_acutecomb; layers = ();},
+{glyphname = a_comb; layers = ();},
+{glyphname = dotcomb; category = Letter; layers = ();},
+{glyphname = dot; category = Mark; subCategory = Nonspacing; layers = ();}
+);
+}"#;
+        let font = Font::from_plist(Plist::parse(source).unwrap());
+        let marks: Vec<&str> = font
+            .glyphs
+            .iter()
+            .filter(|g| g.is_nonspacing_mark())
+            .map(|g| g.glyphname.as_str())
+            .collect();
+        assert_eq!(
+            marks,
+            ["acutecomb", "acutecomb.case", "brevecomb_acutecomb", "dot"]
+        );
     }
 }
@@ -0,0 +1,453 @@
+//! TrueType outlines: conversion of cubic curves to quadratic, and the
+//! `glyf` and `loca` tables.
+
+use std::collections::HashMap;
+
+use kurbo::{Affine, CubicBez, ParamCurve, PathEl, Point};
+
+use crate::font::Path;
+use crate::sfnt::{to_i16, Writer};
+
+/// How far, in font units, the quadratic approximation of a cubic may
+/// stray from it.
+const TOLERANCE: f64 = 1.0;
+
+/// The most quadratic pieces a cubic is split into.
+const MAX_PIECES: usize = 16;
+
+/// The outline of a glyph, before compilation.
+#[derive(Clone, Debug)]
+pub(crate) enum GlyphOutline {
+    /// Contours, as made by `quad_contours`.
+    Simple(Vec<Vec<TtPoint>>),
+    /// References to other glyphs by index, with their transforms.
+    Composite(Vec<(usize, Affine)>),
+}
+
+/// A TrueType contour point: rounded coordinates and whether the point is
+/// on the curve.
+pub(crate) type TtPoint = (f64, f64, bool);
+
+/// The control point of a quadratic approximating a cubic, matching its
+/// tangents at both ends when the cubic is nearly a quadratic.
+fn quad_control(c: CubicBez) -> Point {
+    (((c.p1.to_vec2() + c.p2.to_vec2()) * 3.0 - (c.p0.to_vec2() + c.p3.to_vec2())) * 0.25)
+        .to_point()
+}
+
+fn end_point(el: &PathEl) -> Option<Point> {
+    match *el {
+        PathEl::MoveTo(p) | PathEl::LineTo(p) | PathEl::QuadTo(_, p) | PathEl::CurveTo(_, _, p) => {
+            Some(p)
+        }
+        PathEl::ClosePath => None,
+    }
+}
+
+/// The number of quadratic pieces needed to approximate a cubic within the
+/// tolerance.
+fn quad_pieces(c: CubicBez) -> usize {
+    (1..MAX_PIECES)
+        .find(|&n| {
+            (0..n).all(|i| {
+                let part = c.subsegment(i as f64 / n as f64..(i + 1) as f64 / n as f64);
+                let ctrl = quad_control(part);
+                (1..8).all(|k| {
+                    let t = k as f64 / 8.0;
+                    let mt = 1.0 - t;
+                    let q = (part.p0.to_vec2() * (mt * mt)
+                        + ctrl.to_vec2() * (2.0 * mt * t)
+                        + part.p3.to_vec2() * (t * t))
+                        .to_point();
+                    (q - part.eval(t)).hypot() <= TOLERANCE
+                })
+            })
+        })
+        .unwrap_or(MAX_PIECES)
+}
+
+/// Approximate a cubic by `n` quadratic pieces, returned as (control, end)
+/// pairs.
+fn cubic_to_quads(c: CubicBez, n: usize) -> Vec<(Point, Point)> {
+    (0..n)
+        .map(|i| {
+            let part = c.subsegment(i as f64 / n as f64..(i + 1) as f64 / n as f64);
+            (quad_control(part), part.p3)
+        })
+        .collect()
+}
+
+fn round_pt(p: Point) -> (f64, f64) {
+    (p.x.round(), p.y.round())
+}
+
+/// The closed paths as lists of path elements, if every master has the
+/// same kinds of elements in the same order.
+fn compatible_elements(masters: &[&[Path]]) -> Option<Vec<Vec<Vec<PathEl>>>> {
+    let elements: Vec<Vec<Vec<PathEl>>> = masters
+        .iter()
+        .map(|paths| {
+            paths
+                .iter()
+                .filter(|p| p.closed)
+                .map(|p| p.to_bez_path().elements().to_vec())
+                .collect()
+        })
+        .collect();
+    let kind = |el: &PathEl| std::mem::discriminant(el);
+    let first = &elements[0];
+    let compatible = elements.iter().all(|paths| {
+        paths.len() == first.len()
+            && paths.iter().zip(first).all(|(a, b)| {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| kind(x) == kind(y))
+            })
+    });
+    if compatible {
+        Some(elements)
+    } else {
+        None
+    }
+}
+
+/// Convert paths to TrueType contours, reversed to the clockwise
+/// direction TrueType uses for outer contours. Open paths are dropped, as
+/// they enclose nothing.
+pub(crate) fn quad_contours(paths: &[Path]) -> Vec<Vec<TtPoint>> {
+    quad_contours_compatible(&[paths]).unwrap().remove(0)
+}
+
+/// Convert the paths of several masters of a glyph to TrueType contours
+/// with the same points in each, for interpolation. Each cubic is split
+/// into as many pieces as the most curved master needs, and implied
+/// on-curve points are only left out if they are implied in every master.
+/// Returns `None` if the masters aren't compatible.
+pub(crate) fn quad_contours_compatible(masters: &[&[Path]]) -> Option<Vec<Vec<Vec<TtPoint>>>> {
+    let elements = compatible_elements(masters)?;
+    let mut pieces: HashMap<(usize, usize), usize> = HashMap::new();
+    for paths in &elements {
+        for (i, path) in paths.iter().enumerate() {
+            let mut last = Point::ORIGIN;
+            for (j, el) in path.iter().enumerate() {
+                if let PathEl::CurveTo(p1, p2, p) = *el {
+                    let n = quad_pieces(CubicBez::new(last, p1, p2, p));
+                    let entry = pieces.entry((i, j)).or_insert(1);
+                    *entry = (*entry).max(n);
+                }
+                if let Some(p) = end_point(el) {
+                    last = p;
+                }
+            }
+        }
+    }
+    let mut result: Vec<Vec<Vec<TtPoint>>> = elements
+        .iter()
+        .map(|paths| {
+            paths
+                .iter()
+                .enumerate()
+                .map(|(i, path)| {
+                    let mut points: Vec<TtPoint> = Vec::new();
+                    let on = |points: &mut Vec<TtPoint>, p: Point, is_on: bool| {
+                        let (x, y) = round_pt(p);
+                        points.push((x, y, is_on));
+                    };
+                    let mut last = Point::ORIGIN;
+                    for (j, el) in path.iter().enumerate() {
+                        match *el {
+                            PathEl::MoveTo(p) | PathEl::LineTo(p) => on(&mut points, p, true),
+                            PathEl::QuadTo(p1, p) => {
+                                on(&mut points, p1, false);
+                                on(&mut points, p, true);
+                            }
+                            PathEl::CurveTo(p1, p2, p) => {
+                                let c = CubicBez::new(last, p1, p2, p);
+                                for (ctrl, end) in cubic_to_quads(c, pieces[&(i, j)]) {
+                                    on(&mut points, ctrl, false);
+                                    on(&mut points, end, true);
+                                }
+                            }
+                            PathEl::ClosePath => (),
+                        }
+                        if let Some(p) = end_point(el) {
+                            last = p;
+                        }
+                    }
+                    // The contour closes by itself; the last point repeats
+                    // the start.
+                    if points.len() > 1 {
+                        points.pop();
+                    }
+                    points.reverse();
+                    points
+                })
+                .collect()
+        })
+        .collect();
+    // Leave out on-curve points exactly halfway between two off-curve
+    // points, as TrueType implies them.
+    for i in 0..result[0].len() {
+        let n = result[0][i].len();
+        let implied = |points: &[TtPoint], k: usize| {
+            let (x, y, is_on) = points[k];
+            let prev = points[(k + n - 1) % n];
+            let next = points[(k + 1) % n];
+            is_on
+                && n > 2
+                && !prev.2
+                && !next.2
+                && prev.0 + next.0 == 2.0 * x
+                && prev.1 + next.1 == 2.0 * y
+        };
+        let keep: Vec<bool> = (0..n)
+            .map(|k| !result.iter().all(|contours| implied(&contours[i], k)))
+            .collect();
+        for contours in &mut result {
+            let mut points: Vec<TtPoint> = contours[i]
+                .iter()
+                .zip(&keep)
+                .filter(|(_, k)| **k)
+                .map(|(p, _)| *p)
+                .collect();
+            if let Some(first_on) = points.iter().position(|p| p.2) {
+                points.rotate_left(first_on);
+            }
+            contours[i] = points;
+        }
+    }
+    for contours in &mut result {
+        contours.retain(|c| c.len() >= 2);
+    }
+    Some(result)
+}
+
+/// The bounding box of points as (xMin, yMin, xMax, yMax).
+fn bounds(points: impl Iterator<Item = (f64, f64)>) -> Option<[i16; 4]> {
+    points.fold(None, |b, (x, y)| {
+        let (x, y) = (to_i16(x), to_i16(y));
+        Some(match b {
+            None => [x, y, x, y],
+            Some([x0, y0, x1, y1]) => [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+        })
+    })
+}
+
+fn write_coord(flags: &mut u8, coords: &mut Writer, delta: i16, short: u8, same: u8) {
+    if delta == 0 {
+        *flags |= same;
+    } else if delta.unsigned_abs() < 256 {
+        *flags |= short;
+        if delta > 0 {
+            *flags |= same;
+        }
+        coords.u8(delta.unsigned_abs() as u8);
+    } else {
+        coords.i16(delta);
+    }
+}
+
+fn simple_glyph(contours: &[Vec<TtPoint>], bbox: [i16; 4]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.i16(contours.len() as i16);
+    for &v in &bbox {
+        w.i16(v);
+    }
+    let mut end = 0usize;
+    for contour in contours {
+        end += contour.len();
+        w.u16((end - 1) as u16);
+    }
+    // No instructions.
+    w.u16(0);
+    let mut flags = Vec::new();
+    let mut xs = Writer::new();
+    let mut ys = Writer::new();
+    let (mut x0, mut y0) = (0i16, 0i16);
+    for &(x, y, on) in contours.iter().flatten() {
+        let (x, y) = (to_i16(x), to_i16(y));
+        let mut flag = if on { 0x01 } else { 0 };
+        write_coord(&mut flag, &mut xs, x.wrapping_sub(x0), 0x02, 0x10);
+        write_coord(&mut flag, &mut ys, y.wrapping_sub(y0), 0x04, 0x20);
+        flags.push(flag);
+        x0 = x;
+        y0 = y;
+    }
+    // Runs of equal flags are written once with a repeat count.
+    let mut i = 0;
+    while i < flags.len() {
+        let mut run = 1;
+        while i + run < flags.len() && flags[i + run] == flags[i] && run < 256 {
+            run += 1;
+        }
+        if run > 1 {
+            w.u8(flags[i] | 0x08).u8((run - 1) as u8);
+        } else {
+            w.u8(flags[i]);
+        }
+        i += run;
+    }
+    w.bytes(&xs.data).bytes(&ys.data);
+    w.data
+}
+
+fn composite_glyph(components: &[(usize, Affine)], bbox: [i16; 4]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.i16(-1);
+    for &v in &bbox {
+        w.i16(v);
+    }
+    for (i, &(glyph, transform)) in components.iter().enumerate() {
+        let [a, b, c, d, e, f] = transform.as_coeffs();
+        let (dx, dy) = (to_i16(e), to_i16(f));
+        // ARGS_ARE_XY_VALUES and ROUND_XY_TO_GRID.
+        let mut flags = 0x0002 | 0x0004;
+        let words = !(i8::MIN as i16..=i8::MAX as i16).contains(&dx)
+            || !(i8::MIN as i16..=i8::MAX as i16).contains(&dy);
+        if words {
+            flags |= 0x0001;
+        }
+        if i + 1 < components.len() {
+            flags |= 0x0020;
+        }
+        if b != 0.0 || c != 0.0 {
+            flags |= 0x0080;
+        } else if a != d {
+            flags |= 0x0040;
+        } else if a != 1.0 {
+            flags |= 0x0008;
+        }
+        w.u16(flags).u16(glyph as u16);
+        if words {
+            w.i16(dx).i16(dy);
+        } else {
+            w.u8(dx as i8 as u8).u8(dy as i8 as u8);
+        }
+        if flags & 0x0080 != 0 {
+            w.f2dot14(a).f2dot14(b).f2dot14(c).f2dot14(d);
+        } else if flags & 0x0040 != 0 {
+            w.f2dot14(a).f2dot14(d);
+        } else if flags & 0x0008 != 0 {
+            w.f2dot14(a);
+        }
+    }
+    w.data
+}
+
+/// Whether a transform can be used for a TrueType component: its scale
+/// must fit in 2.14 fixed point, and it mustn't flip the contours, which
+/// would reverse their direction.
+pub(crate) fn component_ok(transform: Affine) -> bool {
+    let c = transform.as_coeffs();
+    c[..4].iter().all(|v| (-2.0..2.0).contains(v)) && c[0] * c[3] - c[1] * c[2] > 0.0
+}
+
+/// Limits of the glyphs, for the `maxp` table.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct MaxStats {
+    pub points: u16,
+    pub contours: u16,
+    pub composite_points: u16,
+    pub composite_contours: u16,
+    pub component_elements: u16,
+    pub component_depth: u16,
+}
+
+/// The compiled `glyf` and `loca` tables.
+pub(crate) struct Glyf {
+    pub glyf: Vec<u8>,
+    pub loca: Vec<u8>,
+    /// The bounding box of each glyph, if it has any points.
+    pub bounds: Vec<Option<[i16; 4]>>,
+    pub stats: MaxStats,
+}
+
+/// The points of a glyph with its components resolved, the number of
+/// contours, and the depth of nesting.
+type Resolved = (Vec<(f64, f64)>, usize, usize);
+
+/// Resolve the components of a glyph, remembering the results in `cache`.
+fn resolve(
+    ix: usize,
+    simple: &[Vec<Vec<TtPoint>>],
+    outlines: &[GlyphOutline],
+    cache: &mut [Option<Resolved>],
+) -> Resolved {
+    if let Some(resolved) = &cache[ix] {
+        return resolved.clone();
+    }
+    // Guard against reference cycles while this glyph is resolved.
+    cache[ix] = Some((Vec::new(), 0, 0));
+    let resolved = match &outlines[ix] {
+        GlyphOutline::Simple(_) => {
+            let points = simple[ix].iter().flatten().map(|&(x, y, _)| (x, y));
+            (points.collect(), simple[ix].len(), 0)
+        }
+        GlyphOutline::Composite(components) => {
+            let mut points = Vec::new();
+            let mut contours = 0;
+            let mut depth = 0;
+            for &(glyph, transform) in components {
+                let (pts, n, d) = resolve(glyph, simple, outlines, cache);
+                let c = transform.as_coeffs();
+                let offset = (c[4].round(), c[5].round());
+                let linear = Affine::new([c[0], c[1], c[2], c[3], 0.0, 0.0]);
+                for (x, y) in pts {
+                    let p = linear * Point::new(x, y);
+                    points.push((p.x + offset.0, p.y + offset.1));
+                }
+                contours += n;
+                depth = depth.max(d + 1);
+            }
+            (points, contours, depth)
+        }
+    };
+    cache[ix] = Some(resolved.clone());
+    resolved
+}
+
+/// Compile outlines into `glyf` and long-format `loca` tables.
+pub(crate) fn build_glyf(outlines: &[GlyphOutline]) -> Glyf {
+    let simple: Vec<Vec<Vec<TtPoint>>> = outlines
+        .iter()
+        .map(|o| match o {
+            GlyphOutline::Simple(contours) => contours.clone(),
+            GlyphOutline::Composite(_) => Vec::new(),
+        })
+        .collect();
+    let mut cache = vec![None; outlines.len()];
+    let mut glyf = Writer::new();
+    let mut loca = Writer::new();
+    let mut all_bounds = Vec::new();
+    let mut stats = MaxStats::default();
+    for (i, outline) in outlines.iter().enumerate() {
+        loca.u32(glyf.len() as u32);
+        let (points, contours, depth) = resolve(i, &simple, outlines, &mut cache);
+        let bbox = bounds(points.iter().copied());
+        all_bounds.push(bbox);
+        let bbox = match bbox {
+            Some(bbox) => bbox,
+            None => continue,
+        };
+        match outline {
+            GlyphOutline::Simple(_) => {
+                stats.points = stats.points.max(points.len() as u16);
+                stats.contours = stats.contours.max(contours as u16);
+                glyf.bytes(&simple_glyph(&simple[i], bbox));
+            }
+            GlyphOutline::Composite(components) => {
+                stats.composite_points = stats.composite_points.max(points.len() as u16);
+                stats.composite_contours = stats.composite_contours.max(contours as u16);
+                stats.component_elements = stats.component_elements.max(components.len() as u16);
+                stats.component_depth = stats.component_depth.max(depth as u16);
+                glyf.bytes(&composite_glyph(components, bbox));
+            }
+        }
+        glyf.align(4);
+    }
+    loca.u32(glyf.len() as u32);
+    Glyf {
+        glyf: glyf.data,
+        loca: loca.data,
+        bounds: all_bounds,
+        stats,
+    }
+}
@@ -0,0 +1,754 @@
+//! Interpolation between masters laid out on a grid over the font's axes.
+//!
+//! Any number of values can be used on each axis, as long as there is a
+//! master for each combination.
+
+use kurbo::Affine;
+
+use crate::font::{Anchor, Component, Font, FontMaster, Glyph, Layer, Node, Path};
+use crate::measure::{stem_at_heights, stem_heights};
+use crate::plist::Plist;
+use crate::ufo::{axes, number_plist};
+
+/// A location in design space: the value on each of the font's axes, by
+/// tag, in the order of the axes.
+pub type Location = Vec<(String, f64)>;
+
+/// The keys of a master's value on each axis, in order, and the value when
+/// the key is missing.
+pub(crate) const MASTER_KEYS: [(&str, f64); 6] = [
+    ("weightValue", 100.0),
+    ("widthValue", 100.0),
+    ("customValue", 0.0),
+    ("customValue1", 0.0),
+    ("customValue2", 0.0),
+    ("customValue3", 0.0),
+];
+
+/// The keys of an instance's value on each axis, in order, and the value
+/// when the key is missing.
+pub(crate) const INSTANCE_KEYS: [(&str, f64); 6] = [
+    ("interpolationWeight", 100.0),
+    ("interpolationWidth", 100.0),
+    ("interpolationCustom", 0.0),
+    ("interpolationCustom1", 0.0),
+    ("interpolationCustom2", 0.0),
+    ("interpolationCustom3", 0.0),
+];
+
+/// The location of a master in design space.
+pub fn master_location(font: &Font, layer_id: &str) -> Option<Location> {
+    let master = font.get_master(layer_id)?;
+    let location = axes(font)
+        .into_iter()
+        .zip(&MASTER_KEYS)
+        .enumerate()
+        .map(|(i, (axis, &(key, default)))| {
+            let value = match i {
+                0 => master.weight_value as f64,
+                1 => master.width_value.map(|w| w as f64).unwrap_or(default),
+                _ => master
+                    .other_stuff
+                    .get(key)
+                    .and_then(Plist::as_f64)
+                    .unwrap_or(default),
+            };
+            (axis.tag, value)
+        })
+        .collect();
+    Some(location)
+}
+
+/// The location of an instance, from the `instances` of the font.
+pub fn instance_location(font: &Font, instance: &Plist) -> Location {
+    axes(font)
+        .into_iter()
+        .zip(&INSTANCE_KEYS)
+        .map(|(axis, &(key, default))| {
+            let value = instance.get(key).and_then(Plist::as_f64);
+            (axis.tag, value.unwrap_or(default))
+        })
+        .collect()
+}
+
+/// The value on an axis, given by tag.
+pub fn axis_value(location: &Location, tag: &str) -> Option<f64> {
+    location.iter().find(|(t, _)| t == tag).map(|&(_, v)| v)
+}
+
+/// The weight and width of a location, for evaluating parameters, with the
+/// axis defaults for axes the font doesn't have.
+pub(crate) fn weight_width(location: &Location) -> (f64, f64) {
+    (
+        axis_value(location, "wght").unwrap_or(AXIS_DEFAULTS[0].1),
+        axis_value(location, "wdth").unwrap_or(AXIS_DEFAULTS[1].1),
+    )
+}
+
+/// A location for messages, like `wght 400, wdth 100`.
+pub fn format_location(location: &Location) -> String {
+    let values: Vec<String> = location
+        .iter()
+        .map(|(tag, v)| format!("{} {}", tag, v))
+        .collect();
+    values.join(", ")
+}
+
+/// The default location of axes registered in OpenType.
+const AXIS_DEFAULTS: [(&str, f64); 2] = [("wght", 400.0), ("wdth", 100.0)];
+
+/// Whether a location is at the default of every registered axis.
+fn at_axis_defaults(location: &Location) -> bool {
+    location.iter().all(|(tag, v)| {
+        AXIS_DEFAULTS
+            .iter()
+            .all(|(default_tag, default)| tag != default_tag || v == default)
+    })
+}
+
+/// The default master for variation: the one named by the `Variable Font
+/// Origin` parameter, or else the one at the axis defaults, or else the
+/// first.
+pub(crate) fn origin(font: &Font) -> Option<&FontMaster> {
+    let named = font
+        .custom_parameter("Variable Font Origin")
+        .and_then(Plist::as_str);
+    named
+        .and_then(|n| font.font_master.iter().find(|m| m.name() == n || m.id == n))
+        .or_else(|| {
+            let mut masters = font.font_master.iter();
+            masters.find(|m| master_location(font, &m.id).is_some_and(|l| at_axis_defaults(&l)))
+        })
+        .or_else(|| font.font_master.first())
+}
+
+/// Find the pair of values bracketing `x`, and the parameter between them.
+///
+/// Values outside the range use the outermost pair, so the parameter is
+/// outside [0, 1] and the result is extrapolated.
+fn bracket(values: &[f64], x: f64) -> (f64, f64, f64) {
+    if values.len() == 1 {
+        return (values[0], values[0], 0.0);
+    }
+    let i = values[1..values.len() - 1]
+        .iter()
+        .take_while(|&&v| v < x)
+        .count();
+    let (v0, v1) = (values[i], values[i + 1]);
+    (v0, v1, (x - v0) / (v1 - v0))
+}
+
+fn lerp_paths(paths0: &[Path], paths1: &[Path], t: f64) -> Option<Vec<Path>> {
+    if paths0.len() != paths1.len() {
+        return None;
+    }
+    paths0
+        .iter()
+        .zip(paths1)
+        .map(|(p0, p1)| {
+            if p0.nodes.len() != p1.nodes.len() {
+                return None;
+            }
+            let nodes = p0
+                .nodes
+                .iter()
+                .zip(&p1.nodes)
+                .map(|(n0, n1)| Node {
+                    pt: n0.pt.lerp(n1.pt, t),
+                    node_type: n0.node_type,
+                })
+                .collect();
+            Some(Path {
+                closed: p0.closed,
+                nodes,
+            })
+        })
+        .collect()
+}
+
+fn lerp_anchors(anchors0: &[Anchor], anchors1: &[Anchor], t: f64) -> Vec<Anchor> {
+    anchors0
+        .iter()
+        .filter_map(|a0| {
+            let a1 = anchors1.iter().find(|a1| a1.name == a0.name)?;
+            let mut anchor = a0.clone();
+            anchor.position = a0.position.lerp(a1.position, t);
+            Some(anchor)
+        })
+        .collect()
+}
+
+/// Interpolate component transforms, if the components match up.
+fn lerp_components(c0: &[Component], c1: &[Component], t: f64) -> Option<Vec<Component>> {
+    if c0.len() != c1.len() || c0.iter().zip(c1).any(|(a, b)| a.name != b.name) {
+        return None;
+    }
+    let components = c0
+        .iter()
+        .zip(c1)
+        .map(|(a, b)| {
+            let mut component = a.clone();
+            if a.transform.is_some() || b.transform.is_some() {
+                let a0 = a.transform.unwrap_or_default().as_coeffs();
+                let a1 = b.transform.unwrap_or_default().as_coeffs();
+                let mut coeffs = [0.0; 6];
+                for i in 0..6 {
+                    coeffs[i] = a0[i] + t * (a1[i] - a0[i]);
+                }
+                component.transform = Some(Affine::new(coeffs));
+            }
+            component
+        })
+        .collect();
+    Some(components)
+}
+
+fn lerp_layer(l0: &Layer, l1: &Layer, t: f64) -> Option<Layer> {
+    let empty = Vec::new();
+    let paths0 = l0.paths.as_ref().unwrap_or(&empty);
+    let paths1 = l1.paths.as_ref().unwrap_or(&empty);
+    let paths = lerp_paths(paths0, paths1, t)?;
+    let mut layer = l0.clone();
+    layer.width = l0.width + t * (l1.width - l0.width);
+    layer.paths = if paths.is_empty() { None } else { Some(paths) };
+    if let (Some(a0), Some(a1)) = (&l0.anchors, &l1.anchors) {
+        layer.anchors = Some(lerp_anchors(a0, a1, t));
+    }
+    if let (Some(c0), Some(c1)) = (&l0.components, &l1.components) {
+        if let Some(components) = lerp_components(c0, c1, t) {
+            layer.components = Some(components);
+        }
+    }
+    Some(layer)
+}
+
+/// The distinct values of the masters on each axis, in order.
+fn grid(font: &Font) -> Result<Vec<Vec<f64>>, String> {
+    if font.font_master.is_empty() {
+        return Err("font has no masters".to_string());
+    }
+    let mut result = vec![Vec::new(); axes(font).len()];
+    for master in &font.font_master {
+        let location = master_location(font, &master.id).unwrap();
+        for (values, (_, v)) in result.iter_mut().zip(location) {
+            values.push(v);
+        }
+    }
+    for values in &mut result {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        values.dedup();
+    }
+    Ok(result)
+}
+
+/// The value on each of the font's axes, in order.
+fn location_values(font: &Font, location: &Location) -> Result<Vec<f64>, String> {
+    axes(font)
+        .iter()
+        .map(|axis| {
+            axis_value(location, &axis.tag)
+                .ok_or_else(|| format!("location has no value for {}", axis.tag))
+        })
+        .collect()
+}
+
+/// The master at the given values on each axis.
+fn master_at<'a>(font: &'a Font, values: &[f64]) -> Result<&'a FontMaster, String> {
+    let location: Location = axes(font)
+        .into_iter()
+        .map(|a| a.tag)
+        .zip(values.iter().copied())
+        .collect();
+    font.font_master
+        .iter()
+        .find(|m| master_location(font, &m.id).as_ref() == Some(&location))
+        .ok_or_else(|| format!("no master at {}", format_location(&location)))
+}
+
+/// The bracketing pair of values and the parameter between them, on each
+/// axis.
+fn brackets(font: &Font, location: &Location) -> Result<Vec<(f64, f64, f64)>, String> {
+    let values = location_values(font, location)?;
+    let grid = grid(font)?;
+    Ok(grid
+        .iter()
+        .zip(values)
+        .map(|(g, v)| bracket(g, v))
+        .collect())
+}
+
+/// Interpolate the layers at the corners of the box given by `brackets`,
+/// along the axes from `corner.len()` on.
+fn interpolate_corners(
+    font: &Font,
+    glyph: &Glyph,
+    brackets: &[(f64, f64, f64)],
+    corner: &mut Vec<f64>,
+) -> Result<Layer, String> {
+    let k = corner.len();
+    if k == brackets.len() {
+        let master = master_at(font, corner)?;
+        return glyph.get_layer(&master.id).cloned().ok_or_else(|| {
+            format!(
+                "glyph {} has no layer for master {}",
+                glyph.glyphname, master.id
+            )
+        });
+    }
+    let (v0, v1, t) = brackets[k];
+    corner.push(v0);
+    let l0 = interpolate_corners(font, glyph, brackets, corner);
+    corner.pop();
+    if v0 == v1 {
+        return l0;
+    }
+    corner.push(v1);
+    let l1 = interpolate_corners(font, glyph, brackets, corner);
+    corner.pop();
+    lerp_layer(&l0?, &l1?, t)
+        .ok_or_else(|| format!("glyph {} has incompatible masters", glyph.glyphname))
+}
+
+/// Interpolate (or extrapolate) a glyph at the given location.
+///
+/// The masters must form a full grid: a master is needed at every
+/// combination of the values in use on each axis. The result is a clone
+/// of a bracketing master layer with paths, anchors and advance width
+/// interpolated; it is not rounded.
+pub fn interpolate(font: &Font, glyph: &Glyph, location: &Location) -> Result<Layer, String> {
+    let brackets = brackets(font, location)?;
+    interpolate_corners(font, glyph, &brackets, &mut Vec::new())
+}
+
+/// The contribution of each master to an instance at the given location,
+/// as used by `interpolate`. Factors may be negative when extrapolating;
+/// they sum to one.
+pub fn master_factors(font: &Font, location: &Location) -> Result<Vec<(String, f64)>, String> {
+    let brackets = brackets(font, location)?;
+    let mut factors: Vec<(String, f64)> = Vec::new();
+    for corner in 0..1usize << brackets.len() {
+        let mut values = Vec::new();
+        let mut factor = 1.0;
+        for (k, &(v0, v1, t)) in brackets.iter().enumerate() {
+            if corner & (1 << k) == 0 {
+                values.push(v0);
+                factor *= 1.0 - t;
+            } else {
+                values.push(v1);
+                factor *= t;
+            }
+        }
+        let master = master_at(font, &values)?;
+        match factors.iter_mut().find(|(id, _)| *id == master.id) {
+            Some((_, f)) => *f += factor,
+            None => factors.push((master.id.clone(), factor)),
+        }
+    }
+    factors.retain(|&(_, f)| f != 0.0);
+    Ok(factors)
+}
+
+/// Blend a value of each master, if all of them have it.
+fn blend(
+    font: &Font,
+    factors: &[(String, f64)],
+    value: impl Fn(&FontMaster) -> Option<f64>,
+) -> Option<f64> {
+    factors.iter().try_fold(0.0, |sum, (id, f)| {
+        Some(sum + f * value(font.get_master(id)?)?)
+    })
+}
+
+/// Blend a list of numbers or points of each master, if they all have one
+/// of the same length.
+fn blend_list(font: &Font, factors: &[(String, f64)], key: &str) -> Option<Plist> {
+    let lists = factors
+        .iter()
+        .map(|(id, _)| font.get_master(id)?.other_stuff.get(key)?.as_array())
+        .collect::<Option<Vec<_>>>()?;
+    let len = lists[0].len();
+    if lists.iter().any(|l| l.len() != len) {
+        return None;
+    }
+    (0..len)
+        .map(|i| {
+            if lists[0][i].as_f64().is_some() {
+                let v = factors
+                    .iter()
+                    .zip(&lists)
+                    .try_fold(0.0, |sum, ((_, f), l)| Some(sum + f * l[i].as_f64()?))?;
+                Some(number_plist(v))
+            } else {
+                let (mut x, mut y) = (0.0, 0.0);
+                for ((_, f), l) in factors.iter().zip(&lists) {
+                    let p = l[i].as_str()?.trim_matches(|c| c == '{' || c == '}');
+                    let mut coords = p.split(',').map(|c| c.trim().parse::<f64>().ok());
+                    x += f * coords.next()??;
+                    y += f * coords.next()??;
+                }
+                Some(Plist::String(format!(
+                    "{{{}, {}}}",
+                    x.round() as i64,
+                    y.round() as i64
+                )))
+            }
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(Plist::Array)
+}
+
+/// Make a font with a single master, interpolated at the given location.
+///
+/// The master is a copy of the master contributing most to the instance,
+/// with its location, vertical metrics, alignment zones, stems and numeric
+/// custom parameters interpolated. Each glyph has one layer, interpolated
+/// as by `interpolate`, and other layers are dropped.
+pub fn instance_font(font: &Font, location: &Location) -> Result<Font, String> {
+    let factors = master_factors(font, location)?;
+    let (base_id, _) = factors
+        .iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap();
+    let mut master = font.get_master(base_id).unwrap().clone();
+    let values = location_values(font, location)?;
+    for (i, (&(key, _), v)) in MASTER_KEYS.iter().zip(values).enumerate() {
+        match i {
+            0 => master.weight_value = v.round() as i64,
+            1 => master.width_value = Some(v.round() as i64),
+            _ => {
+                master.other_stuff.insert(key.to_string(), number_plist(v));
+            }
+        }
+    }
+    master.x_height = blend(font, &factors, |m| m.x_height);
+    master.italic_angle = blend(font, &factors, |m| m.italic_angle);
+    if let Some(stems) = master.vertical_stems.take() {
+        master.vertical_stems = (0..stems.len())
+            .map(|i| {
+                blend(font, &factors, |m| {
+                    m.vertical_stems.as_ref()?.get(i).copied()
+                })
+            })
+            .collect();
+    }
+    for key in &["ascender", "capHeight", "descender"] {
+        let value = blend(font, &factors, |m| m.other_stuff.get(*key)?.as_f64());
+        if let Some(value) = value {
+            master
+                .other_stuff
+                .insert(key.to_string(), number_plist(value));
+        }
+    }
+    for key in &["alignmentZones", "horizontalStems"] {
+        if let Some(value) = blend_list(font, &factors, key) {
+            master.other_stuff.insert(key.to_string(), value);
+        }
+    }
+    if let Some(Plist::Array(params)) = master.other_stuff.get_mut("customParameters") {
+        for param in params {
+            let name = match param.get("name").and_then(Plist::as_str) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let value = blend(font, &factors, |m| m.custom_parameter(&name)?.as_f64());
+            if let (Some(value), Plist::Dictionary(dict)) = (value, param) {
+                dict.insert("value".to_string(), number_plist(value));
+            }
+        }
+    }
+    let glyphs = font
+        .glyphs
+        .iter()
+        .map(|glyph| {
+            let mut layer = interpolate(font, glyph, location)?;
+            layer.layer_id = master.id.clone();
+            let mut glyph = glyph.clone();
+            glyph.layers = vec![layer];
+            Ok(glyph)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Font {
+        glyphs,
+        font_master: vec![master],
+        classes: font.classes.clone(),
+        feature_prefixes: font.feature_prefixes.clone(),
+        features: font.features.clone(),
+        other_stuff: font.other_stuff.clone(),
+    })
+}
+
+/// The location found by `location_for_stem`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StemMatch {
+    /// The value on the axis solved along.
+    pub value: f64,
+    /// The full location.
+    pub location: Location,
+    /// The scaled stem thickness there.
+    pub stem: f64,
+    /// Whether the value lies outside the range of the masters.
+    pub extrapolated: bool,
+}
+
+/// Vertical stem thickness of a glyph: the thinnest run of ink across a few
+/// horizontal lines through the middle of the glyph.
+pub fn glyph_stem(font: &Font, layer: &Layer) -> Option<f64> {
+    let paths = font.decompose(layer);
+    stem_at_heights(&paths, &stem_heights(&paths)?)
+}
+
+/// Which value of a location an axis is, given its tag or name.
+fn axis_index(font: &Font, axis: &str) -> Result<usize, String> {
+    axes(font)
+        .iter()
+        .position(|a| a.tag == axis || a.name == axis)
+        .ok_or_else(|| format!("font has no axis {}", axis))
+}
+
+/// Find where along an axis a glyph, scaled by `scale`, has vertical stems
+/// of thickness `stem`, starting from a location that gives the other axes.
+///
+/// The axis is given by its tag or name in the font's axes. Stems are
+/// measured at each master value on the axis. Between masters the outline,
+/// and so the stem, is linear along the axis, so the answer is found by
+/// linear interpolation within the segment that brackets the target.
+/// Targets past the masters are extrapolated from the outermost segment,
+/// which is reported in the result.
+pub fn location_for_stem(
+    font: &Font,
+    glyph: &Glyph,
+    axis: &str,
+    location: &Location,
+    scale: f64,
+    stem: f64,
+) -> Result<StemMatch, String> {
+    let index = axis_index(font, axis)?;
+    let tag = axes(font).swap_remove(index).tag;
+    let at = |value: f64| -> Location {
+        let mut location = location.clone();
+        match location.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, v)) => *v = value,
+            None => location.push((tag.clone(), value)),
+        }
+        location
+    };
+    let values = grid(font)?.swap_remove(index);
+    if values.len() < 2 {
+        return Err(format!("need masters at two or more values of {}", axis));
+    }
+    let (min, max) = (values[0], values[values.len() - 1]);
+    // Measure at the same heights throughout, so the stem is a continuous
+    // function along the axis.
+    let mid = interpolate(font, glyph, &at((min + max) * 0.5))?;
+    let no_stems = || format!("no stems found in {}", glyph.glyphname);
+    let ys = stem_heights(&font.decompose(&mid)).ok_or_else(no_stems)?;
+    let measure = |value: f64| -> Result<f64, String> {
+        let layer = interpolate(font, glyph, &at(value))?;
+        stem_at_heights(&font.decompose(&layer), &ys)
+            .map(|s| s * scale)
+            .ok_or_else(no_stems)
+    };
+    let stems = values
+        .iter()
+        .map(|&v| measure(v))
+        .collect::<Result<Vec<f64>, String>>()?;
+    let n = values.len();
+    let i = (0..n - 1)
+        .find(|&i| (stems[i] - stem) * (stems[i + 1] - stem) <= 0.0)
+        .unwrap_or(if (stem - stems[0]).abs() < (stem - stems[n - 1]).abs() {
+            0
+        } else {
+            n - 2
+        });
+    let (s0, s1) = (stems[i], stems[i + 1]);
+    if (s1 - s0).abs() < 1e-9 {
+        return Err(format!(
+            "stems of {} don't change along {}",
+            glyph.glyphname, axis
+        ));
+    }
+    let value = values[i] + (stem - s0) * (values[i + 1] - values[i]) / (s1 - s0);
+    Ok(StemMatch {
+        value,
+        location: at(value),
+        stem: measure(value)?,
+        extrapolated: value < min || value > max,
+    })
+}
+
+/// Make a copy of a glyph scaled by `scale`, interpolated at the location
+/// along `axis` that gives vertical stems of thickness `stem` after scaling.
+///
+/// The paths, anchors and advance width of the resulting layer are scaled
+/// around the origin, and not rounded.
+pub fn scaled_glyph(
+    font: &Font,
+    glyph: &Glyph,
+    axis: &str,
+    location: &Location,
+    scale: f64,
+    stem: f64,
+) -> Result<(Layer, StemMatch), String> {
+    let stem_match = location_for_stem(font, glyph, axis, location, scale, stem)?;
+    let mut layer = interpolate(font, glyph, &stem_match.location)?;
+    let a = Affine::scale(scale);
+    if let Some(ref mut paths) = layer.paths {
+        for path in paths {
+            path.apply_affine(a);
+        }
+    }
+    if let Some(ref mut anchors) = layer.anchors {
+        for anchor in anchors {
+            anchor.position = a * anchor.position;
+        }
+    }
+    layer.width *= scale;
+    Ok((layer, stem_match))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+
+    fn font(source: &str) -> Font {
+        Font::from_plist(Plist::parse(source).unwrap())
+    }
+
+    fn loc(weight: f64, width: f64) -> Location {
+        vec![("wght".to_string(), weight), ("wdth".to_string(), width)]
+    }
+
+    #[test]
+    fn origin_is_the_master_at_the_axis_defaults() {
+        let masters = "fontMaster = (
+{id = light; weightValue = 200; widthValue = 100;},
+{id = regular; weightValue = 400; widthValue = 100;},
+{id = bold; weightValue = 900; widthValue = 100;}
+);
+glyphs = ();";
+        assert_eq!(
+            origin(&font(&format!("{{{}}}", masters))).unwrap().id,
+            "regular"
+        );
+        let named = format!(
+            "{{customParameters = ({{name = \"Variable Font Origin\"; value = bold;}});{}}}",
+            masters
+        );
+        assert_eq!(origin(&font(&named)).unwrap().id, "bold");
+    }
+
+    /// A layer with a single stem, 500 units tall, of the given thickness.
+    fn stem_layer(master: &str, stem: f64) -> String {
+        format!(
+            "{{layerId = {}; width = 500; paths = ({{closed = 1; nodes = (\
+             \"100 0 LINE\", \"100 500 LINE\", \"{x} 500 LINE\", \"{x} 0 LINE\");}});}}",
+            master,
+            x = 100.0 + stem
+        )
+    }
+
+    /// A font with masters on a weight and width grid, with the stem of `l`
+    /// growing along both axes.
+    fn stem_font() -> Font {
+        font(&format!(
+            "{{fontMaster = (
+{{id = a; weightValue = 200; widthValue = 50;}},
+{{id = b; weightValue = 900; widthValue = 50;}},
+{{id = c; weightValue = 200; widthValue = 100;}},
+{{id = d; weightValue = 900; widthValue = 100;}}
+);
+glyphs = ({{glyphname = l; layers = ({}, {}, {}, {});}});}}",
+            stem_layer("a", 40.0),
+            stem_layer("b", 110.0),
+            stem_layer("c", 60.0),
+            stem_layer("d", 130.0)
+        ))
+    }
+
+    #[test]
+    fn stem_solved_along_weight() {
+        let font = stem_font();
+        let l = font.get_glyph("l").unwrap();
+        let found = location_for_stem(&font, l, "wght", &loc(400.0, 100.0), 1.0, 95.0).unwrap();
+        assert!((found.value - 550.0).abs() < 1e-6);
+        assert_eq!(axis_value(&found.location, "wdth"), Some(100.0));
+        assert!((found.stem - 95.0).abs() < 1e-6);
+        assert!(!found.extrapolated);
+        // Scaled by half, the stem is reached at twice the thickness.
+        let found = location_for_stem(&font, l, "Weight", &loc(400.0, 50.0), 0.5, 40.0).unwrap();
+        assert!((found.value - 600.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stem_solved_along_width() {
+        let font = stem_font();
+        let l = font.get_glyph("l").unwrap();
+        let found = location_for_stem(&font, l, "wdth", &loc(550.0, 100.0), 1.0, 85.0).unwrap();
+        assert!((found.value - 75.0).abs() < 1e-6);
+        assert_eq!(found.location, loc(550.0, found.value));
+    }
+
+    #[test]
+    fn stem_past_the_masters_is_extrapolated() {
+        let font = stem_font();
+        let l = font.get_glyph("l").unwrap();
+        let found = location_for_stem(&font, l, "wght", &loc(400.0, 100.0), 1.0, 165.0).unwrap();
+        assert!((found.value - 1250.0).abs() < 1e-6);
+        assert!(found.extrapolated);
+        assert!(location_for_stem(&font, l, "opsz", &loc(400.0, 100.0), 1.0, 95.0).is_err());
+    }
+
+    #[test]
+    fn interpolates_along_every_declared_axis() {
+        let axes = "customParameters = ({name = Axes; value = (
+{Name = Weight; Tag = wght;}, {Name = Width; Tag = wdth;}, {Name = Grade; Tag = GRAD;}
+);});";
+        let mut masters = Vec::new();
+        let mut layers = Vec::new();
+        for (i, &(weight, grade)) in [(200, 0), (900, 0), (200, 100), (900, 100)]
+            .iter()
+            .enumerate()
+        {
+            masters.push(format!(
+                "{{id = m{}; weightValue = {}; widthValue = 100; customValue = {};}}",
+                i, weight, grade
+            ));
+            let stem = (weight - 100) as f64 / 10.0 + grade as f64;
+            layers.push(stem_layer(&format!("m{}", i), stem));
+        }
+        let font = font(&format!(
+            "{{{}\nfontMaster = ({});\nglyphs = ({{glyphname = l; layers = ({});}});}}",
+            axes,
+            masters.join(", "),
+            layers.join(", ")
+        ));
+        assert_eq!(
+            master_location(&font, "m2").unwrap(),
+            [
+                ("wght".to_string(), 200.0),
+                ("wdth".to_string(), 100.0),
+                ("GRAD".to_string(), 100.0)
+            ]
+        );
+        let mut location = loc(550.0, 100.0);
+        location.push(("GRAD".to_string(), 50.0));
+        let layer = interpolate(&font, font.get_glyph("l").unwrap(), &location).unwrap();
+        let x: Vec<f64> = layer.paths.unwrap()[0]
+            .nodes
+            .iter()
+            .map(|n| n.pt.x)
+            .collect();
+        assert_eq!(x, [100.0, 100.0, 195.0, 195.0]);
+        let mut factors = master_factors(&font, &location).unwrap();
+        factors.sort_by(|a, b| a.0.cmp(&b.0));
+        let factors: Vec<f64> = factors.iter().map(|&(_, f)| f).collect();
+        assert_eq!(factors, [0.25, 0.25, 0.25, 0.25]);
+        // The grade axis picks the masters just like weight does.
+        let instance = instance_font(&font, &location).unwrap();
+        assert_eq!(
+            master_location(&instance, &instance.font_master[0].id).unwrap(),
+            location
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Kerning, per master, with kerning groups.
+//!
+//! Glyphs stores kerning by master ID, then left and right key. A key is a
+//! glyph name, or a group: `@MMK_L_x` on the left side, for the glyphs whose
+//! right kerning group is `x`, and `@MMK_R_x` on the right side, for those
+//! whose left kerning group is `x`. A pair with a glyph where the glyph has
+//! a group is an exception to the group's kerning.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::font::Font;
+use crate::plist::Plist;
+use crate::ufo::number_plist;
+
+const LEFT_GROUP_PREFIX: &str = "@MMK_L_";
+const RIGHT_GROUP_PREFIX: &str = "@MMK_R_";
+
+/// One side of a kerning pair.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KernSide {
+    Glyph(String),
+    Group(String),
+}
+
+impl KernSide {
+    fn from_key(key: &str, prefix: &str) -> KernSide {
+        match key.strip_prefix(prefix) {
+            Some(group) => KernSide::Group(group.to_string()),
+            None => KernSide::Glyph(key.to_string()),
+        }
+    }
+
+    fn to_key(&self, prefix: &str) -> String {
+        match self {
+            KernSide::Glyph(name) => name.clone(),
+            KernSide::Group(group) => format!("{}{}", prefix, group),
+        }
+    }
+}
+
+/// A glyph by name, a group as `@group`.
+impl fmt::Display for KernSide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KernSide::Glyph(name) => write!(f, "{}", name),
+            KernSide::Group(group) => write!(f, "@{}", group),
+        }
+    }
+}
+
+/// The kerning groups of the glyphs, by the side of the pair they're on.
+#[derive(Clone, Debug, Default)]
+pub struct KerningGroups {
+    /// The group of each glyph on the left of a pair: its right kerning
+    /// group.
+    pub left: HashMap<String, String>,
+    /// The group of each glyph on the right of a pair: its left kerning
+    /// group.
+    pub right: HashMap<String, String>,
+}
+
+impl KerningGroups {
+    pub fn from_font(font: &Font) -> KerningGroups {
+        let mut groups = KerningGroups::default();
+        for glyph in &font.glyphs {
+            let group = |key| glyph.other_stuff.get(key).and_then(Plist::as_str);
+            if let Some(group) = group("rightKerningGroup") {
+                groups
+                    .left
+                    .insert(glyph.glyphname.clone(), group.to_string());
+            }
+            if let Some(group) = group("leftKerningGroup") {
+                groups
+                    .right
+                    .insert(glyph.glyphname.clone(), group.to_string());
+            }
+        }
+        groups
+    }
+}
+
+/// The kerning of one master.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Kerning {
+    pub pairs: BTreeMap<(KernSide, KernSide), f64>,
+}
+
+impl Kerning {
+    /// The kerning of a master; empty if it has none.
+    pub fn from_font(font: &Font, master_id: &str) -> Kerning {
+        let mut pairs = BTreeMap::new();
+        let master = font
+            .other_stuff
+            .get("kerning")
+            .and_then(|k| k.get(master_id))
+            .and_then(Plist::as_dict);
+        for (left, rights) in master.into_iter().flatten() {
+            let left = KernSide::from_key(left, LEFT_GROUP_PREFIX);
+            for (right, value) in rights.as_dict().into_iter().flatten() {
+                if let Some(value) = value.as_f64() {
+                    let right = KernSide::from_key(right, RIGHT_GROUP_PREFIX);
+                    pairs.insert((left.clone(), right), value);
+                }
+            }
+        }
+        Kerning { pairs }
+    }
+
+    /// Store the kerning of a master in the font, replacing what it had.
+    pub fn write_to(&self, font: &mut Font, master_id: &str) {
+        let mut lefts: HashMap<String, HashMap<String, Plist>> = HashMap::new();
+        for ((left, right), &value) in &self.pairs {
+            lefts
+                .entry(left.to_key(LEFT_GROUP_PREFIX))
+                .or_default()
+                .insert(right.to_key(RIGHT_GROUP_PREFIX), number_plist(value));
+        }
+        let lefts = lefts
+            .into_iter()
+            .map(|(left, rights)| (left, Plist::Dictionary(rights)))
+            .collect();
+        let stuff = &mut font.other_stuff;
+        if let Some(Plist::Dictionary(kerning)) = stuff.get_mut("kerning") {
+            kerning.remove(master_id);
+            if kerning.is_empty() {
+                stuff.remove("kerning");
+            }
+        }
+        if self.pairs.is_empty() {
+            return;
+        }
+        let kerning = stuff
+            .entry("kerning".to_string())
+            .or_insert_with(|| Plist::Dictionary(HashMap::new()));
+        if let Plist::Dictionary(kerning) = kerning {
+            kerning.insert(master_id.to_string(), Plist::Dictionary(lefts));
+        }
+    }
+
+    /// The pair that applies to two glyphs, and its value. Exceptions come
+    /// first: glyph and glyph, then glyph and group, then group and glyph,
+    /// and otherwise the pair of groups.
+    pub fn lookup(
+        &self,
+        groups: &KerningGroups,
+        left: &str,
+        right: &str,
+    ) -> Option<(&(KernSide, KernSide), f64)> {
+        let left_glyph = KernSide::Glyph(left.to_string());
+        let right_glyph = KernSide::Glyph(right.to_string());
+        let left_group = groups.left.get(left).cloned().map(KernSide::Group);
+        let right_group = groups.right.get(right).cloned().map(KernSide::Group);
+        let candidates = [
+            Some((left_glyph.clone(), right_glyph.clone())),
+            right_group.clone().map(|r| (left_glyph, r)),
+            left_group.clone().map(|l| (l, right_glyph)),
+            left_group.zip(right_group),
+        ];
+        candidates
+            .iter()
+            .flatten()
+            .find_map(|key| self.pairs.get_key_value(key).map(|(k, &v)| (k, v)))
+    }
+
+    /// The kerning between two glyphs, zero if no pair applies.
+    pub fn value(&self, groups: &KerningGroups, left: &str, right: &str) -> f64 {
+        self.lookup(groups, left, right).map_or(0.0, |(_, v)| v)
+    }
+}
+
+/// Something wrong with the kerning.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KerningIssue {
+    /// Kerning for a master the font doesn't have.
+    UnknownMaster { master_id: String },
+    /// A pair with a glyph the font doesn't have.
+    UnknownGlyph { master_id: String, glyph: String },
+    /// A pair with a group no glyph belongs to, on that side.
+    EmptyGroup { master_id: String, group: String },
+}
+
+/// Check that the kerning of every master refers to masters, glyphs and
+/// groups that exist.
+pub fn check_kerning(font: &Font) -> Vec<KerningIssue> {
+    let groups = KerningGroups::from_font(font);
+    let mut issues = Vec::new();
+    let mut master_ids = font
+        .other_stuff
+        .get("kerning")
+        .and_then(Plist::as_dict)
+        .map(|k| k.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    master_ids.sort();
+    for master_id in master_ids {
+        if font.get_master(&master_id).is_none() {
+            issues.push(KerningIssue::UnknownMaster { master_id });
+            continue;
+        }
+        let kerning = Kerning::from_font(font, &master_id);
+        let mut check = |side: &KernSide, members: &HashMap<String, String>| {
+            let issue = match side {
+                KernSide::Glyph(glyph) if font.get_glyph(glyph).is_none() => {
+                    KerningIssue::UnknownGlyph {
+                        master_id: master_id.clone(),
+                        glyph: glyph.clone(),
+                    }
+                }
+                KernSide::Group(group) if !members.values().any(|g| g == group) => {
+                    KerningIssue::EmptyGroup {
+                        master_id: master_id.clone(),
+                        group: group.clone(),
+                    }
+                }
+                _ => return,
+            };
+            if !issues.contains(&issue) {
+                issues.push(issue);
+            }
+        };
+        for (left, right) in kerning.pairs.keys() {
+            check(left, &groups.left);
+            check(right, &groups.right);
+        }
+    }
+    issues
+}
@@ -0,0 +1,1315 @@
+//! Compiling parsed feature code to the `GSUB`, `GPOS` and `GDEF` tables.
+//!
+//! Rules are grouped into lookups as the AFDKO tools do: consecutive rules
+//! of the same kind in a feature share a lookup, and a change of kind,
+//! lookup flag, script or language starts a new one. Lookups before the
+//! first `script` statement apply to every `languagesystem`. Contextual
+//! rules with a replacement get an anonymous lookup for it. `aalt` gathers
+//! the single and alternate substitutions of the features it names into one
+//! lookup, which goes first.
+//!
+//! Pair positioning with classes is written as glyph pairs, with earlier
+//! pairs taking precedence. `GDEF` classifies glyphs with a `_` anchor as
+//! marks, the results of ligature substitutions as ligatures, and all
+//! others as base glyphs.
+//!
+//! Unless the feature code has its own, `mark` and `mkmk` features are
+//! made from the anchors, as Glyphs does on export: a mark with a `_top`
+//! anchor attaches to the `top` anchor of the glyph before it, one lookup
+//! for each anchor name. The `mkmk` lookups only see the marks of their
+//! anchor, through a mark filtering set, so a mark below doesn't keep a
+//! mark above from stacking on the one before. In a variable font the
+//! anchors vary through the variation store in `GDEF`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::compile::glyph_index;
+use crate::fea::{
+    parse_features, Block, FeatureBlock, GlyphSet, LookupBlock, Positioning, Statement, SubAction,
+    Substitution, ValueRecord,
+};
+use crate::font::{Font, Glyph, Layer};
+use crate::sfnt::{to_i16, TableData, Writer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Table {
+    Gsub,
+    Gpos,
+}
+
+/// Pair adjustments by second glyph.
+type Pairs = BTreeMap<u16, (ValueRecord, ValueRecord)>;
+
+#[derive(Clone, Debug)]
+enum Subtable {
+    Single(BTreeMap<u16, u16>),
+    Multiple(BTreeMap<u16, Vec<u16>>),
+    Alternate(BTreeMap<u16, Vec<u16>>),
+    /// Ligatures by first glyph: the other components, and the ligature.
+    Ligature(BTreeMap<u16, Vec<(Vec<u16>, u16)>>),
+    Context(ContextRule),
+    SinglePos(BTreeMap<u16, ValueRecord>),
+    PairPos(BTreeMap<u16, Pairs>),
+    /// Marks with their class and anchor, and the glyphs they attach to,
+    /// bases or marks, with an anchor for each class.
+    MarkAttach(
+        BTreeMap<u16, (u16, AnchorPoint)>,
+        BTreeMap<u16, Vec<AnchorPoint>>,
+    ),
+}
+
+/// An anchor for mark positioning. In a variable font, the indices of the
+/// rows of its x and y deltas in the variation store of `GDEF`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct AnchorPoint {
+    pub x: i16,
+    pub y: i16,
+    pub deltas: Option<(u16, u16)>,
+}
+
+/// The anchors of each glyph by glyph ID, for the `mark` and `mkmk`
+/// features, and in a variable font the variation store they refer to.
+pub(crate) struct MarkAnchors {
+    pub anchors: Vec<Vec<(String, AnchorPoint)>>,
+    pub store: Option<Vec<u8>>,
+}
+
+impl MarkAnchors {
+    /// The anchors of a font with one master, as in a static instance.
+    pub(crate) fn of_layers(font: &Font, layers: &[&Layer]) -> MarkAnchors {
+        let anchors = layers
+            .iter()
+            .map(|layer| {
+                let anchors = font.anchors(layer).into_iter();
+                anchors
+                    .filter(|a| is_mark_anchor(&a.name))
+                    .map(|a| {
+                        let (x, y) = (to_i16(a.position.x), to_i16(a.position.y));
+                        (a.name, AnchorPoint { x, y, deltas: None })
+                    })
+                    .collect()
+            })
+            .collect();
+        MarkAnchors {
+            anchors,
+            store: None,
+        }
+    }
+}
+
+/// Whether an anchor is for attaching marks, rather than one of the
+/// special anchors of Glyphs for the origin, carets and cursive
+/// attachment.
+pub(crate) fn is_mark_anchor(name: &str) -> bool {
+    let name = name.trim_start_matches('_');
+    !name.is_empty()
+        && !name.starts_with('*')
+        && !["origin", "entry", "exit"].contains(&name)
+        && !name.starts_with("caret")
+}
+
+/// A chaining contextual rule, written in the coverage-based format.
+#[derive(Clone, Debug)]
+struct ContextRule {
+    backtrack: Vec<Vec<u16>>,
+    input: Vec<Vec<u16>>,
+    lookahead: Vec<Vec<u16>>,
+    /// The lookups to apply, by position in the input.
+    lookups: Vec<(u16, usize)>,
+}
+
+#[derive(Clone, Debug)]
+struct Lookup {
+    kind: u16,
+    flag: u16,
+    subtables: Vec<Subtable>,
+    /// The mark filtering set, for a flag with `UseMarkFilteringSet`.
+    mark_set: Option<u16>,
+}
+
+/// The lookup type of a rule.
+fn rule_kind(statement: &Statement) -> Option<(Table, u16)> {
+    match statement {
+        Statement::Substitute(s) if s.contextual => Some((Table::Gsub, 6)),
+        Statement::Substitute(s) => match s.action {
+            SubAction::Single(_) => Some((Table::Gsub, 1)),
+            SubAction::Multiple(_) => Some((Table::Gsub, 2)),
+            SubAction::Alternate(_) => Some((Table::Gsub, 3)),
+            SubAction::Ligature(_) => Some((Table::Gsub, 4)),
+            SubAction::Ignore | SubAction::Lookups(_) => Some((Table::Gsub, 6)),
+        },
+        Statement::Position(p) => Some((Table::Gpos, p.glyphs.len() as u16)),
+        _ => None,
+    }
+}
+
+/// Add the entries of `b` that are not in `a`.
+fn extend_new<K: Ord, V>(a: &mut BTreeMap<K, V>, b: BTreeMap<K, V>) {
+    for (k, v) in b {
+        a.entry(k).or_insert(v);
+    }
+}
+
+/// Add a subtable to a lookup, merging it into the last one if they are of
+/// the same kind.
+fn merge(subtables: &mut Vec<Subtable>, new: Subtable) {
+    match (subtables.last_mut(), new) {
+        (Some(Subtable::Single(a)), Subtable::Single(b)) => extend_new(a, b),
+        (Some(Subtable::Multiple(a)), Subtable::Multiple(b)) => extend_new(a, b),
+        (Some(Subtable::Alternate(a)), Subtable::Alternate(b)) => extend_new(a, b),
+        (Some(Subtable::Ligature(a)), Subtable::Ligature(b)) => {
+            for (first, ligatures) in b {
+                a.entry(first).or_default().extend(ligatures);
+            }
+        }
+        (Some(Subtable::SinglePos(a)), Subtable::SinglePos(b)) => extend_new(a, b),
+        (Some(Subtable::PairPos(a)), Subtable::PairPos(b)) => {
+            for (first, pairs) in b {
+                extend_new(a.entry(first).or_default(), pairs);
+            }
+        }
+        (_, new) => subtables.push(new),
+    }
+}
+
+fn conflicts<K: Ord, V: PartialEq>(a: &BTreeMap<K, V>, b: &BTreeMap<K, V>) -> bool {
+    b.iter().any(|(k, v)| a.get(k).is_some_and(|x| x != v))
+}
+
+/// Whether two replacements of contextual rules can share a lookup.
+fn compatible(a: &Subtable, b: &Subtable) -> bool {
+    match (a, b) {
+        (Subtable::Single(a), Subtable::Single(b)) => !conflicts(a, b),
+        (Subtable::Multiple(a), Subtable::Multiple(b)) => !conflicts(a, b),
+        (Subtable::Alternate(a), Subtable::Alternate(b)) => !conflicts(a, b),
+        _ => false,
+    }
+}
+
+/// The state of a feature block as its statements are compiled.
+struct FeatureState<'a> {
+    tag: &'a str,
+    script: Option<String>,
+    lang: String,
+    flag: u16,
+    /// The lookup taking rules, and its table and type.
+    current: Option<(Table, usize, u16)>,
+}
+
+struct Builder<'a> {
+    index: HashMap<&'a str, usize>,
+    language_systems: Vec<(String, String)>,
+    gsub: Vec<Lookup>,
+    gpos: Vec<Lookup>,
+    named: HashMap<String, (Table, usize)>,
+    /// Lookups by table, feature tag, and script and language.
+    registered: BTreeMap<(Table, String, (String, String)), Vec<usize>>,
+    /// The last anonymous lookup made for the rules of a contextual lookup.
+    nested: HashMap<usize, usize>,
+    ligatures: HashSet<u16>,
+    /// The glyphs of each mark filtering set.
+    mark_sets: Vec<Vec<u16>>,
+}
+
+impl<'a> Builder<'a> {
+    fn lookups(&mut self, table: Table) -> &mut Vec<Lookup> {
+        match table {
+            Table::Gsub => &mut self.gsub,
+            Table::Gpos => &mut self.gpos,
+        }
+    }
+
+    fn new_lookup(&mut self, table: Table, kind: u16, flag: u16) -> usize {
+        let lookups = self.lookups(table);
+        lookups.push(Lookup {
+            kind,
+            flag,
+            subtables: Vec::new(),
+            mark_set: None,
+        });
+        lookups.len() - 1
+    }
+
+    fn gid(&self, name: &str) -> Result<u16, String> {
+        match self.index.get(name) {
+            Some(&gid) => Ok(gid as u16),
+            None => Err(format!("glyph {} is not exported", name)),
+        }
+    }
+
+    fn gids(&self, glyphs: &GlyphSet) -> Result<Vec<u16>, String> {
+        glyphs.iter().map(|g| self.gid(g)).collect()
+    }
+
+    fn register(&mut self, table: Table, state: &FeatureState, lookup: usize) {
+        let targets = match &state.script {
+            Some(script) => vec![(script.clone(), state.lang.clone())],
+            None => self.language_systems.clone(),
+        };
+        for target in targets {
+            let key = (table, state.tag.to_string(), target);
+            let lookups = self.registered.entry(key).or_default();
+            if !lookups.contains(&lookup) {
+                lookups.push(lookup);
+            }
+        }
+    }
+
+    fn feature(&mut self, feature: &'a FeatureBlock) -> Result<(), String> {
+        let mut state = FeatureState {
+            tag: &feature.tag,
+            script: None,
+            lang: "dflt".to_string(),
+            flag: 0,
+            current: None,
+        };
+        for statement in &feature.statements {
+            match statement {
+                Statement::Script(_) | Statement::Language { .. } => {
+                    self.language(statement, &mut state)?
+                }
+                Statement::LookupFlag(flag) => {
+                    state.flag = *flag;
+                    state.current = None;
+                }
+                Statement::Lookup(block) => {
+                    let (table, lookup) = self.named_lookup(block, Some(&mut state))?;
+                    self.register(table, &state, lookup);
+                    state.current = None;
+                }
+                Statement::LookupRef(name) => {
+                    let &(table, lookup) = self
+                        .named
+                        .get(name)
+                        .ok_or_else(|| format!("{}: unknown lookup {}", feature.tag, name))?;
+                    self.register(table, &state, lookup);
+                    state.current = None;
+                }
+                Statement::FeatureRef(_) => {}
+                rule => {
+                    let (table, kind) = rule_kind(rule).unwrap();
+                    let lookup = match state.current {
+                        Some((t, lookup, k)) if t == table && k == kind => lookup,
+                        _ => {
+                            let lookup = self.new_lookup(table, kind, state.flag);
+                            self.register(table, &state, lookup);
+                            state.current = Some((table, lookup, kind));
+                            lookup
+                        }
+                    };
+                    self.add_rule(table, lookup, rule)
+                        .map_err(|e| format!("{}: {}", feature.tag, e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Change the script or language of a feature.
+    fn language(&mut self, statement: &Statement, state: &mut FeatureState) -> Result<(), String> {
+        match statement {
+            Statement::Script(script) => {
+                state.script = Some(script.clone());
+                state.lang = "dflt".to_string();
+            }
+            Statement::Language { tag, include_dflt } => {
+                let script = match &state.script {
+                    Some(script) => script.clone(),
+                    None => return Err(format!("{}: language {} has no script", state.tag, tag)),
+                };
+                for table in [Table::Gsub, Table::Gpos] {
+                    let feature = state.tag.to_string();
+                    let dflt = (table, feature.clone(), (script.clone(), "dflt".into()));
+                    let dflt = self.registered.get(&dflt).cloned().unwrap_or_default();
+                    let key = (table, feature, (script.clone(), tag.clone()));
+                    let lookups = self.registered.entry(key).or_default();
+                    if *include_dflt {
+                        for lookup in dflt {
+                            if !lookups.contains(&lookup) {
+                                lookups.push(lookup);
+                            }
+                        }
+                    } else {
+                        lookups.clear();
+                    }
+                }
+                state.lang = tag.clone();
+            }
+            _ => unreachable!(),
+        }
+        state.flag = 0;
+        state.current = None;
+        Ok(())
+    }
+
+    /// Compile a named lookup. Inside a feature, as Glyphs writes them, it
+    /// may start by changing the script or language of the feature.
+    fn named_lookup(
+        &mut self,
+        block: &LookupBlock,
+        mut state: Option<&mut FeatureState>,
+    ) -> Result<(Table, usize), String> {
+        let mut flag = 0;
+        let mut current: Option<(Table, usize, u16)> = None;
+        for statement in &block.statements {
+            match statement {
+                Statement::Script(_) | Statement::Language { .. } => match &mut state {
+                    Some(state) if current.is_none() => self.language(statement, state)?,
+                    _ => {
+                        return Err(format!(
+                            "lookup {} can only change the script or language of a \
+                             feature, before its rules",
+                            block.name
+                        ))
+                    }
+                },
+                Statement::LookupFlag(f) => {
+                    flag = *f;
+                    if let Some((table, lookup, _)) = current {
+                        self.lookups(table)[lookup].flag = flag;
+                    }
+                }
+                Statement::Substitute(_) | Statement::Position(_) => {
+                    let (table, kind) = rule_kind(statement).unwrap();
+                    let lookup = match current {
+                        None => {
+                            let lookup = self.new_lookup(table, kind, flag);
+                            current = Some((table, lookup, kind));
+                            lookup
+                        }
+                        Some((t, lookup, k)) if t == table && k == kind => lookup,
+                        Some(_) => {
+                            return Err(format!("lookup {} mixes kinds of rules", block.name))
+                        }
+                    };
+                    self.add_rule(table, lookup, statement)
+                        .map_err(|e| format!("lookup {}: {}", block.name, e))?;
+                }
+                _ => return Err(format!("lookup {} can only have rules", block.name)),
+            }
+        }
+        let (table, lookup, _) =
+            current.ok_or_else(|| format!("lookup {} is empty", block.name))?;
+        if self
+            .named
+            .insert(block.name.clone(), (table, lookup))
+            .is_some()
+        {
+            return Err(format!("lookup {} is defined twice", block.name));
+        }
+        Ok((table, lookup))
+    }
+
+    fn add_rule(&mut self, table: Table, lookup: usize, rule: &Statement) -> Result<(), String> {
+        let subtable = match rule {
+            Statement::Substitute(s) if s.contextual => return self.add_context(lookup, s),
+            Statement::Substitute(s) => self.replacement(&s.input, &s.action)?,
+            Statement::Position(p) => self.positioning(p)?,
+            _ => unreachable!(),
+        };
+        merge(&mut self.lookups(table)[lookup].subtables, subtable);
+        Ok(())
+    }
+
+    /// The substitution of a rule, without its context.
+    fn replacement(&mut self, input: &[GlyphSet], action: &SubAction) -> Result<Subtable, String> {
+        let subtable = match action {
+            SubAction::Single(to) => {
+                let from = self.gids(&input[0])?;
+                let to = self.gids(to)?;
+                let pairs = from.iter().enumerate();
+                Subtable::Single(pairs.map(|(i, &g)| (g, to[i.min(to.len() - 1)])).collect())
+            }
+            SubAction::Multiple(to) => {
+                let to = to.iter().map(|g| self.gid(g)).collect::<Result<_, _>>()?;
+                Subtable::Multiple(vec![(self.gid(&input[0][0])?, to)].into_iter().collect())
+            }
+            SubAction::Alternate(to) => {
+                let to = self.gids(to)?;
+                Subtable::Alternate(vec![(self.gid(&input[0][0])?, to)].into_iter().collect())
+            }
+            SubAction::Ligature(to) => {
+                let ligature = self.gid(to)?;
+                self.ligatures.insert(ligature);
+                let mut sequences = vec![Vec::new()];
+                for set in input {
+                    let gids = self.gids(set)?;
+                    sequences = sequences
+                        .into_iter()
+                        .flat_map(|s: Vec<u16>| {
+                            gids.iter().map(move |&g| {
+                                let mut s = s.clone();
+                                s.push(g);
+                                s
+                            })
+                        })
+                        .collect();
+                }
+                let mut ligatures: BTreeMap<u16, Vec<(Vec<u16>, u16)>> = BTreeMap::new();
+                for s in sequences {
+                    ligatures
+                        .entry(s[0])
+                        .or_default()
+                        .push((s[1..].to_vec(), ligature));
+                }
+                Subtable::Ligature(ligatures)
+            }
+            SubAction::Ignore | SubAction::Lookups(_) => unreachable!(),
+        };
+        Ok(subtable)
+    }
+
+    fn add_context(&mut self, lookup: usize, s: &Substitution) -> Result<(), String> {
+        let sets = |sets: &[GlyphSet]| -> Result<Vec<Vec<u16>>, String> {
+            sets.iter().map(|s| self.gids(s)).collect()
+        };
+        let mut rule = ContextRule {
+            backtrack: sets(&s.backtrack)?,
+            input: sets(&s.input)?,
+            lookahead: sets(&s.lookahead)?,
+            lookups: Vec::new(),
+        };
+        match &s.action {
+            SubAction::Ignore => {}
+            SubAction::Lookups(names) => {
+                for (i, name) in names.iter().enumerate() {
+                    if let Some(name) = name {
+                        match self.named.get(name) {
+                            Some((Table::Gsub, index)) => rule.lookups.push((i as u16, *index)),
+                            Some(_) => {
+                                return Err(format!("lookup {} is not a substitution", name))
+                            }
+                            None => return Err(format!("unknown lookup {}", name)),
+                        }
+                    }
+                }
+            }
+            action => {
+                let replacement = self.replacement(&s.input, action)?;
+                let nested = self.nested_lookup(lookup, replacement);
+                rule.lookups.push((0, nested));
+            }
+        }
+        self.gsub[lookup].subtables.push(Subtable::Context(rule));
+        Ok(())
+    }
+
+    /// An anonymous lookup for the replacement of a contextual rule, shared
+    /// with earlier rules of the lookup when they don't conflict.
+    fn nested_lookup(&mut self, lookup: usize, replacement: Subtable) -> usize {
+        if let Some(&nested) = self.nested.get(&lookup) {
+            let last = &mut self.gsub[nested].subtables;
+            if compatible(&last[0], &replacement) {
+                merge(last, replacement);
+                return nested;
+            }
+        }
+        let kind = match replacement {
+            Subtable::Single(_) => 1,
+            Subtable::Multiple(_) => 2,
+            Subtable::Alternate(_) => 3,
+            _ => 4,
+        };
+        let flag = self.gsub[lookup].flag;
+        let nested = self.new_lookup(Table::Gsub, kind, flag);
+        self.gsub[nested].subtables.push(replacement);
+        self.nested.insert(lookup, nested);
+        nested
+    }
+
+    fn positioning(&self, p: &Positioning) -> Result<Subtable, String> {
+        let value = |i: usize| p.values[i].unwrap_or_default();
+        let first = self.gids(&p.glyphs[0])?;
+        if p.glyphs.len() == 1 {
+            return Ok(Subtable::SinglePos(
+                first.into_iter().map(|g| (g, value(0))).collect(),
+            ));
+        }
+        let second = self.gids(&p.glyphs[1])?;
+        let mut pairs: BTreeMap<u16, Pairs> = BTreeMap::new();
+        for &a in &first {
+            for &b in &second {
+                pairs
+                    .entry(a)
+                    .or_default()
+                    .entry(b)
+                    .or_insert((value(0), value(1)));
+            }
+        }
+        Ok(Subtable::PairPos(pairs))
+    }
+
+    /// Fill in the `aalt` lookup, reserved as the first lookup, from the
+    /// features that `aalt` names.
+    fn aalt(&mut self, aalt: &'a FeatureBlock) -> Result<(), String> {
+        let mut alternates: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        for statement in &aalt.statements {
+            let tag = match statement {
+                Statement::FeatureRef(tag) => tag,
+                _ => continue,
+            };
+            let lookups: BTreeSet<usize> = self
+                .registered
+                .iter()
+                .filter(|((table, t, _), _)| *table == Table::Gsub && t == tag)
+                .flat_map(|(_, lookups)| lookups.iter().copied())
+                .collect();
+            for lookup in lookups {
+                for subtable in &self.gsub[lookup].subtables {
+                    let pairs: Vec<(u16, u16)> = match subtable {
+                        Subtable::Single(map) => map.iter().map(|(&a, &b)| (a, b)).collect(),
+                        Subtable::Alternate(map) => map
+                            .iter()
+                            .flat_map(|(&a, alts)| alts.iter().map(move |&b| (a, b)))
+                            .collect(),
+                        _ => continue,
+                    };
+                    for (a, b) in pairs {
+                        let alts = alternates.entry(a).or_default();
+                        if a != b && !alts.contains(&b) {
+                            alts.push(b);
+                        }
+                    }
+                }
+            }
+        }
+        self.gsub[0].subtables = vec![Subtable::Alternate(alternates)];
+        let state = FeatureState {
+            tag: &aalt.tag,
+            script: None,
+            lang: "dflt".to_string(),
+            flag: 0,
+            current: None,
+        };
+        self.register(Table::Gsub, &state, 0);
+        self.feature(aalt)
+    }
+
+    /// Add the `mark` and `mkmk` features made from the anchors, except
+    /// those the feature code defines. `classes` are the `GDEF` classes.
+    fn mark_features(&mut self, anchors: &MarkAnchors, classes: &[u16], defined: &[&str]) {
+        let names: BTreeSet<&str> = anchors
+            .anchors
+            .iter()
+            .flatten()
+            .filter_map(|(name, _)| name.strip_prefix('_'))
+            .collect();
+        let with = |name: &str, marks: bool| -> BTreeMap<u16, AnchorPoint> {
+            let glyphs = anchors.anchors.iter().enumerate();
+            glyphs
+                .filter(|&(gid, _)| (classes[gid] == 3) == marks)
+                .filter_map(|(gid, anchors)| {
+                    let anchor = anchors.iter().find(|(n, _)| n == name)?;
+                    Some((gid as u16, anchor.1))
+                })
+                .collect()
+        };
+        for &(tag, kind) in &[("mark", 4), ("mkmk", 6)] {
+            if defined.contains(&tag) {
+                continue;
+            }
+            let state = FeatureState {
+                tag,
+                script: None,
+                lang: "dflt".to_string(),
+                flag: 0,
+                current: None,
+            };
+            for name in &names {
+                let marks = with(&format!("_{}", name), true);
+                let targets = with(name, kind == 6);
+                if marks.is_empty() || targets.is_empty() {
+                    continue;
+                }
+                let lookup = if kind == 6 {
+                    let lookup = self.new_lookup(Table::Gpos, kind, 0x10);
+                    self.gpos[lookup].mark_set = Some(self.mark_sets.len() as u16);
+                    self.mark_sets.push(marks.keys().copied().collect());
+                    lookup
+                } else {
+                    self.new_lookup(Table::Gpos, kind, 0)
+                };
+                let marks = marks.into_iter().map(|(g, a)| (g, (0, a))).collect();
+                let targets = targets.into_iter().map(|(g, a)| (g, vec![a])).collect();
+                let subtable = Subtable::MarkAttach(marks, targets);
+                self.gpos[lookup].subtables.push(subtable);
+                self.register(Table::Gpos, &state, lookup);
+            }
+        }
+    }
+}
+
+fn tag(s: &str) -> [u8; 4] {
+    let mut tag = *b"    ";
+    for (t, b) in tag.iter_mut().zip(s.bytes()) {
+        *t = b;
+    }
+    tag
+}
+
+/// Append `data`, pointing the 16-bit offset at `pos`, relative to `base`,
+/// at it.
+fn append(w: &mut Writer, pos: usize, base: usize, data: &[u8]) {
+    let offset = u16::try_from(w.len() - base).expect("layout table too large");
+    w.set_u16(pos, offset);
+    w.bytes(data);
+}
+
+/// Write a placeholder offset, returning its position.
+fn placeholder(w: &mut Writer) -> usize {
+    w.u16(0);
+    w.len() - 2
+}
+
+fn coverage(glyphs: &[u16]) -> Vec<u8> {
+    let mut ranges: Vec<(u16, u16, u16)> = Vec::new();
+    for (i, &g) in glyphs.iter().enumerate() {
+        match ranges.last_mut() {
+            Some(r) if r.1 + 1 == g => r.1 = g,
+            _ => ranges.push((g, g, i as u16)),
+        }
+    }
+    let mut w = Writer::new();
+    if ranges.len() * 3 < glyphs.len() {
+        w.u16(2).u16(ranges.len() as u16);
+        for (start, end, index) in ranges {
+            w.u16(start).u16(end).u16(index);
+        }
+    } else {
+        w.u16(1).u16(glyphs.len() as u16);
+        for &g in glyphs {
+            w.u16(g);
+        }
+    }
+    w.data
+}
+
+fn class_def(classes: &[u16]) -> Vec<u8> {
+    let mut ranges: Vec<(u16, u16, u16)> = Vec::new();
+    for (g, &class) in classes.iter().enumerate() {
+        let g = g as u16;
+        match ranges.last_mut() {
+            Some(r) if r.1 + 1 == g && r.2 == class => r.1 = g,
+            _ if class != 0 => ranges.push((g, g, class)),
+            _ => {}
+        }
+    }
+    let mut w = Writer::new();
+    w.u16(2).u16(ranges.len() as u16);
+    for (start, end, class) in ranges {
+        w.u16(start).u16(end).u16(class);
+    }
+    w.data
+}
+
+/// A subtable with a coverage and one record per covered glyph, each
+/// written by `record` and pointed to by an offset.
+fn covered<T>(format: u16, map: &BTreeMap<u16, T>, record: impl Fn(&T) -> Vec<u8>) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.u16(format);
+    let cov = placeholder(&mut w);
+    w.u16(map.len() as u16);
+    let offsets: Vec<usize> = map.keys().map(|_| placeholder(&mut w)).collect();
+    let glyphs: Vec<u16> = map.keys().copied().collect();
+    append(&mut w, cov, 0, &coverage(&glyphs));
+    for (pos, value) in offsets.into_iter().zip(map.values()) {
+        append(&mut w, pos, 0, &record(value));
+    }
+    w.data
+}
+
+fn glyph_array(glyphs: &[u16]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.u16(glyphs.len() as u16);
+    for &g in glyphs {
+        w.u16(g);
+    }
+    w.data
+}
+
+/// An anchor table, with device tables pointing at its deltas if it
+/// varies.
+fn anchor(a: AnchorPoint) -> Vec<u8> {
+    let mut w = Writer::new();
+    match a.deltas {
+        None => {
+            w.u16(1).i16(a.x).i16(a.y);
+        }
+        Some((x, y)) => {
+            w.u16(3).i16(a.x).i16(a.y).u16(10).u16(16);
+            for &inner in &[x, y] {
+                w.u16(0).u16(inner).u16(0x8000);
+            }
+        }
+    }
+    w.data
+}
+
+fn value_format(values: impl Iterator<Item = ValueRecord>) -> u16 {
+    values.fold(0, |format, v| {
+        format
+            | (v.x_placement != 0) as u16
+            | ((v.y_placement != 0) as u16) << 1
+            | ((v.x_advance != 0) as u16) << 2
+            | ((v.y_advance != 0) as u16) << 3
+    })
+}
+
+fn value(w: &mut Writer, format: u16, v: ValueRecord) {
+    let fields = [v.x_placement, v.y_placement, v.x_advance, v.y_advance];
+    for (i, x) in fields.iter().enumerate() {
+        if format & (1 << i) != 0 {
+            w.i16(*x);
+        }
+    }
+}
+
+/// The most pairs in one pair positioning subtable, keeping its offsets in
+/// range.
+const MAX_PAIRS: usize = 3000;
+
+/// The binary subtables for a subtable. Pair positioning is split when it
+/// has too many pairs for one.
+fn subtables(subtable: &Subtable) -> Vec<Vec<u8>> {
+    match subtable {
+        Subtable::Single(map) => {
+            let delta = |(&a, &b): (&u16, &u16)| b.wrapping_sub(a);
+            let first = map.iter().next().map(delta);
+            let mut w = Writer::new();
+            if map.iter().all(|e| Some(delta(e)) == first) {
+                w.u16(1);
+                let cov = placeholder(&mut w);
+                w.u16(first.unwrap_or(0));
+                let glyphs: Vec<u16> = map.keys().copied().collect();
+                append(&mut w, cov, 0, &coverage(&glyphs));
+            } else {
+                w.u16(2);
+                let cov = placeholder(&mut w);
+                w.u16(map.len() as u16);
+                for &g in map.values() {
+                    w.u16(g);
+                }
+                let glyphs: Vec<u16> = map.keys().copied().collect();
+                append(&mut w, cov, 0, &coverage(&glyphs));
+            }
+            vec![w.data]
+        }
+        Subtable::Multiple(map) | Subtable::Alternate(map) => {
+            vec![covered(1, map, |glyphs| glyph_array(glyphs))]
+        }
+        Subtable::Ligature(map) => vec![covered(1, map, |ligatures| {
+            // Longer ligatures first, and the first of any duplicates.
+            let mut ligatures: Vec<&(Vec<u16>, u16)> = ligatures.iter().collect();
+            ligatures.sort_by_key(|l| std::cmp::Reverse(l.0.len()));
+            ligatures.dedup_by(|a, b| a.0 == b.0);
+            let mut w = Writer::new();
+            w.u16(ligatures.len() as u16);
+            let offsets: Vec<usize> = ligatures.iter().map(|_| placeholder(&mut w)).collect();
+            for (pos, (rest, ligature)) in offsets.into_iter().zip(ligatures) {
+                let mut l = Writer::new();
+                l.u16(*ligature).u16(rest.len() as u16 + 1);
+                for &g in rest {
+                    l.u16(g);
+                }
+                append(&mut w, pos, 0, &l.data);
+            }
+            w.data
+        })],
+        Subtable::Context(rule) => {
+            let mut w = Writer::new();
+            w.u16(3);
+            let mut coverages = Vec::new();
+            let backtrack = rule.backtrack.iter().rev();
+            for sequence in [
+                backtrack.collect::<Vec<_>>(),
+                rule.input.iter().collect(),
+                rule.lookahead.iter().collect(),
+            ] {
+                w.u16(sequence.len() as u16);
+                for glyphs in sequence {
+                    let mut glyphs = glyphs.clone();
+                    glyphs.sort_unstable();
+                    glyphs.dedup();
+                    coverages.push((placeholder(&mut w), glyphs));
+                }
+            }
+            w.u16(rule.lookups.len() as u16);
+            for &(index, lookup) in &rule.lookups {
+                w.u16(index).u16(lookup as u16);
+            }
+            for (pos, glyphs) in coverages {
+                append(&mut w, pos, 0, &coverage(&glyphs));
+            }
+            vec![w.data]
+        }
+        Subtable::SinglePos(map) => {
+            let format = value_format(map.values().copied());
+            let first = map.values().next().copied();
+            let mut w = Writer::new();
+            let glyphs: Vec<u16> = map.keys().copied().collect();
+            if map.values().all(|&v| Some(v) == first) {
+                w.u16(1);
+                let cov = placeholder(&mut w);
+                w.u16(format);
+                value(&mut w, format, first.unwrap_or_default());
+                append(&mut w, cov, 0, &coverage(&glyphs));
+            } else {
+                w.u16(2);
+                let cov = placeholder(&mut w);
+                w.u16(format).u16(map.len() as u16);
+                for &v in map.values() {
+                    value(&mut w, format, v);
+                }
+                append(&mut w, cov, 0, &coverage(&glyphs));
+            }
+            vec![w.data]
+        }
+        Subtable::PairPos(map) => {
+            let mut chunks: Vec<BTreeMap<u16, &Pairs>> = vec![BTreeMap::new()];
+            let mut count = 0;
+            for (&first, pairs) in map {
+                if count + pairs.len() > MAX_PAIRS && count > 0 {
+                    chunks.push(BTreeMap::new());
+                    count = 0;
+                }
+                chunks.last_mut().unwrap().insert(first, pairs);
+                count += pairs.len();
+            }
+            chunks
+                .iter()
+                .map(|chunk| {
+                    let values = chunk.values().flat_map(|p| p.values());
+                    let format1 = value_format(values.clone().map(|v| v.0));
+                    let format2 = value_format(values.map(|v| v.1));
+                    let mut w = Writer::new();
+                    w.u16(1);
+                    let cov = placeholder(&mut w);
+                    w.u16(format1).u16(format2).u16(chunk.len() as u16);
+                    let offsets: Vec<usize> = chunk.keys().map(|_| placeholder(&mut w)).collect();
+                    let glyphs: Vec<u16> = chunk.keys().copied().collect();
+                    append(&mut w, cov, 0, &coverage(&glyphs));
+                    for (pos, pairs) in offsets.into_iter().zip(chunk.values()) {
+                        let mut set = Writer::new();
+                        set.u16(pairs.len() as u16);
+                        for (&second, &(v1, v2)) in pairs.iter() {
+                            set.u16(second);
+                            value(&mut set, format1, v1);
+                            value(&mut set, format2, v2);
+                        }
+                        append(&mut w, pos, 0, &set.data);
+                    }
+                    w.data
+                })
+                .collect()
+        }
+        Subtable::MarkAttach(marks, targets) => {
+            let class_count = targets.values().next().map_or(0, Vec::len);
+            let mut w = Writer::new();
+            w.u16(1);
+            let mark_coverage = placeholder(&mut w);
+            let target_coverage = placeholder(&mut w);
+            w.u16(class_count as u16);
+            let mark_array = placeholder(&mut w);
+            let target_array = placeholder(&mut w);
+            let glyphs: Vec<u16> = marks.keys().copied().collect();
+            append(&mut w, mark_coverage, 0, &coverage(&glyphs));
+            let glyphs: Vec<u16> = targets.keys().copied().collect();
+            append(&mut w, target_coverage, 0, &coverage(&glyphs));
+            let mut array = Writer::new();
+            array.u16(marks.len() as u16);
+            let mut offsets = Vec::new();
+            for &(class, a) in marks.values() {
+                array.u16(class);
+                offsets.push((placeholder(&mut array), a));
+            }
+            for (pos, a) in offsets {
+                append(&mut array, pos, 0, &anchor(a));
+            }
+            append(&mut w, mark_array, 0, &array.data);
+            let mut array = Writer::new();
+            array.u16(targets.len() as u16);
+            let mut offsets = Vec::new();
+            for anchors in targets.values() {
+                for &a in anchors {
+                    offsets.push((placeholder(&mut array), a));
+                }
+            }
+            for (pos, a) in offsets {
+                append(&mut array, pos, 0, &anchor(a));
+            }
+            append(&mut w, target_array, 0, &array.data);
+            vec![w.data]
+        }
+    }
+}
+
+/// The lookup list. If the offsets of the plain lookups don't fit in 16
+/// bits, every lookup uses extension subtables instead.
+fn lookup_list(lookups: &[Lookup], table: Table) -> Vec<u8> {
+    let data: Vec<Vec<Vec<u8>>> = lookups
+        .iter()
+        .map(|l| l.subtables.iter().flat_map(subtables).collect())
+        .collect();
+    let header = |w: &mut Writer, kind: u16, lookup: &Lookup, count: usize| -> Vec<usize> {
+        w.u16(kind).u16(lookup.flag).u16(count as u16);
+        let positions = (0..count).map(|_| placeholder(w)).collect();
+        if let Some(set) = lookup.mark_set {
+            w.u16(set);
+        }
+        positions
+    };
+    let fits = {
+        let mut start = 2 + 2 * lookups.len();
+        data.iter().zip(lookups).all(|(subtables, lookup)| {
+            let size = 6 + 2 * subtables.len() + 2 * lookup.mark_set.is_some() as usize;
+            let last = subtables.iter().rev().skip(1).map(Vec::len).sum::<usize>();
+            let fits = start <= 0xFFFF && size + last <= 0xFFFF;
+            start += size + subtables.iter().map(Vec::len).sum::<usize>();
+            fits
+        })
+    };
+    let mut w = Writer::new();
+    w.u16(lookups.len() as u16);
+    let offsets: Vec<usize> = lookups.iter().map(|_| placeholder(&mut w)).collect();
+    if fits {
+        for ((lookup, subtables), pos) in lookups.iter().zip(&data).zip(offsets) {
+            let mut l = Writer::new();
+            let positions = header(&mut l, lookup.kind, lookup, subtables.len());
+            for (pos, subtable) in positions.into_iter().zip(subtables) {
+                append(&mut l, pos, 0, subtable);
+            }
+            append(&mut w, pos, 0, &l.data);
+        }
+        return w.data;
+    }
+    let extension = match table {
+        Table::Gsub => 7,
+        Table::Gpos => 9,
+    };
+    // Lookup headers with their extension subtables, then the subtables.
+    let mut pending = Vec::new();
+    for ((lookup, subtables), pos) in lookups.iter().zip(&data).zip(offsets) {
+        let start = w.len();
+        w.set_u16(pos, start as u16);
+        let positions = header(&mut w, extension, lookup, subtables.len());
+        for (pos, subtable) in positions.into_iter().zip(subtables) {
+            w.set_u16(pos, (w.len() - start) as u16);
+            w.u16(1).u16(lookup.kind);
+            pending.push((w.len(), subtable));
+            w.u32(0);
+        }
+    }
+    for (pos, subtable) in pending {
+        let offset = w.len() - (pos - 4);
+        w.set_u32(pos, offset as u32);
+        w.bytes(subtable);
+    }
+    w.data
+}
+
+/// A `GSUB` or `GPOS` table.
+fn layout_table(builder: &Builder, table: Table, lookups: &[Lookup]) -> Vec<u8> {
+    let registered: Vec<(&String, &(String, String), &Vec<usize>)> = builder
+        .registered
+        .iter()
+        .filter(|((t, _, _), lookups)| *t == table && !lookups.is_empty())
+        .map(|((_, tag, ls), lookups)| (tag, ls, lookups))
+        .collect();
+    let features: BTreeSet<(&String, &Vec<usize>)> =
+        registered.iter().map(|&(tag, _, l)| (tag, l)).collect();
+    let features: Vec<(&String, &Vec<usize>)> = features.into_iter().collect();
+    let mut systems: BTreeMap<[u8; 4], BTreeMap<[u8; 4], Vec<u16>>> = BTreeMap::new();
+    for (script, lang) in &builder.language_systems {
+        systems
+            .entry(tag(script))
+            .or_default()
+            .entry(tag(lang))
+            .or_default();
+    }
+    for &(t, (script, lang), lookups) in &registered {
+        let index = features.iter().position(|&f| f == (t, lookups)).unwrap();
+        let indices = systems
+            .entry(tag(script))
+            .or_default()
+            .entry(tag(lang))
+            .or_default();
+        indices.push(index as u16);
+        indices.sort_unstable();
+    }
+
+    let lang_sys = |indices: &[u16]| {
+        let mut w = Writer::new();
+        w.u16(0).u16(0xFFFF).u16(indices.len() as u16);
+        for &i in indices {
+            w.u16(i);
+        }
+        w.data
+    };
+    let mut scripts = Writer::new();
+    scripts.u16(systems.len() as u16);
+    let mut script_offsets = Vec::new();
+    for script in systems.keys() {
+        scripts.tag(script);
+        script_offsets.push(placeholder(&mut scripts));
+    }
+    for (pos, langs) in script_offsets.into_iter().zip(systems.values()) {
+        let mut s = Writer::new();
+        let default = placeholder(&mut s);
+        let others: Vec<_> = langs.iter().filter(|(l, _)| *l != b"dflt").collect();
+        s.u16(others.len() as u16);
+        let mut offsets = Vec::new();
+        for (lang, _) in &others {
+            s.tag(lang);
+            offsets.push(placeholder(&mut s));
+        }
+        if let Some(indices) = langs.get(b"dflt") {
+            append(&mut s, default, 0, &lang_sys(indices));
+        }
+        for (pos, (_, indices)) in offsets.into_iter().zip(others) {
+            append(&mut s, pos, 0, &lang_sys(indices));
+        }
+        append(&mut scripts, pos, 0, &s.data);
+    }
+
+    let mut feature_list = Writer::new();
+    feature_list.u16(features.len() as u16);
+    let mut offsets = Vec::new();
+    for (t, _) in &features {
+        feature_list.tag(&tag(t));
+        offsets.push(placeholder(&mut feature_list));
+    }
+    for (pos, (_, lookups)) in offsets.into_iter().zip(&features) {
+        let mut f = Writer::new();
+        f.u16(0).u16(lookups.len() as u16);
+        for &l in lookups.iter() {
+            f.u16(l as u16);
+        }
+        append(&mut feature_list, pos, 0, &f.data);
+    }
+
+    let mut w = Writer::new();
+    w.u16(1).u16(0);
+    let positions = [
+        placeholder(&mut w),
+        placeholder(&mut w),
+        placeholder(&mut w),
+    ];
+    append(&mut w, positions[0], 0, &scripts.data);
+    append(&mut w, positions[1], 0, &feature_list.data);
+    append(&mut w, positions[2], 0, &lookup_list(lookups, table));
+    w.data
+}
+
+/// The `GDEF` classes of the glyphs.
+fn glyph_classes(anchors: &MarkAnchors, ligatures: &HashSet<u16>) -> Vec<u16> {
+    anchors
+        .anchors
+        .iter()
+        .enumerate()
+        .map(|(gid, anchors)| {
+            if gid == 0 {
+                0
+            } else if anchors.iter().any(|(name, _)| name.starts_with('_')) {
+                3
+            } else if ligatures.contains(&(gid as u16)) {
+                2
+            } else {
+                1
+            }
+        })
+        .collect()
+}
+
+/// The `GDEF` table: version 1.2 if there are mark filtering sets, and 1.3
+/// with a variation store.
+fn gdef(classes: &[u16], mark_sets: &[Vec<u16>], store: Option<&[u8]>) -> Vec<u8> {
+    let minor = match (mark_sets.is_empty(), store) {
+        (_, Some(_)) => 3,
+        (false, None) => 2,
+        (true, None) => 0,
+    };
+    let mut w = Writer::new();
+    w.u16(1).u16(minor);
+    let class_offset = placeholder(&mut w);
+    w.u16(0).u16(0).u16(0);
+    let sets_offset = placeholder(&mut w);
+    if minor < 2 {
+        w.data.truncate(sets_offset);
+    }
+    let store_offset = w.len();
+    if minor == 3 {
+        w.u32(0);
+    }
+    append(&mut w, class_offset, 0, &class_def(classes));
+    if !mark_sets.is_empty() {
+        let mut sets = Writer::new();
+        sets.u16(1).u16(mark_sets.len() as u16);
+        let offsets: Vec<usize> = mark_sets
+            .iter()
+            .map(|_| {
+                sets.u32(0);
+                sets.len() - 4
+            })
+            .collect();
+        for (pos, glyphs) in offsets.into_iter().zip(mark_sets) {
+            sets.set_u32(pos, sets.len() as u32);
+            sets.bytes(&coverage(glyphs));
+        }
+        append(&mut w, sets_offset, 0, &sets.data);
+    }
+    if let Some(store) = store {
+        w.set_u32(store_offset, w.len() as u32);
+        w.bytes(store);
+    }
+    w.data
+}
+
+/// The layout tables for the font with the glyphs in the given order:
+/// `GSUB` and `GPOS` if the feature code or the anchors have lookups for
+/// them, with `GDEF`.
+pub(crate) fn layout_tables(
+    font: &Font,
+    glyphs: &[&Glyph],
+    anchors: &MarkAnchors,
+) -> Result<Vec<TableData>, String> {
+    let fea = parse_features(font).map_err(|errors| errors.join("\n"))?;
+    let mut language_systems = fea.language_systems.clone();
+    if language_systems.is_empty() {
+        language_systems.push(("DFLT".to_string(), "dflt".to_string()));
+    }
+    let mut builder = Builder {
+        index: glyph_index(glyphs),
+        language_systems,
+        gsub: Vec::new(),
+        gpos: Vec::new(),
+        named: HashMap::new(),
+        registered: BTreeMap::new(),
+        nested: HashMap::new(),
+        ligatures: HashSet::new(),
+        mark_sets: Vec::new(),
+    };
+    let aalt = fea.blocks.iter().find_map(|b| match b {
+        Block::Feature(f) if f.tag == "aalt" => Some(f),
+        _ => None,
+    });
+    if aalt.is_some() {
+        builder.new_lookup(Table::Gsub, 3, 0);
+    }
+    for block in &fea.blocks {
+        match block {
+            Block::Feature(f) if f.tag == "aalt" => {}
+            Block::Feature(f) => builder.feature(f)?,
+            Block::Lookup(l) => {
+                builder.named_lookup(l, None)?;
+            }
+        }
+    }
+    if let Some(aalt) = aalt {
+        builder.aalt(aalt)?;
+    }
+    let classes = glyph_classes(anchors, &builder.ligatures);
+    let defined: Vec<&str> = fea
+        .blocks
+        .iter()
+        .filter_map(|b| match b {
+            Block::Feature(f) => Some(f.tag.as_str()),
+            _ => None,
+        })
+        .collect();
+    builder.mark_features(anchors, &classes, &defined);
+    let mut tables = Vec::new();
+    if !builder.gsub.is_empty() {
+        tables.push((*b"GSUB", layout_table(&builder, Table::Gsub, &builder.gsub)));
+    }
+    if !builder.gpos.is_empty() {
+        tables.push((*b"GPOS", layout_table(&builder, Table::Gpos, &builder.gpos)));
+    }
+    if !tables.is_empty() {
+        let store = anchors.store.as_deref();
+        tables.push((*b"GDEF", gdef(&classes, &builder.mark_sets, store)));
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn font(features: &str) -> Font {
+        let source = format!(
+            r#"{{
+fontMaster = ({{id = m; weightValue = 400;}});
+glyphs = (
+{{glyphname = .notdef; layers = ({{layerId = m; width = 500;}});}},
+{{glyphname = a; unicode = 0061; layers = ({{layerId = m; width = 500;
+anchors = ({{name = top; position = "{{250, 450}}";}}, {{name = origin; position = "{{0, 0}}";}});}});}},
+{{glyphname = acutecomb; unicode = 0301; layers = ({{layerId = m; width = 500;
+anchors = ({{name = _top; position = "{{260, 450}}";}}, {{name = top; position = "{{280, 600}}";}});}});}}
+);
+features = ({features}
+);
+}}"#,
+            features = features
+        );
+        Font::from_plist(Plist::parse(&source).unwrap())
+    }
+
+    fn tables(font: &Font) -> HashMap<[u8; 4], Vec<u8>> {
+        let glyphs: Vec<&Glyph> = font.glyphs.iter().collect();
+        let layers: Vec<&Layer> = glyphs.iter().map(|g| &g.layers[0]).collect();
+        let anchors = MarkAnchors::of_layers(font, &layers);
+        let tables = layout_tables(font, &glyphs, &anchors).unwrap();
+        tables.into_iter().collect()
+    }
+
+    fn u16_at(data: &[u8], pos: usize) -> u16 {
+        u16::from_be_bytes([data[pos], data[pos + 1]])
+    }
+
+    /// The type, flag and mark filtering set of each lookup in `GPOS`.
+    fn lookups(gpos: &[u8]) -> Vec<(u16, u16, Option<u16>)> {
+        let list = u16_at(gpos, 8) as usize;
+        (0..u16_at(gpos, list) as usize)
+            .map(|i| {
+                let lookup = list + u16_at(gpos, list + 2 + 2 * i) as usize;
+                let flag = u16_at(gpos, lookup + 2);
+                let count = u16_at(gpos, lookup + 4) as usize;
+                let set = Some(u16_at(gpos, lookup + 6 + 2 * count)).filter(|_| flag & 0x10 != 0);
+                (u16_at(gpos, lookup), flag, set)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mark_and_mkmk_from_anchors() {
+        let tables = tables(&font(""));
+        assert_eq!(
+            lookups(&tables[b"GPOS"]),
+            [(4, 0, None), (6, 0x10, Some(0))]
+        );
+        // Version 1.2, for the mark filtering set.
+        assert_eq!(u16_at(&tables[b"GDEF"], 2), 2);
+    }
+
+    #[test]
+    fn feature_code_replaces_mark_from_anchors() {
+        let tables = tables(&font(r#"{name = mark; code = "pos a acutecomb -10;";}"#));
+        assert_eq!(
+            lookups(&tables[b"GPOS"]),
+            [(2, 0, None), (6, 0x10, Some(0))]
+        );
+    }
+
+    #[test]
+    fn only_mark_anchors() {
+        assert!(is_mark_anchor("top"));
+        assert!(is_mark_anchor("_bottom"));
+        assert!(!is_mark_anchor("origin"));
+        assert!(!is_mark_anchor("exit"));
+        assert!(!is_mark_anchor("caret_1"));
+        assert!(!is_mark_anchor("*top"));
+    }
+}
@@ -1,16 +1,93 @@
 //! Lightweight library for reading and writing Glyphs font files.
 
+mod anchors;
+mod boxdraw;
+mod cell;
+mod cff;
+mod charset;
+mod clean;
+mod compile;
+mod compose;
+mod derive;
+mod fea;
 mod font;
 mod from_plist;
+mod glyf;
+mod interpolate;
+mod kerning;
+mod layout;
+mod measure;
+mod metrics;
+mod nam;
 pub mod ops;
+mod param;
 mod plist;
 mod region;
+mod round;
+mod sfnt;
+mod shade;
+mod slant;
 mod stretch;
+mod stroke;
+mod subset;
+mod symbols;
 mod to_plist;
+mod ttf;
+mod ufo;
+mod variable;
+mod variant;
+mod xml;
 
-pub use font::{Component, Font, Glyph, Layer, Node, NodeType, Path};
+pub use anchors::{check_anchors, propagate_anchors, AnchorIssue};
+pub use boxdraw::{box_glyph, draw_box_glyphs, BoxDraw, BoxGlyph, BoxStyle, BoxType, Eighths};
+pub use cell::CellMetrics;
+pub use charset::{
+    charset_coverage, coverage_json, default_namelists, CharsetCoverage, DEFAULT_NAMELISTS,
+};
+pub use clean::clean;
+pub use compile::{compile_static, instances, static_file_name, Instance, Outlines};
+pub use compose::{attach, build_composites, Composite, Mark};
+pub use derive::{derive, DeriveIssue, Recipes};
+pub use fea::{
+    automatic_class, parse_features, rename_glyphs_in_code, update_automatic_classes, Block,
+    FeatureBlock, FeatureFile, GlyphSet, LookupBlock, Positioning, Statement, SubAction,
+    Substitution, ValueRecord,
+};
+pub use font::{
+    Anchor, Component, FeatureCode, Font, FontClass, FontMaster, Glyph, Layer, Node, NodeType, Path,
+};
 pub use from_plist::FromPlist;
+pub use interpolate::{
+    glyph_stem, instance_font, interpolate, location_for_stem, master_factors, master_location,
+    scaled_glyph, StemMatch,
+};
+pub use kerning::{check_kerning, KernSide, Kerning, KerningGroups, KerningIssue};
+pub use measure::{
+    coverage, horizontal_stem_at, ink_area, ink_bounds, ink_runs, ink_runs_vertical, layer_stems,
+    stem_at, stems, Stems,
+};
+pub use metrics::{
+    apply_metrics_keys, check_metrics_keys, KeyBase, Metric, MetricsIssue, MetricsKey,
+};
+pub use nam::NameList;
+pub use param::Value;
 pub use plist::Plist;
 pub use region::Region;
-pub use stretch::stretch;
+pub use round::{round, round_layer, round_path, RoundIssue};
+pub use shade::{check_shades, shade_paths, DotShape, ShadePattern};
+pub use slant::{slant, slant_with, SlantOrigin};
+pub use stretch::{stretch, stretch_with, StemSource, StretchOptions};
+pub use stroke::{stroke, Cap, Join, StrokeStyle, KAPPA};
+pub use subset::{subset, SubsetReport};
+pub use symbols::{add_symbols, symbol_paths, SymbolSet};
 pub use to_plist::ToPlist;
+pub use ttf::{compare_fonts, FontAxis, GlyphDifference, TrueTypeFont, UserLocation};
+pub use ufo::{
+    axes, features_fea, instances_outside_masters, ufo_name, write_ufos, Axis, MASTER_ID_KEY,
+};
+pub use variable::{compile_variable, variable_file_name};
+pub use variant::{
+    bake_features, ligconsolata, make_variant, remove_feature, rename_family, rename_feature,
+    rename_glyphs, Variant,
+};
+pub use xml::Element;
@@ -9,8 +9,25 @@ mod inco_fix;
 enum Cmd {
     Merge(MergeCmd),
     IncoFix(IncoFixCmd),
-    IncoScale(IncoScaleCmd),
     IncoSyms(IncoSymsCmd),
+    Round(RoundCmd),
+    Clean(CleanCmd),
+    Slant(SlantCmd),
+    Stretch(StretchCmd),
+    Derive(DeriveCmd),
+    Symbols(SymbolsCmd),
+    Measure(MeasureCmd),
+    Anchors(AnchorsCmd),
+    ToUfo(ToUfoCmd),
+    Compile(CompileCmd),
+    Features(FeaturesCmd),
+    Variant(VariantCmd),
+    BakeFeatures(BakeFeaturesCmd),
+    Kerning(KerningCmd),
+    MetricsKeys(MetricsKeysCmd),
+    Subset(SubsetCmd),
+    Coverage(CoverageCmd),
+    Compare(CompareCmd),
 }
 
 #[derive(StructOpt, Debug)]
@@ -19,11 +36,13 @@ struct MergeCmd {
     #[structopt(parse(from_os_str))]
     font: PathBuf,
 
-    /// The other font file, to use as a source.
+    /// The other font file, to use as a source: a Glyphs file or a
+    /// designspace document with UFO masters.
     #[structopt(parse(from_os_str))]
     other: PathBuf,
 
-    /// The layer to merge (UUID).
+    /// The master to merge: its ID, or its location as values on each axis,
+    /// like `400,100` for weight and width.
     layer: String,
 }
 
@@ -35,25 +54,325 @@ struct IncoFixCmd {
 }
 
 #[derive(StructOpt, Debug)]
-struct IncoScaleCmd {
+struct IncoSymsCmd {
+    /// The font file to operate on.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct RoundCmd {
     /// The font file to operate on.
     #[structopt(parse(from_os_str))]
     font: PathBuf,
 
-    /// Subcommand. 0: numerics. 1: ord.
-    ///
-    /// This should be an enum, but int is easier.
-    subcmd: i32,
+    /// The layer to round (UUID).
+    layer: String,
+
+    /// The grid size to round to.
+    #[structopt(long, default_value = "1")]
+    grid: f64,
 }
 
 #[derive(StructOpt, Debug)]
-struct IncoSymsCmd {
+struct CleanCmd {
+    /// The font file to operate on.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The layer to clean (UUID). All layers are cleaned if not given.
+    /// Extrema added to one master but not the others would make the
+    /// glyphs incompatible, so this requires --no-extremes.
+    #[structopt(long, requires = "no-extremes")]
+    layer: Option<String>,
+
+    /// Don't add nodes at curve extrema.
+    #[structopt(long)]
+    no_extremes: bool,
+
+    /// Round the cleaned layers to this grid size.
+    #[structopt(long)]
+    grid: Option<f64>,
+}
+
+#[derive(StructOpt, Debug)]
+struct SlantCmd {
+    /// The font file to operate on.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The slant angle in degrees; positive leans right.
+    #[structopt(allow_hyphen_values = true)]
+    angle: f64,
+
+    /// The layer to slant (UUID).
+    layer: String,
+
+    /// Skew around the baseline instead of half the x-height.
+    #[structopt(long)]
+    baseline: bool,
+
+    /// Don't shift glyphs to keep them centered in the cell.
+    #[structopt(long)]
+    no_recenter: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct StretchCmd {
+    /// The font file to operate on.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The horizontal scale factor.
+    factor: f64,
+
+    /// The layer to stretch (UUID).
+    layer: String,
+
+    /// Keep vertical stems at this thickness.
+    #[structopt(long)]
+    stem: Option<f64>,
+
+    /// Keep vertical stems at the master's first vertical stem value.
+    #[structopt(long)]
+    master_stems: bool,
+
+    /// Keep vertical stems at the thickness measured in this glyph.
+    #[structopt(long)]
+    measure: Option<String>,
+
+    /// Glyphs to leave alone: names, patterns with `*`, or `@Class`. May be
+    /// repeated.
+    #[structopt(long, number_of_values = 1)]
+    exclude: Vec<String>,
+
+    /// Re-center the excluded glyphs in the stretched advance width.
+    #[structopt(long)]
+    recenter_excluded: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct DeriveCmd {
+    /// The font file to operate on.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The recipe file describing the derived glyphs (TOML).
+    #[structopt(parse(from_os_str))]
+    recipes: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct SymbolsCmd {
     /// The font file to operate on.
     #[structopt(parse(from_os_str))]
     font: PathBuf,
+
+    /// Symbol set to generate: powerline, legacy or braille. Defaults to all.
+    #[structopt(long = "set", number_of_values = 1)]
+    sets: Vec<SymbolSet>,
+}
+
+#[derive(StructOpt, Debug)]
+struct MeasureCmd {
+    /// The font file to measure.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The glyphs to measure.
+    #[structopt(required = true)]
+    glyphs: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct AnchorsCmd {
+    /// The font file to check.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// Copy anchors from components into composites before checking, and
+    /// save the font.
+    #[structopt(long)]
+    propagate: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct ToUfoCmd {
+    /// The font file to export.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The directory to write the UFOs and designspace document to.
+    #[structopt(parse(from_os_str))]
+    out: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct CompileCmd {
+    /// The font file to compile.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The directory to write the fonts to.
+    #[structopt(parse(from_os_str))]
+    out: PathBuf,
+
+    /// Write CFF outlines (`.otf`) rather than TrueType (`.ttf`).
+    #[structopt(long)]
+    otf: bool,
+
+    /// The instances to compile, by name. All are compiled if not given.
+    /// May be repeated.
+    #[structopt(long, number_of_values = 1)]
+    instance: Vec<String>,
+
+    /// Write a TrueType variable font of the masters instead of static
+    /// instances.
+    #[structopt(long, conflicts_with_all = &["otf", "instance"])]
+    variable: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct FeaturesCmd {
+    /// The font file to check.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// Regenerate the automatic classes before checking, and save the font.
+    #[structopt(long)]
+    update_classes: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct VariantCmd {
+    /// The variant family to make: ligconsolata.
+    variant: Variant,
+
+    /// The font file to make it from.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The font file to write the variant to.
+    #[structopt(parse(from_os_str))]
+    out: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct BakeFeaturesCmd {
+    /// The features whose alternates become the defaults, separated by
+    /// commas, like `ss02,ss03`.
+    features: String,
+
+    /// The font file to bake the features into.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The font file to write the result to.
+    #[structopt(parse(from_os_str))]
+    out: PathBuf,
+
+    /// The family name of the result. Defaults to the family name followed
+    /// by the features.
+    #[structopt(long)]
+    family: Option<String>,
 }
 
-use glyphstool::{ops, Font, FromPlist, Plist, ToPlist};
+#[derive(StructOpt, Debug)]
+struct KerningCmd {
+    /// The font file to check.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// Show the kerning between two glyphs in each master, and the pair it
+    /// comes from, instead of checking.
+    #[structopt(number_of_values = 2, value_names = &["left", "right"])]
+    pair: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct MetricsKeysCmd {
+    /// The font file to check.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// Recompute sidebearings and widths from the keys before checking, and
+    /// save the font.
+    #[structopt(long)]
+    apply: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct SubsetCmd {
+    /// The font file to subset.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The font file to write the subset to.
+    #[structopt(parse(from_os_str))]
+    out: PathBuf,
+
+    /// A namelist of the characters and glyphs to keep. Can be given more
+    /// than once.
+    #[structopt(long, required = true, parse(from_os_str))]
+    nam: Vec<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+struct CoverageCmd {
+    /// The font file to check.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// A namelist the font has to cover; the command fails if it doesn't.
+    /// Can be given more than once. Without namelists, those of
+    /// `DEFAULT_NAMELISTS` in the `charset` directory next to the font are
+    /// required, skipping any that aren't there with a warning.
+    #[structopt(long, number_of_values = 1, parse(from_os_str))]
+    nam: Vec<PathBuf>,
+
+    /// A namelist to report on without requiring it. Can be given more than
+    /// once.
+    #[structopt(long, number_of_values = 1, parse(from_os_str))]
+    optional: Vec<PathBuf>,
+
+    /// Write the report as JSON.
+    #[structopt(long)]
+    json: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct CompareCmd {
+    /// The TrueType font to check, like a variable font from `compile
+    /// --variable`.
+    #[structopt(parse(from_os_str))]
+    font: PathBuf,
+
+    /// The TrueType font to compare it with.
+    #[structopt(parse(from_os_str))]
+    reference: PathBuf,
+
+    /// A location to compare at, in user space, like `wght=700,wdth=75`.
+    /// Can be given more than once; if not, the named instances of the
+    /// reference are used.
+    #[structopt(long, number_of_values = 1)]
+    location: Vec<String>,
+
+    /// How far apart, in font units, outlines and advances may be before
+    /// they're reported; the command fails if any are. Builds that each
+    /// keep within a unit of the cubic outlines can be two units apart.
+    #[structopt(long, default_value = "2")]
+    tolerance: f64,
+
+    /// The number of glyphs to list at each location, farthest first.
+    #[structopt(long, default_value = "10")]
+    list: usize,
+}
+
+use glyphstool::{
+    ops, BoxStyle, CellMetrics, CharsetCoverage, Font, FromPlist, GlyphDifference, Kerning,
+    KerningGroups, NameList, Outlines, Plist, Recipes, SlantOrigin, StemSource, StretchOptions,
+    SymbolSet, ToPlist, TrueTypeFont, UserLocation, Variant,
+};
 
 fn read_font(path: &Path) -> Font {
     let contents = fs::read_to_string(path).expect("error reading font file");
@@ -63,7 +382,7 @@ fn read_font(path: &Path) -> Font {
 
 fn write_font(path: &Path, font: Font) {
     let plist = font.to_plist();
-    fs::write(path, &plist.to_string()).unwrap();
+    fs::write(path, plist.to_string()).unwrap();
 }
 
 fn main() {
@@ -73,8 +392,39 @@ fn main() {
         Cmd::Merge(m) => {
             println!("merge {:?}", m);
             let mut font = read_font(&m.font);
-            let other = read_font(&m.other);
-            ops::merge(&mut font, &other, &m.layer);
+            let other = if m.other.extension().is_some_and(|e| e == "designspace") {
+                Font::from_designspace(&m.other).unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                })
+            } else {
+                read_font(&m.other)
+            };
+            let source = match ops::find_master(&other, &m.layer) {
+                Some(master) => master,
+                None => {
+                    eprintln!("error: no master {} in {}", m.layer, m.other.display());
+                    std::process::exit(1);
+                }
+            };
+            // The master with the same ID, or else at the same location.
+            let location = glyphstool::master_location(&other, &source.id).unwrap();
+            let values: Vec<String> = location.iter().map(|(_, v)| v.to_string()).collect();
+            let target = font
+                .get_master(&source.id)
+                .or_else(|| ops::find_master(&font, &values.join(",")));
+            let target_id = match target {
+                Some(master) => master.id.clone(),
+                None => {
+                    eprintln!(
+                        "error: no master matching {} in {}",
+                        m.layer,
+                        m.font.display()
+                    );
+                    std::process::exit(1);
+                }
+            };
+            ops::merge_master(&mut font, &other, &source.id, &target_id);
             write_font(&m.font, font);
         }
         Cmd::IncoFix(m) => {
@@ -82,45 +432,591 @@ fn main() {
             inco_fix::inco_fix(&mut font);
             write_font(&m.font, font);
         }
-        Cmd::IncoScale(m) => {
+        Cmd::IncoSyms(m) => {
             let mut font = read_font(&m.font);
-            inco_fix::inco_scale(&mut font, m.subcmd);
+            inco_fix::inco_syms(&mut font);
+            for issue in glyphstool::check_shades(&font, &BoxStyle::default()) {
+                println!("{}", issue);
+            }
             write_font(&m.font, font);
         }
-        Cmd::IncoSyms(m) => {
+        Cmd::Round(m) => {
             let mut font = read_font(&m.font);
-            inco_fix::inco_syms(&mut font);
+            for (glyphname, issues) in glyphstool::round(&mut font, m.grid, &m.layer) {
+                for issue in issues {
+                    println!("{}: {:?}", glyphname, issue);
+                }
+            }
             write_font(&m.font, font);
         }
-    }
-    /*
-    let mut filename = None;
-    for arg in env::args().skip(1) {
-        if filename.is_none() {
-            filename = Some(arg);
+        Cmd::Clean(m) => {
+            let mut font = read_font(&m.font);
+            let layer = m.layer.as_deref();
+            let skipped = glyphstool::clean(&mut font, layer, !m.no_extremes);
+            for glyphname in &skipped {
+                println!(
+                    "{}: left unchanged, cleaning would make its layers incompatible",
+                    glyphname
+                );
+            }
+            // Inserted extrema generally aren't on the grid.
+            if let Some(grid) = m.grid {
+                for glyph in &mut font.glyphs {
+                    if skipped.contains(&glyph.glyphname) {
+                        continue;
+                    }
+                    for l in &mut glyph.layers {
+                        if layer.map(|id| id == l.layer_id).unwrap_or(true) {
+                            glyphstool::round_layer(l, grid);
+                        }
+                    }
+                }
+            }
+            write_font(&m.font, font);
         }
+        Cmd::Slant(m) => {
+            let mut font = read_font(&m.font);
+            let origin = if m.baseline {
+                SlantOrigin::Baseline
+            } else {
+                SlantOrigin::XHeightCenter
+            };
+            let slanted =
+                glyphstool::slant_with(&mut font, m.angle, &m.layer, origin, !m.no_recenter);
+            for (glyphname, issues) in slanted {
+                for issue in issues {
+                    println!("{}: {:?}", glyphname, issue);
+                }
+            }
+            write_font(&m.font, font);
+        }
+        Cmd::Stretch(m) => {
+            let mut font = read_font(&m.font);
+            let mut options = StretchOptions::new(m.factor);
+            options.stem = if let Some(stem) = m.stem {
+                Some(StemSource::Fixed(stem))
+            } else if m.master_stems {
+                Some(StemSource::Master)
+            } else {
+                m.measure.map(StemSource::Measure)
+            };
+            options.exclude = m.exclude;
+            options.recenter_excluded = m.recenter_excluded;
+            match glyphstool::stretch_with(&mut font, &m.layer, &options) {
+                Ok(stretched) => {
+                    for (glyphname, issues) in stretched {
+                        for issue in issues {
+                            println!("{}: {:?}", glyphname, issue);
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            write_font(&m.font, font);
+        }
+        Cmd::Derive(m) => {
+            let mut font = read_font(&m.font);
+            let contents = fs::read_to_string(&m.recipes).expect("error reading recipe file");
+            let recipes = Recipes::parse(&contents).expect("error parsing recipe file");
+            match glyphstool::derive(&mut font, &recipes) {
+                Ok(issues) => {
+                    for (glyphname, issues) in issues {
+                        for issue in issues {
+                            println!("{}: {:?}", glyphname, issue);
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            write_font(&m.font, font);
+        }
+        Cmd::Symbols(m) => {
+            let mut font = read_font(&m.font);
+            let sets = if m.sets.is_empty() {
+                SymbolSet::ALL.to_vec()
+            } else {
+                m.sets
+            };
+            let drawn = glyphstool::add_symbols(&mut font, &sets, &BoxStyle::default());
+            println!("drew {} glyphs", drawn.len());
+            write_font(&m.font, font);
+        }
+        Cmd::Measure(m) => {
+            let font = read_font(&m.font);
+            let show = |x: Option<f64>| x.map_or("-".to_string(), |x| format!("{:.1}", x));
+            for glyphname in &m.glyphs {
+                let glyph = match font.get_glyph(glyphname) {
+                    Some(glyph) => glyph,
+                    None => {
+                        eprintln!("glyph {} not found", glyphname);
+                        continue;
+                    }
+                };
+                for master in &font.font_master {
+                    let layer = match glyph.get_layer(&master.id) {
+                        Some(layer) => layer,
+                        None => continue,
+                    };
+                    let stems = glyphstool::layer_stems(&font, layer);
+                    // Coverage of the glyph's own advance, over the line height.
+                    let cell = CellMetrics::from_master(&font, &master.id)
+                        .map(|c| CellMetrics::new(layer.width, c.bottom, c.top));
+                    let coverage =
+                        cell.map(|c| glyphstool::coverage(&font.decompose(layer), c.rect()));
+                    println!(
+                        "{} {}: vertical {}, horizontal {}, coverage {}",
+                        glyphname,
+                        master.id,
+                        show(stems.vertical),
+                        show(stems.horizontal),
+                        coverage.map_or("-".to_string(), |c| format!("{:.3}", c)),
+                    );
+                }
+            }
+        }
+        Cmd::Anchors(m) => {
+            let mut font = read_font(&m.font);
+            if m.propagate {
+                let updated = glyphstool::propagate_anchors(&mut font);
+                println!("propagated anchors into {} glyphs", updated.len());
+            }
+            for (glyphname, issues) in glyphstool::check_anchors(&font) {
+                for issue in issues {
+                    println!("{}: {:?}", glyphname, issue);
+                }
+            }
+            if m.propagate {
+                write_font(&m.font, font);
+            }
+        }
+        Cmd::ToUfo(m) => {
+            let font = read_font(&m.font);
+            for name in glyphstool::instances_outside_masters(&font) {
+                println!("{}: left out, it lies outside the masters", name);
+            }
+            match glyphstool::write_ufos(&font, &m.out) {
+                Ok(path) => println!("wrote {}", path.display()),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Cmd::Compile(m) => {
+            let font = read_font(&m.font);
+            if m.variable {
+                let data = match glyphstool::compile_variable(&font) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                fs::create_dir_all(&m.out).expect("error creating output directory");
+                let path = m.out.join(glyphstool::variable_file_name(&font));
+                fs::write(&path, &data).expect("error writing font");
+                println!("wrote {}", path.display());
+                // The axes span the masters only, so state what that leaves out.
+                let compiled = TrueTypeFont::parse(&data).expect("error reading compiled font");
+                for axis in &compiled.axes {
+                    println!(
+                        "  axis {}: {} to {}, default {}",
+                        axis.tag, axis.min, axis.max, axis.default
+                    );
+                }
+                let named = compiled.named_instances().unwrap_or_default();
+                println!("  {} named instances", named.len());
+                for name in glyphstool::instances_outside_masters(&font) {
+                    println!("  {}: left out, it lies outside the masters", name);
+                }
+                return;
+            }
+            let outlines = if m.otf {
+                Outlines::Cff
+            } else {
+                Outlines::TrueType
+            };
+            let mut instances = glyphstool::instances(&font);
+            if !m.instance.is_empty() {
+                for name in &m.instance {
+                    if !instances.iter().any(|i| &i.name == name) {
+                        eprintln!("error: no instance named {}", name);
+                        std::process::exit(1);
+                    }
+                }
+                instances.retain(|i| m.instance.contains(&i.name));
+            }
+            fs::create_dir_all(&m.out).expect("error creating output directory");
+            for instance in &instances {
+                let data = match glyphstool::compile_static(&font, instance, outlines) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        eprintln!("error: {}: {}", instance.name, e);
+                        std::process::exit(1);
+                    }
+                };
+                let path = m
+                    .out
+                    .join(glyphstool::static_file_name(&font, instance, outlines));
+                fs::write(&path, data).expect("error writing font");
+                println!("wrote {}", path.display());
+            }
+        }
+        Cmd::Features(m) => {
+            let mut font = read_font(&m.font);
+            if m.update_classes {
+                for name in glyphstool::update_automatic_classes(&mut font) {
+                    println!("updated class {}", name);
+                }
+            }
+            match glyphstool::parse_features(&font) {
+                Ok(fea) => {
+                    let features = fea
+                        .blocks
+                        .iter()
+                        .filter(|b| matches!(b, glyphstool::Block::Feature(_)))
+                        .count();
+                    println!("{} features, no problems found", features);
+                }
+                Err(errors) => {
+                    for e in errors {
+                        eprintln!("error: {}", e);
+                    }
+                    std::process::exit(1);
+                }
+            }
+            if m.update_classes {
+                write_font(&m.font, font);
+            }
+        }
+        Cmd::Variant(m) => {
+            let mut font = read_font(&m.font);
+            glyphstool::make_variant(&mut font, m.variant);
+            write_font(&m.out, font);
+        }
+        Cmd::BakeFeatures(m) => {
+            let mut font = read_font(&m.font);
+            let tags: Vec<&str> = m.features.split(',').map(str::trim).collect();
+            match glyphstool::bake_features(&mut font, &tags) {
+                Ok(pairs) => {
+                    for (default, alternate) in pairs {
+                        println!("swapped {} and {}", default, alternate);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            let family = m.family.unwrap_or_else(|| {
+                let family = font.other_stuff.get("familyName").and_then(Plist::as_str);
+                format!("{} {}", family.unwrap_or("Untitled"), tags.join(" "))
+            });
+            glyphstool::rename_family(&mut font, &family);
+            write_font(&m.out, font);
+        }
+        Cmd::Kerning(m) => {
+            let font = read_font(&m.font);
+            if let [left, right] = m.pair.as_slice() {
+                let groups = KerningGroups::from_font(&font);
+                for master in &font.font_master {
+                    let kerning = Kerning::from_font(&font, &master.id);
+                    match kerning.lookup(&groups, left, right) {
+                        Some(((l, r), value)) => {
+                            println!("{}: {} from {} {}", master.id, value, l, r)
+                        }
+                        None => println!("{}: 0", master.id),
+                    }
+                }
+                return;
+            }
+            let issues = glyphstool::check_kerning(&font);
+            for issue in &issues {
+                println!("{:?}", issue);
+            }
+            if issues.is_empty() {
+                println!("no problems found");
+            }
+        }
+        Cmd::MetricsKeys(m) => {
+            let mut font = read_font(&m.font);
+            if m.apply {
+                let updated = glyphstool::apply_metrics_keys(&mut font);
+                println!("updated metrics of {} glyphs", updated.len());
+            }
+            for (glyphname, issues) in glyphstool::check_metrics_keys(&font) {
+                for issue in issues {
+                    println!("{}: {:?}", glyphname, issue);
+                }
+            }
+            if m.apply {
+                write_font(&m.font, font);
+            }
+        }
+        Cmd::Subset(m) => {
+            let mut font = read_font(&m.font);
+            let mut list = NameList::default();
+            for path in &m.nam {
+                match NameList::load(path) {
+                    Ok(nam) => list.extend(&nam),
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            let report = match glyphstool::subset(&mut font, &list) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            for cp in &report.missing_codepoints {
+                println!("missing U+{:04X}", cp);
+            }
+            for name in &report.missing_glyphs {
+                println!("missing {}", name);
+            }
+            println!(
+                "kept {} glyphs, removed {}",
+                font.glyphs.len(),
+                report.removed.len()
+            );
+            let errors = glyphstool::parse_features(&font).err().unwrap_or_default();
+            write_font(&m.out, font);
+            for error in &errors {
+                eprintln!("error: {}", error);
+            }
+            if !errors.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Cmd::Coverage(m) => {
+            let font = read_font(&m.font);
+            let mut reports = Vec::new();
+            let mut complete = true;
+            let mut required = m.nam.clone();
+            if m.nam.is_empty() && m.optional.is_empty() {
+                let (found, missing) = glyphstool::default_namelists(&m.font);
+                for path in &missing {
+                    eprintln!(
+                        "warning: {} not found, skipped; the Google Fonts sets come from \
+                         https://github.com/googlefonts/glyphsets",
+                        path.display()
+                    );
+                }
+                if found.is_empty() {
+                    eprintln!("error: none of the default namelists were found");
+                    std::process::exit(1);
+                }
+                required = found;
+            }
+            for (path, required) in required
+                .iter()
+                .map(|p| (p, true))
+                .chain(m.optional.iter().map(|p| (p, false)))
+            {
+                let list = match NameList::load(path) {
+                    Ok(list) => list,
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let report = glyphstool::charset_coverage(&font, &name, &list);
+                complete &= !required || report.is_complete();
+                reports.push(report);
+            }
+            if m.json {
+                println!("{}", glyphstool::coverage_json(&reports));
+            } else {
+                for report in &reports {
+                    print_coverage(report);
+                }
+            }
+            if !complete {
+                std::process::exit(1);
+            }
+        }
+        Cmd::Compare(m) => {
+            let load = |path: &Path| {
+                TrueTypeFont::load(path).unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                })
+            };
+            let font = load(&m.font);
+            let reference = load(&m.reference);
+            print_axis_differences(&font, &reference);
+            let mut locations = if m.location.is_empty() {
+                reference.named_instances().unwrap_or_else(|e| {
+                    eprintln!("error: {}: {}", m.reference.display(), e);
+                    std::process::exit(1);
+                })
+            } else {
+                let parsed = m
+                    .location
+                    .iter()
+                    .map(|s| parse_location(s).map(|l| (String::new(), l)));
+                parsed.collect::<Result<_, _>>().unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                })
+            };
+            if locations.is_empty() {
+                // A static reference is compared at the defaults.
+                locations.push(("default".to_string(), Vec::new()));
+            }
+            let mut same = true;
+            for (name, location) in &locations {
+                let coords: Vec<String> = location
+                    .iter()
+                    .map(|(tag, v)| format!("{}={}", tag, v))
+                    .collect();
+                let name = match name.as_str() {
+                    "" => coords.join(","),
+                    name => format!("{} ({})", name, coords.join(",")),
+                };
+                if !font.contains(location) || !reference.contains(location) {
+                    println!("{}: outside the axes of one of the fonts, skipped", name);
+                    continue;
+                }
+                match glyphstool::compare_fonts(&font, &reference, location) {
+                    Ok(differences) => {
+                        same &= differences.iter().all(|d| {
+                            d.distance <= m.tolerance
+                                && (d.advances.0 - d.advances.1).abs() <= m.tolerance
+                        });
+                        print_comparison(&name, &differences, m.tolerance, m.list);
+                    }
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if !same {
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn parse_location(s: &str) -> Result<UserLocation, String> {
+    s.split(',')
+        .map(|part| {
+            let mut split = part.splitn(2, '=');
+            let tag = split.next().unwrap().trim();
+            let value = split.next().and_then(|v| v.trim().parse().ok());
+            match value {
+                Some(value) if tag.len() == 4 => Ok((tag.to_string(), value)),
+                _ => Err(format!(
+                    "bad location {}, expected like wght=700,wdth=75",
+                    s
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Print where the axes or named instances of the fonts differ, as the
+/// comparison only covers what both share.
+fn print_axis_differences(font: &TrueTypeFont, reference: &TrueTypeFont) {
+    let describe = |font: &TrueTypeFont| {
+        let axes: Vec<String> = font
+            .axes
+            .iter()
+            .map(|a| format!("{} {} to {}", a.tag, a.min, a.max))
+            .collect();
+        axes.join(", ")
+    };
+    let (axes, reference_axes) = (describe(font), describe(reference));
+    if axes != reference_axes {
+        println!(
+            "axes differ: {} vs {} in the reference",
+            axes, reference_axes
+        );
     }
-    if filename.is_none() {
-        usage();
-        return;
+    let count = |font: &TrueTypeFont| font.named_instances().map(|n| n.len()).unwrap_or(0);
+    let (named, reference_named) = (count(font), count(reference));
+    if named != reference_named {
+        println!(
+            "named instances differ: {} vs {} in the reference",
+            named, reference_named
+        );
     }
-    let filename = filename.unwrap();
-    let contents = fs::read_to_string(filename).expect("error reading font");
-    let plist = Plist::parse(&contents).expect("parse error");
-    //println!("Plist: {:?}", plist);
-    /*
-    let font = Font::from_plist(plist);
-    for glyph in font.glyphs() {
-        println!("glyphname: {}", glyph.glyphname());
-        for layer in glyph.layers() {
-            println!("  layer: {}, width = {}", layer.layer_id(), layer.width());
-        }
+}
+
+fn print_comparison(name: &str, differences: &[GlyphDifference], tolerance: f64, list: usize) {
+    let beyond: Vec<&GlyphDifference> = differences
+        .iter()
+        .filter(|d| d.distance > tolerance)
+        .collect();
+    let advances: Vec<&GlyphDifference> = differences
+        .iter()
+        .filter(|d| (d.advances.0 - d.advances.1).abs() > tolerance)
+        .collect();
+    println!(
+        "{}: {} glyphs, {} with outlines apart, {} with advances apart",
+        name,
+        differences.len(),
+        beyond.len(),
+        advances.len()
+    );
+    let mut farthest = beyond.clone();
+    farthest.sort_by(|a, b| b.distance.partial_cmp(&a.distance).unwrap());
+    for d in farthest.iter().take(list) {
+        println!("  U+{:04X}: {:.1} units apart", d.codepoint, d.distance);
+    }
+    for d in advances.iter().take(list) {
+        println!(
+            "  U+{:04X}: advance {} vs {}",
+            d.codepoint, d.advances.0, d.advances.1
+        );
+    }
+}
+
+fn print_coverage(report: &CharsetCoverage) {
+    println!(
+        "{}: {} code points, {} glyph names",
+        report.name, report.codepoints, report.glyph_names
+    );
+    if !report.missing.is_empty() {
+        let missing: Vec<String> = report
+            .missing
+            .iter()
+            .map(|cp| format!("U+{:04X}", cp))
+            .collect();
+        println!("  missing: {}", missing.join(" "));
+    }
+    if !report.unencoded.is_empty() {
+        let unencoded: Vec<String> = report
+            .unencoded
+            .iter()
+            .map(|(cp, glyph)| format!("U+{:04X} ({})", cp, glyph))
+            .collect();
+        println!("  unencoded: {}", unencoded.join(" "));
+    }
+    if !report.missing_glyphs.is_empty() {
+        println!("  missing glyphs: {}", report.missing_glyphs.join(" "));
+    }
+    let width = report.empty.iter().map(|(master, _)| master.len()).max();
+    for (master, empty) in &report.empty {
+        let line = format!(
+            "  {:<width$}  {:>4} empty  {}",
+            master,
+            empty.len(),
+            empty.join(" "),
+            width = width.unwrap_or(0)
+        );
+        println!("{}", line.trim_end());
     }
-    */
-    let mut font: Font = FromPlist::from_plist(plist);
-    //println!("{:?}", font);
-    stretch(&mut font, 0.5, "051EFAE4-8BBE-4FBB-A016-4335C3E52F59");
-    let plist = font.to_plist();
-    println!("{}", plist.to_string());
-    */
 }
@@ -0,0 +1,265 @@
+//! Measurements of outlines.
+//!
+//! Runs of ink are found by casting horizontal or vertical rays through the
+//! outlines, using the nonzero winding rule. From these come the thickness
+//! of stems and the area covered by ink, so that generated glyphs can take
+//! their proportions from the font itself.
+
+use kurbo::{ParamCurve, Point, Rect};
+
+use crate::font::{Font, Layer, Path};
+
+/// Fractions of the ink extent at which stems are measured.
+const STEM_POSITIONS: [f64; 3] = [0.3, 0.5, 0.7];
+
+/// The outlines as closed polygons, approximating curves by line segments.
+fn flatten(paths: &[Path]) -> Vec<Vec<Point>> {
+    paths
+        .iter()
+        .map(|path| {
+            let mut points = Vec::new();
+            for seg in path.to_bez_path().segments() {
+                let c = seg.to_cubic();
+                for k in 0..=16 {
+                    points.push(c.eval(k as f64 / 16.0));
+                }
+            }
+            points
+        })
+        .collect()
+}
+
+/// Swap the axes, so vertical rays can be cast as horizontal ones.
+fn transpose(polys: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    polys
+        .iter()
+        .map(|points| points.iter().map(|p| Point::new(p.y, p.x)).collect())
+        .collect()
+}
+
+/// The runs of ink along the horizontal line at `y` as (start, end) pairs.
+fn runs(polys: &[Vec<Point>], y: f64) -> Vec<(f64, f64)> {
+    let mut crossings = Vec::new();
+    for points in polys {
+        for pair in points.windows(2) {
+            let (p0, pt) = (pair[0], pair[1]);
+            if (p0.y <= y) != (pt.y <= y) {
+                let t = (y - p0.y) / (pt.y - p0.y);
+                let winding = if pt.y > p0.y { 1 } else { -1 };
+                crossings.push((p0.x + t * (pt.x - p0.x), winding));
+            }
+        }
+    }
+    crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut runs = Vec::new();
+    let mut winding = 0;
+    let mut start = 0.0;
+    for (x, w) in crossings {
+        if winding == 0 {
+            start = x;
+        }
+        winding += w;
+        if winding == 0 {
+            runs.push((start, x));
+        }
+    }
+    runs
+}
+
+fn thinnest(widths: impl IntoIterator<Item = f64>) -> Option<f64> {
+    widths.into_iter().fold(None, |min: Option<f64>, w| {
+        Some(min.map_or(w, |m| m.min(w)))
+    })
+}
+
+/// Widths of the runs of ink along the horizontal line at `y`, from left to
+/// right, using the nonzero winding rule.
+pub fn ink_runs(paths: &[Path], y: f64) -> Vec<f64> {
+    runs(&flatten(paths), y)
+        .into_iter()
+        .map(|(x0, x1)| x1 - x0)
+        .collect()
+}
+
+/// Heights of the runs of ink along the vertical line at `x`, from bottom
+/// to top.
+pub fn ink_runs_vertical(paths: &[Path], x: f64) -> Vec<f64> {
+    runs(&transpose(&flatten(paths)), x)
+        .into_iter()
+        .map(|(y0, y1)| y1 - y0)
+        .collect()
+}
+
+/// The thinnest run of ink along the horizontal line at `y`, which for most
+/// glyphs is the thickness of a vertical stem.
+pub fn stem_at(paths: &[Path], y: f64) -> Option<f64> {
+    thinnest(ink_runs(paths, y))
+}
+
+/// The thinnest run of ink along the vertical line at `x`, which for most
+/// glyphs is the thickness of a horizontal stroke, like the bar of `e` or
+/// the top of `o`.
+pub fn horizontal_stem_at(paths: &[Path], x: f64) -> Option<f64> {
+    thinnest(ink_runs_vertical(paths, x))
+}
+
+/// The bounding box of the ink.
+pub fn ink_bounds(paths: &[Path]) -> Option<Rect> {
+    paths
+        .iter()
+        .filter_map(|p| p.bounds())
+        .fold(None, |a: Option<Rect>, b| Some(a.map_or(b, |a| a.union(b))))
+}
+
+/// Heights at which to measure the vertical stems of a glyph.
+pub(crate) fn stem_heights(paths: &[Path]) -> Option<Vec<f64>> {
+    let bounds = ink_bounds(paths)?;
+    let ys = STEM_POSITIONS
+        .iter()
+        .map(|t| bounds.y0 + t * bounds.height());
+    Some(ys.collect())
+}
+
+/// The thinnest vertical stem along horizontal lines at the given heights.
+pub(crate) fn stem_at_heights(paths: &[Path], ys: &[f64]) -> Option<f64> {
+    thinnest(ys.iter().filter_map(|&y| stem_at(paths, y)))
+}
+
+/// Stem thicknesses of a glyph.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stems {
+    /// The thinnest run of ink across a few horizontal lines through the
+    /// middle of the glyph, like the stem of `l`.
+    pub vertical: Option<f64>,
+    /// The thinnest run of ink across a few vertical lines through the
+    /// middle of the glyph, like the top and bottom of `o`.
+    pub horizontal: Option<f64>,
+}
+
+/// Measure the stems of an outline.
+pub fn stems(paths: &[Path]) -> Stems {
+    let bounds = match ink_bounds(paths) {
+        Some(bounds) => bounds,
+        None => return Stems::default(),
+    };
+    let polys = transpose(&flatten(paths));
+    let xs = STEM_POSITIONS
+        .iter()
+        .map(|t| bounds.x0 + t * bounds.width());
+    let horizontal = xs.filter_map(|x| thinnest(runs(&polys, x).iter().map(|(y0, y1)| y1 - y0)));
+    Stems {
+        vertical: stem_heights(paths).and_then(|ys| stem_at_heights(paths, &ys)),
+        horizontal: thinnest(horizontal),
+    }
+}
+
+/// Measure the stems of a layer, with its components decomposed.
+pub fn layer_stems(font: &Font, layer: &Layer) -> Stems {
+    stems(&font.decompose(layer))
+}
+
+/// The area of ink within a rectangle, summing runs along horizontal lines
+/// one unit apart. Overlapping contours are only counted once.
+pub fn ink_area(paths: &[Path], rect: Rect) -> f64 {
+    let polys = flatten(paths);
+    let step = 1.0f64.min(rect.height());
+    let n = (rect.height() / step).ceil() as usize;
+    let mut area = 0.0;
+    for k in 0..n {
+        let y = rect.y0 + (k as f64 + 0.5) * step;
+        let width: f64 = runs(&polys, y)
+            .iter()
+            .map(|&(x0, x1)| (x1.min(rect.x1) - x0.max(rect.x0)).max(0.0))
+            .sum();
+        area += width * step;
+    }
+    area
+}
+
+/// The fraction of a rectangle, typically the glyph's cell, covered with
+/// ink.
+pub fn coverage(paths: &[Path], rect: Rect) -> f64 {
+    let area = rect.area();
+    if area > 0.0 {
+        ink_area(paths, rect) / area
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::NodeType;
+    use crate::stroke::KAPPA;
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64, ccw: bool) -> Path {
+        let mut corners = vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)];
+        if !ccw {
+            corners.reverse();
+        }
+        let mut path = Path::new(true);
+        for pt in corners {
+            path.add(pt, NodeType::Line);
+        }
+        path
+    }
+
+    /// An `o` with 100 unit sides and an 80 unit top and bottom.
+    fn o() -> Vec<Path> {
+        vec![
+            rect(100.0, 0.0, 400.0, 500.0, true),
+            rect(200.0, 80.0, 300.0, 420.0, false),
+        ]
+    }
+
+    #[test]
+    fn stems_of_an_o() {
+        let o = o();
+        assert_eq!(ink_runs(&o, 250.0), [100.0, 100.0]);
+        assert_eq!(stem_at(&o, 250.0), Some(100.0));
+        assert_eq!(stem_at(&o, 40.0), Some(300.0));
+        assert_eq!(stem_at(&o, 600.0), None);
+        assert_eq!(horizontal_stem_at(&o, 250.0), Some(80.0));
+        let stems = stems(&o);
+        assert_eq!(stems.vertical, Some(100.0));
+        assert_eq!(stems.horizontal, Some(80.0));
+        assert_eq!(self::stems(&[]), Stems::default());
+    }
+
+    #[test]
+    fn overlapping_contours_count_once() {
+        let paths = vec![
+            rect(100.0, 0.0, 200.0, 500.0, true),
+            rect(150.0, 0.0, 250.0, 500.0, true),
+        ];
+        assert_eq!(ink_runs(&paths, 250.0), [150.0]);
+        let cell = Rect::new(0.0, 0.0, 500.0, 1000.0);
+        assert_eq!(ink_area(&paths, cell), 150.0 * 500.0);
+        assert_eq!(coverage(&paths, cell), 0.15);
+    }
+
+    #[test]
+    fn area_is_clipped_to_the_rect() {
+        let o = o();
+        let area = 300.0 * 500.0 - 100.0 * 340.0;
+        assert_eq!(ink_area(&o, Rect::new(0.0, -100.0, 500.0, 600.0)), area);
+        // Only the left side and half the bottom and top.
+        let left = Rect::new(0.0, 0.0, 250.0, 500.0);
+        assert_eq!(ink_area(&o, left), 100.0 * 500.0 + 50.0 * 160.0);
+        assert_eq!(coverage(&o, Rect::new(0.0, 0.0, 100.0, 0.0)), 0.0);
+
+        // Curves are followed closely.
+        let mut circle = Path::new(true);
+        let k = 100.0 * KAPPA;
+        for &(x, y) in &[(100.0, 0.0), (0.0, 100.0), (-100.0, 0.0), (0.0, -100.0)] {
+            let (dx, dy) = (-y / 100.0 * k, x / 100.0 * k);
+            circle.add((x - dx, y - dy), NodeType::OffCurve);
+            circle.add((x, y), NodeType::CurveSmooth);
+            circle.add((x + dx, y + dy), NodeType::OffCurve);
+        }
+        let r = Rect::new(-100.0, -100.0, 100.0, 100.0);
+        let area = ink_area(&[circle], r);
+        assert!((area / (std::f64::consts::PI * 1e4) - 1.0).abs() < 0.005);
+    }
+}
@@ -0,0 +1,496 @@
+//! Metrics keys: sidebearings and widths linked to those of other glyphs.
+//!
+//! A key like `=a` gives a glyph the left sidebearing, right sidebearing or
+//! width of `a` in the same master. `=|n` takes the opposite sidebearing of
+//! `n`, and `=|` alone the glyph's own, for symmetric glyphs. Arithmetic
+//! like `=H+10` is applied left to right, and a plain number is a fixed
+//! value. Keys on a layer take precedence over those of the glyph, and a
+//! width key is ignored when there is a right sidebearing key, as in Glyphs.
+
+use std::collections::{HashMap, HashSet};
+
+use kurbo::{Affine, Vec2};
+
+use crate::font::{Font, Glyph, Layer};
+use crate::measure::ink_bounds;
+
+/// The metric a key sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    Left,
+    Right,
+    Width,
+}
+
+impl Metric {
+    const ALL: [Metric; 3] = [Metric::Left, Metric::Right, Metric::Width];
+
+    fn key_name(self) -> &'static str {
+        match self {
+            Metric::Left => "leftMetricsKey",
+            Metric::Right => "rightMetricsKey",
+            Metric::Width => "widthMetricsKey",
+        }
+    }
+}
+
+/// What a metrics key takes its value from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeyBase {
+    Number(f64),
+    /// The same metric of a glyph.
+    Glyph(String),
+    /// The opposite sidebearing of a glyph, or of the glyph itself.
+    Mirrored(Option<String>),
+}
+
+/// A parsed metrics key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsKey {
+    pub base: KeyBase,
+    /// Operators (`+`, `-`, `*` or `/`) and operands, applied in order.
+    pub ops: Vec<(char, f64)>,
+}
+
+impl MetricsKey {
+    /// Parse a key, with or without the leading `=`.
+    pub fn parse(key: &str) -> Option<MetricsKey> {
+        let s = key.trim();
+        let s = s.strip_prefix('=').unwrap_or(s).trim_start();
+        let (mirrored, s) = match s.strip_prefix('|') {
+            Some(rest) => (true, rest.trim_start()),
+            None => (false, s),
+        };
+        // Glyph names can contain `-`, so the name ends at the first
+        // operator that is followed by nothing but arithmetic.
+        let split = s
+            .char_indices()
+            .filter(|&(i, c)| i > 0 && is_operator(c))
+            .map(|(i, _)| i)
+            .find(|&i| parse_ops(&s[i..]).is_some())
+            .unwrap_or(s.len());
+        let term = s[..split].trim();
+        let ops = parse_ops(&s[split..])?;
+        let base = match (term, mirrored) {
+            ("", true) => KeyBase::Mirrored(None),
+            ("", false) => return None,
+            (_, true) => KeyBase::Mirrored(Some(term.to_string())),
+            (_, false) => match term.parse() {
+                Ok(x) => KeyBase::Number(x),
+                Err(_) => KeyBase::Glyph(term.to_string()),
+            },
+        };
+        Some(MetricsKey { base, ops })
+    }
+
+    /// The glyph the key refers to, other than the glyph itself.
+    pub fn glyph(&self) -> Option<&str> {
+        match &self.base {
+            KeyBase::Glyph(name) | KeyBase::Mirrored(Some(name)) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+fn is_operator(c: char) -> bool {
+    "+-*/".contains(c)
+}
+
+fn parse_ops(s: &str) -> Option<Vec<(char, f64)>> {
+    let mut ops = Vec::new();
+    let mut rest = s.trim();
+    while let Some(op) = rest.chars().next() {
+        if !is_operator(op) {
+            return None;
+        }
+        rest = rest[1..].trim_start();
+        let end = rest.find(is_operator).unwrap_or(rest.len());
+        ops.push((op, rest[..end].trim().parse().ok()?));
+        rest = &rest[end..];
+    }
+    Some(ops)
+}
+
+/// Something wrong with the metrics keys of a glyph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricsIssue {
+    /// The key can't be parsed, or mirrors a width.
+    Invalid { metric: Metric, key: String },
+    /// The key refers to a glyph the font doesn't have.
+    UnknownGlyph { metric: Metric, glyph: String },
+    /// The keys of the glyph lead back to it.
+    Cycle { metric: Metric },
+    /// A sidebearing is needed from a layer without an outline.
+    NoOutline {
+        layer_id: String,
+        metric: Metric,
+        glyph: String,
+    },
+    /// The layer doesn't have the value the key gives.
+    OutOfSync {
+        layer_id: String,
+        metric: Metric,
+        expected: f64,
+        actual: f64,
+    },
+}
+
+/// The keys that apply to a layer of a glyph.
+fn layer_keys(glyph: &Glyph, layer: &Layer) -> Vec<(Metric, String)> {
+    let key = |metric: Metric| {
+        let name = metric.key_name();
+        let value = layer
+            .other_stuff
+            .get(name)
+            .or_else(|| glyph.other_stuff.get(name))?;
+        Some((metric, value.as_str()?.to_string()))
+    };
+    let keys: Vec<_> = Metric::ALL.iter().filter_map(|&m| key(m)).collect();
+    let has_right = keys.iter().any(|(m, _)| *m == Metric::Right);
+    keys.into_iter()
+        .filter(|(m, _)| !(has_right && *m == Metric::Width))
+        .collect()
+}
+
+/// The left and right sidebearings of a layer.
+fn sidebearings(font: &Font, layer: &Layer) -> Option<(f64, f64)> {
+    let bounds = ink_bounds(&font.decompose(layer))?;
+    Some((bounds.x0, layer.width - bounds.x1))
+}
+
+fn current(font: &Font, layer: &Layer, metric: Metric) -> Option<f64> {
+    match metric {
+        Metric::Left => sidebearings(font, layer).map(|(l, _)| l),
+        Metric::Right => sidebearings(font, layer).map(|(_, r)| r),
+        Metric::Width => Some(layer.width),
+    }
+}
+
+/// The value a key gives a layer of a glyph, rounded to units.
+fn evaluate(
+    font: &Font,
+    glyph: &Glyph,
+    layer: &Layer,
+    metric: Metric,
+    key: &str,
+) -> Result<f64, MetricsIssue> {
+    let invalid = || MetricsIssue::Invalid {
+        metric,
+        key: key.to_string(),
+    };
+    let parsed = MetricsKey::parse(key).ok_or_else(invalid)?;
+    // The metric of the linked glyph's layer in the same master.
+    let linked = |name: &str, linked_metric| {
+        let glyph = font
+            .get_glyph(name)
+            .ok_or_else(|| MetricsIssue::UnknownGlyph {
+                metric,
+                glyph: name.to_string(),
+            })?;
+        let linked_layer = glyph.get_layer(&layer.layer_id).unwrap_or(layer);
+        current(font, linked_layer, linked_metric).ok_or_else(|| MetricsIssue::NoOutline {
+            layer_id: layer.layer_id.clone(),
+            metric,
+            glyph: name.to_string(),
+        })
+    };
+    let mut value = match &parsed.base {
+        KeyBase::Number(x) => *x,
+        KeyBase::Glyph(name) => linked(name, metric)?,
+        KeyBase::Mirrored(name) => {
+            let opposite = match metric {
+                Metric::Left => Metric::Right,
+                Metric::Right => Metric::Left,
+                Metric::Width => return Err(invalid()),
+            };
+            linked(name.as_deref().unwrap_or(&glyph.glyphname), opposite)?
+        }
+    };
+    for &(op, x) in &parsed.ops {
+        match op {
+            '+' => value += x,
+            '-' => value -= x,
+            '*' => value *= x,
+            _ => value /= x,
+        }
+    }
+    Ok(value.round())
+}
+
+/// Glyphs whose keys lead back to themselves.
+fn cyclic_glyphs(font: &Font) -> HashSet<String> {
+    let mut links: HashMap<&str, Vec<String>> = HashMap::new();
+    for glyph in &font.glyphs {
+        for layer in &glyph.layers {
+            for (_, key) in layer_keys(glyph, layer) {
+                if let Some(name) = MetricsKey::parse(&key).as_ref().and_then(|k| k.glyph()) {
+                    links
+                        .entry(&glyph.glyphname)
+                        .or_default()
+                        .push(name.to_string());
+                }
+            }
+        }
+    }
+    let mut result = HashSet::new();
+    for &start in links.keys() {
+        let mut stack: Vec<&str> = vec![start];
+        let mut seen = HashSet::new();
+        while let Some(name) = stack.pop() {
+            for next in links.get(name).into_iter().flatten() {
+                if next == start {
+                    result.insert(start.to_string());
+                } else if seen.insert(next.as_str()) {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Check the metrics keys of every glyph in the master layers: that they
+/// parse, refer to glyphs that exist without going in circles, and agree
+/// with the layers' sidebearings and widths.
+pub fn check_metrics_keys(font: &Font) -> Vec<(String, Vec<MetricsIssue>)> {
+    let cyclic = cyclic_glyphs(font);
+    let mut result = Vec::new();
+    for glyph in &font.glyphs {
+        let mut issues = Vec::new();
+        for master in &font.font_master {
+            let layer = match glyph.get_layer(&master.id) {
+                Some(layer) => layer,
+                None => continue,
+            };
+            for (metric, key) in layer_keys(glyph, layer) {
+                let issue = if cyclic.contains(&glyph.glyphname) {
+                    Some(MetricsIssue::Cycle { metric })
+                } else {
+                    match evaluate(font, glyph, layer, metric, &key) {
+                        Err(issue) => Some(issue),
+                        Ok(expected) => current(font, layer, metric)
+                            .filter(|actual| (actual - expected).abs() >= 0.5)
+                            .map(|actual| MetricsIssue::OutOfSync {
+                                layer_id: layer.layer_id.clone(),
+                                metric,
+                                expected,
+                                actual,
+                            }),
+                    }
+                };
+                if let Some(issue) = issue.filter(|i| !issues.contains(i)) {
+                    issues.push(issue);
+                }
+            }
+        }
+        if !issues.is_empty() {
+            result.push((glyph.glyphname.clone(), issues));
+        }
+    }
+    result
+}
+
+/// The glyphs in an order where the glyphs that keys refer to, and the
+/// components of composites, come before the glyphs using them.
+fn dependency_order(font: &Font) -> Vec<usize> {
+    let index: HashMap<&str, usize> = font
+        .glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (g.glyphname.as_str(), i))
+        .collect();
+    let dependencies = |glyph: &Glyph| {
+        let mut names = Vec::new();
+        for layer in &glyph.layers {
+            for (_, key) in layer_keys(glyph, layer) {
+                if let Some(key) = MetricsKey::parse(&key) {
+                    names.extend(key.glyph().map(str::to_string));
+                }
+            }
+            for component in layer.components.iter().flatten() {
+                names.push(component.name.clone());
+            }
+        }
+        names
+    };
+    let mut order = Vec::new();
+    let mut visited = vec![false; font.glyphs.len()];
+    for start in 0..font.glyphs.len() {
+        // Depth first, emitting a glyph after its dependencies.
+        let mut stack = vec![(start, false)];
+        while let Some((i, done)) = stack.pop() {
+            if done {
+                order.push(i);
+                continue;
+            }
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            stack.push((i, true));
+            for name in dependencies(&font.glyphs[i]) {
+                if let Some(&j) = index.get(name.as_str()) {
+                    if !visited[j] {
+                        stack.push((j, false));
+                    }
+                }
+            }
+        }
+    }
+    order
+}
+
+fn shift_layer(layer: &mut Layer, dx: f64) {
+    let a = Affine::translate(Vec2::new(dx, 0.0));
+    for path in layer.paths.iter_mut().flatten() {
+        path.apply_affine(a);
+    }
+    for component in layer.components.iter_mut().flatten() {
+        component.transform = Some(a * component.transform.unwrap_or_default());
+    }
+    for anchor in layer.anchors.iter_mut().flatten() {
+        anchor.position.x += dx;
+    }
+    for guide_line in layer.guide_lines.iter_mut().flatten() {
+        guide_line.position.x += dx;
+    }
+}
+
+/// Recompute the sidebearings and widths of the master layers from their
+/// metrics keys, linked glyphs first. Keys that are broken or go in
+/// circles are skipped; `check_metrics_keys` reports them.
+///
+/// Returns the names of the glyphs that changed.
+pub fn apply_metrics_keys(font: &mut Font) -> Vec<String> {
+    let cyclic = cyclic_glyphs(font);
+    let master_ids: Vec<String> = font.font_master.iter().map(|m| m.id.clone()).collect();
+    let mut result = Vec::new();
+    for i in dependency_order(font) {
+        if cyclic.contains(&font.glyphs[i].glyphname) {
+            continue;
+        }
+        let mut changed = false;
+        for master_id in &master_ids {
+            let j = match font.glyphs[i]
+                .layers
+                .iter()
+                .position(|l| &l.layer_id == master_id)
+            {
+                Some(j) => j,
+                None => continue,
+            };
+            // One metric at a time, so the right sidebearing is measured
+            // after the left one is applied.
+            for &metric in &Metric::ALL {
+                let glyph = &font.glyphs[i];
+                let layer = &glyph.layers[j];
+                let keys = layer_keys(glyph, layer);
+                let key = match keys.iter().find(|(m, _)| *m == metric) {
+                    Some((_, key)) => key,
+                    None => continue,
+                };
+                let value = match evaluate(font, glyph, layer, metric, key) {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+                let bounds = ink_bounds(&font.decompose(layer));
+                let layer = &mut font.glyphs[i].layers[j];
+                let width = match (metric, bounds) {
+                    (Metric::Left, Some(b)) => {
+                        let dx = value - b.x0;
+                        if dx != 0.0 {
+                            shift_layer(layer, dx);
+                            changed = true;
+                        }
+                        layer.width + dx
+                    }
+                    (Metric::Right, Some(b)) => b.x1 + value,
+                    (Metric::Width, _) => value,
+                    _ => continue,
+                };
+                if width != layer.width {
+                    layer.width = width;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            result.push(font.glyphs[i].glyphname.clone());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    #[test]
+    fn parses_keys() {
+        let key = |s| MetricsKey::parse(s).unwrap();
+        let glyph = |name: &str| KeyBase::Glyph(name.to_string());
+        assert_eq!(key("=a").base, glyph("a"));
+        assert_eq!(key("=a").ops, []);
+        assert_eq!(key("=|n").base, KeyBase::Mirrored(Some("n".to_string())));
+        assert_eq!(key("=|").base, KeyBase::Mirrored(None));
+        assert_eq!(key("40").base, KeyBase::Number(40.0));
+        assert_eq!(key("=H+10").base, glyph("H"));
+        assert_eq!(key("=H + 10 * 2").ops, [('+', 10.0), ('*', 2.0)]);
+        // A `-` in a glyph name isn't arithmetic.
+        assert_eq!(key("=a-cy-5").base, glyph("a-cy"));
+        assert_eq!(key("=a-cy-5").ops, [('-', 5.0)]);
+        assert_eq!(MetricsKey::parse("="), None);
+    }
+
+    fn square(x0: i64, x1: i64, width: i64) -> String {
+        format!(
+            "{{layerId = m; width = {w}; paths = ({{closed = 1; nodes = (\
+             \"{a} 0 LINE\", \"{a} 100 LINE\", \"{b} 100 LINE\", \"{b} 0 LINE\");}});}}",
+            a = x0,
+            b = x1,
+            w = width
+        )
+    }
+
+    #[test]
+    fn checks_and_applies_keys() {
+        let source = format!(
+            "{{fontMaster = ({{id = m; weightValue = 400;}});
+glyphs = (
+{{glyphname = n; layers = ({});}},
+{{glyphname = m; leftMetricsKey = \"=n\"; rightMetricsKey = \"=|n+5\"; layers = ({});}},
+{{glyphname = o; leftMetricsKey = \"=x\"; layers = ({});}}
+);}}",
+            square(50, 450, 480),
+            square(40, 400, 500),
+            square(50, 450, 500)
+        );
+        let mut font = Font::from_plist(Plist::parse(&source).unwrap());
+        let issues = check_metrics_keys(&font);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].0, "m");
+        assert_eq!(
+            issues[0].1[0],
+            MetricsIssue::OutOfSync {
+                layer_id: "m".to_string(),
+                metric: Metric::Left,
+                expected: 50.0,
+                actual: 40.0,
+            }
+        );
+        assert_eq!(
+            issues[1].1,
+            [MetricsIssue::UnknownGlyph {
+                metric: Metric::Left,
+                glyph: "x".to_string(),
+            }]
+        );
+        assert_eq!(apply_metrics_keys(&mut font), ["m"]);
+        let m = &font.get_glyph("m").unwrap().layers[0];
+        // The right sidebearing is the left one of `n`, plus 5.
+        assert_eq!(sidebearings(&font, m), Some((50.0, 55.0)));
+        assert_eq!(check_metrics_keys(&font).len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+//! Character sets in the Google Fonts namelist (`.nam`) format.
+//!
+//! Each line is a code point, as `0x0041` or `U+00000041`, usually followed
+//! by the character and a description, or, indented, the name of an
+//! unencoded glyph. Lines starting with `#` are comments, except for
+//! `#$ include other.nam`, which adds another namelist, relative to the
+//! including file.
+
+use std::fs;
+use std::path::Path;
+
+/// The code points and unencoded glyph names of a namelist, in order and
+/// without duplicates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NameList {
+    pub codepoints: Vec<u32>,
+    pub glyph_names: Vec<String>,
+    /// The files included with `#$ include`, as written.
+    pub includes: Vec<String>,
+}
+
+impl NameList {
+    /// Parse a namelist. Includes are recorded but not read.
+    pub fn parse(contents: &str) -> Result<NameList, String> {
+        let mut result = NameList::default();
+        for (i, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some(directive) = trimmed.strip_prefix("#$") {
+                if let Some(file) = directive.trim().strip_prefix("include") {
+                    result.includes.push(file.trim().to_string());
+                }
+            } else if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            } else if line.starts_with(char::is_whitespace) {
+                result.add_glyph_name(trimmed);
+            } else {
+                let hex = trimmed
+                    .strip_prefix("0x")
+                    .or_else(|| trimmed.strip_prefix("U+"))
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(cp) => result.add_codepoint(cp),
+                    None => return Err(format!("line {}: expected a code point", i + 1)),
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read a namelist file, along with the files it includes.
+    pub fn load(path: &Path) -> Result<NameList, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("error reading {}: {}", path.display(), e))?;
+        let mut result =
+            NameList::parse(&contents).map_err(|e| format!("{}, {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        for include in result.includes.clone() {
+            result.extend(&NameList::load(&dir.join(include))?);
+        }
+        Ok(result)
+    }
+
+    /// Add the code points and glyph names of another namelist.
+    pub fn extend(&mut self, other: &NameList) {
+        for &cp in &other.codepoints {
+            self.add_codepoint(cp);
+        }
+        for name in &other.glyph_names {
+            self.add_glyph_name(name);
+        }
+    }
+
+    fn add_codepoint(&mut self, cp: u32) {
+        if !self.codepoints.contains(&cp) {
+            self.codepoints.push(cp);
+        }
+    }
+
+    fn add_glyph_name(&mut self, name: &str) {
+        if !self.glyph_names.iter().any(|n| n == name) {
+            self.glyph_names.push(name.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_code_points_glyph_names_and_includes() {
+        let list = NameList::parse(
+            "#$ include GF_Latin_Core.nam\n\
+             # A comment\n\
+             0x0041 A LATIN CAPITAL LETTER A\n\
+             U+00000122      \u{122}\n\
+             \n\
+             \x20        uni0122.loclROM\n\
+             0x0041 A again\n",
+        )
+        .unwrap();
+        assert_eq!(list.codepoints, [0x41, 0x122]);
+        assert_eq!(list.glyph_names, ["uni0122.loclROM"]);
+        assert_eq!(list.includes, ["GF_Latin_Core.nam"]);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_code_point() {
+        let error = NameList::parse("0x0041\nA\n").unwrap_err();
+        assert_eq!(error, "line 2: expected a code point");
+    }
+
+    #[test]
+    fn loads_included_namelists() {
+        let dir = std::env::temp_dir().join(format!("nam-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("core.nam"), "0x0041\n0x0042\n").unwrap();
+        fs::write(
+            dir.join("plus.nam"),
+            "#$ include core.nam\n0x0043\n0x0041\n",
+        )
+        .unwrap();
+        let list = NameList::load(&dir.join("plus.nam"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(list.unwrap().codepoints, [0x43, 0x41, 0x42]);
+    }
+}
@@ -2,13 +2,22 @@
 
 use std::collections::HashMap;
 
-use crate::font::Font;
+use crate::font::{Font, FontMaster};
+use crate::interpolate::master_location;
 
 pub fn merge(font: &mut Font, other: &Font, layer_id: &str) {
+    merge_master(font, other, layer_id, layer_id);
+}
+
+/// Replace the layers of the master `layer_id` with those of the master
+/// `other_id` in the other font. Glyphs missing from the other font keep
+/// their layers, and data the other layers don't have, like backgrounds
+/// and component alignment when merging from UFOs, is kept.
+pub fn merge_master(font: &mut Font, other: &Font, other_id: &str, layer_id: &str) {
     let mut map = HashMap::new();
     for glyph in &other.glyphs {
         for layer in &glyph.layers {
-            if layer.layer_id == layer_id {
+            if layer.layer_id == other_id {
                 map.insert(glyph.glyphname.to_owned(), layer);
             }
         }
@@ -17,8 +26,48 @@ pub fn merge(font: &mut Font, other: &Font, layer_id: &str) {
     for glyph in &mut font.glyphs {
         for layer in &mut glyph.layers {
             if layer.layer_id == layer_id {
-                *layer = (*map[&glyph.glyphname]).clone();
+                if let Some(other_layer) = map.get(&glyph.glyphname) {
+                    let mut merged = (*other_layer).clone();
+                    merged.layer_id = layer_id.to_owned();
+                    for (key, value) in layer.other_stuff.drain() {
+                        merged.other_stuff.entry(key).or_insert(value);
+                    }
+                    let pairs = merged
+                        .components
+                        .iter_mut()
+                        .flatten()
+                        .zip(layer.components.iter().flatten());
+                    for (component, old) in pairs {
+                        if component.name == old.name {
+                            for (key, value) in &old.other_stuff {
+                                component
+                                    .other_stuff
+                                    .entry(key.clone())
+                                    .or_insert_with(|| value.clone());
+                            }
+                        }
+                    }
+                    *layer = merged;
+                }
             }
         }
     }
 }
+
+/// Find a master by its ID, or by its location given as comma-separated
+/// values on the font's axes in order, like `400,100` for weight and width.
+/// Values for the last axes may be left out.
+pub fn find_master<'a>(font: &'a Font, spec: &str) -> Option<&'a FontMaster> {
+    if let Some(master) = font.get_master(spec) {
+        return Some(master);
+    }
+    let values = spec
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    font.font_master.iter().find(|m| {
+        let location = master_location(font, &m.id).unwrap();
+        values.len() <= location.len() && values.iter().zip(&location).all(|(v, (_, l))| v == l)
+    })
+}
@@ -0,0 +1,64 @@
+//! Numbers that vary with a master's location in design space.
+
+use serde::Deserialize;
+
+/// A number that can vary with the master's location.
+///
+/// The table form gives piecewise linear functions of `wght` and `wdth` as
+/// lists of `[location, value]` pairs. These are multiplied together, or
+/// added if `sum` is set; a missing axis contributes nothing.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Number(f64),
+    Axes {
+        #[serde(default)]
+        wght: Vec<[f64; 2]>,
+        #[serde(default)]
+        wdth: Vec<[f64; 2]>,
+        #[serde(default)]
+        sum: bool,
+    },
+}
+
+/// Evaluate a piecewise linear function, extrapolating past the ends.
+pub(crate) fn piecewise(table: &[[f64; 2]], x: f64) -> f64 {
+    match table.len() {
+        0 => 0.0,
+        1 => table[0][1],
+        n => {
+            let i = table[1..n - 1].iter().take_while(|p| p[0] < x).count();
+            let ([x0, y0], [x1, y1]) = (table[i], table[i + 1]);
+            y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+        }
+    }
+}
+
+impl Value {
+    /// The product of piecewise linear functions of weight and width.
+    pub fn product(wght: &[[f64; 2]], wdth: &[[f64; 2]]) -> Value {
+        Value::Axes {
+            wght: wght.to_vec(),
+            wdth: wdth.to_vec(),
+            sum: false,
+        }
+    }
+
+    pub fn eval(&self, weight: f64, width: f64) -> f64 {
+        match self {
+            Value::Number(x) => *x,
+            Value::Axes { wght, wdth, sum } => {
+                let terms = [(wght, weight), (wdth, width)];
+                let terms = terms
+                    .iter()
+                    .filter(|(table, _)| !table.is_empty())
+                    .map(|(table, x)| piecewise(table, *x));
+                if *sum {
+                    terms.sum()
+                } else {
+                    terms.product()
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
 
 /// An enum representing a property list.
 #[derive(Clone, Debug)]
@@ -32,11 +33,11 @@ enum Token<'a> {
 }
 
 fn is_numeric(b: u8) -> bool {
-    (b >= b'0' && b <= b'9') || b == b'.' || b == b'-'
+    b.is_ascii_digit() || b == b'.' || b == b'-'
 }
 
 fn is_alnum(b: u8) -> bool {
-    is_numeric(b) || (b >= b'A' && b <= b'Z') || (b >= b'a' && b <= b'z') || b == b'_'
+    is_numeric(b) || b.is_ascii_alphabetic() || b == b'_'
 }
 
 // Used for serialization; make sure UUID's get quoted
@@ -45,11 +46,11 @@ fn is_alnum_strict(b: u8) -> bool {
 }
 
 fn is_ascii_digit(b: u8) -> bool {
-    b >= b'0' && b <= b'9'
+    b.is_ascii_digit()
 }
 
 fn is_hex_upper(b: u8) -> bool {
-    (b >= b'0' && b <= b'9') || (b >= b'A' && b <= b'F')
+    b.is_ascii_digit() || (b'A'..=b'F').contains(&b)
 }
 
 fn is_ascii_whitespace(b: u8) -> bool {
@@ -61,6 +62,10 @@ fn numeric_ok(s: &str) -> bool {
     if s.is_empty() {
         return false;
     }
+    // Otherwise names like "infinity" and "nan" would parse as floats.
+    if !s.iter().all(|&b| is_numeric(b)) {
+        return false;
+    }
     if s.iter().all(|&b| is_hex_upper(b)) && !s.iter().all(|&b| is_ascii_digit(b)) {
         return false;
     }
@@ -236,16 +241,10 @@ impl Plist {
         Plist::String(s.into())
     }
 
-    pub fn to_string(&self) -> String {
-        let mut s = String::new();
-        self.push_to_string(&mut s);
-        s
-    }
-
     fn push_to_string(&self, s: &mut String) {
         match self {
             Plist::Array(a) => {
-                s.push_str("(");
+                s.push('(');
                 let mut delim = "\n";
                 for el in a {
                     s.push_str(delim);
@@ -266,7 +265,7 @@ impl Plist {
                     el.push_to_string(s);
                     s.push_str(";\n");
                 }
-                s.push_str("}");
+                s.push('}');
             }
             Plist::String(st) => escape_string(s, st),
             Plist::Integer(i) => {
@@ -279,6 +278,14 @@ impl Plist {
     }
 }
 
+impl fmt::Display for Plist {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        self.push_to_string(&mut s);
+        f.write_str(&s)
+    }
+}
+
 impl<'a> Token<'a> {
     fn lex(s: &'a str, ix: usize) -> Result<(Token<'a>, usize), Error> {
         let start = skip_ws(s, ix);
@@ -324,11 +331,13 @@ impl<'a> Token<'a> {
                                     cow_start = ix + 1;
                                 }
                                 _ => {
-                                    if b >= b'0' && b <= b'3' && ix + 2 < s.len() {
+                                    if (b'0'..=b'3').contains(&b) && ix + 2 < s.len() {
                                         // octal escape
                                         let b1 = s.as_bytes()[ix + 1];
                                         let b2 = s.as_bytes()[ix + 2];
-                                        if b1 >= b'0' && b1 <= b'7' && b2 >= b'0' && b2 <= b'7' {
+                                        if (b'0'..=b'7').contains(&b1)
+                                            && (b'0'..=b'7').contains(&b2)
+                                        {
                                             let oct =
                                                 (b - b'0') * 64 + (b1 - b'0') * 8 + (b2 - b'0');
                                             buf.push(oct as char);
@@ -1,8 +1,13 @@
 //! A data structure representing the union of rectangles.
+//!
+//! Regions are stored as a sequence of horizontal slices, each of which is a
+//! sorted list of disjoint intervals. In addition to unioning in rectangles,
+//! regions can be combined with boolean operations, which makes it easy to
+//! carve shapes out of a full cell.
 
 use std::collections::BTreeSet;
 
-use kurbo::{Point, Rect};
+use kurbo::{Point, Rect, Vec2};
 
 use crate::font::{NodeType, Path};
 
@@ -24,6 +29,12 @@ struct Interval {
     x1: f64,
 }
 
+impl Interval {
+    fn contains(&self, x: f64) -> bool {
+        self.x0 <= x && x < self.x1
+    }
+}
+
 impl From<(f64, f64)> for Interval {
     fn from(xs: (f64, f64)) -> Interval {
         Interval { x0: xs.0, x1: xs.1 }
@@ -71,10 +82,54 @@ impl Slice {
             intervals: self.intervals.clone(),
         }
     }
+
+    fn contains(&self, x: f64) -> bool {
+        self.intervals.iter().any(|iv| iv.contains(x))
+    }
+}
+
+/// Combine two sorted interval lists, keeping the parts where `op` is true.
+fn combine_intervals(
+    a: &[Interval],
+    b: &[Interval],
+    op: impl Fn(bool, bool) -> bool,
+) -> Vec<Interval> {
+    let mut xs: Vec<f64> = a
+        .iter()
+        .chain(b.iter())
+        .flat_map(|iv| vec![iv.x0, iv.x1])
+        .collect();
+    xs.sort_by(|x0, x1| x0.partial_cmp(x1).unwrap());
+    xs.dedup();
+    let mut result: Vec<Interval> = Vec::new();
+    for w in xs.windows(2) {
+        let xm = 0.5 * (w[0] + w[1]);
+        let in_a = a.iter().any(|iv| iv.contains(xm));
+        let in_b = b.iter().any(|iv| iv.contains(xm));
+        if op(in_a, in_b) {
+            if let Some(last) = result.last_mut() {
+                if last.x1 == w[0] {
+                    last.x1 = w[1];
+                    continue;
+                }
+            }
+            result.push((w[0], w[1]).into());
+        }
+    }
+    result
+}
+
+impl From<Rect> for Region {
+    fn from(rect: Rect) -> Region {
+        Region::default().add(rect)
+    }
 }
 
 impl Region {
     fn push(&mut self, slice: Slice) {
+        if slice.intervals.is_empty() || slice.y0 >= slice.y1 {
+            return;
+        }
         if let Some(last) = self.slices.last_mut() {
             if last.y1 == slice.y0 && last.intervals == slice.intervals {
                 last.y1 = slice.y1;
@@ -84,8 +139,9 @@ impl Region {
         self.slices.push(slice);
     }
 
+    /// Union a rectangle into the region.
     pub fn add(&self, rect: impl Into<Rect>) -> Region {
-        let rect = rect.into();
+        let rect = rect.into().abs();
         let mut result = Region::default();
         let mut i = 0;
         while i < self.slices.len() && self.slices[i].y1 <= rect.y0 {
@@ -122,6 +178,198 @@ impl Region {
         result
     }
 
+    /// Whether the region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    /// The intervals covering the horizontal line at `y`.
+    fn intervals_at(&self, y: f64) -> &[Interval] {
+        self.slices
+            .iter()
+            .find(|slice| slice.y0 <= y && y < slice.y1)
+            .map(|slice| &slice.intervals[..])
+            .unwrap_or(&[])
+    }
+
+    /// Apply a boolean operation, as a function of membership in `self` and
+    /// `other`, to produce a new region.
+    fn combine(&self, other: &Region, op: impl Fn(bool, bool) -> bool) -> Region {
+        let mut ys: Vec<f64> = self
+            .slices
+            .iter()
+            .chain(other.slices.iter())
+            .flat_map(|slice| vec![slice.y0, slice.y1])
+            .collect();
+        ys.sort_by(|y0, y1| y0.partial_cmp(y1).unwrap());
+        ys.dedup();
+        let mut result = Region::default();
+        for w in ys.windows(2) {
+            let ym = 0.5 * (w[0] + w[1]);
+            let intervals = combine_intervals(self.intervals_at(ym), other.intervals_at(ym), &op);
+            result.push(Slice {
+                y0: w[0],
+                y1: w[1],
+                intervals,
+            });
+        }
+        result
+    }
+
+    /// The union of two regions.
+    pub fn union(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a || b)
+    }
+
+    /// The parts of this region not covered by `other`.
+    pub fn subtract(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a && !b)
+    }
+
+    /// The parts covered by both regions.
+    pub fn intersect(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a && b)
+    }
+
+    /// The parts covered by exactly one of the regions.
+    pub fn xor(&self, other: &Region) -> Region {
+        self.combine(other, |a, b| a != b)
+    }
+
+    /// Move the region by the given offset.
+    pub fn translate(&self, offset: impl Into<Vec2>) -> Region {
+        let offset = offset.into();
+        let slices = self
+            .slices
+            .iter()
+            .map(|slice| Slice {
+                y0: slice.y0 + offset.y,
+                y1: slice.y1 + offset.y,
+                intervals: slice
+                    .intervals
+                    .iter()
+                    .map(|iv| (iv.x0 + offset.x, iv.x1 + offset.x).into())
+                    .collect(),
+            })
+            .collect();
+        Region { slices }
+    }
+
+    /// Scale the region about the origin. Negative factors flip the region.
+    pub fn scale(&self, sx: f64, sy: f64) -> Region {
+        let mut result = Region::default();
+        if sx == 0.0 || sy == 0.0 {
+            return result;
+        }
+        let mut slices: Vec<Slice> = self
+            .slices
+            .iter()
+            .map(|slice| {
+                let mut intervals: Vec<Interval> = slice
+                    .intervals
+                    .iter()
+                    .map(|iv| {
+                        let (x0, x1) = (iv.x0 * sx, iv.x1 * sx);
+                        (x0.min(x1), x0.max(x1)).into()
+                    })
+                    .collect();
+                if sx < 0.0 {
+                    intervals.reverse();
+                }
+                let (y0, y1) = (slice.y0 * sy, slice.y1 * sy);
+                Slice {
+                    y0: y0.min(y1),
+                    y1: y0.max(y1),
+                    intervals,
+                }
+            })
+            .collect();
+        if sy < 0.0 {
+            slices.reverse();
+        }
+        for slice in slices {
+            result.push(slice);
+        }
+        result
+    }
+
+    /// Whether the point is inside the region.
+    ///
+    /// Following the usual convention, the bottom and left edges are inside
+    /// and the top and right edges are not.
+    pub fn contains(&self, pt: Point) -> bool {
+        self.slices
+            .iter()
+            .any(|slice| slice.y0 <= pt.y && pt.y < slice.y1 && slice.contains(pt.x))
+    }
+
+    /// Create a region from rectilinear closed paths, using the nonzero
+    /// winding rule.
+    ///
+    /// Open paths are ignored. Returns `None` if any closed path has curves
+    /// or diagonal segments.
+    pub fn from_paths(paths: &[Path]) -> Option<Region> {
+        // Vertical edges as (x, y0, y1, winding).
+        let mut edges = Vec::new();
+        for path in paths.iter().filter(|path| path.closed) {
+            let n = path.nodes.len();
+            for i in 0..n {
+                let n0 = &path.nodes[i];
+                let n1 = &path.nodes[(i + 1) % n];
+                match n1.node_type {
+                    NodeType::Line | NodeType::LineSmooth => (),
+                    _ => return None,
+                }
+                let (p0, p1) = (n0.pt, n1.pt);
+                if p0.x == p1.x {
+                    if p0.y != p1.y {
+                        let winding = if p1.y > p0.y { 1 } else { -1 };
+                        edges.push((p0.x, p0.y.min(p1.y), p0.y.max(p1.y), winding));
+                    }
+                } else if p0.y != p1.y {
+                    return None;
+                }
+            }
+        }
+        let mut ys: Vec<f64> = edges.iter().flat_map(|e| vec![e.1, e.2]).collect();
+        ys.sort_by(|y0, y1| y0.partial_cmp(y1).unwrap());
+        ys.dedup();
+        let mut result = Region::default();
+        for w in ys.windows(2) {
+            let ym = 0.5 * (w[0] + w[1]);
+            let mut crossings: Vec<(f64, i32)> = edges
+                .iter()
+                .filter(|e| e.1 <= ym && ym < e.2)
+                .map(|e| (e.0, e.3))
+                .collect();
+            crossings.sort_by(|c0, c1| c0.0.partial_cmp(&c1.0).unwrap());
+            let mut intervals: Vec<Interval> = Vec::new();
+            let mut winding = 0;
+            let mut start = 0.0;
+            for (x, w) in crossings {
+                let was_inside = winding != 0;
+                winding += w;
+                if !was_inside && winding != 0 {
+                    start = x;
+                } else if was_inside && winding == 0 && x > start {
+                    if let Some(last) = intervals.last_mut() {
+                        if last.x1 == start {
+                            last.x1 = x;
+                            continue;
+                        }
+                    }
+                    intervals.push((start, x).into());
+                }
+            }
+            result.push(Slice {
+                y0: w[0],
+                y1: w[1],
+                intervals,
+            });
+        }
+        Some(result)
+    }
+
     /*
     // This is the dumb version, for reference
     pub fn to_paths(&self) -> Vec<Path> {
@@ -155,7 +403,7 @@ impl Region {
                 tracer.process_line(&slice.intervals, &[], y1);
             }
         }
-        tracer.to_paths()
+        tracer.trace_paths()
     }
 }
 
@@ -301,7 +549,7 @@ impl PathTracer {
         }
     }
 
-    fn to_paths(&mut self) -> Vec<Path> {
+    fn trace_paths(&mut self) -> Vec<Path> {
         self.pending_edges = (0..self.edges.len()).collect();
         let mut result = Vec::new();
         while let Some(path) = self.trace_path() {
@@ -310,3 +558,154 @@ impl PathTracer {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A slice as `(y0, y1, [(x0, x1), ...])`.
+    type Spans = (f64, f64, Vec<(f64, f64)>);
+
+    fn slices(region: &Region) -> Vec<Spans> {
+        let slices = region.slices.iter();
+        slices
+            .map(|s| {
+                let intervals = s.intervals.iter().map(|iv| (iv.x0, iv.x1)).collect();
+                (s.y0, s.y1, intervals)
+            })
+            .collect()
+    }
+
+    fn rect(x0: f64, y0: f64, x1: f64, y1: f64) -> Region {
+        Region::from(Rect::new(x0, y0, x1, y1))
+    }
+
+    fn polygon(points: &[(f64, f64)]) -> Path {
+        let mut path = Path::new(true);
+        for &pt in points {
+            path.add(pt, NodeType::Line);
+        }
+        path
+    }
+
+    #[test]
+    fn overlapping_rects() {
+        let (a, b) = (rect(0.0, 0.0, 10.0, 10.0), rect(5.0, 5.0, 15.0, 15.0));
+        assert_eq!(
+            slices(&a.union(&b)),
+            [
+                (0.0, 5.0, vec![(0.0, 10.0)]),
+                (5.0, 10.0, vec![(0.0, 15.0)]),
+                (10.0, 15.0, vec![(5.0, 15.0)]),
+            ]
+        );
+        assert_eq!(
+            slices(&a.subtract(&b)),
+            [(0.0, 5.0, vec![(0.0, 10.0)]), (5.0, 10.0, vec![(0.0, 5.0)])]
+        );
+        assert_eq!(slices(&a.intersect(&b)), [(5.0, 10.0, vec![(5.0, 10.0)])]);
+        assert_eq!(
+            slices(&a.xor(&b)),
+            [
+                (0.0, 5.0, vec![(0.0, 10.0)]),
+                (5.0, 10.0, vec![(0.0, 5.0), (10.0, 15.0)]),
+                (10.0, 15.0, vec![(5.0, 15.0)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn touching_rects() {
+        let (a, b) = (rect(0.0, 0.0, 10.0, 10.0), rect(10.0, 0.0, 20.0, 10.0));
+        assert_eq!(slices(&a.union(&b)), [(0.0, 10.0, vec![(0.0, 20.0)])]);
+        assert!(a.intersect(&b).is_empty());
+        assert_eq!(slices(&a.subtract(&b)), slices(&a));
+        let above = rect(0.0, 10.0, 10.0, 20.0);
+        assert_eq!(slices(&a.union(&above)), [(0.0, 20.0, vec![(0.0, 10.0)])]);
+        assert!(a.contains(Point::new(0.0, 0.0)));
+        assert!(!a.contains(Point::new(10.0, 5.0)));
+        assert!(!a.contains(Point::new(5.0, 10.0)));
+    }
+
+    #[test]
+    fn nested_rects() {
+        let (outer, inner) = (rect(0.0, 0.0, 30.0, 30.0), rect(10.0, 10.0, 20.0, 20.0));
+        let ring = outer.subtract(&inner);
+        assert_eq!(
+            slices(&ring),
+            [
+                (0.0, 10.0, vec![(0.0, 30.0)]),
+                (10.0, 20.0, vec![(0.0, 10.0), (20.0, 30.0)]),
+                (20.0, 30.0, vec![(0.0, 30.0)]),
+            ]
+        );
+        assert_eq!(slices(&outer.intersect(&inner)), slices(&inner));
+        assert_eq!(slices(&outer.union(&inner)), slices(&outer));
+        assert!(inner.subtract(&outer).is_empty());
+        assert_eq!(slices(&ring.xor(&outer)), slices(&inner));
+    }
+
+    #[test]
+    fn scale_and_translate() {
+        let a = rect(0.0, 0.0, 10.0, 20.0).union(&rect(0.0, 20.0, 5.0, 30.0));
+        assert_eq!(
+            slices(&a.scale(-2.0, 1.0)),
+            [
+                (0.0, 20.0, vec![(-20.0, 0.0)]),
+                (20.0, 30.0, vec![(-10.0, 0.0)])
+            ]
+        );
+        assert_eq!(
+            slices(&a.scale(1.0, -1.0)),
+            [
+                (-30.0, -20.0, vec![(0.0, 5.0)]),
+                (-20.0, 0.0, vec![(0.0, 10.0)])
+            ]
+        );
+        assert!(a.scale(0.0, 1.0).is_empty());
+        assert_eq!(
+            slices(&a.translate((1.0, 2.0))),
+            [
+                (2.0, 22.0, vec![(1.0, 11.0)]),
+                (22.0, 32.0, vec![(1.0, 6.0)])
+            ]
+        );
+    }
+
+    #[test]
+    fn from_paths_nonzero_winding() {
+        let square =
+            |x0: f64, y0: f64, x1: f64, y1: f64| polygon(&[(x0, y0), (x0, y1), (x1, y1), (x1, y0)]);
+        let reversed =
+            |x0: f64, y0: f64, x1: f64, y1: f64| polygon(&[(x0, y0), (x1, y0), (x1, y1), (x0, y1)]);
+        // Overlapping contours in the same direction are unioned.
+        let region =
+            Region::from_paths(&[square(0.0, 0.0, 10.0, 10.0), square(5.0, 0.0, 15.0, 10.0)]);
+        assert_eq!(slices(&region.unwrap()), [(0.0, 10.0, vec![(0.0, 15.0)])]);
+        // So are nested ones; a counter in the other direction is a hole.
+        let outer = square(0.0, 0.0, 30.0, 30.0);
+        let filled = Region::from_paths(&[outer.clone(), square(10.0, 10.0, 20.0, 20.0)]);
+        assert_eq!(slices(&filled.unwrap()), [(0.0, 30.0, vec![(0.0, 30.0)])]);
+        let ring = Region::from_paths(&[outer, reversed(10.0, 10.0, 20.0, 20.0)]).unwrap();
+        assert_eq!(
+            slices(&ring),
+            slices(&rect(0.0, 0.0, 30.0, 30.0).subtract(&rect(10.0, 10.0, 20.0, 20.0)))
+        );
+        // Open paths don't count.
+        let mut open = square(0.0, 0.0, 10.0, 10.0);
+        open.closed = false;
+        assert!(Region::from_paths(&[open]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn from_paths_rejects_curves_and_diagonals() {
+        let diagonal = polygon(&[(0.0, 0.0), (10.0, 10.0), (10.0, 0.0)]);
+        assert!(Region::from_paths(&[diagonal]).is_none());
+        let mut curve = polygon(&[(0.0, 0.0), (0.0, 10.0)]);
+        curve.add((5.0, 15.0), NodeType::OffCurve);
+        curve.add((10.0, 15.0), NodeType::OffCurve);
+        curve.add((10.0, 10.0), NodeType::Curve);
+        curve.add((10.0, 0.0), NodeType::Line);
+        assert!(Region::from_paths(&[curve]).is_none());
+    }
+}
@@ -0,0 +1,390 @@
+//! Rounding of outlines to a grid, keeping smooth nodes smooth.
+//!
+//! Naively rounding every point can break the collinearity of the handles
+//! around smooth nodes, or collapse a short handle onto (or past) its on-curve
+//! point. The rounding here moves handles along with their on-curve points,
+//! then searches nearby grid points to keep smooth nodes as straight as
+//! possible.
+
+use kurbo::{Affine, ParamCurve, Point, Vec2};
+
+use crate::font::{Font, Layer, NodeType, Path};
+
+/// Sine of the angle past which a smooth node is considered kinked.
+const KINK_TOLERANCE: f64 = 0.02;
+
+/// Something that rounding broke in a contour.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoundIssue {
+    /// The smooth node at this index is no longer smooth.
+    Kink { path: usize, node: usize },
+    /// The contour now intersects itself.
+    SelfIntersection { path: usize },
+}
+
+fn round_pt(pt: Point, grid: f64) -> Point {
+    // Adding zero turns -0, which would be written out as such, into 0.
+    let round = |x: f64| (x / grid).round() * grid + 0.0;
+    Point::new(round(pt.x), round(pt.y))
+}
+
+/// Round each coordinate away from zero, so nonzero vectors stay nonzero.
+fn round_away(v: Vec2, grid: f64) -> Vec2 {
+    let f = |x: f64| {
+        if x == 0.0 {
+            0.0
+        } else {
+            (x.abs() / grid).ceil().copysign(x) * grid
+        }
+    };
+    Vec2::new(f(v.x), f(v.y))
+}
+
+fn is_smooth(node_type: NodeType) -> bool {
+    node_type == NodeType::LineSmooth || node_type == NodeType::CurveSmooth
+}
+
+/// Sine of the angle between the incoming and outgoing directions.
+fn kink(p_in: Point, p: Point, p_out: Point) -> f64 {
+    let a = p - p_in;
+    let b = p_out - p;
+    let denom = a.hypot() * b.hypot();
+    if denom == 0.0 {
+        0.0
+    } else {
+        (a.cross(b) / denom).abs()
+    }
+}
+
+/// Neighboring node indices, or `None` at the ends of an open path.
+fn neighbors(path: &Path, i: usize) -> Option<(usize, usize)> {
+    let n = path.nodes.len();
+    if n < 3 || (!path.closed && (i == 0 || i == n - 1)) {
+        return None;
+    }
+    Some(((i + n - 1) % n, (i + 1) % n))
+}
+
+/// The on-curve point that an off-curve handle belongs to.
+fn owner(path: &Path, j: usize) -> Option<usize> {
+    let n = path.nodes.len();
+    let prev = (j + n - 1) % n;
+    let next = (j + 1) % n;
+    if (path.closed || j > 0) && path.nodes[prev].node_type != NodeType::OffCurve {
+        Some(prev)
+    } else if (path.closed || j < n - 1) && path.nodes[next].node_type != NodeType::OffCurve {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+/// Grid points around `pt`, nearest first.
+fn candidates(pt: Point, grid: f64) -> Vec<Point> {
+    let mut result = Vec::new();
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            result.push(pt + Vec2::new(dx as f64 * grid, dy as f64 * grid));
+        }
+    }
+    result.sort_by(|a, b| {
+        let da = (*a - pt).hypot2();
+        let db = (*b - pt).hypot2();
+        da.partial_cmp(&db).unwrap()
+    });
+    result
+}
+
+/// Whether a handle still points the same way from its on-curve point.
+fn same_side(handle: Point, on: Point, orig_handle: Point, orig_on: Point) -> bool {
+    let v_orig = orig_handle - orig_on;
+    v_orig.hypot2() == 0.0 || (handle - on).dot(v_orig) > 0.0
+}
+
+/// Straighten the smooth node at `i` by choosing nearby grid positions for
+/// its handles.
+fn straighten(path: &mut Path, orig: &[Point], i: usize, i_in: usize, i_out: usize, grid: f64) {
+    let off_in = path.nodes[i_in].node_type == NodeType::OffCurve;
+    let off_out = path.nodes[i_out].node_type == NodeType::OffCurve;
+    if !off_in && !off_out {
+        return;
+    }
+    let p = path.nodes[i].pt;
+    let before = kink(orig[i_in], orig[i], orig[i_out]);
+    if kink(path.nodes[i_in].pt, p, path.nodes[i_out].pt) <= before {
+        return;
+    }
+    let cands_in = if off_in {
+        candidates(path.nodes[i_in].pt, grid)
+    } else {
+        vec![path.nodes[i_in].pt]
+    };
+    let cands_out = if off_out {
+        candidates(path.nodes[i_out].pt, grid)
+    } else {
+        vec![path.nodes[i_out].pt]
+    };
+    let mut best = None;
+    for &h_in in &cands_in {
+        if off_in && !same_side(h_in, p, orig[i_in], orig[i]) {
+            continue;
+        }
+        for &h_out in &cands_out {
+            if off_out && !same_side(h_out, p, orig[i_out], orig[i]) {
+                continue;
+            }
+            let dist =
+                (h_in - path.nodes[i_in].pt).hypot2() + (h_out - path.nodes[i_out].pt).hypot2();
+            // Anything as straight as the original is good enough; then prefer
+            // the smallest movement.
+            let excess = (kink(h_in, p, h_out) - before).max(0.0);
+            let score = (excess, dist);
+            if best.map(|(s, _, _)| score < s).unwrap_or(true) {
+                best = Some((score, h_in, h_out));
+            }
+        }
+    }
+    if let Some((_, h_in, h_out)) = best {
+        path.nodes[i_in].pt = h_in;
+        path.nodes[i_out].pt = h_out;
+    }
+}
+
+/// A polyline approximation of a path, for intersection testing.
+fn flatten(path: &Path) -> Vec<Point> {
+    let mut result = Vec::new();
+    for seg in path.to_bez_path().segments() {
+        let c = seg.to_cubic();
+        if result.is_empty() {
+            result.push(c.p0);
+        }
+        if c.p1 == c.p0 && c.p2 == c.p3 {
+            result.push(c.p3);
+        } else {
+            for k in 1..=8 {
+                result.push(c.eval(k as f64 / 8.0));
+            }
+        }
+    }
+    result
+}
+
+fn segments_cross(a0: Point, a1: Point, b0: Point, b1: Point) -> bool {
+    let da = a1 - a0;
+    let db = b1 - b0;
+    let det = da.cross(db);
+    if det == 0.0 {
+        return false;
+    }
+    let w = b0 - a0;
+    let ta = w.cross(db) / det;
+    let tb = w.cross(da) / det;
+    ta > 0.0 && ta < 1.0 && tb > 0.0 && tb < 1.0
+}
+
+fn count_self_intersections(path: &Path) -> usize {
+    let pts = flatten(path);
+    let n = pts.len();
+    let mut count = 0;
+    for i in 0..n.saturating_sub(1) {
+        for j in i + 2..n - 1 {
+            if segments_cross(pts[i], pts[i + 1], pts[j], pts[j + 1]) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Round a path to the grid, returning any problems introduced.
+pub fn round_path(path: &mut Path, grid: f64, path_ix: usize) -> Vec<RoundIssue> {
+    let orig: Vec<Point> = path.nodes.iter().map(|node| node.pt).collect();
+    let orig_path = path.clone();
+    let n = path.nodes.len();
+    for node in &mut path.nodes {
+        if node.node_type != NodeType::OffCurve {
+            node.pt = round_pt(node.pt, grid);
+        }
+    }
+    // Handles move along with their on-curve points.
+    for j in 0..n {
+        if path.nodes[j].node_type == NodeType::OffCurve {
+            let pt = match owner(path, j) {
+                Some(o) => {
+                    let on = path.nodes[o].pt;
+                    let rounded = round_pt(orig[j] + (on - orig[o]), grid);
+                    if same_side(rounded, on, orig[j], orig[o]) {
+                        rounded
+                    } else {
+                        on + round_away(orig[j] - orig[o], grid)
+                    }
+                }
+                None => round_pt(orig[j], grid),
+            };
+            path.nodes[j].pt = pt;
+        }
+    }
+    let mut issues = Vec::new();
+    for i in 0..n {
+        if !is_smooth(path.nodes[i].node_type) {
+            continue;
+        }
+        if let Some((i_in, i_out)) = neighbors(path, i) {
+            straighten(path, &orig, i, i_in, i_out, grid);
+            let before = kink(orig[i_in], orig[i], orig[i_out]);
+            let after = kink(path.nodes[i_in].pt, path.nodes[i].pt, path.nodes[i_out].pt);
+            if after > KINK_TOLERANCE && before <= KINK_TOLERANCE {
+                issues.push(RoundIssue::Kink {
+                    path: path_ix,
+                    node: i,
+                });
+            }
+        }
+    }
+    if count_self_intersections(path) > count_self_intersections(&orig_path) {
+        issues.push(RoundIssue::SelfIntersection { path: path_ix });
+    }
+    issues
+}
+
+fn round_affine(affine: Affine, grid: f64) -> Affine {
+    let c = affine.as_coeffs();
+    let t = round_pt(Point::new(c[4], c[5]), grid);
+    Affine::new([c[0], c[1], c[2], c[3], t.x, t.y])
+}
+
+/// Round everything in a layer to the grid: paths, component translations,
+/// anchors, guidelines and the advance width.
+pub fn round_layer(layer: &mut Layer, grid: f64) -> Vec<RoundIssue> {
+    let mut issues = Vec::new();
+    layer.width = (layer.width / grid).round() * grid;
+    if let Some(ref mut paths) = layer.paths {
+        for (i, path) in paths.iter_mut().enumerate() {
+            issues.extend(round_path(path, grid, i));
+        }
+    }
+    if let Some(ref mut components) = layer.components {
+        for component in components {
+            if let Some(ref mut transform) = component.transform {
+                *transform = round_affine(*transform, grid);
+            }
+        }
+    }
+    if let Some(ref mut anchors) = layer.anchors {
+        for anchor in anchors {
+            anchor.position = round_pt(anchor.position, grid);
+        }
+    }
+    if let Some(ref mut guide_lines) = layer.guide_lines {
+        for guide_line in guide_lines {
+            guide_line.position = round_pt(guide_line.position, grid);
+        }
+    }
+    issues
+}
+
+/// Round all glyphs in a layer, returning the problems found per glyph.
+pub fn round(font: &mut Font, grid: f64, layer_id: &str) -> Vec<(String, Vec<RoundIssue>)> {
+    let mut result = Vec::new();
+    for glyph in &mut font.glyphs {
+        for layer in &mut glyph.layers {
+            if layer.layer_id == layer_id {
+                let issues = round_layer(layer, grid);
+                if !issues.is_empty() {
+                    result.push((glyph.glyphname.clone(), issues));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(nodes: &[(f64, f64, NodeType)]) -> Path {
+        let mut path = Path::new(true);
+        for &(x, y, node_type) in nodes {
+            path.add((x, y), node_type);
+        }
+        path
+    }
+
+    fn points(path: &Path) -> Vec<(f64, f64)> {
+        path.nodes.iter().map(|n| (n.pt.x, n.pt.y)).collect()
+    }
+
+    #[test]
+    fn handles_follow_their_node() {
+        use NodeType::*;
+        // A vertical tangent at the curve's end stays vertical, and a short
+        // handle isn't collapsed onto its node or flipped past it.
+        let mut p = path(&[
+            (0.0, 0.0, Line),
+            (10.4, 20.0, OffCurve),
+            (10.9, 0.0, OffCurve),
+            (10.6, 0.0, Curve),
+        ]);
+        assert!(round_path(&mut p, 1.0, 0).is_empty());
+        assert_eq!(
+            points(&p),
+            [(0.0, 0.0), (10.0, 20.0), (12.0, 0.0), (11.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn smooth_nodes_stay_collinear() {
+        use NodeType::*;
+        let mut p = path(&[
+            (0.0, 0.0, Line),
+            (7.3, 8.6, OffCurve),
+            (9.2, 9.3, OffCurve),
+            (11.6, 10.1, CurveSmooth),
+            (14.0, 10.9, OffCurve),
+            (20.3, 5.2, OffCurve),
+            (20.0, 0.0, Curve),
+        ]);
+        let orig = points(&p);
+        assert!(round_path(&mut p, 1.0, 0).is_empty());
+        let pt = |i: usize| p.nodes[i].pt;
+        assert_eq!(kink(pt(2), pt(3), pt(4)), 0.0);
+        for (node, (x, y)) in p.nodes.iter().zip(&orig) {
+            assert!((node.pt.x - x).abs() <= 1.5 && (node.pt.y - y).abs() <= 1.5);
+        }
+        // Plain rounding would have bent it.
+        let naive: Vec<Point> = orig[2..5]
+            .iter()
+            .map(|&(x, y)| round_pt(Point::new(x, y), 1.0))
+            .collect();
+        assert!(kink(naive[0], naive[1], naive[2]) > KINK_TOLERANCE);
+    }
+
+    #[test]
+    fn reports_kinks_and_self_intersections() {
+        use NodeType::*;
+        // A smooth node between two lines can only be bent.
+        let mut p = path(&[
+            (0.0, 0.0, Line),
+            (1.4, 0.6, LineSmooth),
+            (2.8, 1.2, Line),
+            (2.8, -5.0, Line),
+        ]);
+        assert_eq!(
+            round_path(&mut p, 1.0, 3),
+            [RoundIssue::Kink { path: 3, node: 1 }]
+        );
+
+        // The last node moves from above the first edge to below it.
+        let mut p = path(&[
+            (0.0, 0.0, Line),
+            (20.0, 1.5, Line),
+            (20.0, 3.0, Line),
+            (12.0, 0.99, Line),
+        ]);
+        assert_eq!(
+            round_path(&mut p, 1.0, 0),
+            [RoundIssue::SelfIntersection { path: 0 }]
+        );
+    }
+}
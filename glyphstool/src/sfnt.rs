@@ -0,0 +1,154 @@
+//! Writing of binary OpenType data: big-endian values, and the table
+//! directory of a font file.
+
+/// A table's tag and data.
+pub(crate) type TableData = ([u8; 4], Vec<u8>);
+
+/// A buffer of big-endian binary data.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Writer {
+    pub data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn u8(&mut self, x: u8) -> &mut Writer {
+        self.data.push(x);
+        self
+    }
+
+    pub fn u16(&mut self, x: u16) -> &mut Writer {
+        self.data.extend_from_slice(&x.to_be_bytes());
+        self
+    }
+
+    pub fn i16(&mut self, x: i16) -> &mut Writer {
+        self.data.extend_from_slice(&x.to_be_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, x: u32) -> &mut Writer {
+        self.data.extend_from_slice(&x.to_be_bytes());
+        self
+    }
+
+    pub fn i32(&mut self, x: i32) -> &mut Writer {
+        self.data.extend_from_slice(&x.to_be_bytes());
+        self
+    }
+
+    pub fn i64(&mut self, x: i64) -> &mut Writer {
+        self.data.extend_from_slice(&x.to_be_bytes());
+        self
+    }
+
+    /// A 16.16 fixed point number.
+    pub fn fixed(&mut self, x: f64) -> &mut Writer {
+        self.i32((x * 65536.0).round() as i32)
+    }
+
+    /// A 2.14 fixed point number.
+    pub fn f2dot14(&mut self, x: f64) -> &mut Writer {
+        self.i16((x * 16384.0).round() as i16)
+    }
+
+    pub fn tag(&mut self, tag: &[u8; 4]) -> &mut Writer {
+        self.bytes(tag)
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Writer {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    /// Pad with zeros to a multiple of `n` bytes.
+    pub fn align(&mut self, n: usize) -> &mut Writer {
+        while !self.data.len().is_multiple_of(n) {
+            self.data.push(0);
+        }
+        self
+    }
+
+    /// Overwrite a 16-bit value written earlier.
+    pub fn set_u16(&mut self, pos: usize, x: u16) {
+        self.data[pos..pos + 2].copy_from_slice(&x.to_be_bytes());
+    }
+
+    /// Overwrite a 32-bit value written earlier.
+    pub fn set_u32(&mut self, pos: usize, x: u32) {
+        self.data[pos..pos + 4].copy_from_slice(&x.to_be_bytes());
+    }
+}
+
+/// Round and clamp to the range of a 16-bit signed value.
+pub(crate) fn to_i16(x: f64) -> i16 {
+    x.round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+}
+
+/// Round and clamp to the range of a 16-bit unsigned value.
+pub(crate) fn to_u16(x: f64) -> u16 {
+    x.round().clamp(0.0, u16::MAX as f64) as u16
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// The searchRange, entrySelector and rangeShift fields for binary search
+/// over `n` items of `size` bytes, as used by table directories and cmap.
+pub(crate) fn search_params(n: u16, size: u16) -> (u16, u16, u16) {
+    let mut power = 1u16;
+    let mut log = 0u16;
+    while power * 2 <= n {
+        power *= 2;
+        log += 1;
+    }
+    let range = power * size;
+    (range, log, n * size - range)
+}
+
+/// Assemble tables into a font file. `version` is 0x00010000 for TrueType
+/// outlines or `OTTO` for CFF. The checksum adjustment of the `head`
+/// table is filled in.
+pub(crate) fn font_file(version: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|t| t.0);
+    let n = tables.len() as u16;
+    let mut w = Writer::new();
+    let (search_range, entry_selector, range_shift) = search_params(n, 16);
+    w.u32(version)
+        .u16(n)
+        .u16(search_range)
+        .u16(entry_selector)
+        .u16(range_shift);
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+    for (tag, data) in &tables {
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        w.tag(tag)
+            .u32(checksum(data))
+            .u32(offset as u32)
+            .u32(data.len() as u32);
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in &tables {
+        w.bytes(data).align(4);
+    }
+    if let Some(head) = head_offset {
+        let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&w.data));
+        w.set_u32(head + 8, adjustment);
+    }
+    w.data
+}
@@ -0,0 +1,317 @@
+//! Shade glyphs (░▒▓) drawn as dither patterns.
+//!
+//! The cell is divided into a grid of tiles, each with a dot centered on its
+//! corners and one in its middle, like a checkerboard. Dots on the edges of
+//! the cell are cut along their center lines, and the missing parts are
+//! drawn by the neighboring cells, so the pattern continues seamlessly
+//! across lines and columns of text.
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use kurbo::{Point, Rect, Vec2};
+
+use crate::boxdraw::BoxStyle;
+use crate::cell::CellMetrics;
+use crate::font::{Font, NodeType, Path};
+use crate::interpolate::{format_location, master_location};
+use crate::measure::coverage as ink_coverage;
+use crate::param::Value;
+use crate::region::Region;
+use crate::round::round_path;
+use crate::stroke::KAPPA;
+
+/// The shape of the dots of a shade pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DotShape {
+    Square,
+    Diamond,
+    Dot,
+}
+
+impl FromStr for DotShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DotShape, String> {
+        match s {
+            "square" => Ok(DotShape::Square),
+            "diamond" => Ok(DotShape::Diamond),
+            "dot" => Ok(DotShape::Dot),
+            _ => Err(format!("unknown dot shape {}", s)),
+        }
+    }
+}
+
+/// A dither pattern for one of the shade glyphs.
+#[derive(Clone, Debug)]
+pub struct ShadePattern {
+    pub shape: DotShape,
+    /// The fraction of the cell covered with ink.
+    pub density: Value,
+    /// Number of tiles across the cell.
+    pub columns: usize,
+    /// Number of tiles up the cell.
+    pub rows: usize,
+}
+
+impl ShadePattern {
+    /// A pattern with the tile size used for Inconsolata.
+    pub fn new(shape: DotShape, density: Value) -> ShadePattern {
+        ShadePattern {
+            shape,
+            density,
+            columns: 3,
+            rows: 7,
+        }
+    }
+}
+
+/// The area where two circles of radius `r` with centers `d` apart overlap.
+fn lens_area(r: f64, d: f64) -> f64 {
+    if d >= 2.0 * r {
+        return 0.0;
+    }
+    2.0 * r * r * (d / (2.0 * r)).acos() - 0.5 * d * (4.0 * r * r - d * d).sqrt()
+}
+
+/// Half the size of the dots, as a fraction of the tile, that covers the
+/// given fraction of the tile.
+///
+/// There are two dots per tile, and each touches its four diagonal
+/// neighbors once the pattern is dense enough, so the overlap is taken out.
+fn dot_radius(shape: DotShape, density: f64) -> f64 {
+    let density = density.clamp(0.0, 1.0);
+    match shape {
+        DotShape::Square => {
+            if density <= 0.5 {
+                0.5 * (0.5 * density).sqrt()
+            } else {
+                0.5 * (1.0 - (0.5 * (1.0 - density)).sqrt())
+            }
+        }
+        // Diamonds fill the plane without overlapping.
+        DotShape::Diamond => 0.5 * density.sqrt(),
+        DotShape::Dot => {
+            let coverage = |r: f64| 2.0 * PI * r * r - 4.0 * lens_area(r, 0.5f64.sqrt());
+            let (mut lo, mut hi) = (0.0, 0.5);
+            for _ in 0..50 {
+                let mid = 0.5 * (lo + hi);
+                if coverage(mid) < density {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            lo
+        }
+    }
+}
+
+/// A diamond or round dot, keeping only the quarters on the inside of the
+/// cell. Quarters are numbered counterclockwise starting from the upper
+/// right.
+fn dot_path(shape: DotShape, center: Point, rx: f64, ry: f64, quarters: &[bool; 4]) -> Path {
+    let axis = |k: usize| {
+        let (dx, dy) = [(1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)][k % 4];
+        Point::new(center.x + dx * rx, center.y + dy * ry)
+    };
+    let add_quarter = |path: &mut Path, k: usize, node_type: NodeType| {
+        let (p0, p1) = (axis(k), axis(k + 1));
+        if shape == DotShape::Dot {
+            // The tangents at both ends meet in the corner of the quarter.
+            let corner = if k.is_multiple_of(2) {
+                Point::new(p0.x, p1.y)
+            } else {
+                Point::new(p1.x, p0.y)
+            };
+            path.add(p0.lerp(corner, KAPPA), NodeType::OffCurve);
+            path.add(p1.lerp(corner, KAPPA), NodeType::OffCurve);
+            path.add(p1, node_type);
+        } else {
+            path.add(p1, NodeType::Line);
+        }
+    };
+    let mut path = Path::new(true);
+    // Start at the first quarter following a missing one, so the kept ones
+    // are contiguous.
+    match (0..4).find(|&k| quarters[k] && !quarters[(k + 3) % 4]) {
+        None => {
+            for k in 0..4 {
+                add_quarter(&mut path, k, NodeType::CurveSmooth);
+            }
+        }
+        Some(first) => {
+            let n = (0..4).take_while(|&i| quarters[(first + i) % 4]).count();
+            // A half dot closes along its diameter, a quarter through the
+            // center.
+            if n < 2 {
+                path.add(center, NodeType::Line);
+            }
+            path.add(axis(first), NodeType::Line);
+            for i in 0..n {
+                let node_type = if i + 1 < n {
+                    NodeType::CurveSmooth
+                } else {
+                    NodeType::Curve
+                };
+                add_quarter(&mut path, first + i, node_type);
+            }
+        }
+    }
+    path
+}
+
+/// The paths of a shade pattern for a master at the given weight and width.
+pub fn shade_paths(pattern: &ShadePattern, wght: f64, wdth: f64, cell: &CellMetrics) -> Vec<Path> {
+    let tile_w = cell.advance / pattern.columns as f64;
+    let tile_h = cell.height() / pattern.rows as f64;
+    let r = dot_radius(pattern.shape, pattern.density.eval(wght, wdth));
+    let (rx, ry) = (r * tile_w, r * tile_h);
+    // Each dot with the quarters of it that are inside the cell.
+    let mut dots = Vec::new();
+    for i in 0..=pattern.columns {
+        for j in 0..=pattern.rows {
+            let right = i < pattern.columns;
+            let left = i > 0;
+            let up = j < pattern.rows;
+            let down = j > 0;
+            let center = Point::new(i as f64 * tile_w, cell.bottom + j as f64 * tile_h);
+            dots.push((
+                center,
+                [right && up, left && up, left && down, right && down],
+            ));
+            if right && up {
+                let center = center + Vec2::new(0.5 * tile_w, 0.5 * tile_h);
+                dots.push((center, [true; 4]));
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    if r <= 0.0 {
+        return paths;
+    }
+    if pattern.shape == DotShape::Square {
+        let mut region = Region::default();
+        for (c, _) in dots {
+            region = region.add(Rect::new(c.x - rx, c.y - ry, c.x + rx, c.y + ry).round());
+        }
+        let clip = Region::default().add(cell.rect().round());
+        paths = region.intersect(&clip).to_paths();
+    } else {
+        for (c, quarters) in dots {
+            paths.push(dot_path(pattern.shape, c, rx, ry, &quarters));
+        }
+    }
+    for (i, path) in paths.iter_mut().enumerate() {
+        round_path(path, 1.0, i);
+    }
+    paths
+}
+
+/// Check that the light, medium and dark shades have increasing ink
+/// coverage in every master. Returns a description of each problem.
+pub fn check_shades(font: &Font, style: &BoxStyle) -> Vec<String> {
+    let mut issues = Vec::new();
+    let glyphs: Vec<_> = (0x2591..=0x2593)
+        .map(|cp| font.glyphs.iter().find(|g| g.unicodes().contains(&cp)))
+        .collect();
+    for master in &font.font_master {
+        let cell = match style.cell(font, &master.id) {
+            Some(cell) => cell,
+            None => continue,
+        };
+        let mut coverage = Vec::new();
+        for glyph in glyphs.iter().flatten() {
+            if let Some(layer) = glyph.get_layer(&master.id) {
+                let paths = layer.paths.as_deref().unwrap_or(&[]);
+                coverage.push(ink_coverage(paths, cell.rect()));
+            }
+        }
+        if coverage.windows(2).any(|w| w[0] >= w[1]) {
+            let location = master_location(font, &master.id).unwrap_or_default();
+            let coverage: Vec<String> = coverage.iter().map(|c| format!("{:.3}", c)).collect();
+            issues.push(format!(
+                "master {} ({}): shade coverage doesn't increase: {}",
+                master.id,
+                format_location(&location),
+                coverage.join(", ")
+            ));
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+    use kurbo::Affine;
+
+    #[test]
+    fn coverage_increases_from_light_to_dark() {
+        let source = r#"{
+fontMaster = ({id = m; weightValue = 400; ascender = 800; descender = -200;});
+glyphs = (
+{glyphname = shadelight; unicode = 2591; layers = ({layerId = m; width = 600;});},
+{glyphname = shade; unicode = 2592; layers = ({layerId = m; width = 600;});},
+{glyphname = shadedark; unicode = 2593; layers = ({layerId = m; width = 600;});}
+);
+}"#;
+        let mut font = Font::from_plist(Plist::parse(source).unwrap());
+        let style = BoxStyle::default();
+        let cell = style.cell(&font, "m").unwrap();
+        for (glyph, pattern) in font.glyphs.iter_mut().zip(&style.shades) {
+            let paths = shade_paths(pattern, 400.0, 100.0, &cell);
+            let density = pattern.density.eval(400.0, 100.0);
+            assert!((ink_coverage(&paths, cell.rect()) - density).abs() < 0.01);
+            glyph.layers[0].paths = Some(paths);
+        }
+        assert!(check_shades(&font, &style).is_empty());
+
+        let light = font.glyphs[0].layers[0].paths.take();
+        font.glyphs[0].layers[0].paths = font.glyphs[2].layers[0].paths.take();
+        font.glyphs[2].layers[0].paths = light;
+        let issues = check_shades(&font, &style);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("master m (wght 400): shade coverage doesn't increase"));
+    }
+
+    #[test]
+    fn pattern_tiles_across_cell_edges() {
+        let cell = CellMetrics::new(600.0, -400.0, 1000.0);
+        let (tile_w, tile_h) = (200.0, 200.0);
+        for &shape in &[DotShape::Square, DotShape::Diamond, DotShape::Dot] {
+            let pattern = ShadePattern::new(shape, Value::product(&[[400.0, 0.3]], &[]));
+            let paths = shade_paths(&pattern, 400.0, 100.0, &cell);
+            // The cell with its neighbors on all sides.
+            let mut tiled = Vec::new();
+            for dx in &[-cell.advance, 0.0, cell.advance] {
+                for dy in &[-cell.height(), 0.0, cell.height()] {
+                    for path in &paths {
+                        let mut path = path.clone();
+                        path.apply_affine(Affine::translate((*dx, *dy)));
+                        tiled.push(path);
+                    }
+                }
+            }
+            // A tile centered on a dot holds the ink of two dots, whether the
+            // dot is inside the cell or on its corner.
+            let window = |x: f64, y: f64| {
+                let rect = Rect::new(
+                    x - 0.5 * tile_w,
+                    y - 0.5 * tile_h,
+                    x + 0.5 * tile_w,
+                    y + 0.5 * tile_h,
+                );
+                ink_coverage(&tiled, rect)
+            };
+            let inside = window(tile_w, cell.bottom + tile_h);
+            let corner = window(0.0, cell.bottom);
+            let side = window(cell.advance, cell.bottom + 3.0 * tile_h);
+            assert!((inside - 0.3).abs() < 0.02, "{:?}: {}", shape, inside);
+            assert!((corner - inside).abs() < 0.01, "{:?}: {}", shape, corner);
+            assert!((side - inside).abs() < 0.01, "{:?}: {}", shape, side);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Slanting a font into an oblique.
+
+use std::collections::HashMap;
+
+use kurbo::{Affine, Point, Vec2};
+
+use crate::font::{Font, Layer};
+use crate::measure::ink_bounds;
+use crate::round::{round_layer, RoundIssue};
+
+/// The height around which the outlines are skewed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlantOrigin {
+    /// Points on the baseline stay put.
+    Baseline,
+    /// Points at half the master's x-height stay put, which keeps lowercase
+    /// glyphs roughly in place.
+    XHeightCenter,
+}
+
+fn affine_skew(angle: f64, y0: f64) -> Affine {
+    let t = angle.to_radians().tan();
+    Affine::new([1., 0., t, 1., -t * y0, 0.])
+}
+
+pub(crate) fn affine_translate_x(dx: f64) -> Affine {
+    Affine::new([1., 0., 0., 1., dx, 0.])
+}
+
+/// Inverse of an invertible affine; this version of kurbo lacks one.
+fn affine_inverse(a: Affine) -> Affine {
+    let [a, b, c, d, e, f] = a.as_coeffs();
+    let det = a * d - b * c;
+    Affine::new([
+        d / det,
+        -b / det,
+        -c / det,
+        a / det,
+        (c * f - d * e) / det,
+        (b * e - a * f) / det,
+    ])
+}
+
+/// Whether an affine is the identity, once its translation is rounded.
+fn is_identity(a: Affine) -> bool {
+    let c = a.as_coeffs();
+    let id = [1., 0., 0., 1.];
+    (0..4).all(|i| (c[i] - id[i]).abs() < 1e-9) && c[4].abs() < 0.5 && c[5].abs() < 0.5
+}
+
+/// Horizontal shift for each glyph that keeps the center of its ink where it
+/// was before skewing.
+///
+/// Composites take the shift of their first component, so that an accented
+/// letter lines up with its base.
+fn recenter_shifts(font: &Font, skew: Affine, layer_id: &str) -> HashMap<String, f64> {
+    let mut result = HashMap::new();
+    for glyph in &font.glyphs {
+        if let Some(layer) = glyph.get_layer(layer_id) {
+            let mut paths = font.decompose(layer);
+            let before = ink_bounds(&paths);
+            for path in &mut paths {
+                path.apply_affine(skew);
+            }
+            let after = ink_bounds(&paths);
+            if let (Some(before), Some(after)) = (before, after) {
+                let shift = before.center().x - after.center().x;
+                result.insert(glyph.glyphname.clone(), shift);
+            }
+        }
+    }
+    let mut bases = HashMap::new();
+    for glyph in &font.glyphs {
+        let layer = glyph.get_layer(layer_id);
+        let first = layer
+            .and_then(|l| l.components.as_ref())
+            .and_then(|c| c.first());
+        if let Some(component) = first {
+            bases.insert(glyph.glyphname.clone(), component.name.clone());
+        }
+    }
+    for (glyphname, base) in &bases {
+        let mut base = base;
+        // Follow nested composites down to the glyph with the outline.
+        for _ in 0..16 {
+            match bases.get(base) {
+                Some(next) => base = next,
+                None => break,
+            }
+        }
+        if let Some(&shift) = result.get(base) {
+            result.insert(glyphname.clone(), shift);
+        }
+    }
+    result
+}
+
+fn slant_layer(
+    layer: &mut Layer,
+    a: Affine,
+    shifts: &HashMap<String, f64>,
+    skew: Affine,
+) -> Vec<RoundIssue> {
+    if let Some(ref mut paths) = layer.paths {
+        for path in paths {
+            path.apply_affine(a);
+        }
+    }
+    if let Some(ref mut anchors) = layer.anchors {
+        for anchor in anchors {
+            anchor.position = a * anchor.position;
+        }
+    }
+    if let Some(ref mut guide_lines) = layer.guide_lines {
+        for guide_line in guide_lines {
+            guide_line.position = a * guide_line.position;
+            if let Some(ref mut angle) = guide_line.angle {
+                let dir = Vec2::from_angle(angle.to_radians()).to_point();
+                let dir = (a * dir) - (a * Point::ORIGIN);
+                *angle = dir.atan2().to_degrees();
+            }
+        }
+    }
+    if let Some(ref mut components) = layer.components {
+        for component in components {
+            let shift = shifts.get(&component.name).copied().unwrap_or(0.0);
+            let a_base = affine_translate_x(shift) * skew;
+            let transform = a * component.transform.unwrap_or_default() * affine_inverse(a_base);
+            if component.transform.is_some() || !is_identity(transform) {
+                component.transform = Some(transform);
+            }
+        }
+    }
+    round_layer(layer, 1.0)
+}
+
+/// Slant all glyphs in a layer by `angle` degrees (positive leans right),
+/// skewing around the given origin height.
+///
+/// If `recenter` is set, each glyph is shifted horizontally so that its ink
+/// stays centered in the cell as before. The italic angle of the master with
+/// the same id as the layer is set to `angle`. Returns the problems that
+/// rounding the slanted outlines introduced, per glyph, as `round` does.
+pub fn slant_with(
+    font: &mut Font,
+    angle: f64,
+    layer_id: &str,
+    origin: SlantOrigin,
+    recenter: bool,
+) -> Vec<(String, Vec<RoundIssue>)> {
+    let master = font.get_master(layer_id);
+    let y0 = match origin {
+        SlantOrigin::Baseline => 0.0,
+        SlantOrigin::XHeightCenter => master.and_then(|m| m.x_height).unwrap_or(0.0) * 0.5,
+    };
+    let skew = affine_skew(angle, y0);
+    let shifts = if recenter {
+        recenter_shifts(font, skew, layer_id)
+    } else {
+        HashMap::new()
+    };
+    let mut result = Vec::new();
+    for glyph in &mut font.glyphs {
+        let shift = shifts.get(&glyph.glyphname).copied().unwrap_or(0.0);
+        let a = affine_translate_x(shift) * skew;
+        if let Some(layer) = glyph.get_layer_mut(layer_id) {
+            let issues = slant_layer(layer, a, &shifts, skew);
+            if !issues.is_empty() {
+                result.push((glyph.glyphname.clone(), issues));
+            }
+        }
+    }
+    if let Some(master) = font.get_master_mut(layer_id) {
+        master.italic_angle = Some(angle);
+    }
+    result
+}
+
+/// Slant all glyphs in a layer around half the x-height, re-centering each
+/// glyph in its cell.
+pub fn slant(font: &mut Font, angle: f64, layer_id: &str) -> Vec<(String, Vec<RoundIssue>)> {
+    slant_with(font, angle, layer_id, SlantOrigin::XHeightCenter, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Path;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn font() -> Font {
+        let source = r#"{
+fontMaster = ({id = m; weightValue = 400; xHeight = 500;});
+glyphs = (
+{glyphname = bar; layers = ({layerId = m; width = 500; paths = ({closed = 1; nodes = (
+"100 0 LINE", "200 0 LINE", "200 500 LINE", "100 500 LINE");});});},
+{glyphname = dot; layers = ({layerId = m; width = 500; paths = ({closed = 1; nodes = (
+"300 600 LINE", "400 600 LINE", "400 700 LINE", "300 700 LINE");});});},
+{glyphname = bardot; layers = ({layerId = m; width = 500; components = ({name = bar;}, {name = dot;});});}
+);
+}"#;
+        Font::from_plist(Plist::parse(source).unwrap())
+    }
+
+    fn points(font: &Font, glyphname: &str) -> Vec<(f64, f64)> {
+        let layer = font.get_glyph(glyphname).unwrap().get_layer("m").unwrap();
+        let paths = font.decompose(layer);
+        paths
+            .iter()
+            .flat_map(|p| p.nodes.iter().map(|n| (n.pt.x, n.pt.y)))
+            .collect()
+    }
+
+    #[test]
+    fn skews_around_the_origin() {
+        let mut baseline = font();
+        assert!(slant_with(&mut baseline, 45.0, "m", SlantOrigin::Baseline, false).is_empty());
+        assert_eq!(
+            points(&baseline, "bar"),
+            [(100.0, 0.0), (200.0, 0.0), (700.0, 500.0), (600.0, 500.0)]
+        );
+        assert_eq!(baseline.get_master("m").unwrap().italic_angle, Some(45.0));
+
+        let mut center = font();
+        slant_with(&mut center, 45.0, "m", SlantOrigin::XHeightCenter, false);
+        assert_eq!(
+            points(&center, "bar"),
+            [(-150.0, 0.0), (-50.0, 0.0), (450.0, 500.0), (350.0, 500.0)]
+        );
+    }
+
+    #[test]
+    fn recentering_keeps_the_ink_in_place() {
+        let mut font = font();
+        slant_with(&mut font, 45.0, "m", SlantOrigin::Baseline, true);
+        let layer = font.get_glyph("bar").unwrap().get_layer("m").unwrap();
+        let bounds = ink_bounds(&font.decompose(layer)).unwrap();
+        assert_eq!(bounds.center().x, 150.0);
+        assert_eq!(
+            points(&font, "bar"),
+            [(-150.0, 0.0), (-50.0, 0.0), (450.0, 500.0), (350.0, 500.0)]
+        );
+    }
+
+    #[test]
+    fn composites_follow_their_first_component() {
+        let original = font();
+        let mut font = original.clone();
+        slant_with(&mut font, 45.0, "m", SlantOrigin::Baseline, true);
+        // The whole composite moves with its base, not with the dot.
+        let a = affine_translate_x(-250.0) * affine_skew(45.0, 0.0);
+        let layer = original
+            .get_glyph("bardot")
+            .unwrap()
+            .get_layer("m")
+            .unwrap();
+        let mut expected: Vec<Path> = original.decompose(layer);
+        for path in &mut expected {
+            path.apply_affine(a);
+        }
+        let expected: Vec<(f64, f64)> = expected
+            .iter()
+            .flat_map(|p| p.nodes.iter().map(|n| (n.pt.x.round(), n.pt.y.round())))
+            .collect();
+        assert_eq!(points(&font, "bardot"), expected);
+        assert_ne!(points(&font, "dot")[0], expected[4]);
+        let layer = font.get_glyph("bardot").unwrap().get_layer("m").unwrap();
+        assert!(layer.components.as_ref().unwrap()[0].transform.is_none());
+    }
+}
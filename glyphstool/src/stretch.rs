@@ -1,54 +1,381 @@
 //! A little logic to apply horizontal stretching to a font.
+//!
+//! Plain scaling makes vertical stems heavier (or lighter) along with
+//! everything else. The compensated mode then moves the outline horizontally
+//! so stems get back to their original thickness, which amounts to eroding
+//! (or dilating) the shape by a horizontal line segment.
 
-use kurbo::Affine;
+use std::collections::HashMap;
 
-use crate::font::{Font, Glyph, Layer};
+use kurbo::{Affine, Point, Vec2};
+
+use crate::font::{Font, Layer, NodeType, Path};
+use crate::measure::stem_at;
+use crate::round::{round_layer, RoundIssue};
+use crate::slant::affine_translate_x;
+
+/// Horizontal component of a normal below which a node is considered to be
+/// on a horizontal edge, and is left in place by stem compensation.
+const HORIZONTAL_TOLERANCE: f64 = 0.1;
+
+/// Where to get the vertical stem thickness used for compensation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StemSource {
+    /// A fixed thickness in font units.
+    Fixed(f64),
+    /// The first of the master's `verticalStems`.
+    Master,
+    /// The thinnest run of ink across the given glyph at half the x-height.
+    Measure(String),
+}
+
+/// Options for `stretch_with`.
+#[derive(Clone, Debug)]
+pub struct StretchOptions {
+    /// The horizontal scale factor.
+    pub factor: f64,
+    /// Keep vertical stems at their original thickness, if set.
+    pub stem: Option<StemSource>,
+    /// Glyphs that are left alone, neither scaled nor moved.
+    ///
+    /// Each entry is a glyph name, a pattern with `*` wildcards, or `@Name`
+    /// for a glyph class of the font.
+    pub exclude: Vec<String>,
+    /// Re-center the excluded glyphs in the stretched advance width instead
+    /// of leaving them alone.
+    pub recenter_excluded: bool,
+}
+
+impl StretchOptions {
+    pub fn new(factor: f64) -> StretchOptions {
+        StretchOptions {
+            factor,
+            stem: None,
+            exclude: Vec::new(),
+            recenter_excluded: false,
+        }
+    }
+}
 
 fn affine_stretch(stretch: f64) -> Affine {
     Affine::new([stretch, 0., 0., 1., 0., 0.])
 }
 
-fn stretch_layer(layer: &mut Layer, stretch: f64) {
-    let a = affine_stretch(stretch);
-    let a_inv = affine_stretch(stretch.recip());
-    layer.width = (layer.width * stretch).round();
+/// Match a glyph name against a pattern where `*` matches any run of
+/// characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !name.starts_with(first) {
+        return false;
+    }
+    let mut rest = &name[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() {
+        return rest.is_empty();
+    }
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(ix) => rest = &rest[ix + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn is_excluded(font: &Font, glyphname: &str, exclude: &[String]) -> bool {
+    exclude.iter().any(|pattern| {
+        if let Some(class) = pattern.strip_prefix('@') {
+            font.get_class(class)
+                .map(|c| c.glyph_names().any(|name| name == glyphname))
+                .unwrap_or(false)
+        } else {
+            glob_match(pattern, glyphname)
+        }
+    })
+}
+
+fn resolve_stem(font: &Font, source: &StemSource, layer_id: &str) -> Result<f64, String> {
+    let master = font.get_master(layer_id);
+    match source {
+        StemSource::Fixed(stem) => Ok(*stem),
+        StemSource::Master => master
+            .and_then(|m| m.vertical_stems.as_ref())
+            .and_then(|stems| stems.first().copied())
+            .ok_or_else(|| format!("master {} has no vertical stems", layer_id)),
+        StemSource::Measure(glyphname) => {
+            let layer = font
+                .get_glyph(glyphname)
+                .and_then(|g| g.get_layer(layer_id))
+                .ok_or_else(|| format!("glyph {} not found in layer {}", glyphname, layer_id))?;
+            let y = master.and_then(|m| m.x_height).unwrap_or(500.0) * 0.5;
+            stem_at(&font.decompose(layer), y)
+                .ok_or_else(|| format!("no stems found in {}", glyphname))
+        }
+    }
+}
+
+/// Twice the signed area of the path's control polygon; positive for
+/// counterclockwise.
+fn signed_area(path: &Path) -> f64 {
+    let n = path.nodes.len();
+    (0..n)
+        .map(|i| {
+            let p0 = path.nodes[i].pt.to_vec2();
+            let p1 = path.nodes[(i + 1) % n].pt.to_vec2();
+            p0.cross(p1)
+        })
+        .sum()
+}
+
+/// Unit direction from `p0` to the first distinct point in `pts`.
+fn direction(p0: Point, pts: impl Iterator<Item = Point>) -> Option<Vec2> {
+    for pt in pts {
+        let d = pt - p0;
+        if d.hypot() > 1e-9 {
+            return Some(d.normalize());
+        }
+    }
+    None
+}
+
+/// Move the nodes of a path horizontally so that vertical stems get thinner
+/// by `2 * delta`. Handles move along with their on-curve points.
+///
+/// `ccw` is the direction of the glyph's outer contours; counters run the
+/// other way, which keeps the ink on the same side of every edge.
+fn compensate_path(path: &mut Path, delta: f64, ccw: bool) {
+    let n = path.nodes.len();
+    if !path.closed || n < 3 {
+        return;
+    }
+    let pts: Vec<Point> = path.nodes.iter().map(|node| node.pt).collect();
+    let mut dxs = vec![0.0; n];
+    for i in 0..n {
+        if path.nodes[i].node_type == NodeType::OffCurve {
+            continue;
+        }
+        let p = pts[i];
+        let d_in = direction(p, (1..n).map(|k| pts[(i + n - k) % n])).map(|d| -d);
+        let d_out = direction(p, (1..n).map(|k| pts[(i + k) % n]));
+        // The outward normal of a counterclockwise outline is to the right.
+        let normal_x = |d: Vec2| if ccw { d.y } else { -d.y };
+        let nx = d_in.map(normal_x).unwrap_or(0.0) + d_out.map(normal_x).unwrap_or(0.0);
+        if nx.abs() > HORIZONTAL_TOLERANCE {
+            dxs[i] = -delta * nx.signum();
+        }
+    }
+    for j in 0..n {
+        if path.nodes[j].node_type == NodeType::OffCurve {
+            let prev = (j + n - 1) % n;
+            let next = (j + 1) % n;
+            dxs[j] = if path.nodes[prev].node_type != NodeType::OffCurve {
+                dxs[prev]
+            } else {
+                dxs[next]
+            };
+        }
+    }
+    for (node, dx) in path.nodes.iter_mut().zip(dxs) {
+        node.pt.x += dx;
+    }
+}
+
+fn transform_layer(layer: &mut Layer, a: Affine, affines: &HashMap<String, Affine>) {
     if let Some(ref mut paths) = layer.paths {
         for path in paths {
-            for node in &mut path.nodes {
-                node.pt = (a * node.pt).round();
-            }
+            path.apply_affine(a);
         }
     }
     if let Some(ref mut anchors) = layer.anchors {
         for anchor in anchors {
-            anchor.position = (a * anchor.position).round();
+            anchor.position = a * anchor.position;
         }
     }
     if let Some(ref mut guide_lines) = layer.guide_lines {
         for guide_line in guide_lines {
-            guide_line.position = (a * guide_line.position).round();
+            guide_line.position = a * guide_line.position;
         }
     }
     if let Some(ref mut components) = layer.components {
         for component in components {
-            if let Some(ref mut transform) = component.transform {
-                // TODO: round the translation component
-                *transform = a * *transform * a_inv;
+            // The base glyph has been transformed by its own affine, which
+            // the component transform must undo.
+            let a_base = affines.get(&component.name).copied().unwrap_or_default();
+            let transform = a * component.transform.unwrap_or_default() * inverse_stretch(a_base);
+            let c = transform.as_coeffs();
+            if component.transform.is_some() || c[0] != 1.0 || c[4].abs() >= 0.5 {
+                component.transform = Some(transform);
             }
         }
     }
 }
 
-fn stretch_glyph(glyph: &mut Glyph, stretch: f64, layer_id: &str) {
-    for layer in &mut glyph.layers {
-        if layer.layer_id == layer_id {
-            stretch_layer(layer, stretch);
+/// Inverse of a horizontal scale followed by a horizontal translation.
+fn inverse_stretch(a: Affine) -> Affine {
+    let c = a.as_coeffs();
+    Affine::new([c[0].recip(), 0., 0., 1., -c[4] / c[0], 0.])
+}
+
+/// Stretch all glyphs in a layer horizontally, with optional stem
+/// compensation and exclusions. Returns the problems that rounding the
+/// stretched outlines introduced, per glyph, as `round` does.
+pub fn stretch_with(
+    font: &mut Font,
+    layer_id: &str,
+    options: &StretchOptions,
+) -> Result<Vec<(String, Vec<RoundIssue>)>, String> {
+    let factor = options.factor;
+    let delta = match options.stem {
+        Some(ref source) => resolve_stem(font, source, layer_id)? * (factor - 1.0) * 0.5,
+        None => 0.0,
+    };
+    let mut affines = HashMap::new();
+    for glyph in &font.glyphs {
+        if let Some(layer) = glyph.get_layer(layer_id) {
+            let a = if !is_excluded(font, &glyph.glyphname, &options.exclude) {
+                affine_stretch(factor)
+            } else if options.recenter_excluded {
+                affine_translate_x(layer.width * (factor - 1.0) * 0.5)
+            } else {
+                continue;
+            };
+            affines.insert(glyph.glyphname.clone(), a);
+        }
+    }
+    let mut result = Vec::new();
+    for glyph in &mut font.glyphs {
+        let a = match affines.get(&glyph.glyphname) {
+            Some(&a) => a,
+            None => continue,
+        };
+        let scaled = a.as_coeffs()[0] != 1.0;
+        if let Some(layer) = glyph.get_layer_mut(layer_id) {
+            transform_layer(layer, a, &affines);
+            layer.width *= factor;
+            if scaled && delta != 0.0 {
+                if let Some(ref mut paths) = layer.paths {
+                    // The largest contour is an outer one.
+                    let outer = paths
+                        .iter()
+                        .map(signed_area)
+                        .max_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap());
+                    let ccw = outer.map(|area| area > 0.0).unwrap_or(true);
+                    for path in paths {
+                        compensate_path(path, delta, ccw);
+                    }
+                }
+            }
+            let issues = round_layer(layer, 1.0);
+            if !issues.is_empty() {
+                result.push((glyph.glyphname.clone(), issues));
+            }
         }
     }
+    Ok(result)
 }
 
-pub fn stretch(font: &mut Font, stretch: f64, layer_id: &str) {
-    for glyph in &mut font.glyphs {
-        stretch_glyph(glyph, stretch, layer_id);
+/// Scale all glyphs in a layer horizontally by a single factor.
+pub fn stretch(font: &mut Font, stretch: f64, layer_id: &str) -> Vec<(String, Vec<RoundIssue>)> {
+    stretch_with(font, layer_id, &StretchOptions::new(stretch)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+
+    fn font() -> Font {
+        let source = r#"{
+classes = ({name = Marks; code = "dot";});
+fontMaster = ({id = m; weightValue = 400; xHeight = 500;});
+glyphs = (
+{glyphname = o; layers = ({layerId = m; width = 500; paths = (
+{closed = 1; nodes = ("100 0 LINE", "400 0 LINE", "400 500 LINE", "100 500 LINE");},
+{closed = 1; nodes = ("200 100 LINE", "200 400 LINE", "300 400 LINE", "300 100 LINE");}
+);});},
+{glyphname = o.alt; layers = ({layerId = m; width = 500; paths = (
+{closed = 1; nodes = ("100 0 LINE", "400 0 LINE", "400 500 LINE", "100 500 LINE");}
+);});},
+{glyphname = dot; layers = ({layerId = m; width = 500; paths = (
+{closed = 1; nodes = ("200 0 LINE", "300 0 LINE", "300 100 LINE", "200 100 LINE");}
+);});}
+);
+}"#;
+        Font::from_plist(Plist::parse(source).unwrap())
+    }
+
+    fn layer<'a>(font: &'a Font, glyphname: &str) -> &'a Layer {
+        font.get_glyph(glyphname).unwrap().get_layer("m").unwrap()
+    }
+
+    fn xs(layer: &Layer) -> Vec<Vec<f64>> {
+        let paths = layer.paths.as_ref().unwrap();
+        paths
+            .iter()
+            .map(|p| p.nodes.iter().map(|n| n.pt.x).collect())
+            .collect()
+    }
+
+    #[test]
+    fn compensation_keeps_vertical_stems() {
+        let mut plain = font();
+        stretch_with(&mut plain, "m", &StretchOptions::new(1.5)).unwrap();
+        assert_eq!(
+            xs(layer(&plain, "o")),
+            [
+                vec![150.0, 600.0, 600.0, 150.0],
+                vec![300.0, 300.0, 450.0, 450.0]
+            ]
+        );
+
+        let mut font = font();
+        let mut options = StretchOptions::new(1.5);
+        options.stem = Some(StemSource::Fixed(100.0));
+        assert!(stretch_with(&mut font, "m", &options).unwrap().is_empty());
+        // Both stems are 100 units again, and the counter takes up the rest.
+        let o = layer(&font, "o");
+        assert_eq!(
+            xs(o),
+            [
+                vec![175.0, 575.0, 575.0, 175.0],
+                vec![275.0, 275.0, 475.0, 475.0]
+            ]
+        );
+        assert_eq!(o.width, 750.0);
+        let paths = o.paths.as_ref().unwrap();
+        let ys: Vec<f64> = paths[0].nodes.iter().map(|n| n.pt.y).collect();
+        assert_eq!(ys, [0.0, 0.0, 500.0, 500.0]);
+
+        // The measured stem gives the same result.
+        let mut measured = self::font();
+        options.stem = Some(StemSource::Measure("o".to_string()));
+        stretch_with(&mut measured, "m", &options).unwrap();
+        assert_eq!(xs(layer(&measured, "o")), xs(o));
+    }
+
+    #[test]
+    fn excluded_glyphs_are_left_alone() {
+        let original = font();
+        let mut options = StretchOptions::new(1.5);
+        options.exclude = vec!["o.*".to_string(), "@Marks".to_string()];
+        let mut font = original.clone();
+        stretch_with(&mut font, "m", &options).unwrap();
+        assert_eq!(layer(&font, "o").width, 750.0);
+        for glyphname in &["o.alt", "dot"] {
+            assert_eq!(layer(&font, glyphname).width, 500.0);
+            assert_eq!(xs(layer(&font, glyphname)), xs(layer(&original, glyphname)));
+        }
+
+        options.recenter_excluded = true;
+        let mut font = original.clone();
+        stretch_with(&mut font, "m", &options).unwrap();
+        let dot = layer(&font, "dot");
+        assert_eq!(dot.width, 750.0);
+        assert_eq!(xs(dot), [vec![325.0, 425.0, 425.0, 325.0]]);
     }
 }
@@ -0,0 +1,620 @@
+//! Expansion of centerline strokes into filled outlines.
+//!
+//! The output structure depends only on the structure of the centerline and
+//! the style, not on the exact coordinates, so strokes generated for different
+//! masters from compatible centerlines remain interpolation-compatible.
+
+use kurbo::{BezPath, CubicBez, ParamCurve, ParamCurveArea, ParamCurveDeriv, PathEl, Point, Vec2};
+
+use crate::font::{NodeType, Path};
+
+/// Bézier point distance for circular arcs.
+pub const KAPPA: f64 = 4.0 * (std::f64::consts::SQRT_2 - 1.0) / 3.0;
+
+/// Treatment of the ends of open strokes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cap {
+    Butt,
+    Square,
+    Round,
+}
+
+/// Treatment of the outside of corners between segments.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Join {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Clone, Debug)]
+pub struct StrokeStyle {
+    pub width: f64,
+    pub cap: Cap,
+    pub join: Join,
+    /// Ratio of miter length to stroke width past which a miter join falls
+    /// back to a bevel.
+    pub miter_limit: f64,
+    /// Number of pieces each curve segment is split into before offsetting.
+    pub subdivisions: usize,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f64) -> StrokeStyle {
+        StrokeStyle {
+            width,
+            cap: Cap::Butt,
+            join: Join::Miter,
+            miter_limit: 4.0,
+            subdivisions: 1,
+        }
+    }
+}
+
+/// A segment of the centerline, or of the resulting outline.
+#[derive(Clone, Copy, Debug)]
+enum Seg {
+    Line(Point, Point),
+    Cubic(CubicBez),
+}
+
+impl Seg {
+    fn start(&self) -> Point {
+        match self {
+            Seg::Line(p0, _) => *p0,
+            Seg::Cubic(c) => c.p0,
+        }
+    }
+
+    fn end(&self) -> Point {
+        match self {
+            Seg::Line(_, p1) => *p1,
+            Seg::Cubic(c) => c.p3,
+        }
+    }
+
+    fn start_tangent(&self) -> Vec2 {
+        match self {
+            Seg::Line(p0, p1) => (*p1 - *p0).normalize(),
+            Seg::Cubic(c) => cubic_tangent(c, 0.0),
+        }
+    }
+
+    fn end_tangent(&self) -> Vec2 {
+        match self {
+            Seg::Line(p0, p1) => (*p1 - *p0).normalize(),
+            Seg::Cubic(c) => cubic_tangent(c, 1.0),
+        }
+    }
+
+    fn reverse(&self) -> Seg {
+        match self {
+            Seg::Line(p0, p1) => Seg::Line(*p1, *p0),
+            Seg::Cubic(c) => Seg::Cubic(CubicBez::new(c.p3, c.p2, c.p1, c.p0)),
+        }
+    }
+
+    fn signed_area(&self) -> f64 {
+        match self {
+            Seg::Line(p0, p1) => 0.5 * (p0.x * p1.y - p1.x * p0.y),
+            Seg::Cubic(c) => c.signed_area(),
+        }
+    }
+
+    fn is_degenerate(&self) -> bool {
+        match self {
+            Seg::Line(p0, p1) => p0 == p1,
+            Seg::Cubic(c) => c.p0 == c.p1 && c.p0 == c.p2 && c.p0 == c.p3,
+        }
+    }
+}
+
+/// Unit tangent of a cubic, robust to coincident control points.
+fn cubic_tangent(c: &CubicBez, t: f64) -> Vec2 {
+    let d = c.deriv().eval(t).to_vec2();
+    if d.hypot2() > 1e-12 {
+        return d.normalize();
+    }
+    let d = if t < 0.5 { c.p2 - c.p0 } else { c.p3 - c.p1 };
+    if d.hypot2() > 1e-12 {
+        d.normalize()
+    } else {
+        (c.p3 - c.p0).normalize()
+    }
+}
+
+/// The normal pointing to the left of the direction of travel.
+fn left_normal(tangent: Vec2) -> Vec2 {
+    Vec2::new(-tangent.y, tangent.x)
+}
+
+/// Offset a cubic by `d` to the left, fitting the midpoint exactly.
+fn offset_cubic(c: &CubicBez, d: f64) -> CubicBez {
+    let t0 = cubic_tangent(c, 0.0);
+    let t1 = cubic_tangent(c, 1.0);
+    let p0 = c.p0 + left_normal(t0) * d;
+    let p3 = c.p3 + left_normal(t1) * d;
+    let q = c.eval(0.5) + left_normal(cubic_tangent(c, 0.5)) * d;
+    // Solve 3a * t0 - 3b * t1 = 8q - 4p0 - 4p3 for the handle lengths.
+    let rhs = (q.to_vec2() * 8.0 - p0.to_vec2() * 4.0 - p3.to_vec2() * 4.0) / 3.0;
+    let det = t0.cross(-t1);
+    let (a, b) = if det.abs() > 1e-6 {
+        (rhs.cross(-t1) / det, t0.cross(rhs) / det)
+    } else {
+        // Parallel end tangents; scale the original handles.
+        let len = (c.p3 - c.p0).hypot();
+        let scale = if len > 0.0 {
+            (p3 - p0).hypot() / len
+        } else {
+            1.0
+        };
+        ((c.p1 - c.p0).hypot() * scale, (c.p3 - c.p2).hypot() * scale)
+    };
+    CubicBez::new(p0, p0 + t0 * a, p3 - t1 * b, p3)
+}
+
+fn offset_seg(seg: &Seg, d: f64, subdivisions: usize, out: &mut Vec<Seg>) {
+    match seg {
+        Seg::Line(p0, p1) => {
+            let n = left_normal(seg.start_tangent()) * d;
+            out.push(Seg::Line(*p0 + n, *p1 + n));
+        }
+        Seg::Cubic(c) => {
+            let k = subdivisions.max(1);
+            for i in 0..k {
+                let piece = c.subsegment(i as f64 / k as f64..(i + 1) as f64 / k as f64);
+                out.push(Seg::Cubic(offset_cubic(&piece, d)));
+            }
+        }
+    }
+}
+
+/// Circular arc from `a` to `b` around `center`, in pieces of at most a
+/// quarter turn.
+fn arc(center: Point, a: Point, b: Point, clockwise: bool, out: &mut Vec<Seg>) {
+    let r = (a - center).hypot();
+    let th0 = (a - center).atan2();
+    let mut sweep = (b - center).atan2() - th0;
+    if clockwise {
+        while sweep > 0.0 {
+            sweep -= 2.0 * std::f64::consts::PI;
+        }
+    } else {
+        while sweep < 0.0 {
+            sweep += 2.0 * std::f64::consts::PI;
+        }
+    }
+    let n = (sweep.abs() / std::f64::consts::FRAC_PI_2 - 1e-9)
+        .ceil()
+        .max(1.0) as usize;
+    let step = sweep / n as f64;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let mut p = a;
+    for i in 0..n {
+        let th = th0 + step * (i + 1) as f64;
+        let q = if i == n - 1 {
+            b
+        } else {
+            center + Vec2::from_angle(th) * r
+        };
+        let tp = left_normal(p - center) * k;
+        let tq = left_normal(q - center) * k;
+        out.push(Seg::Cubic(CubicBez::new(p, p + tp, q - tq, q)));
+        p = q;
+    }
+}
+
+fn line_intersection(a0: Point, a1: Point, b0: Point, b1: Point) -> Option<(f64, f64)> {
+    let da = a1 - a0;
+    let db = b1 - b0;
+    let det = da.cross(db);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let w = b0 - a0;
+    Some((w.cross(db) / det, w.cross(da) / det))
+}
+
+/// Add the join between the end of `out` and `next`, where both are offset by
+/// `d` from the corner `p`. The start of `next` may be trimmed.
+fn join(
+    out: &mut Vec<Seg>,
+    next: &mut Seg,
+    p: Point,
+    t_in: Vec2,
+    t_out: Vec2,
+    d: f64,
+    style: &StrokeStyle,
+) {
+    let a = out.last().unwrap().end();
+    let b = next.start();
+    let cross = t_in.cross(t_out);
+    let dot = t_in.dot(t_out);
+    if cross.abs() < 1e-9 && dot > 0.0 {
+        if a != b {
+            out.push(Seg::Line(a, b));
+        }
+        return;
+    }
+    let outer = cross * d < 0.0;
+    if !outer {
+        if let (Some(Seg::Line(a0, _)), Seg::Line(_, b1)) = (out.last().copied(), *next) {
+            if let Some((ta, tb)) = line_intersection(a0, a, b, b1) {
+                if ta > 0.0 && ta <= 1.0 && (0.0..1.0).contains(&tb) {
+                    let x = a0.lerp(a, ta);
+                    *out.last_mut().unwrap() = Seg::Line(a0, x);
+                    *next = Seg::Line(x, b1);
+                    return;
+                }
+            }
+        }
+        // Going through the center point keeps the covered area correct
+        // under the nonzero rule.
+        out.push(Seg::Line(a, p));
+        out.push(Seg::Line(p, b));
+        return;
+    }
+    match style.join {
+        Join::Bevel => out.push(Seg::Line(a, b)),
+        Join::Round => arc(p, a, b, d > 0.0, out),
+        Join::Miter => {
+            let n_in = left_normal(t_in);
+            let n_out = left_normal(t_out);
+            let denom = 1.0 + n_in.dot(n_out);
+            let m = p + (n_in + n_out) * (d / denom);
+            if denom > 1e-9 && (m - p).hypot() <= style.miter_limit * d.abs() {
+                // Extend adjacent lines to the miter point rather than adding
+                // collinear nodes.
+                match out.last_mut() {
+                    Some(Seg::Line(_, p1)) => *p1 = m,
+                    _ => out.push(Seg::Line(a, m)),
+                }
+                match next {
+                    Seg::Line(p0, _) => *p0 = m,
+                    _ => out.push(Seg::Line(m, b)),
+                }
+            } else {
+                out.push(Seg::Line(a, b));
+            }
+        }
+    }
+}
+
+/// Offset one side of a subpath, to the left by `d`.
+fn offset_side(segs: &[Seg], closed: bool, d: f64, style: &StrokeStyle) -> Vec<Seg> {
+    let mut out = Vec::new();
+    for (i, seg) in segs.iter().enumerate() {
+        let mut offset = Vec::new();
+        offset_seg(seg, d, style.subdivisions, &mut offset);
+        if i > 0 {
+            let prev = &segs[i - 1];
+            join(
+                &mut out,
+                &mut offset[0],
+                seg.start(),
+                prev.end_tangent(),
+                seg.start_tangent(),
+                d,
+                style,
+            );
+        }
+        out.extend(offset);
+    }
+    if closed && !segs.is_empty() {
+        let last = &segs[segs.len() - 1];
+        let mut first = out[0];
+        join(
+            &mut out,
+            &mut first,
+            segs[0].start(),
+            last.end_tangent(),
+            segs[0].start_tangent(),
+            d,
+            style,
+        );
+        out[0] = first;
+    }
+    out
+}
+
+/// Add the cap at the end of a stroke, from the left side at `a` to the
+/// right side at `b`, where `t` is the direction of travel at the end.
+fn cap(out: &mut Vec<Seg>, p: Point, a: Point, b: Point, t: Vec2, style: &StrokeStyle) {
+    match style.cap {
+        Cap::Butt => out.push(Seg::Line(a, b)),
+        Cap::Square => {
+            let ext = t * (0.5 * style.width);
+            out.push(Seg::Line(a, a + ext));
+            out.push(Seg::Line(a + ext, b + ext));
+            out.push(Seg::Line(b + ext, b));
+        }
+        Cap::Round => arc(p, a, b, true, out),
+    }
+}
+
+fn signed_area(segs: &[Seg]) -> f64 {
+    segs.iter().map(Seg::signed_area).sum()
+}
+
+fn reverse_segs(segs: &[Seg]) -> Vec<Seg> {
+    segs.iter().rev().map(Seg::reverse).collect()
+}
+
+/// Convert a closed loop of segments into a Glyphs path, marking nodes
+/// smooth where the tangents are continuous.
+fn segs_to_path(segs: &[Seg]) -> Path {
+    let segs: Vec<Seg> = segs
+        .iter()
+        .filter(|seg| !seg.is_degenerate())
+        .copied()
+        .collect();
+    let mut path = Path::new(true);
+    let n = segs.len();
+    for (i, seg) in segs.iter().enumerate() {
+        let next = &segs[(i + 1) % n];
+        let t_in = seg.end_tangent();
+        let t_out = next.start_tangent();
+        let has_curve = matches!(seg, Seg::Cubic(_)) || matches!(next, Seg::Cubic(_));
+        let smooth = has_curve && t_in.cross(t_out).abs() < 1e-3 && t_in.dot(t_out) > 0.0;
+        match seg {
+            Seg::Line(_, p1) => {
+                let nt = if smooth {
+                    NodeType::LineSmooth
+                } else {
+                    NodeType::Line
+                };
+                path.add(*p1, nt);
+            }
+            Seg::Cubic(c) => {
+                path.add(c.p1, NodeType::OffCurve);
+                path.add(c.p2, NodeType::OffCurve);
+                let nt = if smooth {
+                    NodeType::CurveSmooth
+                } else {
+                    NodeType::Curve
+                };
+                path.add(c.p3, nt);
+            }
+        }
+    }
+    path
+}
+
+/// Split a kurbo path into subpaths of segments, with a closed flag.
+fn subpaths(bez: &BezPath) -> Vec<(Vec<Seg>, bool)> {
+    let mut result = Vec::new();
+    let mut segs = Vec::new();
+    let mut start = Point::ORIGIN;
+    let mut last = Point::ORIGIN;
+    for el in bez.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                if !segs.is_empty() {
+                    result.push((std::mem::take(&mut segs), false));
+                }
+                start = p;
+                last = p;
+            }
+            PathEl::LineTo(p) => {
+                segs.push(Seg::Line(last, p));
+                last = p;
+            }
+            PathEl::QuadTo(p1, p2) => {
+                let q = kurbo::QuadBez::new(last, p1, p2);
+                segs.push(Seg::Cubic(q.raise()));
+                last = p2;
+            }
+            PathEl::CurveTo(p1, p2, p3) => {
+                segs.push(Seg::Cubic(CubicBez::new(last, p1, p2, p3)));
+                last = p3;
+            }
+            PathEl::ClosePath => {
+                if last != start {
+                    segs.push(Seg::Line(last, start));
+                }
+                last = start;
+                if !segs.is_empty() {
+                    result.push((std::mem::take(&mut segs), true));
+                }
+            }
+        }
+    }
+    if !segs.is_empty() {
+        result.push((segs, false));
+    }
+    for (segs, _) in &mut result {
+        segs.retain(|seg| !seg.is_degenerate());
+    }
+    result.retain(|(segs, _)| !segs.is_empty());
+    result
+}
+
+/// Expand a centerline into closed outlines.
+///
+/// Each open subpath results in one contour; each closed subpath results in an
+/// outer and an inner contour. Outer contours are counterclockwise and inner
+/// ones clockwise, matching Glyphs' "correct path direction". Contours may
+/// overlap themselves at sharp inner corners of curves, so the result should
+/// be treated with the nonzero fill rule.
+pub fn stroke(bez: &BezPath, style: &StrokeStyle) -> Vec<Path> {
+    let hw = 0.5 * style.width;
+    let mut result = Vec::new();
+    for (segs, closed) in subpaths(bez) {
+        let rev = reverse_segs(&segs);
+        if closed {
+            let left = offset_side(&segs, true, hw, style);
+            let right = offset_side(&rev, true, hw, style);
+            let (mut outer, mut inner) = if signed_area(&left).abs() >= signed_area(&right).abs() {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            if signed_area(&outer) < 0.0 {
+                outer = reverse_segs(&outer);
+            }
+            if signed_area(&inner) > 0.0 {
+                inner = reverse_segs(&inner);
+            }
+            result.push(segs_to_path(&outer));
+            result.push(segs_to_path(&inner));
+        } else {
+            let mut contour = offset_side(&segs, false, hw, style);
+            let right = offset_side(&rev, false, hw, style);
+            let last = &segs[segs.len() - 1];
+            let a = contour.last().unwrap().end();
+            cap(
+                &mut contour,
+                last.end(),
+                a,
+                right[0].start(),
+                last.end_tangent(),
+                style,
+            );
+            contour.extend(right);
+            let a = contour.last().unwrap().end();
+            let b = contour[0].start();
+            cap(
+                &mut contour,
+                segs[0].start(),
+                a,
+                b,
+                -segs[0].start_tangent(),
+                style,
+            );
+            if signed_area(&contour) < 0.0 {
+                contour = reverse_segs(&contour);
+            }
+            result.push(segs_to_path(&contour));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(points: &[(f64, f64)]) -> BezPath {
+        let mut bez = BezPath::new();
+        bez.move_to(points[0]);
+        for &p in &points[1..] {
+            bez.line_to(p);
+        }
+        bez
+    }
+
+    fn style(cap: Cap, join: Join) -> StrokeStyle {
+        StrokeStyle {
+            cap,
+            join,
+            ..StrokeStyle::new(20.0)
+        }
+    }
+
+    fn on_curve(path: &Path) -> Vec<(f64, f64)> {
+        let nodes = path
+            .nodes
+            .iter()
+            .filter(|n| n.node_type != NodeType::OffCurve);
+        nodes.map(|n| (n.pt.x.round(), n.pt.y.round())).collect()
+    }
+
+    fn bounds(path: &Path) -> (f64, f64, f64, f64) {
+        let (xs, ys): (Vec<f64>, Vec<f64>) = path.nodes.iter().map(|n| (n.pt.x, n.pt.y)).unzip();
+        let min = |v: &[f64]| v.iter().cloned().fold(f64::INFINITY, f64::min).round();
+        let max = |v: &[f64]| v.iter().cloned().fold(f64::NEG_INFINITY, f64::max).round();
+        (min(&xs), min(&ys), max(&xs), max(&ys))
+    }
+
+    fn area(path: &Path) -> f64 {
+        let points: Vec<Point> = path.nodes.iter().map(|n| n.pt).collect();
+        let n = points.len();
+        (0..n)
+            .map(|i| points[i].to_vec2().cross(points[(i + 1) % n].to_vec2()))
+            .sum::<f64>()
+            * 0.5
+    }
+
+    #[test]
+    fn caps() {
+        let bez = line(&[(0.0, 0.0), (100.0, 0.0)]);
+        let butt = stroke(&bez, &style(Cap::Butt, Join::Miter));
+        assert_eq!(butt.len(), 1);
+        assert_eq!(butt[0].nodes.len(), 4);
+        assert_eq!(bounds(&butt[0]), (0.0, -10.0, 100.0, 10.0));
+        assert!(area(&butt[0]) > 0.0, "counterclockwise");
+
+        let square = stroke(&bez, &style(Cap::Square, Join::Miter));
+        assert_eq!(bounds(&square[0]), (-10.0, -10.0, 110.0, 10.0));
+        assert!(square[0]
+            .nodes
+            .iter()
+            .all(|n| n.node_type == NodeType::Line));
+
+        let round = stroke(&bez, &style(Cap::Round, Join::Miter));
+        assert_eq!(bounds(&round[0]), (-10.0, -10.0, 110.0, 10.0));
+        let curves = round[0]
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == NodeType::CurveSmooth);
+        assert_eq!(curves.count(), 4, "two quarter circles at each end");
+        let ends = on_curve(&round[0]);
+        assert!(ends.contains(&(110.0, 0.0)) && ends.contains(&(-10.0, 0.0)));
+    }
+
+    #[test]
+    fn joins() {
+        // A right angle turning left, so the outside of the corner is at
+        // the bottom right.
+        let bez = line(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)]);
+        let miter = on_curve(&stroke(&bez, &style(Cap::Butt, Join::Miter))[0]);
+        assert!(miter.contains(&(110.0, -10.0)));
+        assert_eq!(miter.len(), 6);
+
+        let bevel = on_curve(&stroke(&bez, &style(Cap::Butt, Join::Bevel))[0]);
+        assert!(bevel.contains(&(100.0, -10.0)) && bevel.contains(&(110.0, 0.0)));
+        assert!(!bevel.contains(&(110.0, -10.0)));
+
+        let round = stroke(&bez, &style(Cap::Butt, Join::Round));
+        let corner = Point::new(100.0, 0.0);
+        let outside = round[0]
+            .nodes
+            .iter()
+            .filter(|n| n.pt.x > 100.0 && n.pt.y < 0.0);
+        for node in outside.filter(|n| n.node_type != NodeType::OffCurve) {
+            assert!(((node.pt - corner).hypot() - 10.0).abs() < 1e-9);
+        }
+        assert!(round[0]
+            .nodes
+            .iter()
+            .any(|n| n.node_type == NodeType::OffCurve));
+
+        // The inside of the corner is cut where the two sides cross.
+        assert!(miter.contains(&(90.0, 10.0)));
+    }
+
+    #[test]
+    fn sharp_miters_fall_back_to_bevels() {
+        let bez = line(&[(0.0, 0.0), (100.0, 0.0), (0.0, 10.0)]);
+        let path = &stroke(&bez, &style(Cap::Butt, Join::Miter))[0];
+        let (_, _, x_max, _) = bounds(path);
+        assert!(x_max <= 110.0);
+        let unlimited = StrokeStyle {
+            miter_limit: 100.0,
+            ..style(Cap::Butt, Join::Miter)
+        };
+        let (_, _, x_max, _) = bounds(&stroke(&bez, &unlimited)[0]);
+        assert!(x_max > 150.0);
+    }
+
+    #[test]
+    fn closed_centerlines_give_an_outer_and_an_inner_contour() {
+        let mut bez = line(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)]);
+        bez.close_path();
+        let paths = stroke(&bez, &style(Cap::Butt, Join::Miter));
+        assert_eq!(paths.len(), 2);
+        assert_eq!(bounds(&paths[0]), (-10.0, -10.0, 110.0, 110.0));
+        assert_eq!(bounds(&paths[1]), (10.0, 10.0, 90.0, 90.0));
+        assert!(area(&paths[0]) > 0.0 && area(&paths[1]) < 0.0);
+    }
+}
@@ -0,0 +1,699 @@
+//! Subsetting a font to a character set.
+//!
+//! The glyphs kept are those with the listed code points and names, and
+//! everything they need: components, corner components, and the glyphs that
+//! substitutions in the feature code can produce from them. The feature
+//! code, classes and kerning are then pruned of the removed glyphs. Feature
+//! code is edited as text, so rules that don't mention removed glyphs keep
+//! their formatting.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::fea::{is_name_char, parse_features, update_automatic_classes};
+use crate::fea::{Block, Statement, SubAction, Substitution};
+use crate::font::Font;
+use crate::kerning::{KernSide, Kerning, KerningGroups};
+use crate::nam::NameList;
+use crate::plist::Plist;
+use crate::variant::remove_feature;
+
+/// What subsetting did.
+#[derive(Clone, Debug, Default)]
+pub struct SubsetReport {
+    /// The glyphs removed from the font.
+    pub removed: Vec<String>,
+    /// Code points in the list that no glyph has.
+    pub missing_codepoints: Vec<u32>,
+    /// Glyph names in the list that the font doesn't have.
+    pub missing_glyphs: Vec<String>,
+}
+
+/// Reduce the font to the glyphs of a namelist, with what they need. The
+/// feature code has to be valid, to find the glyphs substitutions produce.
+pub fn subset(font: &mut Font, list: &NameList) -> Result<SubsetReport, String> {
+    let fea = parse_features(font).map_err(|errors| errors.join("\n"))?;
+    let mut report = SubsetReport::default();
+    let mut keep: HashSet<String> = HashSet::new();
+    if font.get_glyph(".notdef").is_some() {
+        keep.insert(".notdef".to_string());
+    }
+    for &cp in &list.codepoints {
+        match font.glyphs.iter().find(|g| g.unicodes().contains(&cp)) {
+            Some(glyph) => keep.insert(glyph.glyphname.clone()),
+            None => {
+                report.missing_codepoints.push(cp);
+                continue;
+            }
+        };
+    }
+    for name in &list.glyph_names {
+        if font.get_glyph(name).is_some() {
+            keep.insert(name.clone());
+        } else {
+            report.missing_glyphs.push(name.clone());
+        }
+    }
+    let mut substitutions = Vec::new();
+    for block in &fea.blocks {
+        let statements = match block {
+            Block::Lookup(lookup) => &lookup.statements,
+            Block::Feature(feature) => &feature.statements,
+        };
+        collect_substitutions(statements, &mut substitutions);
+    }
+    loop {
+        let count = keep.len();
+        add_components(font, &mut keep);
+        add_substituted(&substitutions, &mut keep);
+        if keep.len() == count {
+            break;
+        }
+    }
+
+    report.removed = font
+        .glyphs
+        .iter()
+        .filter(|g| !keep.contains(&g.glyphname))
+        .map(|g| g.glyphname.clone())
+        .collect();
+    font.glyphs.retain(|g| keep.contains(&g.glyphname));
+    let removed: HashSet<String> = report.removed.iter().cloned().collect();
+    prune_backgrounds(font, &removed);
+    prune_classes_and_features(font, &removed);
+    prune_kerning(font);
+    Ok(report)
+}
+
+fn collect_substitutions<'a>(statements: &'a [Statement], result: &mut Vec<&'a Substitution>) {
+    for statement in statements {
+        match statement {
+            Statement::Substitute(sub) => result.push(sub),
+            Statement::Lookup(lookup) => collect_substitutions(&lookup.statements, result),
+            _ => (),
+        }
+    }
+}
+
+/// Add the components and corner components of the kept glyphs.
+fn add_components(font: &Font, keep: &mut HashSet<String>) {
+    let mut added = Vec::new();
+    for glyph in font.glyphs.iter().filter(|g| keep.contains(&g.glyphname)) {
+        for layer in &glyph.layers {
+            for component in layer.components.iter().flatten() {
+                added.push(component.name.clone());
+            }
+            let hints = layer.other_stuff.get("hints").and_then(Plist::as_array);
+            for hint in hints.into_iter().flatten() {
+                if let Some(name) = hint.get("name").and_then(Plist::as_str) {
+                    added.push(name.to_string());
+                }
+            }
+        }
+    }
+    keep.extend(added);
+}
+
+/// Add the glyphs that substitutions can produce from the kept glyphs.
+/// A rule applies if every position of its context and input can match a
+/// kept glyph.
+fn add_substituted(substitutions: &[&Substitution], keep: &mut HashSet<String>) {
+    let mut added = Vec::new();
+    for sub in substitutions {
+        let mut positions = sub.backtrack.iter().chain(&sub.input).chain(&sub.lookahead);
+        if !positions.all(|set| set.iter().any(|g| keep.contains(g))) {
+            continue;
+        }
+        match &sub.action {
+            SubAction::Single(output) if sub.input.len() == 1 => {
+                for (i, glyph) in sub.input[0].iter().enumerate() {
+                    if keep.contains(glyph) {
+                        added.push(output[if output.len() == 1 { 0 } else { i }].clone());
+                    }
+                }
+            }
+            SubAction::Multiple(output) | SubAction::Alternate(output) => {
+                added.extend(output.iter().cloned())
+            }
+            SubAction::Ligature(ligature) => added.push(ligature.clone()),
+            _ => (),
+        }
+    }
+    keep.extend(added);
+}
+
+/// Drop components of removed glyphs from the layer backgrounds.
+fn prune_backgrounds(font: &mut Font, removed: &HashSet<String>) {
+    for glyph in &mut font.glyphs {
+        for layer in &mut glyph.layers {
+            let background = layer.other_stuff.get_mut("background");
+            if let Some(Plist::Dictionary(background)) = background {
+                if let Some(Plist::Array(components)) = background.get_mut("components") {
+                    components.retain(|c| {
+                        let name = c.get("name").and_then(Plist::as_str).unwrap_or("");
+                        !removed.contains(name)
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn prune_classes_and_features(font: &mut Font, removed: &HashSet<String>) {
+    update_automatic_classes(font);
+    let mut pruning = Pruning::new(font, removed);
+    if let Some(classes) = &mut font.classes {
+        for class in classes.iter_mut() {
+            let dropped = &pruning.dropped[&class.name];
+            let names: Vec<&str> = class
+                .glyph_names()
+                .enumerate()
+                .filter(|(i, _)| !dropped.contains(i))
+                .map(|(_, name)| name)
+                .collect();
+            class.code = names.join(" ");
+        }
+        classes.retain(|class| {
+            let empty = class.code.is_empty();
+            if empty {
+                pruning.removed_classes.insert(class.name.clone());
+            }
+            !empty
+        });
+    }
+    let code = font.feature_prefixes.iter_mut().chain(&mut font.features);
+    for feature in code.flatten() {
+        feature.code = pruning.prune_code(&feature.code);
+    }
+    let empty: Vec<String> = font
+        .features
+        .iter()
+        .flatten()
+        .filter(|f| !has_rules(&f.code))
+        .map(|f| f.name.clone())
+        .collect();
+    for tag in empty {
+        remove_feature(font, &tag);
+    }
+}
+
+/// Drop kerning pairs with removed glyphs, or groups left without glyphs.
+fn prune_kerning(font: &mut Font) {
+    let groups = KerningGroups::from_font(font);
+    let names: HashSet<&str> = font.glyphs.iter().map(|g| g.glyphname.as_str()).collect();
+    let exists = |side: &KernSide, members: &HashMap<String, String>| match side {
+        KernSide::Glyph(name) => names.contains(name.as_str()),
+        KernSide::Group(group) => members.values().any(|g| g == group),
+    };
+    let master_ids: Vec<String> = font
+        .other_stuff
+        .get("kerning")
+        .and_then(Plist::as_dict)
+        .map(|k| k.keys().cloned().collect())
+        .unwrap_or_default();
+    let mut pruned = Vec::new();
+    for master_id in master_ids {
+        let mut kerning = Kerning::from_font(font, &master_id);
+        let count = kerning.pairs.len();
+        kerning
+            .pairs
+            .retain(|(left, right), _| exists(left, &groups.left) && exists(right, &groups.right));
+        if kerning.pairs.len() != count {
+            pruned.push((master_id, kerning));
+        }
+    }
+    for (master_id, kerning) in pruned {
+        kerning.write_to(font, &master_id);
+    }
+}
+
+/// A token of a statement in feature code.
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    Name(String),
+    Class(String),
+    Punct(char),
+    Str(String),
+}
+
+const RULE_KEYWORDS: &[&str] = &[
+    "sub",
+    "substitute",
+    "rsub",
+    "reversesub",
+    "pos",
+    "position",
+    "enum",
+    "ignore",
+];
+
+/// Split code into pieces ending with `;`, `{` or `}`, outside comments and
+/// strings; the last piece may have no end.
+fn split_statements(code: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut chars = code.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                }
+            }
+            ';' | '{' | '}' => {
+                pieces.push(&code[start..=i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if start < code.len() {
+        pieces.push(&code[start..]);
+    }
+    pieces
+}
+
+/// Split a statement into its leading whitespace and comments, and its
+/// tokens.
+fn tokenize_statement(statement: &str) -> (&str, Vec<Item>) {
+    let mut body_start = 0;
+    let mut in_comment = false;
+    for (i, c) in statement.char_indices() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if c == '#' {
+            in_comment = true;
+        } else if !c.is_whitespace() {
+            body_start = i;
+            break;
+        } else {
+            body_start = i + c.len_utf8();
+        }
+    }
+    let (prefix, body) = statement.split_at(body_start);
+    let mut items = Vec::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        } else if c == '#' {
+            while chars.next().is_some_and(|c| c != '\n') {}
+        } else if c == '"' {
+            items.push(Item::Str(
+                chars.by_ref().take_while(|&c| c != '"').collect(),
+            ));
+        } else if c == '@' || is_name_char(c) {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|&&c| is_name_char(c)) {
+                word.push(c);
+                chars.next();
+            }
+            items.push(if c == '@' {
+                Item::Class(word)
+            } else {
+                Item::Name(format!("{}{}", c, word))
+            });
+        } else {
+            items.push(Item::Punct(c));
+        }
+    }
+    (prefix, items)
+}
+
+fn render(items: &[Item]) -> String {
+    let mut result = String::new();
+    for (i, item) in items.iter().enumerate() {
+        let text = match item {
+            Item::Name(name) => name.clone(),
+            Item::Class(name) => format!("@{}", name),
+            Item::Punct(c) => c.to_string(),
+            Item::Str(s) => format!("\"{}\"", s),
+        };
+        let joined = matches!(
+            (i.checked_sub(1).map(|j| &items[j]), item),
+            (None, _)
+                | (Some(Item::Punct('[' | '<' | '(')), _)
+                | (_, Item::Punct(']' | '>' | ')' | '\'' | ','))
+        );
+        if !joined {
+            result.push(' ');
+        }
+        result.push_str(&text);
+    }
+    result
+}
+
+/// What to do with a statement.
+enum Pruned {
+    Keep,
+    Drop,
+    Replace(String),
+}
+
+/// Removing glyphs from feature code.
+struct Pruning<'a> {
+    removed: &'a HashSet<String>,
+    /// Classes that were left empty, and removed.
+    removed_classes: HashSet<String>,
+    /// Lookups that were left without rules, and removed.
+    removed_lookups: HashSet<String>,
+    /// The members to drop from each class defined as a list of glyphs, by
+    /// index: the removed glyphs, and those paired with a dropped member by
+    /// a substitution from one class to the other.
+    dropped: HashMap<String, HashSet<usize>>,
+}
+
+impl<'a> Pruning<'a> {
+    fn new(font: &Font, removed: &'a HashSet<String>) -> Pruning<'a> {
+        let mut members: HashMap<String, Vec<String>> = HashMap::new();
+        for class in font.classes.iter().flatten() {
+            let names = class.glyph_names().map(str::to_string).collect();
+            members.insert(class.name.clone(), names);
+        }
+        let mut pairs = Vec::new();
+        let code = font.feature_prefixes.iter().chain(&font.features);
+        for feature in code.flatten() {
+            for piece in split_statements(&feature.code) {
+                let (_, items) = tokenize_statement(piece.trim_end_matches(';'));
+                if let Some((name, names)) = class_definition(&items) {
+                    members.insert(name, names);
+                } else {
+                    pairs.extend(class_pair(&items));
+                }
+            }
+        }
+        let mut dropped: HashMap<String, HashSet<usize>> = members
+            .iter()
+            .map(|(name, names)| {
+                let indices = names
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, n)| removed.contains(*n));
+                (name.clone(), indices.map(|(i, _)| i).collect())
+            })
+            .collect();
+        loop {
+            let mut changed = false;
+            for (input, output) in &pairs {
+                match (members.get(input), members.get(output)) {
+                    (Some(a), Some(b)) if a.len() == b.len() => (),
+                    _ => continue,
+                }
+                let both: HashSet<usize> =
+                    dropped[input].union(&dropped[output]).copied().collect();
+                changed |=
+                    both.len() != dropped[input].len() || both.len() != dropped[output].len();
+                dropped.insert(input.clone(), both.clone());
+                dropped.insert(output.clone(), both);
+            }
+            if !changed {
+                break;
+            }
+        }
+        Pruning {
+            removed,
+            removed_classes: HashSet::new(),
+            removed_lookups: HashSet::new(),
+            dropped,
+        }
+    }
+
+    fn is_removed(&self, item: &Item) -> bool {
+        match item {
+            Item::Name(name) => self.removed.contains(name.trim_start_matches('\\')),
+            Item::Class(name) => self.removed_classes.contains(name),
+            _ => false,
+        }
+    }
+
+    /// Feature code without the removed glyphs and classes. Removed glyphs
+    /// are taken out of glyph classes, keeping the two classes of a single
+    /// substitution paired. Rules are dropped if they would be left with
+    /// nothing to match, as are class definitions left empty.
+    fn prune_code(&mut self, code: &str) -> String {
+        let mut result = String::with_capacity(code.len());
+        // The lookup block being pruned: its name, where it starts in the
+        // result, and whether it still has rules.
+        let mut lookup: Option<(String, usize, bool)> = None;
+        // A lookup left empty, to remove once its block ends.
+        let mut empty_lookup = None;
+        for piece in split_statements(code) {
+            if let Some((name, start)) = empty_lookup.take() {
+                result.truncate(start);
+                self.removed_lookups.insert(name);
+                continue;
+            }
+            let statement = match piece.strip_suffix(';') {
+                Some(statement) => statement,
+                None => {
+                    let (_, items) = tokenize_statement(piece);
+                    match items.as_slice() {
+                        [Item::Name(keyword), Item::Name(name), .., Item::Punct('{')]
+                            if keyword == "lookup" =>
+                        {
+                            lookup = Some((name.clone(), result.len(), false));
+                        }
+                        [Item::Punct('}')] => {
+                            if let Some((name, start, false)) = lookup.take() {
+                                empty_lookup = Some((name, start));
+                            }
+                        }
+                        _ => (),
+                    }
+                    result.push_str(piece);
+                    continue;
+                }
+            };
+            let (prefix, items) = tokenize_statement(statement);
+            let pruned = self.prune_statement(&items);
+            if let (Some((_, _, has_rules)), Some(Item::Name(keyword))) =
+                (&mut lookup, items.first())
+            {
+                if RULE_KEYWORDS.contains(&keyword.as_str()) && !matches!(pruned, Pruned::Drop) {
+                    *has_rules = true;
+                }
+            }
+            match pruned {
+                Pruned::Keep => result.push_str(piece),
+                Pruned::Drop => (),
+                Pruned::Replace(text) => {
+                    result.push_str(prefix);
+                    result.push_str(&text);
+                    result.push(';');
+                }
+            }
+        }
+        if !code.starts_with(char::is_whitespace) {
+            result = result.trim_start().to_string();
+        }
+        result
+    }
+
+    fn prune_statement(&mut self, items: &[Item]) -> Pruned {
+        let definition = match items {
+            [Item::Class(name), Item::Punct('='), ..] => Some(name.clone()),
+            [Item::Name(keyword), ..] if RULE_KEYWORDS.contains(&keyword.as_str()) => None,
+            [Item::Name(keyword), Item::Name(name)] if keyword == "lookup" => {
+                return match self.removed_lookups.contains(name) {
+                    true => Pruned::Drop,
+                    false => Pruned::Keep,
+                };
+            }
+            _ => return Pruned::Keep,
+        };
+        let mut keep: Vec<bool> = items.iter().map(|i| !self.is_removed(i)).collect();
+        for (i, pair) in items.windows(2).enumerate() {
+            if let [Item::Name(keyword), Item::Name(name)] = pair {
+                if keyword == "lookup" && self.removed_lookups.contains(name) {
+                    keep[i + 1] = false;
+                }
+            }
+        }
+        if let Some(dropped) = definition.as_ref().and_then(|name| self.dropped.get(name)) {
+            if class_definition(items).is_some() {
+                for (i, keep) in keep[3..items.len() - 1].iter_mut().enumerate() {
+                    *keep = !dropped.contains(&i);
+                }
+            }
+        }
+        if keep.iter().all(|&k| k) {
+            return Pruned::Keep;
+        }
+        // The glyph classes written out in brackets, as ranges of items.
+        let mut brackets = Vec::new();
+        let mut open = None;
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                Item::Punct('[') => open = Some(i),
+                Item::Punct(']') => brackets.extend(open.take().map(|start| (start, i))),
+                _ => (),
+            }
+        }
+        // In `sub [a b]' by [c d]`, drop c with a, and a with c.
+        let by = items
+            .iter()
+            .position(|i| *i == Item::Name("by".to_string()));
+        if let Some(by) = by {
+            let marked =
+                |&&(_, end): &&(usize, usize)| items.get(end + 1) == Some(&Item::Punct('\''));
+            let before: Vec<_> = brackets.iter().filter(|(start, _)| *start < by).collect();
+            let any_marked = before.iter().any(marked);
+            let inputs: Vec<_> = before
+                .into_iter()
+                .filter(|b| !any_marked || marked(b))
+                .collect();
+            let outputs: Vec<_> = brackets.iter().filter(|(start, _)| *start > by).collect();
+            if let ([input], [output]) = (inputs.as_slice(), outputs.as_slice()) {
+                if input.1 - input.0 == output.1 - output.0 {
+                    for offset in 1..input.1 - input.0 {
+                        let both = keep[input.0 + offset] && keep[output.0 + offset];
+                        keep[input.0 + offset] = both;
+                        keep[output.0 + offset] = both;
+                    }
+                }
+            }
+        }
+        let in_bracket = |i: usize| brackets.iter().any(|&(start, end)| start < i && i < end);
+        let dropped_outside = (0..items.len()).any(|i| !keep[i] && !in_bracket(i));
+        let emptied = brackets
+            .iter()
+            .any(|&(start, end)| (start + 1..end).all(|i| !keep[i]));
+        if dropped_outside || emptied {
+            if let Some(name) = definition {
+                self.removed_classes.insert(name);
+            }
+            return Pruned::Drop;
+        }
+        let kept: Vec<Item> = items
+            .iter()
+            .zip(&keep)
+            .filter(|(_, &k)| k)
+            .map(|(i, _)| i.clone())
+            .collect();
+        Pruned::Replace(render(&kept))
+    }
+}
+
+/// The name and glyphs of a class defined as a list of glyphs, like
+/// `@name = [a b c]`.
+fn class_definition(items: &[Item]) -> Option<(String, Vec<String>)> {
+    match items {
+        [Item::Class(name), Item::Punct('='), Item::Punct('['), members @ .., Item::Punct(']')] => {
+            let names = members.iter().map(|item| match item {
+                Item::Name(name) => Some(name.trim_start_matches('\\').to_string()),
+                _ => None,
+            });
+            Some((name.clone(), names.collect::<Option<_>>()?))
+        }
+        _ => None,
+    }
+}
+
+/// The input and output classes of a substitution from one class to
+/// another, like `sub @a' @b by @c`.
+fn class_pair(items: &[Item]) -> Option<(String, String)> {
+    match items.first() {
+        Some(Item::Name(keyword)) if keyword == "sub" || keyword == "substitute" => (),
+        _ => return None,
+    }
+    let by = items
+        .iter()
+        .position(|i| *i == Item::Name("by".to_string()))?;
+    let marked: Vec<&Item> = (1..by)
+        .filter(|&i| items[i + 1] == Item::Punct('\''))
+        .map(|i| &items[i])
+        .collect();
+    let input = if marked.is_empty() {
+        items[1..by].iter().collect()
+    } else {
+        marked
+    };
+    match (input.as_slice(), &items[by + 1..]) {
+        ([Item::Class(input)], [Item::Class(output)]) => Some((input.clone(), output.clone())),
+        _ => None,
+    }
+}
+
+/// Whether feature code has any rules, feature references or lookups left.
+fn has_rules(code: &str) -> bool {
+    split_statements(code).iter().any(|piece| {
+        let (_, items) = tokenize_statement(piece);
+        match items.first() {
+            Some(Item::Name(keyword)) => {
+                RULE_KEYWORDS.contains(&keyword.as_str())
+                    || keyword == "feature"
+                    || keyword == "lookup"
+            }
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_plist::FromPlist;
+
+    #[test]
+    fn keeps_what_the_list_needs_and_prunes_the_rest() {
+        let source = r#"{
+fontMaster = ({id = m; weightValue = 400;});
+glyphs = (
+{glyphname = a; unicode = 0061; layers = ({layerId = m; width = 500;});},
+{glyphname = b; unicode = 0062; layers = ({layerId = m; width = 500;});},
+{glyphname = c; unicode = 0063; layers = ({layerId = m; width = 500;});},
+{glyphname = f; unicode = 0066; layers = ({layerId = m; width = 300;});},
+{glyphname = i; unicode = 0069; layers = ({layerId = m; width = 250;});},
+{glyphname = acute; layers = ({layerId = m; width = 300;});},
+{glyphname = aacute; unicode = 00E1; layers = ({layerId = m; width = 500;
+components = ({name = a;}, {name = acute;});});},
+{glyphname = f_i; layers = ({layerId = m; width = 550;});},
+{glyphname = a.sc; layers = ({layerId = m; width = 450;});},
+{glyphname = b.sc; layers = ({layerId = m; width = 450;});}
+);
+classes = ({name = Lower; code = "a b";}, {name = Small; code = "a.sc b.sc";});
+features = (
+{name = smcp; code = "sub @Lower by @Small;";},
+{name = liga; code = "sub f i by f_i;";},
+{name = ss01; code = "sub c by b;";}
+);
+kerning = {m = {a = {b = -10; f = -5;};};};
+}"#;
+        let mut font = Font::from_plist(Plist::parse(source).unwrap());
+        let list =
+            NameList::parse("0x0061 a\n0x00E1 aacute\n0x0066 f\n0x0069 i\n0x0078 x\n").unwrap();
+        let report = subset(&mut font, &list).unwrap();
+        assert_eq!(report.removed, ["b", "c", "b.sc"]);
+        assert_eq!(report.missing_codepoints, [0x78]);
+        let names: Vec<&str> = font.glyphs.iter().map(|g| g.glyphname.as_str()).collect();
+        assert_eq!(names, ["a", "f", "i", "acute", "aacute", "f_i", "a.sc"]);
+        let classes: Vec<(&str, &str)> = font
+            .classes
+            .iter()
+            .flatten()
+            .map(|c| (c.name.as_str(), c.code.as_str()))
+            .collect();
+        assert_eq!(classes, [("Lower", "a"), ("Small", "a.sc")]);
+        let features: Vec<&str> = font
+            .features
+            .iter()
+            .flatten()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(features, ["smcp", "liga"]);
+        let kerning = Kerning::from_font(&font, "m");
+        let pairs: Vec<_> = kerning.pairs.keys().collect();
+        assert_eq!(
+            pairs,
+            [&(KernSide::Glyph("a".into()), KernSide::Glyph("f".into()))]
+        );
+    }
+}
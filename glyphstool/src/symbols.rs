@@ -0,0 +1,604 @@
+//! Cell-filling symbols for terminal use: Powerline separators, Symbols for
+//! Legacy Computing, and braille patterns.
+//!
+//! Shapes are described in coordinates relative to the character cell, where
+//! (0, 0) is the bottom left and (1, 1) the top right, and mapped onto the
+//! cell of each master. The cell is the same one the box drawing characters
+//! fill, so these connect with them.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use kurbo::{BezPath, PathEl, Point, Rect};
+
+use crate::boxdraw::{BoxDraw, BoxGlyph, BoxStyle, Eighths};
+use crate::cell::CellMetrics;
+use crate::font::{Font, Glyph, Layer, NodeType, Path};
+use crate::interpolate::{master_location, weight_width};
+use crate::plist::Plist;
+use crate::region::Region;
+use crate::round::round_path;
+use crate::stroke::{stroke, StrokeStyle, KAPPA};
+
+/// A group of symbols that can be generated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolSet {
+    /// Powerline separators (U+E0B0–U+E0BF). The icons elsewhere in the
+    /// Powerline range aren't geometric and are left to the designer.
+    Powerline,
+    /// The geometric Symbols for Legacy Computing (U+1FB00–U+1FBFF): sextants,
+    /// smooth mosaics, eighth blocks, diagonals and segmented digits.
+    LegacyComputing,
+    /// Braille patterns (U+2800–U+28FF).
+    Braille,
+}
+
+impl SymbolSet {
+    pub const ALL: [SymbolSet; 3] = [
+        SymbolSet::Powerline,
+        SymbolSet::LegacyComputing,
+        SymbolSet::Braille,
+    ];
+
+    /// The code points of the set that can be drawn.
+    pub fn codepoints(self) -> Vec<u32> {
+        let range = match self {
+            SymbolSet::Powerline => 0xE0A0..=0xE0D4,
+            SymbolSet::LegacyComputing => 0x1FB00..=0x1FBFF,
+            SymbolSet::Braille => 0x2800..=0x28FF,
+        };
+        range.filter(|&cp| symbol(cp).is_some()).collect()
+    }
+}
+
+impl FromStr for SymbolSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SymbolSet, String> {
+        match s {
+            "powerline" => Ok(SymbolSet::Powerline),
+            "legacy" => Ok(SymbolSet::LegacyComputing),
+            "braille" => Ok(SymbolSet::Braille),
+            _ => Err(format!("unknown symbol set {}", s)),
+        }
+    }
+}
+
+/// How a symbol is drawn, in cell coordinates.
+#[derive(Clone, Debug)]
+enum Symbol {
+    /// Filled polygons.
+    Polygons(Vec<Vec<Point>>),
+    /// Filled rectangles, as (x0, y0, x1, y1).
+    Rects(Vec<(f64, f64, f64, f64)>),
+    /// Block elements in eighths of the cell, drawn like U+2580–U+259F.
+    Blocks(&'static [Eighths]),
+    /// Light lines through the named points of the cell, a space starting a
+    /// new line. A line ending where it started is closed.
+    Lines(&'static str),
+    /// Half an ellipse filling the cell, bulging to the right or left.
+    HalfEllipse { right: bool, filled: bool },
+    /// Braille dots, as the bits of the pattern.
+    Braille(u8),
+    /// A seven-segment digit.
+    Digit(u8),
+}
+
+/// Lines (x0, y0, x1, y1) cutting off the smooth mosaic wedges of
+/// U+1FB3C–U+1FB51, and whether the block is on the side of the lower left
+/// corner (rather than the lower right). U+1FB52–U+1FB67 are their
+/// complements.
+const WEDGES: [(f64, f64, f64, f64, bool); 22] = [
+    (0.0, T1, 0.5, 0.0, true),
+    (0.0, T1, 1.0, 0.0, true),
+    (0.0, T2, 0.5, 0.0, true),
+    (0.0, T2, 1.0, 0.0, true),
+    (0.0, 1.0, 0.5, 0.0, true),
+    (0.0, T2, 0.5, 1.0, false),
+    (0.0, T2, 1.0, 1.0, false),
+    (0.0, T1, 0.5, 1.0, false),
+    (0.0, T1, 1.0, 1.0, false),
+    (0.0, 0.0, 0.5, 1.0, false),
+    (0.0, T1, 1.0, T2, false),
+    (0.5, 0.0, 1.0, T1, false),
+    (0.0, 0.0, 1.0, T1, false),
+    (0.5, 0.0, 1.0, T2, false),
+    (0.0, 0.0, 1.0, T2, false),
+    (0.5, 0.0, 1.0, 1.0, false),
+    (0.5, 1.0, 1.0, T2, true),
+    (0.0, 1.0, 1.0, T2, true),
+    (0.5, 1.0, 1.0, T1, true),
+    (0.0, 1.0, 1.0, T1, true),
+    (0.5, 1.0, 1.0, 0.0, true),
+    (0.0, T2, 1.0, T1, true),
+];
+
+const T1: f64 = 1.0 / 3.0;
+const T2: f64 = 2.0 / 3.0;
+
+/// Eighth blocks of U+1FB70–U+1FB8B.
+const LEGACY_BLOCKS: &[(u32, &[Eighths])] = &[
+    (0x1FB70, &[(1, 2, 0, 8)]),
+    (0x1FB71, &[(2, 3, 0, 8)]),
+    (0x1FB72, &[(3, 4, 0, 8)]),
+    (0x1FB73, &[(4, 5, 0, 8)]),
+    (0x1FB74, &[(5, 6, 0, 8)]),
+    (0x1FB75, &[(6, 7, 0, 8)]),
+    (0x1FB76, &[(0, 8, 6, 7)]),
+    (0x1FB77, &[(0, 8, 5, 6)]),
+    (0x1FB78, &[(0, 8, 4, 5)]),
+    (0x1FB79, &[(0, 8, 3, 4)]),
+    (0x1FB7A, &[(0, 8, 2, 3)]),
+    (0x1FB7B, &[(0, 8, 1, 2)]),
+    (0x1FB7C, &[(0, 1, 0, 8), (0, 8, 0, 1)]),
+    (0x1FB7D, &[(0, 1, 0, 8), (0, 8, 7, 8)]),
+    (0x1FB7E, &[(7, 8, 0, 8), (0, 8, 7, 8)]),
+    (0x1FB7F, &[(7, 8, 0, 8), (0, 8, 0, 1)]),
+    (0x1FB80, &[(0, 8, 7, 8), (0, 8, 0, 1)]),
+    (
+        0x1FB81,
+        &[(0, 8, 7, 8), (0, 8, 5, 6), (0, 8, 3, 4), (0, 8, 0, 1)],
+    ),
+    (0x1FB82, &[(0, 8, 6, 8)]),
+    (0x1FB83, &[(0, 8, 5, 8)]),
+    (0x1FB84, &[(0, 8, 3, 8)]),
+    (0x1FB85, &[(0, 8, 2, 8)]),
+    (0x1FB86, &[(0, 8, 1, 8)]),
+    (0x1FB87, &[(6, 8, 0, 8)]),
+    (0x1FB88, &[(5, 8, 0, 8)]),
+    (0x1FB89, &[(3, 8, 0, 8)]),
+    (0x1FB8A, &[(2, 8, 0, 8)]),
+    (0x1FB8B, &[(1, 8, 0, 8)]),
+];
+
+/// Light diagonals of U+1FBA0–U+1FBAE, through the upper and lower centre
+/// (`U`, `D`) and the middle left and right (`L`, `R`).
+const LEGACY_LINES: [&str; 15] = [
+    "UL", "UR", "LD", "RD", "ULD", "URD", "LDR", "LUR", "UL RD", "UR LD", "URDL", "ULDR", "LURD",
+    "RULD", "ULDRU",
+];
+
+/// Segments lit for each digit, as bits in the order a-g (top, upper right,
+/// lower right, bottom, lower left, upper left, middle).
+const DIGIT_SEGMENTS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111,
+    0b1111111, 0b1101111,
+];
+
+fn pt(x: f64, y: f64) -> Point {
+    Point::new(x, y)
+}
+
+/// The part of a convex polygon on the same side of the line through `a`
+/// and `b` as `keep`.
+fn clip(poly: &[Point], a: Point, b: Point, keep: Point) -> Vec<Point> {
+    let side = |p: Point| (b - a).cross(p - a);
+    let sign = side(keep).signum();
+    let mut result: Vec<Point> = Vec::new();
+    for i in 0..poly.len() {
+        let (p0, p1) = (poly[i], poly[(i + 1) % poly.len()]);
+        let (s0, s1) = (side(p0) * sign, side(p1) * sign);
+        if s0 >= -1e-9 {
+            result.push(p0);
+        }
+        if (s0 > 1e-9 && s1 < -1e-9) || (s0 < -1e-9 && s1 > 1e-9) {
+            result.push(p0.lerp(p1, s0 / (s0 - s1)));
+        }
+    }
+    result.dedup_by(|p, q| (*p - *q).hypot() < 1e-9);
+    result
+}
+
+fn clip_square(a: Point, b: Point, keep: Point) -> Vec<Point> {
+    let square = [pt(0.0, 0.0), pt(1.0, 0.0), pt(1.0, 1.0), pt(0.0, 1.0)];
+    clip(&square, a, b, keep)
+}
+
+/// Trim the ends of a straight-line stroke that stick out of the cell.
+fn clip_to_cell(cell: &CellMetrics, path: &Path) -> Path {
+    clip_to_rect(cell.rect(), path)
+}
+
+/// The part of a convex straight-line path inside a rectangle.
+pub(crate) fn clip_to_rect(r: Rect, path: &Path) -> Path {
+    let center = r.center();
+    let corners = [
+        pt(r.x0, r.y0),
+        pt(r.x1, r.y0),
+        pt(r.x1, r.y1),
+        pt(r.x0, r.y1),
+    ];
+    let mut points: Vec<Point> = path.nodes.iter().map(|n| n.pt).collect();
+    for i in 0..4 {
+        points = clip(&points, corners[i], corners[(i + 1) % 4], center);
+    }
+    let mut result = Path::new(true);
+    for p in points {
+        result.add(p, NodeType::Line);
+    }
+    result
+}
+
+fn sextant(bits: u32) -> Symbol {
+    let mut rects = Vec::new();
+    for i in 0..6 {
+        if bits & (1 << i) != 0 {
+            let x = (i % 2) as f64 * 0.5;
+            let y = (2 - i / 2) as f64 / 3.0;
+            rects.push((x, y, x + 0.5, y + 1.0 / 3.0));
+        }
+    }
+    Symbol::Rects(rects)
+}
+
+fn checkerboard(inverse: bool) -> Symbol {
+    let mut rects = Vec::new();
+    for j in 0..4 {
+        for i in 0..2 {
+            if (i + j) % 2 == inverse as usize {
+                let (x, y) = (i as f64 * 0.5, j as f64 * 0.25);
+                rects.push((x, y, x + 0.5, y + 0.25));
+            }
+        }
+    }
+    Symbol::Rects(rects)
+}
+
+fn polygons(polys: &[&[(f64, f64)]]) -> Symbol {
+    let polys = polys
+        .iter()
+        .map(|poly| poly.iter().map(|&(x, y)| pt(x, y)).collect())
+        .collect();
+    Symbol::Polygons(polys)
+}
+
+/// How to draw a code point, or `None` if it isn't supported.
+fn symbol(codepoint: u32) -> Option<Symbol> {
+    let symbol = match codepoint {
+        0xE0B0 => polygons(&[&[(0.0, 0.0), (1.0, 0.5), (0.0, 1.0)]]),
+        0xE0B1 => Symbol::Lines("ARB"),
+        0xE0B2 => polygons(&[&[(1.0, 0.0), (1.0, 1.0), (0.0, 0.5)]]),
+        0xE0B3 => Symbol::Lines("ELF"),
+        0xE0B4 => Symbol::HalfEllipse {
+            right: true,
+            filled: true,
+        },
+        0xE0B5 => Symbol::HalfEllipse {
+            right: true,
+            filled: false,
+        },
+        0xE0B6 => Symbol::HalfEllipse {
+            right: false,
+            filled: true,
+        },
+        0xE0B7 => Symbol::HalfEllipse {
+            right: false,
+            filled: false,
+        },
+        0xE0B8 => polygons(&[&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]]),
+        0xE0B9 | 0xE0BF => Symbol::Lines("BE"),
+        0xE0BA => polygons(&[&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]]),
+        0xE0BB | 0xE0BD => Symbol::Lines("AF"),
+        0xE0BC => polygons(&[&[(0.0, 0.0), (1.0, 1.0), (0.0, 1.0)]]),
+        0xE0BE => polygons(&[&[(1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]]),
+        0x1FB00..=0x1FB3B => {
+            // The sextants skip the two that are left and right half blocks.
+            let mut bits = codepoint - 0x1FB00 + 1;
+            if bits >= 21 {
+                bits += 1;
+            }
+            if bits >= 42 {
+                bits += 1;
+            }
+            sextant(bits)
+        }
+        0x1FB3C..=0x1FB67 => {
+            let i = (codepoint - 0x1FB3C) as usize;
+            let (x0, y0, x1, y1, lower_left) = WEDGES[i % 22];
+            let keep = match (lower_left, i < 22) {
+                (true, true) => pt(0.0, 0.0),
+                (false, true) => pt(1.0, 0.0),
+                (true, false) => pt(1.0, 1.0),
+                (false, false) => pt(0.0, 1.0),
+            };
+            Symbol::Polygons(vec![clip_square(pt(x0, y0), pt(x1, y1), keep)])
+        }
+        0x1FB68 => polygons(&[&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.5, 0.5)]]),
+        0x1FB69 => polygons(&[&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.5, 0.5), (0.0, 1.0)]]),
+        0x1FB6A => polygons(&[&[(0.0, 0.0), (1.0, 0.0), (0.5, 0.5), (1.0, 1.0), (0.0, 1.0)]]),
+        0x1FB6B => polygons(&[&[(0.0, 0.0), (0.5, 0.5), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]]),
+        0x1FB6C => polygons(&[&[(0.0, 0.0), (0.5, 0.5), (0.0, 1.0)]]),
+        0x1FB6D => polygons(&[&[(0.0, 1.0), (0.5, 0.5), (1.0, 1.0)]]),
+        0x1FB6E => polygons(&[&[(1.0, 0.0), (1.0, 1.0), (0.5, 0.5)]]),
+        0x1FB6F => polygons(&[&[(0.0, 0.0), (1.0, 0.0), (0.5, 0.5)]]),
+        0x1FB70..=0x1FB8B => {
+            let &(_, rects) = LEGACY_BLOCKS.iter().find(|(cp, _)| *cp == codepoint)?;
+            Symbol::Blocks(rects)
+        }
+        0x1FB95 => checkerboard(false),
+        0x1FB96 => checkerboard(true),
+        0x1FB9A => polygons(&[
+            &[(0.0, 0.0), (1.0, 0.0), (0.5, 0.5)],
+            &[(0.0, 1.0), (0.5, 0.5), (1.0, 1.0)],
+        ]),
+        0x1FB9B => polygons(&[
+            &[(0.0, 0.0), (0.5, 0.5), (0.0, 1.0)],
+            &[(1.0, 0.0), (1.0, 1.0), (0.5, 0.5)],
+        ]),
+        0x1FBA0..=0x1FBAE => Symbol::Lines(LEGACY_LINES[(codepoint - 0x1FBA0) as usize]),
+        0x1FBF0..=0x1FBF9 => Symbol::Digit((codepoint - 0x1FBF0) as u8),
+        0x2800..=0x28FF => Symbol::Braille((codepoint - 0x2800) as u8),
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// Named points used in line drawings: the middle of the edges (`U`, `D`,
+/// `L`, `R`) and the corners (`A`, `B` on the left, `E`, `F` on the right,
+/// bottom first).
+fn named(cell: &CellMetrics, c: char) -> Point {
+    let (x, y) = match c {
+        'U' => (0.5, 1.0),
+        'D' => (0.5, 0.0),
+        'L' => (0.0, 0.5),
+        'R' => (1.0, 0.5),
+        'A' => (0.0, 0.0),
+        'B' => (0.0, 1.0),
+        'E' => (1.0, 0.0),
+        'F' => (1.0, 1.0),
+        _ => panic!("unknown point {}", c),
+    };
+    cell.pt(x, y)
+}
+
+fn polygon_path(cell: &CellMetrics, points: &[Point]) -> Path {
+    let mut path = Path::new(true);
+    for &p in points {
+        path.add(cell.pt(p.x, p.y), NodeType::Line);
+    }
+    path
+}
+
+fn circle_path(center: Point, r: f64) -> Path {
+    let k = KAPPA * r;
+    let (x, y) = (center.x, center.y);
+    let mut path = Path::new(true);
+    for &(p1, p2, p3) in &[
+        ((x + r, y + k), (x + k, y + r), (x, y + r)),
+        ((x - k, y + r), (x - r, y + k), (x - r, y)),
+        ((x - r, y - k), (x - k, y - r), (x, y - r)),
+        ((x + k, y - r), (x + r, y - k), (x + r, y)),
+    ] {
+        path.add(p1, NodeType::OffCurve);
+        path.add(p2, NodeType::OffCurve);
+        path.add(p3, NodeType::CurveSmooth);
+    }
+    path
+}
+
+/// A half ellipse from one end of the left or right edge of a rectangle to
+/// the other.
+fn half_ellipse(rect: Rect, right: bool) -> BezPath {
+    // The left one is turned around so it also runs counterclockwise.
+    let p = |x: f64, y: f64| {
+        let (x, y) = if right { (x, y) } else { (1.0 - x, 1.0 - y) };
+        pt(rect.x0 + x * rect.width(), rect.y0 + y * rect.height())
+    };
+    let mut bez = BezPath::new();
+    bez.move_to(p(0.0, 0.0));
+    bez.curve_to(p(KAPPA, 0.0), p(1.0, 0.5 - 0.5 * KAPPA), p(1.0, 0.5));
+    bez.curve_to(p(1.0, 0.5 + 0.5 * KAPPA), p(KAPPA, 1.0), p(0.0, 1.0));
+    bez
+}
+
+fn bez_to_path(bez: &BezPath) -> Path {
+    let mut path = Path::new(true);
+    for el in bez.elements() {
+        match *el {
+            PathEl::MoveTo(p) | PathEl::LineTo(p) => path.add(p, NodeType::Line),
+            PathEl::CurveTo(p1, p2, p3) => {
+                path.add(p1, NodeType::OffCurve);
+                path.add(p2, NodeType::OffCurve);
+                path.add(p3, NodeType::Curve);
+            }
+            _ => (),
+        }
+    }
+    path
+}
+
+fn region_paths(cell: &CellMetrics, rects: &[(f64, f64, f64, f64)]) -> Vec<Path> {
+    let mut region = Region::default();
+    for &(x0, y0, x1, y1) in rects {
+        let rect = Rect::from_points(cell.pt(x0, y0), cell.pt(x1, y1));
+        region = region.add(rect.round());
+    }
+    region.to_paths()
+}
+
+fn digit_paths(cell: &CellMetrics, digit: u8, light: f64) -> Vec<Path> {
+    let p0 = cell.pt(0.2, 0.3);
+    let p1 = cell.pt(0.8, 0.8);
+    // Seven segments are a lot to fit in; thin them a little.
+    let t = 0.75 * light;
+    let (x0, y0, x1, y1) = (p0.x, p0.y, p1.x, p1.y);
+    let ym = 0.5 * (y0 + y1);
+    let g = t / 3.0;
+    // The horizontal segments span the width, with the vertical ones between
+    // them.
+    let segments = [
+        (x0 + g, y1 - t, x1 - g, y1),
+        (x1 - t, ym + 0.5 * t + g, x1, y1 - t - g),
+        (x1 - t, y0 + t + g, x1, ym - 0.5 * t - g),
+        (x0 + g, y0, x1 - g, y0 + t),
+        (x0, y0 + t + g, x0 + t, ym - 0.5 * t - g),
+        (x0, ym + 0.5 * t + g, x0 + t, y1 - t - g),
+        (x0 + g, ym - 0.5 * t, x1 - g, ym + 0.5 * t),
+    ];
+    let mut region = Region::default();
+    for (i, &(x0, y0, x1, y1)) in segments.iter().enumerate() {
+        if DIGIT_SEGMENTS[digit as usize] & (1 << i) != 0 {
+            region = region.add(Rect::new(x0, y0, x1, y1).round());
+        }
+    }
+    region.to_paths()
+}
+
+fn braille_paths(cell: &CellMetrics, bits: u8, diameter: f64) -> Vec<Path> {
+    let dx = cell.advance * 0.5;
+    let dy = dx.min(cell.height() * 0.25);
+    let xc = cell.advance * 0.5;
+    // Dots 1-3 and 4-6 are the top three rows of the left and right
+    // columns; dots 7 and 8 are the bottom row.
+    let positions = [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (0, 3),
+        (1, 3),
+    ];
+    let mut paths = Vec::new();
+    for (i, &(col, row)) in positions.iter().enumerate() {
+        if bits & (1 << i) != 0 {
+            let x = xc + (col as f64 - 0.5) * dx;
+            let y = cell.center + (1.5 - row as f64) * dy;
+            paths.push(circle_path(pt(x, y), (diameter * 0.5).round()));
+        }
+    }
+    paths
+}
+
+/// The paths of a symbol for a master at the given weight and width, drawn
+/// to its cell, or `None` if the code point isn't supported.
+pub fn symbol_paths(
+    codepoint: u32,
+    wght: f64,
+    wdth: f64,
+    cell: &CellMetrics,
+    style: &BoxStyle,
+) -> Option<Vec<Path>> {
+    let light = style.light.eval(wght, wdth);
+    let thin = StrokeStyle::new(2.0 * (light * 0.5).round());
+    let mut paths = match symbol(codepoint)? {
+        Symbol::Polygons(polys) => polys.iter().map(|p| polygon_path(cell, p)).collect(),
+        Symbol::Rects(rects) => region_paths(cell, &rects),
+        Symbol::Blocks(rects) => {
+            BoxDraw::new(wght, wdth, *cell, style).draw(BoxGlyph::Blocks(rects))
+        }
+        Symbol::Lines(lines) => {
+            let mut bez = BezPath::new();
+            for line in lines.split(' ') {
+                let points: Vec<Point> = line.chars().map(|c| named(cell, c)).collect();
+                bez.move_to(points[0]);
+                let closed = points.len() > 2 && points[0] == points[points.len() - 1];
+                let end = if closed {
+                    points.len() - 1
+                } else {
+                    points.len()
+                };
+                for &p in &points[1..end] {
+                    bez.line_to(p);
+                }
+                if closed {
+                    bez.close_path();
+                }
+            }
+            let paths = stroke(&bez, &thin);
+            paths.iter().map(|p| clip_to_cell(cell, p)).collect()
+        }
+        Symbol::HalfEllipse {
+            right,
+            filled: true,
+        } => vec![bez_to_path(&half_ellipse(cell.rect(), right))],
+        Symbol::HalfEllipse {
+            right,
+            filled: false,
+        } => {
+            // Keep the outside of the stroke within the cell.
+            let h = 0.5 * thin.width;
+            let r = cell.rect();
+            let rect = if right {
+                Rect::new(r.x0, r.y0 + h, r.x1 - h, r.y1 - h)
+            } else {
+                Rect::new(r.x0 + h, r.y0 + h, r.x1, r.y1 - h)
+            };
+            stroke(&half_ellipse(rect, right), &thin)
+        }
+        Symbol::Braille(bits) => braille_paths(cell, bits, style.dot.eval(wght, wdth)),
+        Symbol::Digit(digit) => digit_paths(cell, digit, light),
+    };
+    for (i, path) in paths.iter_mut().enumerate() {
+        round_path(path, 1.0, i);
+    }
+    Some(paths)
+}
+
+/// The name of a new glyph for a code point.
+pub(crate) fn glyph_name(codepoint: u32) -> String {
+    if codepoint > 0xFFFF {
+        format!("u{:05X}", codepoint)
+    } else {
+        format!("uni{:04X}", codepoint)
+    }
+}
+
+/// Draw the symbols of the given sets in every master, adding glyphs for
+/// code points the font doesn't have yet. Returns the names of the glyphs
+/// drawn.
+pub fn add_symbols(font: &mut Font, sets: &[SymbolSet], style: &BoxStyle) -> Vec<String> {
+    let masters: Vec<(String, (f64, f64), CellMetrics)> = font
+        .font_master
+        .iter()
+        .filter_map(|m| {
+            let location = weight_width(&master_location(font, &m.id)?);
+            Some((m.id.clone(), location, style.cell(font, &m.id)?))
+        })
+        .collect();
+    let mut existing = HashMap::new();
+    for (i, glyph) in font.glyphs.iter().enumerate() {
+        for cp in glyph.unicodes() {
+            existing.insert(cp, i);
+        }
+    }
+    let mut result = Vec::new();
+    for set in sets {
+        for cp in set.codepoints() {
+            let ix = match existing.get(&cp) {
+                Some(&ix) => ix,
+                None => {
+                    let mut other_stuff = HashMap::new();
+                    other_stuff.insert("unicode".to_string(), Plist::String(format!("{:04X}", cp)));
+                    font.glyphs.push(Glyph {
+                        layers: Vec::new(),
+                        glyphname: glyph_name(cp),
+                        other_stuff,
+                    });
+                    existing.insert(cp, font.glyphs.len() - 1);
+                    font.glyphs.len() - 1
+                }
+            };
+            let glyph = &mut font.glyphs[ix];
+            for (layer_id, (wght, wdth), cell) in &masters {
+                let paths = symbol_paths(cp, *wght, *wdth, cell, style).unwrap();
+                let paths = if paths.is_empty() { None } else { Some(paths) };
+                match glyph.get_layer_mut(layer_id) {
+                    Some(layer) => layer.paths = paths,
+                    None => glyph.layers.push(Layer {
+                        layer_id: layer_id.clone(),
+                        width: cell.advance,
+                        paths,
+                        components: None,
+                        anchors: None,
+                        guide_lines: None,
+                        other_stuff: HashMap::new(),
+                    }),
+                }
+            }
+            result.push(glyph.glyphname.clone());
+        }
+    }
+    result
+}
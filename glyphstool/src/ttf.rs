@@ -0,0 +1,1041 @@
+//! Reading of TrueType fonts, enough to compare one build with another:
+//! the outlines and advances of glyphs at any location of a variable font,
+//! through `avar` and `gvar`, and the glyphs for the code points in `cmap`.
+//!
+//! Compilers differ in how they split curves into quadratics and in which
+//! on-curve points they leave implied, so outlines are compared by distance
+//! rather than point by point. Each outline is sampled every couple of
+//! units, and the distance between two glyphs is how far the farthest
+//! sample of either is from the other's outline.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use kurbo::{BezPath, Line, ParamCurve, PathSeg, Point, Rect};
+
+use crate::glyf::TtPoint;
+
+/// How far apart, in font units, the samples of an outline are.
+const SAMPLE_SPACING: f64 = 2.0;
+
+/// How far, in font units, the lines that curves are measured against may
+/// stray from them.
+const FLATTEN_TOLERANCE: f64 = 0.01;
+
+/// How deeply composite glyphs may nest.
+const MAX_DEPTH: usize = 8;
+
+/// A big-endian reader of binary data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader { data, pos }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| "unexpected end of table".to_string())?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, String> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A 16.16 fixed point number.
+    fn fixed(&mut self) -> Result<f64, String> {
+        Ok(self.u32()? as i32 as f64 / 65536.0)
+    }
+
+    /// A 2.14 fixed point number.
+    fn f2dot14(&mut self) -> Result<f64, String> {
+        Ok(self.i16()? as f64 / 16384.0)
+    }
+}
+
+/// An axis of a variable font, in user space.
+#[derive(Clone, Debug)]
+pub struct FontAxis {
+    pub tag: String,
+    pub min: f64,
+    pub default: f64,
+    pub max: f64,
+}
+
+/// A location in user space, as axis tags and values.
+pub type UserLocation = Vec<(String, f64)>;
+
+/// A TrueType font, static or variable.
+pub struct TrueTypeFont {
+    tables: HashMap<[u8; 4], Vec<u8>>,
+    loca: Vec<usize>,
+    /// The axes, from `fvar`; a static font has none.
+    pub axes: Vec<FontAxis>,
+    /// For each axis, the map of normalized coordinates in `avar`.
+    avar: Vec<Vec<(f64, f64)>>,
+}
+
+/// The outline of a glyph as stored in `glyf`.
+enum Outline {
+    /// Points, and the index of the last point of each contour.
+    Simple(Vec<TtPoint>, Vec<usize>),
+    /// Glyph indices, with the 2×2 matrix and offset of each.
+    Composite(Vec<(u16, [f64; 4], (f64, f64))>),
+}
+
+/// A glyph at some location: its contours and advance width.
+struct Instance {
+    contours: Vec<Vec<TtPoint>>,
+    advance: f64,
+}
+
+impl TrueTypeFont {
+    /// Read a font file.
+    pub fn load(path: &Path) -> Result<TrueTypeFont, String> {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        TrueTypeFont::parse(&data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Read a font from its binary data.
+    pub fn parse(data: &[u8]) -> Result<TrueTypeFont, String> {
+        let mut r = Reader::new(data, 4);
+        let num_tables = r.u16()?;
+        let mut tables = HashMap::new();
+        for i in 0..num_tables as usize {
+            let mut r = Reader::new(data, 12 + 16 * i);
+            let mut tag = [0; 4];
+            tag.copy_from_slice(r.bytes(4)?);
+            r.u32()?;
+            let offset = r.u32()? as usize;
+            let length = r.u32()? as usize;
+            let table = Reader::new(data, offset).bytes(length)?;
+            tables.insert(tag, table.to_vec());
+        }
+        for tag in &[b"head", b"maxp", b"hhea", b"hmtx", b"loca", b"glyf"] {
+            if !tables.contains_key(*tag) {
+                return Err(format!(
+                    "no {} table; only TrueType outlines can be read",
+                    String::from_utf8_lossy(*tag)
+                ));
+            }
+        }
+        let mut font = TrueTypeFont {
+            tables,
+            loca: Vec::new(),
+            axes: Vec::new(),
+            avar: Vec::new(),
+        };
+        font.loca = font.read_loca()?;
+        font.axes = font.read_axes()?;
+        font.avar = font.read_avar()?;
+        Ok(font)
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+        self.tables.get(tag).map(Vec::as_slice)
+    }
+
+    fn read_loca(&self) -> Result<Vec<usize>, String> {
+        let num_glyphs = Reader::new(self.table(b"maxp").unwrap(), 4).u16()? as usize;
+        let long = Reader::new(self.table(b"head").unwrap(), 50).i16()? != 0;
+        let mut r = Reader::new(self.table(b"loca").unwrap(), 0);
+        (0..=num_glyphs)
+            .map(|_| {
+                if long {
+                    r.u32().map(|x| x as usize)
+                } else {
+                    r.u16().map(|x| 2 * x as usize)
+                }
+            })
+            .collect()
+    }
+
+    fn read_axes(&self) -> Result<Vec<FontAxis>, String> {
+        let fvar = match self.table(b"fvar") {
+            Some(fvar) => fvar,
+            None => return Ok(Vec::new()),
+        };
+        let mut r = Reader::new(fvar, 4);
+        let axes_offset = r.u16()? as usize;
+        r.u16()?;
+        let axis_count = r.u16()? as usize;
+        let axis_size = r.u16()? as usize;
+        (0..axis_count)
+            .map(|i| {
+                let mut r = Reader::new(fvar, axes_offset + i * axis_size);
+                Ok(FontAxis {
+                    tag: String::from_utf8_lossy(r.bytes(4)?).into_owned(),
+                    min: r.fixed()?,
+                    default: r.fixed()?,
+                    max: r.fixed()?,
+                })
+            })
+            .collect()
+    }
+
+    fn read_avar(&self) -> Result<Vec<Vec<(f64, f64)>>, String> {
+        let avar = match self.table(b"avar") {
+            Some(avar) => avar,
+            None => return Ok(Vec::new()),
+        };
+        let mut r = Reader::new(avar, 6);
+        let axis_count = r.u16()?;
+        (0..axis_count)
+            .map(|_| {
+                let count = r.u16()?;
+                (0..count)
+                    .map(|_| Ok((r.f2dot14()?, r.f2dot14()?)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The number of glyphs.
+    pub fn num_glyphs(&self) -> usize {
+        self.loca.len() - 1
+    }
+
+    /// An entry of the `name` table, preferring the Windows English one.
+    fn name(&self, name_id: u16) -> Result<Option<String>, String> {
+        let name = match self.table(b"name") {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let mut r = Reader::new(name, 2);
+        let count = r.u16()?;
+        let storage = r.u16()? as usize;
+        let mut found = None;
+        for _ in 0..count {
+            let (platform, _, language, id) = (r.u16()?, r.u16()?, r.u16()?, r.u16()?);
+            let length = r.u16()? as usize;
+            let offset = r.u16()? as usize;
+            if platform != 3 || id != name_id || (found.is_some() && language != 0x409) {
+                continue;
+            }
+            let bytes = Reader::new(name, storage + offset).bytes(length)?;
+            let units: Vec<u16> = bytes
+                .chunks(2)
+                .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+                .collect();
+            found = Some(String::from_utf16_lossy(&units));
+        }
+        Ok(found)
+    }
+
+    /// The named instances in `fvar`, with their locations.
+    pub fn named_instances(&self) -> Result<Vec<(String, UserLocation)>, String> {
+        let fvar = match self.table(b"fvar") {
+            Some(fvar) => fvar,
+            None => return Ok(Vec::new()),
+        };
+        let mut r = Reader::new(fvar, 4);
+        let axes_offset = r.u16()? as usize;
+        r.u16()?;
+        let axis_count = r.u16()? as usize;
+        let axis_size = r.u16()? as usize;
+        let instance_count = r.u16()? as usize;
+        let instance_size = r.u16()? as usize;
+        let start = axes_offset + axis_count * axis_size;
+        (0..instance_count)
+            .map(|i| {
+                let mut r = Reader::new(fvar, start + i * instance_size);
+                let name_id = r.u16()?;
+                r.u16()?;
+                let mut location = Vec::new();
+                for axis in &self.axes {
+                    location.push((axis.tag.clone(), r.fixed()?));
+                }
+                let name = self.name(name_id)?;
+                Ok((name.unwrap_or_else(|| format!("instance {}", i)), location))
+            })
+            .collect()
+    }
+
+    /// Whether a location is within the range of the font's axes. Axes
+    /// the location doesn't mention are at their defaults.
+    pub fn contains(&self, location: &[(String, f64)]) -> bool {
+        location.iter().all(
+            |(tag, value)| match self.axes.iter().find(|a| &a.tag == tag) {
+                Some(axis) => axis.min <= *value && *value <= axis.max,
+                None => false,
+            },
+        )
+    }
+
+    /// The normalized coordinates of a location in user space, mapped
+    /// through `avar`.
+    fn normalize(&self, location: &[(String, f64)]) -> Vec<f64> {
+        let f2dot14 = |x: f64| (x * 16384.0).round() / 16384.0;
+        self.axes
+            .iter()
+            .enumerate()
+            .map(|(i, axis)| {
+                let value = location
+                    .iter()
+                    .find(|(tag, _)| *tag == axis.tag)
+                    .map_or(axis.default, |&(_, v)| v.max(axis.min).min(axis.max));
+                let v = if value < axis.default {
+                    (value - axis.default) / (axis.default - axis.min)
+                } else if value > axis.default {
+                    (value - axis.default) / (axis.max - axis.default)
+                } else {
+                    0.0
+                };
+                let v = f2dot14(v);
+                match self.avar.get(i) {
+                    Some(map) if !map.is_empty() => f2dot14(avar_map(map, v)),
+                    _ => v,
+                }
+            })
+            .collect()
+    }
+
+    /// The glyph for each code point, from the Unicode `cmap` subtables.
+    pub fn cmap(&self) -> Result<BTreeMap<u32, u16>, String> {
+        let cmap = self.table(b"cmap").ok_or("no cmap table")?;
+        let mut r = Reader::new(cmap, 2);
+        let count = r.u16()?;
+        let mut best = None;
+        for _ in 0..count {
+            let (platform, encoding, offset) = (r.u16()?, r.u16()?, r.u32()? as usize);
+            let format = Reader::new(cmap, offset).u16()?;
+            let rank = match (platform, encoding, format) {
+                (3, 10, 12) | (0, 4, 12) => 2,
+                (3, 1, 4) | (0, _, 4) => 1,
+                _ => continue,
+            };
+            if best.is_none_or(|(r, _)| rank > r) {
+                best = Some((rank, offset));
+            }
+        }
+        let offset = best.ok_or("no Unicode cmap subtable")?.1;
+        let mut map = BTreeMap::new();
+        if Reader::new(cmap, offset).u16()? == 12 {
+            let mut r = Reader::new(cmap, offset + 12);
+            for _ in 0..r.u32()? {
+                let (start, end, glyph) = (r.u32()?, r.u32()?, r.u32()?);
+                for cp in start..=end {
+                    map.insert(cp, (glyph + cp - start) as u16);
+                }
+            }
+            return Ok(map);
+        }
+        let seg_count = Reader::new(cmap, offset + 6).u16()? as usize / 2;
+        let ends = offset + 14;
+        let starts = ends + 2 * seg_count + 2;
+        let deltas = starts + 2 * seg_count;
+        let range_offsets = deltas + 2 * seg_count;
+        for i in 0..seg_count {
+            let end = Reader::new(cmap, ends + 2 * i).u16()?;
+            let start = Reader::new(cmap, starts + 2 * i).u16()?;
+            let delta = Reader::new(cmap, deltas + 2 * i).u16()?;
+            let range_offset = Reader::new(cmap, range_offsets + 2 * i).u16()? as usize;
+            for cp in start..=end {
+                if cp == 0xFFFF {
+                    break;
+                }
+                let glyph = if range_offset == 0 {
+                    cp.wrapping_add(delta)
+                } else {
+                    let at = range_offsets + 2 * i + range_offset + 2 * (cp - start) as usize;
+                    match Reader::new(cmap, at).u16()? {
+                        0 => 0,
+                        g => g.wrapping_add(delta),
+                    }
+                };
+                if glyph != 0 {
+                    map.insert(cp as u32, glyph);
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    fn outline(&self, gid: u16) -> Result<Outline, String> {
+        let gid = gid as usize;
+        if gid >= self.num_glyphs() {
+            return Err(format!("glyph {} out of range", gid));
+        }
+        let (start, end) = (self.loca[gid], self.loca[gid + 1]);
+        if start >= end {
+            return Ok(Outline::Simple(Vec::new(), Vec::new()));
+        }
+        let data = Reader::new(self.table(b"glyf").unwrap(), start).bytes(end - start)?;
+        let mut r = Reader::new(data, 0);
+        let num_contours = r.i16()?;
+        r.bytes(8)?;
+        if num_contours < 0 {
+            let mut components = Vec::new();
+            loop {
+                let flags = r.u16()?;
+                let glyph = r.u16()?;
+                let offset = if flags & 0x1 != 0 {
+                    (r.i16()? as f64, r.i16()? as f64)
+                } else {
+                    (r.i8()? as f64, r.i8()? as f64)
+                };
+                if flags & 0x2 == 0 {
+                    return Err(format!(
+                        "glyph {}: components placed by point are not supported",
+                        gid
+                    ));
+                }
+                let matrix = if flags & 0x8 != 0 {
+                    let s = r.f2dot14()?;
+                    [s, 0.0, 0.0, s]
+                } else if flags & 0x40 != 0 {
+                    [r.f2dot14()?, 0.0, 0.0, r.f2dot14()?]
+                } else if flags & 0x80 != 0 {
+                    [r.f2dot14()?, r.f2dot14()?, r.f2dot14()?, r.f2dot14()?]
+                } else {
+                    [1.0, 0.0, 0.0, 1.0]
+                };
+                components.push((glyph, matrix, offset));
+                if flags & 0x20 == 0 {
+                    return Ok(Outline::Composite(components));
+                }
+            }
+        }
+        let ends: Vec<usize> = (0..num_contours)
+            .map(|_| r.u16().map(|e| e as usize))
+            .collect::<Result<_, _>>()?;
+        let num_points = ends.last().map_or(0, |e| e + 1);
+        let instructions = r.u16()? as usize;
+        r.bytes(instructions)?;
+        let mut flags = Vec::with_capacity(num_points);
+        while flags.len() < num_points {
+            let flag = r.u8()?;
+            flags.push(flag);
+            if flag & 0x8 != 0 {
+                for _ in 0..r.u8()? {
+                    flags.push(flag);
+                }
+            }
+        }
+        let mut coords = |short: u8, same: u8| -> Result<Vec<f64>, String> {
+            let mut v = 0.0;
+            flags
+                .iter()
+                .map(|&flag| {
+                    if flag & short != 0 {
+                        let d = r.u8()? as f64;
+                        v += if flag & same != 0 { d } else { -d };
+                    } else if flag & same == 0 {
+                        v += r.i16()? as f64;
+                    }
+                    Ok(v)
+                })
+                .collect()
+        };
+        let xs = coords(0x2, 0x10)?;
+        let ys = coords(0x4, 0x20)?;
+        let points = (0..num_points)
+            .map(|i| (xs[i], ys[i], flags[i] & 0x1 != 0))
+            .collect();
+        Ok(Outline::Simple(points, ends))
+    }
+
+    fn advance(&self, gid: u16) -> Result<f64, String> {
+        let num_metrics = Reader::new(self.table(b"hhea").unwrap(), 34).u16()? as usize;
+        let i = (gid as usize).min(num_metrics.max(1) - 1);
+        Ok(Reader::new(self.table(b"hmtx").unwrap(), 4 * i).u16()? as f64)
+    }
+
+    /// The deltas in `gvar` for the points of a glyph at normalized
+    /// coordinates, with the four phantom points last. Points a variation
+    /// leaves out are inferred for simple glyphs, as for rendering.
+    fn deltas(
+        &self,
+        gid: u16,
+        points: &[(f64, f64)],
+        ends: Option<&[usize]>,
+        coords: &[f64],
+    ) -> Result<Vec<(f64, f64)>, String> {
+        let mut total = vec![(0.0, 0.0); points.len()];
+        let gvar = match self.table(b"gvar") {
+            Some(gvar) if !coords.iter().all(|&c| c == 0.0) => gvar,
+            _ => return Ok(total),
+        };
+        let mut r = Reader::new(gvar, 4);
+        let axis_count = r.u16()? as usize;
+        let shared_count = r.u16()? as usize;
+        let shared_offset = r.u32()? as usize;
+        r.u16()?;
+        let long_offsets = r.u16()? & 1 != 0;
+        let data_offset = r.u32()? as usize;
+        let offset = |i: usize| -> Result<usize, String> {
+            if long_offsets {
+                Reader::new(gvar, 20 + 4 * i).u32().map(|x| x as usize)
+            } else {
+                Reader::new(gvar, 20 + 2 * i).u16().map(|x| 2 * x as usize)
+            }
+        };
+        let (start, end) = (offset(gid as usize)?, offset(gid as usize + 1)?);
+        if start >= end {
+            return Ok(total);
+        }
+        let data = Reader::new(gvar, data_offset + start).bytes(end - start)?;
+        let mut header = Reader::new(data, 0);
+        let count = header.u16()?;
+        let mut serialized = Reader::new(data, header.u16()? as usize);
+        let shared_points = if count & 0x8000 != 0 {
+            packed_points(&mut serialized)?
+        } else {
+            None
+        };
+        let tuple = |r: &mut Reader| -> Result<Vec<f64>, String> {
+            (0..axis_count).map(|_| r.f2dot14()).collect()
+        };
+        for _ in 0..count & 0x0FFF {
+            let size = header.u16()? as usize;
+            let index = header.u16()?;
+            let peak = if index & 0x8000 != 0 {
+                tuple(&mut header)?
+            } else {
+                let shared = (index & 0x0FFF) as usize;
+                if shared >= shared_count {
+                    return Err(format!("glyph {}: bad shared tuple index", gid));
+                }
+                tuple(&mut Reader::new(
+                    gvar,
+                    shared_offset + 2 * axis_count * shared,
+                ))?
+            };
+            let (start, end) = if index & 0x4000 != 0 {
+                (tuple(&mut header)?, tuple(&mut header)?)
+            } else {
+                (
+                    peak.iter().map(|&p| p.min(0.0)).collect(),
+                    peak.iter().map(|&p| p.max(0.0)).collect(),
+                )
+            };
+            let mut r = Reader::new(serialized.bytes(size)?, 0);
+            let scalar = region_scalar(&peak, &start, &end, coords);
+            let private_points;
+            let indices = if index & 0x2000 != 0 {
+                private_points = packed_points(&mut r)?;
+                private_points.as_deref()
+            } else {
+                shared_points.as_deref()
+            };
+            let all: Vec<usize>;
+            let indices = match indices {
+                Some(indices) => indices,
+                None => {
+                    all = (0..points.len()).collect();
+                    &all
+                }
+            };
+            let xs = packed_deltas(&mut r, indices.len())?;
+            let ys = packed_deltas(&mut r, indices.len())?;
+            if scalar == 0.0 {
+                continue;
+            }
+            let mut deltas: Vec<Option<(f64, f64)>> = vec![None; points.len()];
+            for (k, &i) in indices.iter().enumerate() {
+                if let Some(d) = deltas.get_mut(i) {
+                    *d = Some((xs[k], ys[k]));
+                }
+            }
+            if let Some(ends) = ends {
+                if indices.len() < points.len() {
+                    infer_deltas(points, &mut deltas, ends);
+                }
+            }
+            for (t, d) in total.iter_mut().zip(&deltas) {
+                if let Some((dx, dy)) = d {
+                    t.0 += scalar * dx;
+                    t.1 += scalar * dy;
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    /// A glyph at normalized coordinates, with its components resolved.
+    fn instance(&self, gid: u16, coords: &[f64], depth: usize) -> Result<Instance, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("glyph {}: components nest too deeply", gid));
+        }
+        let outline = self.outline(gid)?;
+        let mut points: Vec<(f64, f64)> = match &outline {
+            Outline::Simple(points, _) => points.iter().map(|&(x, y, _)| (x, y)).collect(),
+            Outline::Composite(components) => components.iter().map(|c| c.2).collect(),
+        };
+        let advance = self.advance(gid)?;
+        points.extend(&[(0.0, 0.0), (advance, 0.0), (0.0, 0.0), (0.0, 0.0)]);
+        let ends = match &outline {
+            Outline::Simple(_, ends) => Some(ends.as_slice()),
+            Outline::Composite(_) => None,
+        };
+        let deltas = self.deltas(gid, &points, ends, coords)?;
+        let moved: Vec<(f64, f64)> = points
+            .iter()
+            .zip(&deltas)
+            .map(|(p, d)| (p.0 + d.0, p.1 + d.1))
+            .collect();
+        let advance = moved[moved.len() - 3].0 - moved[moved.len() - 4].0;
+        let contours = match outline {
+            Outline::Simple(points, ends) => {
+                let mut contours = Vec::new();
+                let mut start = 0;
+                for end in ends {
+                    let contour = (start..=end)
+                        .map(|i| (moved[i].0, moved[i].1, points[i].2))
+                        .collect();
+                    contours.push(contour);
+                    start = end + 1;
+                }
+                contours
+            }
+            Outline::Composite(components) => {
+                let mut contours = Vec::new();
+                for (i, (glyph, m, _)) in components.into_iter().enumerate() {
+                    let (dx, dy) = (moved[i].0.round(), moved[i].1.round());
+                    for contour in self.instance(glyph, coords, depth + 1)?.contours {
+                        let placed = contour
+                            .iter()
+                            .map(|&(x, y, on)| {
+                                (m[0] * x + m[2] * y + dx, m[1] * x + m[3] * y + dy, on)
+                            })
+                            .collect();
+                        contours.push(placed);
+                    }
+                }
+                contours
+            }
+        };
+        Ok(Instance { contours, advance })
+    }
+}
+
+/// Map a normalized coordinate through the segment map of an axis.
+fn avar_map(map: &[(f64, f64)], v: f64) -> f64 {
+    for pair in map.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        if x0 <= v && v <= x1 {
+            if x1 == x0 {
+                return y0;
+            }
+            return y0 + (v - x0) * (y1 - y0) / (x1 - x0);
+        }
+    }
+    v
+}
+
+/// How much of a variation applies at some coordinates.
+fn region_scalar(peak: &[f64], start: &[f64], end: &[f64], coords: &[f64]) -> f64 {
+    let mut scalar = 1.0;
+    for i in 0..peak.len() {
+        let (p, s, e) = (peak[i], start[i], end[i]);
+        let v = coords.get(i).cloned().unwrap_or(0.0);
+        if p == 0.0 || v == p {
+            continue;
+        }
+        if v <= s || v >= e {
+            return 0.0;
+        }
+        scalar *= if v < p {
+            (v - s) / (p - s)
+        } else {
+            (e - v) / (e - p)
+        };
+    }
+    scalar
+}
+
+/// Point numbers, or `None` for all of the points.
+fn packed_points(r: &mut Reader) -> Result<Option<Vec<usize>>, String> {
+    let first = r.u8()? as usize;
+    let count = match first {
+        0 => return Ok(None),
+        n if n & 0x80 != 0 => ((n & 0x7F) << 8) | r.u8()? as usize,
+        n => n,
+    };
+    let mut points = Vec::with_capacity(count);
+    let mut last = 0;
+    while points.len() < count {
+        let control = r.u8()?;
+        for _ in 0..(control & 0x7F) as usize + 1 {
+            last += if control & 0x80 != 0 {
+                r.u16()? as usize
+            } else {
+                r.u8()? as usize
+            };
+            points.push(last);
+        }
+    }
+    Ok(Some(points))
+}
+
+fn packed_deltas(r: &mut Reader, count: usize) -> Result<Vec<f64>, String> {
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = r.u8()?;
+        for _ in 0..(control & 0x3F) as usize + 1 {
+            deltas.push(if control & 0x80 != 0 {
+                0.0
+            } else if control & 0x40 != 0 {
+                r.i16()? as f64
+            } else {
+                r.i8()? as f64
+            });
+        }
+    }
+    Ok(deltas)
+}
+
+/// Infer the deltas of the points a variation leaves out, contour by
+/// contour, from the neighbouring points that have them.
+fn infer_deltas(points: &[(f64, f64)], deltas: &mut [Option<(f64, f64)>], ends: &[usize]) {
+    let mut start = 0;
+    for &end in ends {
+        let n = end + 1 - start;
+        let refs: Vec<usize> = (start..=end).filter(|&i| deltas[i].is_some()).collect();
+        if refs.len() == 1 {
+            let only = deltas[refs[0]];
+            for d in &mut deltas[start..=end] {
+                *d = only;
+            }
+        }
+        if refs.len() >= 2 {
+            for (k, &a) in refs.iter().enumerate() {
+                let b = refs[(k + 1) % refs.len()];
+                let mut i = start + (a - start + 1) % n;
+                while i != b {
+                    let (da, db) = (deltas[a].unwrap(), deltas[b].unwrap());
+                    let x = infer(points[i].0, (points[a].0, da.0), (points[b].0, db.0));
+                    let y = infer(points[i].1, (points[a].1, da.1), (points[b].1, db.1));
+                    deltas[i] = Some((x, y));
+                    i = start + (i - start + 1) % n;
+                }
+            }
+        }
+        start = end + 1;
+    }
+}
+
+/// The inferred delta of a coordinate between two reference points.
+fn infer(x: f64, (x1, d1): (f64, f64), (x2, d2): (f64, f64)) -> f64 {
+    if x1 == x2 {
+        return if d1 == d2 { d1 } else { 0.0 };
+    }
+    let ((x1, d1), (x2, d2)) = if x1 < x2 {
+        ((x1, d1), (x2, d2))
+    } else {
+        ((x2, d2), (x1, d1))
+    };
+    if x <= x1 {
+        d1
+    } else if x >= x2 {
+        d2
+    } else {
+        d1 + (x - x1) * (d2 - d1) / (x2 - x1)
+    }
+}
+
+/// The path of TrueType contours, with implied on-curve points restored.
+fn contours_path(contours: &[Vec<TtPoint>]) -> BezPath {
+    let mut path = BezPath::new();
+    for contour in contours {
+        let n = contour.len();
+        let pt = |i: usize| Point::new(contour[i % n].0, contour[i % n].1);
+        let start = match contour.iter().position(|p| p.2) {
+            Some(i) => i,
+            None if n > 0 => {
+                // No point is on the curve: start at an implied one.
+                path.move_to(pt(0).midpoint(pt(1)));
+                for i in 1..=n {
+                    path.quad_to(pt(i), pt(i).midpoint(pt(i + 1)));
+                }
+                path.close_path();
+                continue;
+            }
+            None => continue,
+        };
+        path.move_to(pt(start));
+        let mut control: Option<Point> = None;
+        for i in start + 1..=start + n {
+            let on = contour[i % n].2;
+            match (control, on) {
+                (None, true) => path.line_to(pt(i)),
+                (Some(c), true) => {
+                    path.quad_to(c, pt(i));
+                    control = None;
+                }
+                (None, false) => control = Some(pt(i)),
+                (Some(c), false) => {
+                    path.quad_to(c, c.midpoint(pt(i)));
+                    control = Some(pt(i));
+                }
+            }
+        }
+        path.close_path();
+    }
+    path
+}
+
+fn seg_bounds(seg: &PathSeg) -> Rect {
+    match seg {
+        PathSeg::Line(l) => Rect::from_points(l.p0, l.p1),
+        PathSeg::Quad(q) => Rect::from_points(q.p0, q.p1).union_pt(q.p2),
+        PathSeg::Cubic(c) => Rect::from_points(c.p0, c.p1).union_pt(c.p2).union_pt(c.p3),
+    }
+}
+
+/// The segments of a path as lines, no farther than `FLATTEN_TOLERANCE`
+/// from the curves, with their bounds.
+///
+/// Distances are measured to these lines rather than with kurbo's
+/// `nearest`, which gives wrong answers for curves in this version.
+fn flatten_lines(path: &BezPath) -> Vec<(Line, Rect)> {
+    let mut result = Vec::new();
+    for seg in path.segments() {
+        // A chord of a curve piece of parameter length h deviates from the
+        // curve by at most h^2 / 8 times the largest second derivative.
+        let second = match seg {
+            PathSeg::Line(_) => 0.0,
+            PathSeg::Quad(q) => {
+                2.0 * (q.p0.to_vec2() - 2.0 * q.p1.to_vec2() + q.p2.to_vec2()).hypot()
+            }
+            PathSeg::Cubic(c) => {
+                let d0 = c.p0.to_vec2() - 2.0 * c.p1.to_vec2() + c.p2.to_vec2();
+                let d1 = c.p1.to_vec2() - 2.0 * c.p2.to_vec2() + c.p3.to_vec2();
+                6.0 * d0.hypot().max(d1.hypot())
+            }
+        };
+        let n = (second / (8.0 * FLATTEN_TOLERANCE)).sqrt().ceil().max(1.0) as usize;
+        let mut p0 = seg.eval(0.0);
+        for k in 1..=n {
+            let p1 = seg.eval(k as f64 / n as f64);
+            result.push((Line::new(p0, p1), Rect::from_points(p0, p1)));
+            p0 = p1;
+        }
+    }
+    result
+}
+
+/// The squared distance from a point to a line segment.
+fn line_distance2(line: &Line, p: Point) -> f64 {
+    let d = line.p1 - line.p0;
+    let len2 = d.hypot2();
+    let t = if len2 > 0.0 {
+        ((p - line.p0).dot(d) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (line.eval(t) - p).hypot2()
+}
+
+/// How far the farthest sample of one path is from the other, given as
+/// lines.
+fn farthest_sample(path: &BezPath, other: &[(Line, Rect)]) -> f64 {
+    let mut farthest: f64 = 0.0;
+    let mut last = 0;
+    for seg in path.segments() {
+        let bounds = seg_bounds(&seg);
+        let n = ((bounds.width() + bounds.height()) / SAMPLE_SPACING)
+            .ceil()
+            .max(1.0) as usize;
+        for k in 0..n {
+            let p = seg.eval(k as f64 / n as f64);
+            // Start from the line nearest the last sample, and skip those
+            // whose bounds are farther than the nearest so far.
+            let mut best = line_distance2(&other[last].0, p);
+            for (i, (line, r)) in other.iter().enumerate() {
+                let dx = (r.x0 - p.x).max(p.x - r.x1).max(0.0);
+                let dy = (r.y0 - p.y).max(p.y - r.y1).max(0.0);
+                if dx * dx + dy * dy >= best {
+                    continue;
+                }
+                let d = line_distance2(line, p);
+                if d < best {
+                    best = d;
+                    last = i;
+                }
+            }
+            farthest = farthest.max(best);
+        }
+    }
+    farthest.sqrt()
+}
+
+/// The distance between two outlines: how far the farthest point of either
+/// is from the other. Infinite if only one of them is empty.
+fn outline_distance(a: &[Vec<TtPoint>], b: &[Vec<TtPoint>]) -> f64 {
+    let (a, b) = (contours_path(a), contours_path(b));
+    let (la, lb) = (flatten_lines(&a), flatten_lines(&b));
+    match (la.is_empty(), lb.is_empty()) {
+        (true, true) => 0.0,
+        (false, false) => farthest_sample(&a, &lb).max(farthest_sample(&b, &la)),
+        _ => f64::INFINITY,
+    }
+}
+
+/// How the glyph of a code point in one font differs from the other's.
+#[derive(Clone, Debug)]
+pub struct GlyphDifference {
+    pub codepoint: u32,
+    /// The distance between the outlines in font units, infinite if only
+    /// one of them is empty.
+    pub distance: f64,
+    /// The advance widths in each font.
+    pub advances: (f64, f64),
+}
+
+/// Compare the glyphs two fonts have for the same code points, at a
+/// location in user space.
+pub fn compare_fonts(
+    font: &TrueTypeFont,
+    other: &TrueTypeFont,
+    location: &[(String, f64)],
+) -> Result<Vec<GlyphDifference>, String> {
+    let (coords, other_coords) = (font.normalize(location), other.normalize(location));
+    let (cmap, other_cmap) = (font.cmap()?, other.cmap()?);
+    let mut differences = Vec::new();
+    for (&codepoint, &gid) in &cmap {
+        let other_gid = match other_cmap.get(&codepoint) {
+            Some(&g) => g,
+            None => continue,
+        };
+        let a = font.instance(gid, &coords, 0)?;
+        let b = other.instance(other_gid, &other_coords, 0)?;
+        differences.push(GlyphDifference {
+            codepoint,
+            distance: outline_distance(&a.contours, &b.contours),
+            advances: (a.advance, b.advance),
+        });
+    }
+    Ok(differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Font;
+    use crate::from_plist::FromPlist;
+    use crate::plist::Plist;
+    use crate::variable::compile_variable;
+
+    fn square(master: &str, size: i64) -> String {
+        format!(
+            "{{layerId = {}; width = 600; paths = ({{closed = 1; nodes = (\
+             \"100 0 LINE\", \"100 {s} LINE\", \"{e} {s} LINE\", \"{e} 0 LINE\");}});}}",
+            master,
+            s = size,
+            e = 100 + size
+        )
+    }
+
+    fn font() -> TrueTypeFont {
+        let source = format!(
+            "{{familyName = Test; unitsPerEm = 1000;
+fontMaster = ({{id = regular; weightValue = 400;}}, {{id = bold; weightValue = 900;}});
+glyphs = ({{glyphname = .notdef; layers = ({}, {});}},
+{{glyphname = A; unicode = 0041; layers = ({}, {});}});
+}}",
+            square("regular", 500),
+            square("bold", 500),
+            square("regular", 300),
+            square("bold", 400)
+        );
+        let font = Font::from_plist(Plist::parse(&source).unwrap());
+        TrueTypeFont::parse(&compile_variable(&font).unwrap()).unwrap()
+    }
+
+    fn location(weight: f64) -> UserLocation {
+        vec![("wght".to_string(), weight)]
+    }
+
+    #[test]
+    fn reads_outlines_at_a_location() {
+        let font = font();
+        assert_eq!(font.axes.len(), 1);
+        assert_eq!((font.axes[0].min, font.axes[0].max), (400.0, 900.0));
+        let gid = font.cmap().unwrap()[&0x41];
+        let top = |weight| {
+            let coords = font.normalize(&location(weight));
+            let glyph = font.instance(gid, &coords, 0).unwrap();
+            glyph.contours[0].iter().map(|p| p.1).fold(0.0, f64::max)
+        };
+        assert_eq!(top(400.0), 300.0);
+        assert_eq!(top(650.0), 350.0);
+        assert_eq!(top(900.0), 400.0);
+    }
+
+    #[test]
+    fn outline_distance_is_the_farthest_point() {
+        let square = |x: f64| {
+            vec![vec![
+                (x, 0.0, true),
+                (x, 100.0, true),
+                (x + 100.0, 100.0, true),
+                (x + 100.0, 0.0, true),
+            ]]
+        };
+        assert_eq!(outline_distance(&square(0.0), &square(0.0)), 0.0);
+        assert!((outline_distance(&square(0.0), &square(3.0)) - 3.0).abs() < 1e-6);
+        assert_eq!(outline_distance(&square(0.0), &[]), f64::INFINITY);
+    }
+
+    #[test]
+    fn outline_distance_of_quadratic_curves() {
+        // A circle of radius 100 made of eight quadratic curves, with only
+        // off-curve points, or with the implied on-curve points explicit.
+        let circle = |x: f64, r: f64, explicit: bool| {
+            let mut contour = Vec::new();
+            let c = r / (std::f64::consts::PI / 8.0).cos();
+            for k in 0..8 {
+                let a = (k as f64 + 0.5) * std::f64::consts::PI / 4.0;
+                contour.push((x + c * a.cos(), c * a.sin(), false));
+                if explicit {
+                    let a = a + std::f64::consts::PI / 8.0;
+                    contour.push((x + r * a.cos(), r * a.sin(), true));
+                }
+            }
+            vec![contour]
+        };
+        let d = outline_distance(&circle(0.0, 100.0, false), &circle(0.0, 100.0, true));
+        assert!(d <= FLATTEN_TOLERANCE, "{}", d);
+        let d = outline_distance(&circle(0.0, 100.0, false), &circle(2.0, 100.0, true));
+        assert!((d - 2.0).abs() < 0.02, "{}", d);
+        let d = outline_distance(&circle(0.0, 100.0, false), &circle(0.0, 97.0, false));
+        assert!((d - 3.0).abs() < 0.02, "{}", d);
+    }
+
+    #[test]
+    fn compares_a_font_with_itself() {
+        let font = font();
+        for weight in &[400.0, 700.0, 900.0] {
+            let differences = compare_fonts(&font, &font, &location(*weight)).unwrap();
+            assert_eq!(differences.len(), 1);
+            assert!(differences[0].distance < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,1496 @@
+//! Export to UFO 3 sources and a designspace document, and import back.
+//!
+//! Each master becomes a UFO, holding the master layer of every glyph, and
+//! the designspace document ties them together with the axes and the
+//! instances. This covers what our build needs from the Glyphs source:
+//! outlines, components, anchors, metrics, kerning and feature code. Brace
+//! and bracket layers, hinting and Glyphs-only settings are not exported.
+//! Anchors are exported as they are in the layers; run `propagate_anchors`
+//! first to give composites the anchors of their components.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use kurbo::{Affine, Point};
+
+use crate::font::{
+    Anchor, Component, FeatureCode, Font, FontClass, FontMaster, Glyph, GuideLine, Layer, NodeType,
+    Path as GlyphsPath,
+};
+use crate::from_plist::FromPlist;
+use crate::interpolate::{instance_location, master_location, origin, INSTANCE_KEYS, MASTER_KEYS};
+use crate::kerning::{KernSide, Kerning};
+use crate::plist::Plist;
+use crate::to_plist::ToPlist;
+use crate::xml::{self, Element};
+
+/// The lib key recording the Glyphs master a UFO was exported from.
+pub const MASTER_ID_KEY: &str = "com.schriftgestaltung.fontMasterID";
+
+/// A design axis, from the `Axes` custom parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Axis {
+    pub name: String,
+    pub tag: String,
+}
+
+/// The design axes of the font. Glyphs stores the positions of masters and
+/// instances on the first two axes as weight and width values, and on the
+/// others as custom values.
+pub fn axes(font: &Font) -> Vec<Axis> {
+    let declared = font.custom_parameter("Axes").and_then(Plist::as_array);
+    match declared {
+        Some(axes) => axes
+            .iter()
+            .filter_map(|a| {
+                Some(Axis {
+                    name: a.get("Name")?.as_str()?.to_string(),
+                    tag: a.get("Tag")?.as_str()?.to_string(),
+                })
+            })
+            .collect(),
+        None => {
+            let mut axes = vec![("Weight", "wght")];
+            if font.font_master.iter().any(|m| m.width_value.is_some()) {
+                axes.push(("Width", "wdth"));
+            }
+            axes.into_iter()
+                .map(|(name, tag)| Axis {
+                    name: name.to_string(),
+                    tag: tag.to_string(),
+                })
+                .collect()
+        }
+    }
+}
+
+/// The file name of a master's UFO.
+pub fn ufo_name(font: &Font, master: &FontMaster) -> String {
+    let style: String = master.name().split_whitespace().collect();
+    format!("{}-{}.ufo", family_name(font).replace(' ', ""), style)
+}
+
+fn family_name(font: &Font) -> &str {
+    font.other_stuff
+        .get("familyName")
+        .and_then(Plist::as_str)
+        .unwrap_or("Untitled")
+}
+
+/// Convert a glyph name to a file name, following the UFO 3 user name to
+/// file name rules: uppercase letters are followed by `_`, illegal
+/// characters are replaced, and clashes (ignoring case) get a number.
+fn glif_file_name(name: &str, existing: &mut HashSet<String>) -> String {
+    const ILLEGAL: &str = "\"*+/:<>?[\\]|";
+    const RESERVED: &[&str] = &["con", "prn", "aux", "clock$", "nul"];
+    let mut base = String::new();
+    for (i, c) in name.chars().enumerate() {
+        let illegal = (c as u32) < 0x20 || c == '\x7f' || ILLEGAL.contains(c);
+        if illegal || (i == 0 && c == '.') {
+            base.push('_');
+        } else {
+            base.push(c);
+            if c.is_uppercase() {
+                base.push('_');
+            }
+        }
+    }
+    let base = base
+        .split('.')
+        .map(|part| {
+            let lower = part.to_lowercase();
+            let numbered = (lower.starts_with("com") || lower.starts_with("lpt"))
+                && lower.len() == 4
+                && lower.as_bytes()[3].is_ascii_digit();
+            if RESERVED.contains(&lower.as_str()) || numbered {
+                format!("_{}", part)
+            } else {
+                part.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".");
+    // Leave room for a clash number and the suffix.
+    let base: String = base.chars().take(255 - 15 - 5).collect();
+    let mut file_name = format!("{}.glif", base);
+    let mut n = 1;
+    while existing.contains(&file_name.to_lowercase()) {
+        file_name = format!("{}{:015}.glif", base, n);
+        n += 1;
+    }
+    existing.insert(file_name.to_lowercase());
+    file_name
+}
+
+fn point_element(pt: Point) -> Element {
+    Element::new("point").attr("x", pt.x).attr("y", pt.y)
+}
+
+/// The glif document for one layer of a glyph.
+fn glif(glyph: &Glyph, layer: &Layer) -> String {
+    let mut root = Element::new("glyph")
+        .attr("name", &glyph.glyphname)
+        .attr("format", 2);
+    root.push(Element::new("advance").attr("width", layer.width));
+    for u in glyph.unicodes() {
+        root.push(Element::new("unicode").attr("hex", format!("{:04X}", u)));
+    }
+    for guide in layer.guide_lines.iter().flatten() {
+        root.push(
+            Element::new("guideline")
+                .attr("x", guide.position.x)
+                .attr("y", guide.position.y)
+                .attr("angle", guide.angle.unwrap_or(0.0)),
+        );
+    }
+    for anchor in layer.anchors.iter().flatten() {
+        root.push(
+            Element::new("anchor")
+                .attr("x", anchor.position.x)
+                .attr("y", anchor.position.y)
+                .attr("name", &anchor.name),
+        );
+    }
+    let mut outline = Element::new("outline");
+    for path in layer.paths.iter().flatten() {
+        let mut contour = Element::new("contour");
+        let mut nodes = path.nodes.clone();
+        // Glyphs puts the start node of a closed path last; UFO first.
+        if path.closed {
+            nodes.rotate_right(1);
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            let mut point = point_element(node.pt);
+            let (kind, smooth) = match node.node_type {
+                NodeType::OffCurve => (None, false),
+                _ if i == 0 && !path.closed => (Some("move"), false),
+                NodeType::Line => (Some("line"), false),
+                NodeType::LineSmooth => (Some("line"), true),
+                NodeType::Curve => (Some("curve"), false),
+                NodeType::CurveSmooth => (Some("curve"), true),
+            };
+            if let Some(kind) = kind {
+                point = point.attr("type", kind);
+            }
+            if smooth {
+                point = point.attr("smooth", "yes");
+            }
+            contour.push(point);
+        }
+        outline.push(contour);
+    }
+    for component in layer.components.iter().flatten() {
+        let mut element = Element::new("component").attr("base", &component.name);
+        if let Some(transform) = component.transform {
+            let c = transform.as_coeffs();
+            let keys = [
+                "xScale", "xyScale", "yxScale", "yScale", "xOffset", "yOffset",
+            ];
+            let identity = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+            for i in 0..6 {
+                if c[i] != identity[i] {
+                    element = element.attr(keys[i], c[i]);
+                }
+            }
+        }
+        outline.push(element);
+    }
+    if !outline.children.is_empty() {
+        root.push(outline);
+    }
+    root.to_document()
+}
+
+fn string(s: &str) -> Element {
+    Element::text("string", s)
+}
+
+/// Alignment zones as (position, size) pairs.
+fn alignment_zones(master: &FontMaster) -> Vec<(f64, f64)> {
+    let zones = master
+        .other_stuff
+        .get("alignmentZones")
+        .and_then(Plist::as_array)
+        .unwrap_or(&[]);
+    zones
+        .iter()
+        .map(|z| {
+            let p = Point::from_plist(z.clone());
+            (p.x, p.y)
+        })
+        .collect()
+}
+
+fn fontinfo(font: &Font, master: &FontMaster) -> String {
+    let mut entries: Vec<(&str, Element)> = Vec::new();
+    entries.push(("familyName", string(family_name(font))));
+    entries.push(("styleName", string(master.name())));
+    for &(key, glyphs_key) in &[
+        ("copyright", "copyright"),
+        ("openTypeNameDesigner", "designer"),
+        ("openTypeNameDesignerURL", "designerURL"),
+        ("openTypeNameManufacturer", "manufacturer"),
+        ("openTypeNameManufacturerURL", "manufacturerURL"),
+    ] {
+        if let Some(s) = font.other_stuff.get(glyphs_key).and_then(Plist::as_str) {
+            entries.push((key, string(s)));
+        }
+    }
+    for &(key, glyphs_key) in &[
+        ("unitsPerEm", "unitsPerEm"),
+        ("versionMajor", "versionMajor"),
+        ("versionMinor", "versionMinor"),
+    ] {
+        if let Some(x) = font.other_stuff.get(glyphs_key).and_then(Plist::as_f64) {
+            entries.push((key, xml::number(x)));
+        }
+    }
+    if let Some(date) = font.other_stuff.get("date").and_then(Plist::as_str) {
+        // "2019-12-11 20:47:13 +0000" becomes "2019/12/11 20:47:13".
+        let date = date.get(..19).unwrap_or(date).replace('-', "/");
+        entries.push(("openTypeHeadCreated", string(&date)));
+    }
+    for &(key, param) in &[
+        ("openTypeNameLicense", "license"),
+        ("openTypeNameLicenseURL", "licenseURL"),
+        ("openTypeOS2VendorID", "vendorID"),
+    ] {
+        if let Some(s) = font.custom_parameter(param).and_then(Plist::as_str) {
+            entries.push((key, string(s)));
+        }
+    }
+    for &(key, param) in &[
+        ("openTypeOS2Type", "fsType"),
+        ("openTypeOS2Panose", "panose"),
+    ] {
+        if let Some(values) = font.custom_parameter(param).and_then(Plist::as_array) {
+            let mut array = Element::new("array");
+            for x in values.iter().filter_map(Plist::as_f64) {
+                array.push(xml::number(x));
+            }
+            entries.push((key, array));
+        }
+    }
+    let flag = |param| font.custom_parameter(param).and_then(Plist::as_i64) == Some(1);
+    if flag("Use Typo Metrics") {
+        entries.push((
+            "openTypeOS2Selection",
+            Element::new("array").child(xml::number(7.0)),
+        ));
+    }
+    if flag("isFixedPitch") {
+        entries.push(("postscriptIsFixedPitch", xml::boolean(true)));
+    }
+
+    let metric = |key: &str| master.other_stuff.get(key).and_then(Plist::as_f64);
+    let metrics = [
+        ("ascender", metric("ascender")),
+        ("descender", metric("descender")),
+        ("capHeight", metric("capHeight")),
+        ("xHeight", master.x_height),
+        ("italicAngle", master.italic_angle.map(|a| -a)),
+    ];
+    for &(key, value) in &metrics {
+        if let Some(x) = value {
+            entries.push((key, xml::number(x)));
+        }
+    }
+    for &(key, param) in &[
+        ("openTypeOS2TypoAscender", "typoAscender"),
+        ("openTypeOS2TypoDescender", "typoDescender"),
+        ("openTypeOS2TypoLineGap", "typoLineGap"),
+        ("openTypeOS2WinAscent", "winAscent"),
+        ("openTypeOS2WinDescent", "winDescent"),
+        ("openTypeHheaAscender", "hheaAscender"),
+        ("openTypeHheaDescender", "hheaDescender"),
+        ("openTypeHheaLineGap", "hheaLineGap"),
+        ("postscriptUnderlinePosition", "underlinePosition"),
+        ("postscriptUnderlineThickness", "underlineThickness"),
+    ] {
+        if let Some(x) = master.custom_parameter(param).and_then(Plist::as_f64) {
+            entries.push((key, xml::number(x)));
+        }
+    }
+
+    // The baseline zone and those above it are blue zones, the ones below
+    // are other blues.
+    let mut blues = Vec::new();
+    let mut other_blues = Vec::new();
+    for (position, size) in alignment_zones(master) {
+        let zone = if position >= 0.0 {
+            &mut blues
+        } else {
+            &mut other_blues
+        };
+        zone.push(position.min(position + size));
+        zone.push(position.max(position + size));
+    }
+    let horizontal_stems: Vec<f64> = master
+        .other_stuff
+        .get("horizontalStems")
+        .and_then(Plist::as_array)
+        .map(|a| a.iter().filter_map(Plist::as_f64).collect())
+        .unwrap_or_default();
+    let vertical_stems = master.vertical_stems.clone().unwrap_or_default();
+    for (key, mut values) in [
+        ("postscriptBlueValues", blues),
+        ("postscriptOtherBlues", other_blues),
+        ("postscriptStemSnapH", horizontal_stems),
+        ("postscriptStemSnapV", vertical_stems),
+    ] {
+        if !values.is_empty() {
+            if key.starts_with("postscriptStem") {
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            } else {
+                sort_zones(&mut values);
+            }
+            let mut array = Element::new("array");
+            for x in values {
+                array.push(xml::number(x));
+            }
+            entries.push((key, array));
+        }
+    }
+
+    let guides = master
+        .other_stuff
+        .get("guideLines")
+        .and_then(Plist::as_array);
+    if let Some(guides) = guides {
+        let mut array = Element::new("array");
+        for guide in guides {
+            let position = guide
+                .get("position")
+                .map_or(Point::ORIGIN, |p| Point::from_plist(p.clone()));
+            let mut entries = vec![
+                ("x".to_string(), xml::number(position.x)),
+                ("y".to_string(), xml::number(position.y)),
+                (
+                    "angle".to_string(),
+                    xml::number(guide.get("angle").and_then(Plist::as_f64).unwrap_or(0.0)),
+                ),
+            ];
+            if let Some(name) = guide.get("name").and_then(Plist::as_str) {
+                entries.push(("name".to_string(), string(name)));
+            }
+            array.push(xml::dict(entries));
+        }
+        entries.push(("guidelines", array));
+    }
+    let entries = entries
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    xml::plist_document(xml::dict(entries))
+}
+
+/// Sort the (bottom, top) pairs of zones by their bottom.
+fn sort_zones(values: &mut Vec<f64>) {
+    let mut pairs: Vec<(f64, f64)> = values.chunks(2).map(|c| (c[0], c[1])).collect();
+    pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    *values = pairs.into_iter().flat_map(|(a, b)| vec![a, b]).collect();
+}
+
+/// Kerning groups from the glyphs' kerning group names. The right group of
+/// a glyph is used when it is on the left of a pair, so it's a `kern1`
+/// group.
+fn groups(font: &Font) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for glyph in &font.glyphs {
+        for &(key, prefix) in &[
+            ("rightKerningGroup", "public.kern1."),
+            ("leftKerningGroup", "public.kern2."),
+        ] {
+            if let Some(group) = glyph.other_stuff.get(key).and_then(Plist::as_str) {
+                groups
+                    .entry(format!("{}{}", prefix, group))
+                    .or_default()
+                    .push(glyph.glyphname.clone());
+            }
+        }
+    }
+    groups
+}
+
+/// The kerning of a master with UFO keys: a left side group `x` is
+/// `public.kern1.x`, a right side group `public.kern2.x`.
+fn kerning(font: &Font, master: &FontMaster) -> BTreeMap<String, BTreeMap<String, f64>> {
+    let key = |side: &KernSide, prefix| match side {
+        KernSide::Glyph(name) => name.clone(),
+        KernSide::Group(group) => format!("{}{}", prefix, group),
+    };
+    let mut result: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+    for ((left, right), value) in Kerning::from_font(font, &master.id).pairs {
+        result
+            .entry(key(&left, "public.kern1."))
+            .or_default()
+            .insert(key(&right, "public.kern2."), value);
+    }
+    result
+}
+
+/// The feature file: prefixes, then classes, then features.
+pub fn features_fea(font: &Font) -> String {
+    let mut fea = String::new();
+    for prefix in font.feature_prefixes.iter().flatten() {
+        if !prefix.is_disabled() {
+            fea.push_str(prefix.code.trim_end());
+            fea.push_str("\n\n");
+        }
+    }
+    let classes: Vec<&FontClass> = font
+        .classes
+        .iter()
+        .flatten()
+        .filter(|c| !c.is_disabled())
+        .collect();
+    for class in &classes {
+        let names: Vec<&str> = class.glyph_names().collect();
+        fea.push_str(&format!("@{} = [{}];\n", class.name, names.join(" ")));
+    }
+    if !classes.is_empty() {
+        fea.push('\n');
+    }
+    for feature in font.features.iter().flatten() {
+        if feature.is_disabled() {
+            continue;
+        }
+        let tag = &feature.name;
+        fea.push_str(&format!("feature {} {{\n", tag));
+        for line in feature.code.trim_end().lines() {
+            fea.push_str(&format!("{}\n", line));
+        }
+        fea.push_str(&format!("}} {};\n\n", tag));
+    }
+    fea
+}
+
+fn lib(font: &Font, master: &FontMaster) -> String {
+    let mut entries = vec![(MASTER_ID_KEY.to_string(), string(&master.id))];
+    let mut order = Element::new("array");
+    for glyph in &font.glyphs {
+        order.push(string(&glyph.glyphname));
+    }
+    entries.push(("public.glyphOrder".to_string(), order));
+    let skipped: Vec<&Glyph> = font
+        .glyphs
+        .iter()
+        .filter(|g| g.other_stuff.get("export").and_then(Plist::as_i64) == Some(0))
+        .collect();
+    if !skipped.is_empty() {
+        let mut array = Element::new("array");
+        for glyph in skipped {
+            array.push(string(&glyph.glyphname));
+        }
+        entries.push(("public.skipExportGlyphs".to_string(), array));
+    }
+    xml::plist_document(xml::dict(entries))
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|e| format!("error writing {}: {}", path.display(), e))
+}
+
+fn write_ufo(font: &Font, master: &FontMaster, path: &Path) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    // Replace an earlier export, so glyphs that are gone don't linger.
+    if path.join("metainfo.plist").exists() {
+        fs::remove_dir_all(path).map_err(err)?;
+    }
+    let glyphs_dir = path.join("glyphs");
+    fs::create_dir_all(&glyphs_dir).map_err(err)?;
+
+    let metainfo = xml::dict(vec![
+        ("creator".to_string(), string("org.levien.glyphstool")),
+        ("formatVersion".to_string(), xml::number(3.0)),
+    ]);
+    write(&path.join("metainfo.plist"), &xml::plist_document(metainfo))?;
+    write(&path.join("fontinfo.plist"), &fontinfo(font, master))?;
+    let layer_contents = Element::new("array").child(
+        Element::new("array")
+            .child(string("public.default"))
+            .child(string("glyphs")),
+    );
+    write(
+        &path.join("layercontents.plist"),
+        &xml::plist_document(layer_contents),
+    )?;
+    write(&path.join("lib.plist"), &lib(font, master))?;
+    write(&path.join("features.fea"), &features_fea(font))?;
+
+    let groups = groups(font);
+    if !groups.is_empty() {
+        let entries = groups
+            .into_iter()
+            .map(|(name, glyphs)| {
+                let mut array = Element::new("array");
+                for glyph in glyphs {
+                    array.push(string(&glyph));
+                }
+                (name, array)
+            })
+            .collect();
+        write(
+            &path.join("groups.plist"),
+            &xml::plist_document(xml::dict(entries)),
+        )?;
+    }
+    let kerning = kerning(font, master);
+    if !kerning.is_empty() {
+        let entries = kerning
+            .into_iter()
+            .map(|(left, rights)| {
+                let rights = rights
+                    .into_iter()
+                    .map(|(right, value)| (right, xml::number(value)))
+                    .collect();
+                (left, xml::dict(rights))
+            })
+            .collect();
+        write(
+            &path.join("kerning.plist"),
+            &xml::plist_document(xml::dict(entries)),
+        )?;
+    }
+
+    let mut file_names = HashSet::new();
+    let mut contents = Vec::new();
+    for glyph in &font.glyphs {
+        let layer = match glyph.get_layer(&master.id) {
+            Some(layer) => layer,
+            None => continue,
+        };
+        let file_name = glif_file_name(&glyph.glyphname, &mut file_names);
+        write(&glyphs_dir.join(&file_name), &glif(glyph, layer))?;
+        contents.push((glyph.glyphname.clone(), string(&file_name)));
+    }
+    write(
+        &glyphs_dir.join("contents.plist"),
+        &xml::plist_document(xml::dict(contents)),
+    )
+}
+
+fn location(axes: &[Axis], values: &[f64]) -> Element {
+    let mut result = Element::new("location");
+    for (axis, value) in axes.iter().zip(values) {
+        result.push(
+            Element::new("dimension")
+                .attr("name", &axis.name)
+                .attr("xvalue", value),
+        );
+    }
+    result
+}
+
+fn master_values(font: &Font) -> Vec<Vec<f64>> {
+    font.font_master
+        .iter()
+        .map(|m| {
+            let location = master_location(font, &m.id).unwrap();
+            location.into_iter().map(|(_, v)| v).collect()
+        })
+        .collect()
+}
+
+/// An instance from the font's `instances`, with its value on each axis.
+type InstanceValues<'a> = (&'a Plist, Vec<f64>);
+
+/// The exported instances of the font, split into those within the extent
+/// of the masters on every axis and those outside.
+fn split_instances(font: &Font) -> (Vec<InstanceValues<'_>>, Vec<InstanceValues<'_>>) {
+    let master_values = master_values(font);
+    let extent = |i: usize| {
+        let values = master_values.iter().map(|v| v[i]);
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        (min, values.fold(f64::NEG_INFINITY, f64::max))
+    };
+    font.other_stuff
+        .get("instances")
+        .and_then(Plist::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .filter(|i| i.get("exports").and_then(Plist::as_i64) != Some(0))
+        .map(|i| {
+            let location = instance_location(font, i);
+            (
+                i,
+                location.into_iter().map(|(_, v)| v).collect::<Vec<f64>>(),
+            )
+        })
+        .partition(|(_, values)| {
+            values.iter().enumerate().all(|(i, v)| {
+                let (min, max) = extent(i);
+                min <= *v && *v <= max
+            })
+        })
+}
+
+/// The names of the exported instances that lie outside the masters. The
+/// designspace document leaves them out, as they could only be
+/// extrapolated, and its axes span the masters.
+pub fn instances_outside_masters(font: &Font) -> Vec<String> {
+    let (_, outside) = split_instances(font);
+    outside
+        .iter()
+        .map(|(i, _)| {
+            i.get("name")
+                .and_then(Plist::as_str)
+                .unwrap_or("Regular")
+                .to_string()
+        })
+        .collect()
+}
+
+fn designspace(font: &Font, ufo_names: &[String]) -> String {
+    let axes = axes(font);
+    let family = family_name(font);
+    let master_values = master_values(font);
+    let (instances, _) = split_instances(font);
+    let default_master = origin(font).map(|m| m.id.clone());
+    let default_values = font
+        .font_master
+        .iter()
+        .position(|m| Some(&m.id) == default_master.as_ref())
+        .map(|i| master_values[i].clone())
+        .unwrap_or_default();
+
+    let mut axes_element = Element::new("axes");
+    for (i, axis) in axes.iter().enumerate() {
+        let values = master_values.iter().map(|v| v[i]);
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        let max = values.fold(f64::NEG_INFINITY, f64::max);
+        axes_element.push(
+            Element::new("axis")
+                .attr("tag", &axis.tag)
+                .attr("name", &axis.name)
+                .attr("minimum", min)
+                .attr("maximum", max)
+                .attr("default", default_values.get(i).copied().unwrap_or(min)),
+        );
+    }
+
+    let mut sources = Element::new("sources");
+    for ((master, values), ufo_name) in font.font_master.iter().zip(&master_values).zip(ufo_names) {
+        let mut source = Element::new("source")
+            .attr("filename", ufo_name)
+            .attr("name", format!("{} {}", family, master.name()))
+            .attr("familyname", family)
+            .attr("stylename", master.name());
+        if Some(&master.id) == default_master.as_ref() {
+            source.push(Element::new("info").attr("copy", 1));
+        }
+        source.push(location(&axes, values));
+        sources.push(source);
+    }
+
+    let mut instances_element = Element::new("instances");
+    for (instance, values) in &instances {
+        let style = instance
+            .get("name")
+            .and_then(Plist::as_str)
+            .unwrap_or("Regular");
+        let ps_name = format!("{}-{}", family, style).replace(' ', "");
+        instances_element.push(
+            Element::new("instance")
+                .attr("name", format!("{} {}", family, style))
+                .attr("familyname", family)
+                .attr("stylename", style)
+                .attr("postscriptfontname", &ps_name)
+                .attr("filename", format!("instance_ufos/{}.ufo", ps_name))
+                .child(location(&axes, values)),
+        );
+    }
+
+    Element::new("designspace")
+        .attr("format", "4.1")
+        .child(axes_element)
+        .child(sources)
+        .child(instances_element)
+        .to_document()
+}
+
+/// Write a UFO for each master into `dir`, and a designspace document
+/// describing them. Returns the path of the designspace document.
+pub fn write_ufos(font: &Font, dir: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut names = Vec::new();
+    for master in &font.font_master {
+        let name = ufo_name(font, master);
+        write_ufo(font, master, &dir.join(&name))?;
+        names.push(name);
+    }
+    let path = dir.join(format!(
+        "{}.designspace",
+        family_name(font).replace(' ', "")
+    ));
+    write(&path, &designspace(font, &names))?;
+    Ok(path)
+}
+
+fn read_xml(path: &Path) -> Result<Element, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("error reading {}: {}", path.display(), e))?;
+    Element::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Read a plist file, or an empty dictionary if it doesn't exist.
+fn read_plist(path: &Path) -> Result<Plist, String> {
+    if !path.exists() {
+        return Ok(Plist::Dictionary(HashMap::new()));
+    }
+    xml::parse_plist(&read_xml(path)?).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn number_attr(element: &Element, name: &str, default: f64) -> Result<f64, String> {
+    match element.get(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("bad {} {:?} in <{}>", name, value, element.name)),
+        None => Ok(default),
+    }
+}
+
+fn point_attrs(element: &Element) -> Result<Point, String> {
+    Ok(Point::new(
+        number_attr(element, "x", 0.0)?,
+        number_attr(element, "y", 0.0)?,
+    ))
+}
+
+/// Convert a glif contour to a path.
+///
+/// A contour without a `move` point is closed. Glyphs puts the start node
+/// of a closed path last and UFO first, so closed contours are rotated left
+/// by one, undoing what `glif` does; a contour that ends on an off-curve
+/// point in Glyphs starts on one in UFO. Quadratic `qcurve` points become
+/// curve nodes, with their off-curve points kept as they are.
+fn contour_path(contour: &Element) -> Result<GlyphsPath, String> {
+    let points: Vec<&Element> = contour.children_named("point").collect();
+    let closed = points.first().and_then(|p| p.get("type")) != Some("move");
+    let mut path = GlyphsPath::new(closed);
+    for point in &points {
+        let smooth = point.get("smooth") == Some("yes");
+        let node_type = match point.get("type") {
+            None | Some("offcurve") => NodeType::OffCurve,
+            Some("move") | Some("line") if smooth => NodeType::LineSmooth,
+            Some("move") | Some("line") => NodeType::Line,
+            Some("curve") | Some("qcurve") if smooth => NodeType::CurveSmooth,
+            Some("curve") | Some("qcurve") => NodeType::Curve,
+            Some(other) => return Err(format!("unknown point type {}", other)),
+        };
+        path.add(point_attrs(point)?, node_type);
+    }
+    if closed {
+        path.rotate_left(1);
+    }
+    Ok(path)
+}
+
+fn component(element: &Element) -> Result<Component, String> {
+    let name = element
+        .get("base")
+        .ok_or_else(|| "component without base".to_string())?;
+    let keys = [
+        "xScale", "xyScale", "yxScale", "yScale", "xOffset", "yOffset",
+    ];
+    let identity = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    let mut c = identity;
+    for i in 0..6 {
+        c[i] = number_attr(element, keys[i], identity[i])?;
+    }
+    Ok(Component {
+        name: name.to_string(),
+        transform: if c == identity {
+            None
+        } else {
+            Some(Affine::new(c))
+        },
+        other_stuff: HashMap::new(),
+    })
+}
+
+/// Read a glif file into a layer, with the code points of the glyph.
+fn read_glif(path: &Path, layer_id: &str) -> Result<(Layer, Vec<u32>), String> {
+    let root = read_xml(path)?;
+    let wrap = |e: String| format!("{}: {}", path.display(), e);
+    let width = match root.find("advance") {
+        Some(advance) => number_attr(advance, "width", 0.0).map_err(wrap)?,
+        None => 0.0,
+    };
+    let unicodes = root
+        .children_named("unicode")
+        .filter_map(|u| u32::from_str_radix(u.get("hex")?, 16).ok())
+        .collect();
+    let mut paths = Vec::new();
+    let mut components = Vec::new();
+    if let Some(outline) = root.find("outline") {
+        for child in &outline.children {
+            match child.name.as_str() {
+                "contour" => paths.push(contour_path(child).map_err(wrap)?),
+                "component" => components.push(component(child).map_err(wrap)?),
+                _ => (),
+            }
+        }
+    }
+    let anchors = root
+        .children_named("anchor")
+        .map(|a| {
+            Ok(Anchor {
+                name: a.get("name").unwrap_or("").to_string(),
+                position: point_attrs(a)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(wrap)?;
+    let guide_lines = root
+        .children_named("guideline")
+        .map(|g| {
+            let angle = number_attr(g, "angle", 0.0)?;
+            Ok(GuideLine {
+                angle: if angle == 0.0 { None } else { Some(angle) },
+                position: point_attrs(g)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(wrap)?;
+    let some = |v: bool| if v { Some(()) } else { None };
+    let layer = Layer {
+        layer_id: layer_id.to_string(),
+        width,
+        paths: some(!paths.is_empty()).map(|_| paths),
+        components: some(!components.is_empty()).map(|_| components),
+        anchors: some(!anchors.is_empty()).map(|_| anchors),
+        guide_lines: some(!guide_lines.is_empty()).map(|_| guide_lines),
+        other_stuff: HashMap::new(),
+    };
+    Ok((layer, unicodes))
+}
+
+/// A master read from a UFO.
+struct UfoMaster {
+    master: FontMaster,
+    info: Plist,
+    lib: Plist,
+    groups: Plist,
+    kerning: Plist,
+    features: String,
+    glyphs: Vec<(String, Layer, Vec<u32>)>,
+}
+
+fn parameter(name: &str, value: Plist) -> Plist {
+    let mut dict = HashMap::new();
+    dict.insert("name".to_string(), Plist::String(name.to_string()));
+    dict.insert("value".to_string(), value);
+    Plist::Dictionary(dict)
+}
+
+fn numbers(plist: Option<&Plist>) -> Vec<f64> {
+    plist
+        .and_then(Plist::as_array)
+        .map(|a| a.iter().filter_map(Plist::as_f64).collect())
+        .unwrap_or_default()
+}
+
+/// Number plists are integers where possible, as Glyphs writes them.
+pub(crate) fn number_plist(x: f64) -> Plist {
+    if x.fract() == 0.0 {
+        Plist::Integer(x as i64)
+    } else {
+        Plist::Float(x)
+    }
+}
+
+/// Alignment zones in Glyphs form from the blue values: zones at and
+/// above the baseline are measured up from their bottom, the baseline
+/// zone and other blues down from their top.
+fn zones_from_blues(info: &Plist) -> Vec<Plist> {
+    let mut zones = Vec::new();
+    for (key, below) in &[
+        ("postscriptBlueValues", false),
+        ("postscriptOtherBlues", true),
+    ] {
+        for pair in numbers(info.get(key)).chunks(2) {
+            if let [bottom, top] = *pair {
+                if *below || top == 0.0 {
+                    zones.push((top, bottom - top));
+                } else {
+                    zones.push((bottom, top - bottom));
+                }
+            }
+        }
+    }
+    zones.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    zones
+        .into_iter()
+        .map(|(position, size)| Point::new(position, size).to_plist())
+        .collect()
+}
+
+fn read_master(path: &Path, location: &[f64], fallback_id: &str) -> Result<UfoMaster, String> {
+    let info = read_plist(&path.join("fontinfo.plist"))?;
+    let lib = read_plist(&path.join("lib.plist"))?;
+    let id = lib
+        .get(MASTER_ID_KEY)
+        .and_then(Plist::as_str)
+        .unwrap_or(fallback_id)
+        .to_string();
+
+    let mut other_stuff = HashMap::new();
+    let style = info
+        .get("styleName")
+        .and_then(Plist::as_str)
+        .unwrap_or("Regular");
+    other_stuff.insert("name".to_string(), Plist::String(style.to_string()));
+    for key in &["ascender", "descender", "capHeight"] {
+        if let Some(x) = info.get(key).and_then(Plist::as_f64) {
+            other_stuff.insert(key.to_string(), number_plist(x));
+        }
+    }
+    let zones = zones_from_blues(&info);
+    if !zones.is_empty() {
+        other_stuff.insert("alignmentZones".to_string(), Plist::Array(zones));
+    }
+    let horizontal_stems = numbers(info.get("postscriptStemSnapH"));
+    if !horizontal_stems.is_empty() {
+        let stems = horizontal_stems.into_iter().map(number_plist).collect();
+        other_stuff.insert("horizontalStems".to_string(), Plist::Array(stems));
+    }
+    let mut params = Vec::new();
+    for &(key, param) in MASTER_PARAMETERS {
+        if let Some(x) = info.get(key).and_then(Plist::as_f64) {
+            params.push(parameter(param, number_plist(x)));
+        }
+    }
+    if !params.is_empty() {
+        other_stuff.insert("customParameters".to_string(), Plist::Array(params));
+    }
+    if let Some(guides) = info.get("guidelines").and_then(Plist::as_array) {
+        let guides = guides
+            .iter()
+            .map(|g| {
+                let mut dict = HashMap::new();
+                let x = g.get("x").and_then(Plist::as_f64).unwrap_or(0.0);
+                let y = g.get("y").and_then(Plist::as_f64).unwrap_or(0.0);
+                dict.insert("position".to_string(), Point::new(x, y).to_plist());
+                if let Some(angle) = g.get("angle").and_then(Plist::as_f64) {
+                    if angle != 0.0 {
+                        dict.insert("angle".to_string(), number_plist(angle));
+                    }
+                }
+                if let Some(name) = g.get("name") {
+                    dict.insert("name".to_string(), name.clone());
+                }
+                Plist::Dictionary(dict)
+            })
+            .collect();
+        other_stuff.insert("guideLines".to_string(), Plist::Array(guides));
+    }
+    for (&(key, _), &value) in MASTER_KEYS.iter().zip(location).skip(2) {
+        other_stuff.insert(key.to_string(), number_plist(value));
+    }
+    let stems = numbers(info.get("postscriptStemSnapV"));
+    let master = FontMaster {
+        id: id.clone(),
+        weight_value: location.first().copied().unwrap_or(100.0).round() as i64,
+        width_value: location.get(1).map(|w| w.round() as i64),
+        x_height: info.get("xHeight").and_then(Plist::as_f64),
+        italic_angle: info
+            .get("italicAngle")
+            .and_then(Plist::as_f64)
+            .map(|a| -a + 0.0),
+        vertical_stems: if stems.is_empty() { None } else { Some(stems) },
+        other_stuff,
+    };
+
+    // The default layer is the first in layercontents.plist.
+    let layer_dir = read_plist(&path.join("layercontents.plist"))?
+        .as_array()
+        .and_then(|layers| {
+            layers
+                .first()?
+                .as_array()?
+                .get(1)?
+                .as_str()
+                .map(String::from)
+        })
+        .unwrap_or_else(|| "glyphs".to_string());
+    let glyphs_dir = path.join(layer_dir);
+    let contents = read_plist(&glyphs_dir.join("contents.plist"))?;
+    let mut names: Vec<&String> = contents
+        .as_dict()
+        .into_iter()
+        .flatten()
+        .map(|(k, _)| k)
+        .collect();
+    names.sort();
+    let mut glyphs = Vec::new();
+    for name in names {
+        let file_name = contents.get(name).and_then(Plist::as_str).unwrap_or("");
+        let (layer, unicodes) = read_glif(&glyphs_dir.join(file_name), &id)?;
+        glyphs.push((name.clone(), layer, unicodes));
+    }
+    let features = fs::read_to_string(path.join("features.fea")).unwrap_or_default();
+    Ok(UfoMaster {
+        master,
+        info,
+        lib,
+        groups: read_plist(&path.join("groups.plist"))?,
+        kerning: read_plist(&path.join("kerning.plist"))?,
+        features,
+        glyphs,
+    })
+}
+
+/// Master metrics stored as custom parameters in Glyphs.
+const MASTER_PARAMETERS: &[(&str, &str)] = &[
+    ("openTypeOS2TypoAscender", "typoAscender"),
+    ("openTypeOS2TypoDescender", "typoDescender"),
+    ("openTypeOS2TypoLineGap", "typoLineGap"),
+    ("openTypeOS2WinAscent", "winAscent"),
+    ("openTypeOS2WinDescent", "winDescent"),
+    ("openTypeHheaAscender", "hheaAscender"),
+    ("openTypeHheaDescender", "hheaDescender"),
+    ("openTypeHheaLineGap", "hheaLineGap"),
+    ("postscriptUnderlinePosition", "underlinePosition"),
+    ("postscriptUnderlineThickness", "underlineThickness"),
+];
+
+/// Font-wide names stored at the top level in Glyphs.
+const FONT_NAMES: &[(&str, &str)] = &[
+    ("familyName", "familyName"),
+    ("copyright", "copyright"),
+    ("openTypeNameDesigner", "designer"),
+    ("openTypeNameDesignerURL", "designerURL"),
+    ("openTypeNameManufacturer", "manufacturer"),
+    ("openTypeNameManufacturerURL", "manufacturerURL"),
+];
+
+/// Split a feature file written by `features_fea` back into prefixes,
+/// classes and features. Top-level class definitions and `feature` blocks
+/// are recognized line by line; everything else is prefix code.
+fn split_features(fea: &str) -> (Vec<FeatureCode>, Vec<FontClass>, Vec<FeatureCode>) {
+    let block = |name: &str, code: String| FeatureCode {
+        name: name.to_string(),
+        code,
+        other_stuff: HashMap::new(),
+    };
+    let mut prefix = String::new();
+    let mut classes = Vec::new();
+    let mut features = Vec::new();
+    let mut lines = fea.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let class = trimmed
+            .strip_prefix('@')
+            .and_then(|c| c.strip_suffix("];"))
+            .and_then(|c| {
+                let mut split = c.splitn(2, '=');
+                let name = split.next()?.trim();
+                let code = split.next()?.trim().strip_prefix('[')?;
+                Some((name, code))
+            });
+        let feature = trimmed
+            .strip_prefix("feature ")
+            .and_then(|f| f.strip_suffix('{'))
+            .map(str::trim);
+        if let Some((name, code)) = class {
+            classes.push(FontClass {
+                name: name.to_string(),
+                code: code.split_whitespace().collect::<Vec<_>>().join(" "),
+                other_stuff: HashMap::new(),
+            });
+        } else if let Some(tag) = feature {
+            let end = format!("}} {};", tag);
+            let mut code = String::new();
+            for line in lines.by_ref() {
+                if line.trim() == end {
+                    break;
+                }
+                code.push_str(line);
+                code.push('\n');
+            }
+            features.push(block(tag, code));
+        } else {
+            prefix.push_str(line);
+            prefix.push('\n');
+        }
+    }
+    let prefix = prefix.trim();
+    let prefixes = if prefix.is_empty() {
+        Vec::new()
+    } else {
+        vec![block("Prefix", format!("{}\n", prefix))]
+    };
+    (prefixes, classes, features)
+}
+
+/// Convert a UFO kerning key to Glyphs, the reverse of `kerning_key`.
+fn glyphs_kerning_key(key: &str) -> String {
+    if let Some(group) = key.strip_prefix("public.kern1.") {
+        format!("@MMK_L_{}", group)
+    } else if let Some(group) = key.strip_prefix("public.kern2.") {
+        format!("@MMK_R_{}", group)
+    } else {
+        key.to_string()
+    }
+}
+
+fn location_values(element: &Element, axes: &[(String, f64)]) -> Vec<f64> {
+    axes.iter()
+        .map(|(name, default)| {
+            element
+                .find("location")
+                .into_iter()
+                .flat_map(|l| l.children_named("dimension"))
+                .find(|d| d.get("name") == Some(name))
+                .and_then(|d| d.get("xvalue")?.parse().ok())
+                .unwrap_or(*default)
+        })
+        .collect()
+}
+
+impl Font {
+    /// Read the UFO 3 masters of a designspace document into a font.
+    ///
+    /// Masters keep the ID recorded by `write_ufos`, so a round trip
+    /// preserves them; other masters are identified by their source name.
+    /// The axes give the weight, width and custom values of masters and
+    /// instances, in order. Names, metrics, kerning groups and feature code come
+    /// from the UFOs, where font-wide data is taken from the first one.
+    pub fn from_designspace(path: &Path) -> Result<Font, String> {
+        let doc = read_xml(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let axes_element = doc.find("axes");
+        let axes: Vec<(String, String, f64)> = axes_element
+            .into_iter()
+            .flat_map(|a| a.children_named("axis"))
+            .map(|a| {
+                let name = a.get("name").unwrap_or("").to_string();
+                let tag = a.get("tag").unwrap_or("").to_string();
+                let default = number_attr(a, "default", 0.0).unwrap_or(0.0);
+                (name, tag, default)
+            })
+            .collect();
+        let axis_defaults: Vec<(String, f64)> =
+            axes.iter().map(|(n, _, d)| (n.clone(), *d)).collect();
+
+        let mut masters = Vec::new();
+        let sources = doc
+            .find("sources")
+            .into_iter()
+            .flat_map(|s| s.children_named("source"));
+        for (i, source) in sources.enumerate() {
+            let file_name = source
+                .get("filename")
+                .ok_or_else(|| format!("{}: source without filename", path.display()))?;
+            let fallback_id = source
+                .get("name")
+                .map_or_else(|| format!("master{}", i), String::from);
+            let location = location_values(source, &axis_defaults);
+            masters.push(read_master(&dir.join(file_name), &location, &fallback_id)?);
+        }
+        let first = masters
+            .first()
+            .ok_or_else(|| format!("{}: no sources", path.display()))?;
+
+        let mut other_stuff = HashMap::new();
+        for &(key, glyphs_key) in FONT_NAMES {
+            if let Some(s) = first.info.get(key) {
+                other_stuff.insert(glyphs_key.to_string(), s.clone());
+            }
+        }
+        for key in &["unitsPerEm", "versionMajor", "versionMinor"] {
+            if let Some(x) = first.info.get(key).and_then(Plist::as_f64) {
+                other_stuff.insert(key.to_string(), number_plist(x));
+            }
+        }
+        if let Some(date) = first
+            .info
+            .get("openTypeHeadCreated")
+            .and_then(Plist::as_str)
+        {
+            let date = format!("{} +0000", date.replacen('/', "-", 2));
+            other_stuff.insert("date".to_string(), Plist::String(date));
+        }
+
+        let mut params = Vec::new();
+        for &(key, param) in &[
+            ("openTypeOS2Type", "fsType"),
+            ("openTypeOS2Panose", "panose"),
+        ] {
+            if let Some(values) = first.info.get(key) {
+                params.push(parameter(param, values.clone()));
+            }
+        }
+        let selection = numbers(first.info.get("openTypeOS2Selection"));
+        if selection.contains(&7.0) {
+            params.push(parameter("Use Typo Metrics", Plist::Integer(1)));
+        }
+        if first
+            .info
+            .get("postscriptIsFixedPitch")
+            .and_then(Plist::as_i64)
+            == Some(1)
+        {
+            params.push(parameter("isFixedPitch", Plist::Integer(1)));
+        }
+        for &(key, param) in &[
+            ("openTypeNameLicense", "license"),
+            ("openTypeOS2VendorID", "vendorID"),
+            ("openTypeNameLicenseURL", "licenseURL"),
+        ] {
+            if let Some(s) = first.info.get(key) {
+                params.push(parameter(param, s.clone()));
+            }
+        }
+        if !axes.is_empty() {
+            let axes = axes
+                .iter()
+                .map(|(name, tag, _)| {
+                    let mut dict = HashMap::new();
+                    dict.insert("Name".to_string(), Plist::String(name.clone()));
+                    dict.insert("Tag".to_string(), Plist::String(tag.clone()));
+                    Plist::Dictionary(dict)
+                })
+                .collect();
+            params.push(parameter("Axes", Plist::Array(axes)));
+        }
+        other_stuff.insert("customParameters".to_string(), Plist::Array(params));
+
+        let (prefixes, classes, features) = split_features(&first.features);
+
+        let instances: Vec<Plist> = doc
+            .find("instances")
+            .into_iter()
+            .flat_map(|i| i.children_named("instance"))
+            .map(|instance| {
+                let mut dict = HashMap::new();
+                let name = instance.get("stylename").unwrap_or("Regular");
+                dict.insert("name".to_string(), Plist::String(name.to_string()));
+                let values = location_values(instance, &axis_defaults);
+                // Glyphs leaves out the values that are the default.
+                for (&(key, default), value) in INSTANCE_KEYS.iter().zip(values) {
+                    if value != default {
+                        dict.insert(key.to_string(), number_plist(value));
+                    }
+                }
+                Plist::Dictionary(dict)
+            })
+            .collect();
+        if !instances.is_empty() {
+            other_stuff.insert("instances".to_string(), Plist::Array(instances));
+        }
+
+        let mut kerning = HashMap::new();
+        for m in &masters {
+            let mut pairs = HashMap::new();
+            for (left, rights) in m.kerning.as_dict().into_iter().flatten() {
+                let rights = rights
+                    .as_dict()
+                    .into_iter()
+                    .flatten()
+                    .map(|(right, value)| (glyphs_kerning_key(right), value.clone()))
+                    .collect();
+                pairs.insert(glyphs_kerning_key(left), Plist::Dictionary(rights));
+            }
+            if !pairs.is_empty() {
+                kerning.insert(m.master.id.clone(), Plist::Dictionary(pairs));
+            }
+        }
+        if !kerning.is_empty() {
+            other_stuff.insert("kerning".to_string(), Plist::Dictionary(kerning));
+        }
+
+        // Glyphs in the order of the first master, then any others.
+        let mut order: Vec<String> = first
+            .lib
+            .get("public.glyphOrder")
+            .and_then(Plist::as_array)
+            .unwrap_or(&[])
+            .iter()
+            .filter_map(|name| name.as_str().map(String::from))
+            .collect();
+        for m in &masters {
+            for (name, _, _) in &m.glyphs {
+                if !order.contains(name) {
+                    order.push(name.clone());
+                }
+            }
+        }
+        let skipped = numbers_or_names(first.lib.get("public.skipExportGlyphs"));
+        let mut glyphs = Vec::new();
+        for name in order {
+            let mut glyph = Glyph {
+                layers: Vec::new(),
+                glyphname: name.clone(),
+                other_stuff: HashMap::new(),
+            };
+            for m in &masters {
+                if let Some((_, layer, unicodes)) = m.glyphs.iter().find(|g| g.0 == name) {
+                    glyph.layers.push(layer.clone());
+                    if !unicodes.is_empty() && !glyph.other_stuff.contains_key("unicode") {
+                        let hex: Vec<String> =
+                            unicodes.iter().map(|u| format!("{:04X}", u)).collect();
+                        glyph
+                            .other_stuff
+                            .insert("unicode".to_string(), Plist::String(hex.join(",")));
+                    }
+                }
+            }
+            if glyph.layers.is_empty() {
+                continue;
+            }
+            for (prefix, key) in &[
+                ("public.kern1.", "rightKerningGroup"),
+                ("public.kern2.", "leftKerningGroup"),
+            ] {
+                let group = first
+                    .groups
+                    .as_dict()
+                    .into_iter()
+                    .flatten()
+                    .find(|(g, members)| {
+                        g.starts_with(prefix) && numbers_or_names(Some(members)).contains(&name)
+                    });
+                if let Some((group, _)) = group {
+                    let group = group[prefix.len()..].to_string();
+                    glyph
+                        .other_stuff
+                        .insert(key.to_string(), Plist::String(group));
+                }
+            }
+            if skipped.contains(&name) {
+                glyph
+                    .other_stuff
+                    .insert("export".to_string(), Plist::Integer(0));
+            }
+            glyphs.push(glyph);
+        }
+
+        Ok(Font {
+            glyphs,
+            font_master: masters.into_iter().map(|m| m.master).collect(),
+            classes: non_empty(classes),
+            feature_prefixes: non_empty(prefixes),
+            features: non_empty(features),
+            other_stuff,
+        })
+    }
+}
+
+fn non_empty<T>(items: Vec<T>) -> Option<Vec<T>> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
+
+fn numbers_or_names(plist: Option<&Plist>) -> Vec<String> {
+    plist
+        .and_then(Plist::as_array)
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|name| name.as_str().map(String::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(path: &GlyphsPath) -> Vec<(f64, f64, NodeType)> {
+        let nodes = path.nodes.iter();
+        nodes.map(|n| (n.pt.x, n.pt.y, n.node_type)).collect()
+    }
+
+    #[test]
+    fn glif_round_trip_keeps_node_order() {
+        let glyph = Glyph::from_plist(
+            Plist::parse(
+                r#"{
+glyphname = test;
+layers = (
+{
+layerId = m01;
+width = 500;
+paths = (
+{
+closed = 1;
+nodes = (
+"300 200 CURVE SMOOTH",
+"300 300 OFFCURVE",
+"200 400 OFFCURVE",
+"100 400 CURVE",
+"50 300 OFFCURVE",
+"50 100 OFFCURVE",
+"100 0 CURVE",
+"200 0 LINE",
+"250 50 OFFCURVE",
+"300 100 OFFCURVE"
+);
+},
+{
+closed = 1;
+nodes = (
+"0 0 LINE",
+"0 100 LINE",
+"100 100 LINE"
+);
+},
+{
+closed = 0;
+nodes = (
+"0 0 LINE",
+"50 50 OFFCURVE",
+"100 50 OFFCURVE",
+"150 0 CURVE"
+);
+}
+);
+}
+);
+}"#,
+            )
+            .unwrap(),
+        );
+        let root = Element::parse(&glif(&glyph, &glyph.layers[0])).unwrap();
+        let contours = root.find("outline").unwrap().children_named("contour");
+        let paths: Vec<GlyphsPath> = contours.map(|c| contour_path(c).unwrap()).collect();
+        let original = glyph.layers[0].paths.as_ref().unwrap();
+        assert_eq!(paths.len(), original.len());
+        for (path, original) in paths.iter().zip(original) {
+            assert_eq!(path.closed, original.closed);
+            assert_eq!(nodes(path), nodes(original));
+        }
+    }
+
+    #[test]
+    fn designspace_axes_span_the_masters() {
+        let font = Font::from_plist(
+            Plist::parse(
+                r#"{
+familyName = Test;
+fontMaster = ({id = light; weightValue = 200;}, {id = bold; weightValue = 900;});
+glyphs = ();
+instances = (
+{name = Thin; interpolationWeight = 100;},
+{name = Regular; interpolationWeight = 400;},
+{name = Black; interpolationWeight = 900;}
+);
+}"#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(instances_outside_masters(&font), ["Thin"]);
+        let names = vec!["Light.ufo".to_string(), "Bold.ufo".to_string()];
+        let doc = Element::parse(&designspace(&font, &names)).unwrap();
+        let axis = doc.find("axes").unwrap().find("axis").unwrap();
+        assert_eq!(axis.get("minimum"), Some("200"));
+        assert_eq!(axis.get("maximum"), Some("900"));
+        let instances = doc.find("instances").unwrap().children_named("instance");
+        let styles: Vec<&str> = instances.map(|i| i.get("stylename").unwrap()).collect();
+        assert_eq!(styles, ["Regular", "Black"]);
+    }
+}
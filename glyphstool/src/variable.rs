@@ -0,0 +1,1097 @@
+//! Compilation of the masters to a TrueType variable font.
+//!
+//! The masters must form a full grid, as for interpolation. The default
+//! master, named by the `Variable Font Origin` parameter or else the one at
+//! the axis defaults, gives the `glyf` outlines and the tables a static
+//! font has; the other masters become deltas in `gvar`, and in `HVAR` for
+//! advance widths. Each delta set is scaled by a tent over the neighbouring
+//! masters on each axis, so instances match the piecewise bilinear
+//! interpolation of `interpolate`. The `compare` command checks a build
+//! against another font, point by point along the outlines.
+//!
+//! Axes are in user space in `fvar`. The mapping to the design space of
+//! the masters comes from the `Axis Mappings` font parameter or the `Axis
+//! Location` parameters of the masters, and is written to `avar` when it
+//! isn't the identity. Named instances and `STAT` come from the font's
+//! instances; those outside the range of the masters are left out, as a
+//! variable font can't extrapolate.
+
+use std::collections::HashMap;
+
+use kurbo::{Affine, Point};
+
+use crate::compile::{
+    common_tables, glyph_index, glyph_order, instances, notdef_first, Context, Instance, Outlines,
+};
+use crate::fea::update_automatic_classes;
+use crate::font::{Anchor, Font, FontMaster, Glyph, Layer};
+use crate::glyf::{build_glyf, component_ok, quad_contours_compatible, GlyphOutline};
+use crate::interpolate::{master_location, origin};
+use crate::layout::{is_mark_anchor, layout_tables, AnchorPoint, MarkAnchors};
+use crate::param::piecewise;
+use crate::plist::Plist;
+use crate::sfnt::{font_file, to_i16, Writer};
+use crate::ufo::axes;
+
+/// How far, in font units, deltas inferred by IUP may be from the exact
+/// deltas.
+const IUP_TOLERANCE: f64 = 0.5;
+
+/// The order of axes in `STAT`, as Google Fonts has it. Other axes follow.
+const STAT_ORDER: &[&str] = &["opsz", "wdth", "wght", "ital", "slnt"];
+
+/// `STAT` names of weight classes 100 to 900.
+const WEIGHT_NAMES: [&str; 9] = [
+    "Thin",
+    "ExtraLight",
+    "Light",
+    "Regular",
+    "Medium",
+    "SemiBold",
+    "Bold",
+    "ExtraBold",
+    "Black",
+];
+
+/// `STAT` names of width classes 1 to 9.
+const WIDTH_NAMES: [&str; 9] = [
+    "UltraCondensed",
+    "ExtraCondensed",
+    "Condensed",
+    "SemiCondensed",
+    "Normal",
+    "SemiExpanded",
+    "Expanded",
+    "ExtraExpanded",
+    "UltraExpanded",
+];
+
+/// An axis along which the masters vary.
+struct VarAxis {
+    tag: [u8; 4],
+    name: String,
+    /// Which value of a location the axis is, in the order of the font's
+    /// axes.
+    index: usize,
+    /// The design values of the masters, sorted.
+    values: Vec<f64>,
+    /// The design value of the default master.
+    default: f64,
+    /// Pairs of user and design values, by design value, or empty for the
+    /// identity.
+    mapping: Vec<(f64, f64)>,
+}
+
+impl VarAxis {
+    fn min(&self) -> f64 {
+        self.values[0]
+    }
+
+    fn max(&self) -> f64 {
+        self.values[self.values.len() - 1]
+    }
+
+    fn normalize(&self, design: f64) -> f64 {
+        normalize(design, self.min(), self.default, self.max())
+    }
+
+    /// The user value of a design value, extending the mapping beyond its
+    /// ends along the first and last pieces. A single pair is an offset.
+    fn to_user(&self, design: f64) -> f64 {
+        let table: Vec<[f64; 2]> = self.mapping.iter().map(|&(u, d)| [d, u]).collect();
+        match table.len() {
+            0 => design,
+            1 => design - table[0][0] + table[0][1],
+            _ => piecewise(&table, design),
+        }
+    }
+
+    /// The normalized user value of each mapping pair, with the normalized
+    /// design value it maps to.
+    fn avar_pairs(&self) -> Vec<(f64, f64)> {
+        let (min, default, max) = (
+            self.to_user(self.min()),
+            self.to_user(self.default),
+            self.to_user(self.max()),
+        );
+        let mut pairs: Vec<(f64, f64)> = self
+            .mapping
+            .iter()
+            .map(|&(u, d)| (normalize(u, min, default, max), self.normalize(d)))
+            .filter(|&(u, _)| (-1.0..=1.0).contains(&u))
+            .chain(vec![(-1.0, -1.0), (0.0, 0.0), (1.0, 1.0)])
+            .collect();
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        pairs.dedup_by(|a, b| a.0 == b.0);
+        pairs
+    }
+}
+
+/// Map a value into [-1, 1], with the default at 0.
+fn normalize(v: f64, min: f64, default: f64, max: f64) -> f64 {
+    if v < default {
+        (v - default) / (default - min)
+    } else if v > default {
+        (v - default) / (max - default)
+    } else {
+        0.0
+    }
+}
+
+/// User and design value pairs for an axis, from the `Axis Mappings` font
+/// parameter or else the `Axis Location` parameters of the masters, sorted
+/// by design value with one pair for each.
+fn axis_mapping(font: &Font, tag: &str, name: &str, index: usize) -> Vec<(f64, f64)> {
+    let mut pairs = axis_mapping_pairs(font, tag, name, index);
+    pairs.sort_by(|a, b| (a.1, a.0).partial_cmp(&(b.1, b.0)).unwrap());
+    pairs.dedup_by(|a, b| a.1 == b.1);
+    pairs
+}
+
+fn axis_mapping_pairs(font: &Font, tag: &str, name: &str, index: usize) -> Vec<(f64, f64)> {
+    if let Some(map) = font
+        .custom_parameter("Axis Mappings")
+        .and_then(|m| m.get(tag))
+        .and_then(Plist::as_dict)
+    {
+        return map
+            .iter()
+            .filter_map(|(user, design)| Some((user.parse().ok()?, design.as_f64()?)))
+            .collect();
+    }
+    font.font_master
+        .iter()
+        .filter_map(|m| {
+            let user = m
+                .custom_parameter("Axis Location")?
+                .as_array()?
+                .iter()
+                .find(|l| l.get("Axis").and_then(Plist::as_str) == Some(name))?
+                .get("Location")?
+                .as_f64()?;
+            let location = master_location(font, &m.id)?;
+            Some((user, location[index].1))
+        })
+        .collect()
+}
+
+/// The axes along which the masters vary. The masters must form a full
+/// grid over them.
+fn var_axes(font: &Font) -> Result<Vec<VarAxis>, String> {
+    let default = origin(font).ok_or("font has no masters")?;
+    let default = master_location(font, &default.id).unwrap();
+    let mut result = Vec::new();
+    for (index, axis) in axes(font).iter().enumerate() {
+        let mut values: Vec<f64> = font
+            .font_master
+            .iter()
+            .map(|m| master_location(font, &m.id).unwrap()[index].1)
+            .collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        values.dedup();
+        if values.len() < 2 {
+            continue;
+        }
+        let mut tag = *b"    ";
+        for (t, b) in tag.iter_mut().zip(axis.tag.bytes()) {
+            *t = b;
+        }
+        result.push(VarAxis {
+            tag,
+            name: axis.name.clone(),
+            index,
+            values,
+            default: default[index].1,
+            mapping: axis_mapping(font, &axis.tag, &axis.name, index),
+        });
+    }
+    if result.is_empty() {
+        return Err("the masters don't vary along any axis".to_string());
+    }
+    Ok(result)
+}
+
+/// The location of a master in design space, on each axis.
+fn location(font: &Font, master: &FontMaster, axes: &[VarAxis]) -> Vec<f64> {
+    let location = master_location(font, &master.id).unwrap();
+    axes.iter().map(|a| location[a.index].1).collect()
+}
+
+/// The deltas of a non-default master: the tent over which they apply,
+/// as (start, peak, end) on each axis in normalized values, and the
+/// masters they're made from, with signs.
+struct Variation {
+    tent: Vec<(f64, f64, f64)>,
+    terms: Vec<(usize, f64)>,
+}
+
+impl Variation {
+    /// Whether the tent is other than the one implied by the peak.
+    fn intermediate(&self) -> bool {
+        self.tent
+            .iter()
+            .any(|&(start, peak, end)| start != peak.min(0.0) || end != peak.max(0.0))
+    }
+}
+
+/// The variations of the font, one for each master but the default.
+///
+/// The deltas of a master are those of the bilinear interpolation in the
+/// cell between it and the default: the master minus the masters at the
+/// corners nearer the default, by inclusion and exclusion.
+fn variations(locations: &[Vec<f64>], default: usize, axes: &[VarAxis]) -> Vec<Variation> {
+    let find = |loc: &[f64]| locations.iter().position(|l| l.as_slice() == loc);
+    let mut result = Vec::new();
+    for (m, loc) in locations.iter().enumerate() {
+        if m == default {
+            continue;
+        }
+        let tent = axes
+            .iter()
+            .zip(loc)
+            .map(|(axis, &v)| {
+                let peak = axis.normalize(v);
+                if peak == 0.0 {
+                    return (0.0, 0.0, 0.0);
+                }
+                let values: Vec<f64> = axis.values.iter().map(|&v| axis.normalize(v)).collect();
+                let below = values.iter().rev().find(|&&x| x < peak).copied();
+                let above = values.iter().find(|&&x| x > peak).copied();
+                if peak > 0.0 {
+                    (below.unwrap_or(0.0), peak, above.unwrap_or(peak))
+                } else {
+                    (below.unwrap_or(peak), peak, above.unwrap_or(0.0))
+                }
+            })
+            .collect();
+        let moved: Vec<usize> = (0..axes.len())
+            .filter(|&a| loc[a] != axes[a].default)
+            .collect();
+        let terms = (0..1usize << moved.len())
+            .map(|reset| {
+                let mut corner = loc.clone();
+                let mut sign = 1.0;
+                for (bit, &a) in moved.iter().enumerate() {
+                    if reset & 1 << bit != 0 {
+                        corner[a] = axes[a].default;
+                        sign = -sign;
+                    }
+                }
+                (find(&corner).unwrap(), sign)
+            })
+            .collect();
+        result.push(Variation { tent, terms });
+    }
+    result
+}
+
+/// A glyph in every master, with the same points in each.
+struct VarGlyph {
+    /// The outline in the default master.
+    outline: GlyphOutline,
+    /// The points in each master: those of the contours, or the component
+    /// offsets, followed by the four phantom points.
+    points: Vec<Vec<(f64, f64)>>,
+    /// The number of points in each contour; empty for a composite.
+    contours: Vec<usize>,
+}
+
+/// The components of a glyph in each master, if it can stay a composite:
+/// the masters must have the same components, varying only in their
+/// offsets, as `gvar` can't vary the rest of a transform.
+fn var_components(
+    layers: &[&Layer],
+    index: &HashMap<&str, usize>,
+) -> Option<Vec<Vec<(usize, Affine)>>> {
+    let linear = |t: Affine| {
+        let c = t.as_coeffs();
+        [c[0], c[1], c[2], c[3]]
+    };
+    let mut result: Vec<Vec<(usize, Affine)>> = Vec::new();
+    for layer in layers {
+        let components = layer.components.as_deref().unwrap_or(&[]);
+        let has_paths = layer.paths.as_ref().is_some_and(|p| !p.is_empty());
+        if components.is_empty() || has_paths {
+            return None;
+        }
+        let refs: Vec<(usize, Affine)> = components
+            .iter()
+            .map(|c| {
+                let transform = c.transform.unwrap_or_default();
+                let ix = *index.get(c.name.as_str())?;
+                Some((ix, transform)).filter(|_| component_ok(transform))
+            })
+            .collect::<Option<_>>()?;
+        if let Some(first) = result.first() {
+            let same = first.len() == refs.len()
+                && first
+                    .iter()
+                    .zip(&refs)
+                    .all(|(a, b)| a.0 == b.0 && linear(a.1) == linear(b.1));
+            if !same {
+                return None;
+            }
+        }
+        result.push(refs);
+    }
+    Some(result)
+}
+
+/// The glyph in every master: a composite if it can stay one, and
+/// otherwise its decomposed outlines, converted to quadratics together.
+fn var_glyph(
+    font: &Font,
+    masters: &[&FontMaster],
+    default: usize,
+    glyph: &Glyph,
+    index: &HashMap<&str, usize>,
+) -> Result<VarGlyph, String> {
+    let layers: Vec<&Layer> = masters
+        .iter()
+        .map(|m| {
+            glyph.get_layer(&m.id).ok_or_else(|| {
+                format!(
+                    "glyph {} has no layer for master {}",
+                    glyph.glyphname,
+                    m.name()
+                )
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    let phantom = |layer: &Layer| {
+        let advance = if glyph.is_nonspacing_mark() {
+            0.0
+        } else {
+            layer.width.round().max(0.0)
+        };
+        vec![(0.0, 0.0), (advance, 0.0), (0.0, 0.0), (0.0, 0.0)]
+    };
+    if let Some(components) = var_components(&layers, index) {
+        let points = components
+            .iter()
+            .zip(&layers)
+            .map(|(refs, layer)| {
+                let mut points: Vec<(f64, f64)> = refs
+                    .iter()
+                    .map(|(_, t)| {
+                        let c = t.as_coeffs();
+                        (c[4].round(), c[5].round())
+                    })
+                    .collect();
+                points.extend(phantom(layer));
+                points
+            })
+            .collect();
+        return Ok(VarGlyph {
+            outline: GlyphOutline::Composite(components[default].clone()),
+            points,
+            contours: Vec::new(),
+        });
+    }
+    let paths: Vec<_> = layers.iter().map(|l| font.decompose(l)).collect();
+    let slices: Vec<&[_]> = paths.iter().map(Vec::as_slice).collect();
+    let contours = quad_contours_compatible(&slices)
+        .ok_or_else(|| format!("glyph {} has incompatible masters", glyph.glyphname))?;
+    let points = contours
+        .iter()
+        .zip(&layers)
+        .map(|(contours, layer)| {
+            let mut points: Vec<(f64, f64)> =
+                contours.iter().flatten().map(|&(x, y, _)| (x, y)).collect();
+            points.extend(phantom(layer));
+            points
+        })
+        .collect();
+    Ok(VarGlyph {
+        outline: GlyphOutline::Simple(contours[default].clone()),
+        points,
+        contours: contours[default].iter().map(Vec::len).collect(),
+    })
+}
+
+/// The delta of an unreferenced point on one axis, inferred from the
+/// referenced points before and after it in the contour. `None` where
+/// implementations disagree: the references are at the same coordinate
+/// with different deltas.
+fn iup_value(x: f64, (x1, d1): (f64, f64), (x2, d2): (f64, f64)) -> Option<f64> {
+    if x1 == x2 {
+        return Some(d1).filter(|_| d1 == d2);
+    }
+    let ((x1, d1), (x2, d2)) = if x1 < x2 {
+        ((x1, d1), (x2, d2))
+    } else {
+        ((x2, d2), (x1, d1))
+    };
+    Some(if x <= x1 {
+        d1
+    } else if x >= x2 {
+        d2
+    } else {
+        d1 + (x - x1) * (d2 - d1) / (x2 - x1)
+    })
+}
+
+/// Whether interpolating the deltas of the referenced points of a
+/// contour gives the deltas of the others within the tolerance.
+fn iup_ok(coords: &[(f64, f64)], deltas: &[(f64, f64)], refs: &[bool]) -> bool {
+    let n = coords.len();
+    let referenced: Vec<usize> = (0..n).filter(|&k| refs[k]).collect();
+    let close = |k: usize, d: (f64, f64)| {
+        (d.0 - deltas[k].0).abs() <= IUP_TOLERANCE && (d.1 - deltas[k].1).abs() <= IUP_TOLERANCE
+    };
+    match referenced.len() {
+        0 => (0..n).all(|k| close(k, (0.0, 0.0))),
+        1 => (0..n).all(|k| close(k, deltas[referenced[0]])),
+        r => (0..r).all(|i| {
+            let (a, b) = (referenced[i], referenced[(i + 1) % r]);
+            let mut k = (a + 1) % n;
+            while k != b {
+                let x = iup_value(
+                    coords[k].0,
+                    (coords[a].0, deltas[a].0),
+                    (coords[b].0, deltas[b].0),
+                );
+                let y = iup_value(
+                    coords[k].1,
+                    (coords[a].1, deltas[a].1),
+                    (coords[b].1, deltas[b].1),
+                );
+                match (x, y) {
+                    (Some(x), Some(y)) if close(k, (x, y)) => (),
+                    _ => return false,
+                }
+                k = (k + 1) % n;
+            }
+            true
+        }),
+    }
+}
+
+/// The points of a contour whose deltas must be given, leaving out those
+/// that IUP infers closely enough from the others.
+fn iup_optimize(coords: &[(f64, f64)], deltas: &[(f64, f64)]) -> Vec<bool> {
+    let mut refs = vec![true; coords.len()];
+    for k in 0..coords.len() {
+        refs[k] = false;
+        if !iup_ok(coords, deltas, &refs) {
+            refs[k] = true;
+        }
+    }
+    refs
+}
+
+/// Packed point numbers; `None` means all points.
+fn packed_points(w: &mut Writer, points: Option<&[usize]>) {
+    let points = match points {
+        Some(points) => points,
+        None => {
+            w.u8(0);
+            return;
+        }
+    };
+    if points.len() < 0x80 {
+        w.u8(points.len() as u8);
+    } else {
+        w.u16(0x8000 | points.len() as u16);
+    }
+    let diffs: Vec<usize> = points
+        .iter()
+        .scan(0, |last, &p| {
+            let d = p - *last;
+            *last = p;
+            Some(d)
+        })
+        .collect();
+    let mut i = 0;
+    while i < diffs.len() {
+        let words = diffs[i] > 0xFF;
+        let mut run = 1;
+        while i + run < diffs.len() && run < 128 && (diffs[i + run] > 0xFF) == words {
+            run += 1;
+        }
+        w.u8((run - 1) as u8 | if words { 0x80 } else { 0 });
+        for &d in &diffs[i..i + run] {
+            if words {
+                w.u16(d as u16);
+            } else {
+                w.u8(d as u8);
+            }
+        }
+        i += run;
+    }
+}
+
+/// Packed deltas, in runs of zeros, bytes and words.
+fn packed_deltas(w: &mut Writer, deltas: &[i16]) {
+    let byte = |d: i16| (i8::MIN as i16..=i8::MAX as i16).contains(&d);
+    let mut i = 0;
+    while i < deltas.len() {
+        let mut run = 1;
+        if deltas[i] == 0 {
+            while i + run < deltas.len() && run < 64 && deltas[i + run] == 0 {
+                run += 1;
+            }
+            w.u8(0x80 | (run - 1) as u8);
+        } else if byte(deltas[i]) {
+            // A lone zero costs less inside a run of bytes than as a run
+            // of its own.
+            let fits = |k: usize| {
+                byte(deltas[k]) && (deltas[k] != 0 || deltas.get(k + 1).is_some_and(|&d| d != 0))
+            };
+            while i + run < deltas.len() && run < 64 && fits(i + run) {
+                run += 1;
+            }
+            w.u8((run - 1) as u8);
+            for &d in &deltas[i..i + run] {
+                w.u8(d as i8 as u8);
+            }
+        } else {
+            while i + run < deltas.len()
+                && run < 64
+                && deltas[i + run] != 0
+                && !byte(deltas[i + run])
+            {
+                run += 1;
+            }
+            w.u8(0x40 | (run - 1) as u8);
+            for &d in &deltas[i..i + run] {
+                w.i16(d);
+            }
+        }
+        i += run;
+    }
+}
+
+/// The deltas of a glyph for a variation, rounded.
+fn glyph_deltas(glyph: &VarGlyph, variation: &Variation) -> Vec<(f64, f64)> {
+    (0..glyph.points[0].len())
+        .map(|k| {
+            let (mut dx, mut dy) = (0.0, 0.0);
+            for &(m, sign) in &variation.terms {
+                dx += sign * glyph.points[m][k].0;
+                dy += sign * glyph.points[m][k].1;
+            }
+            (dx.round(), dy.round())
+        })
+        .collect()
+}
+
+/// The glyph variation data of a glyph, with private point numbers for
+/// each tuple.
+fn glyph_variations(glyph: &VarGlyph, variations: &[Variation], default: &[(f64, f64)]) -> Vec<u8> {
+    let mut headers = Writer::new();
+    let mut data = Writer::new();
+    let mut count = 0;
+    for (i, variation) in variations.iter().enumerate() {
+        let deltas = glyph_deltas(glyph, variation);
+        if deltas.iter().all(|&d| d == (0.0, 0.0)) {
+            continue;
+        }
+        // Composites don't use inferred deltas, so only the points that
+        // move are given.
+        let mut refs: Vec<bool> = deltas.iter().map(|&d| d != (0.0, 0.0)).collect();
+        let mut start = 0;
+        for &n in &glyph.contours {
+            let range = start..start + n;
+            let contour_refs = iup_optimize(&default[range.clone()], &deltas[range.clone()]);
+            refs[range].copy_from_slice(&contour_refs);
+            start += n;
+        }
+        let points: Vec<usize> = (0..refs.len()).filter(|&k| refs[k]).collect();
+        let mut w = Writer::new();
+        packed_points(
+            &mut w,
+            Some(points.as_slice()).filter(|p| p.len() < refs.len()),
+        );
+        let xs: Vec<i16> = points.iter().map(|&k| deltas[k].0 as i16).collect();
+        let ys: Vec<i16> = points.iter().map(|&k| deltas[k].1 as i16).collect();
+        packed_deltas(&mut w, &xs);
+        packed_deltas(&mut w, &ys);
+
+        // An embedded peak is never needed, as the peaks are shared.
+        let mut flags = 0x2000 | i as u16;
+        if variation.intermediate() {
+            flags |= 0x4000;
+        }
+        headers.u16(w.len() as u16).u16(flags);
+        if variation.intermediate() {
+            for &(start, _, _) in &variation.tent {
+                headers.f2dot14(start);
+            }
+            for &(_, _, end) in &variation.tent {
+                headers.f2dot14(end);
+            }
+        }
+        data.bytes(&w.data);
+        count += 1;
+    }
+    if count == 0 {
+        return Vec::new();
+    }
+    let mut w = Writer::new();
+    w.u16(count).u16((4 + headers.len()) as u16);
+    w.bytes(&headers.data).bytes(&data.data);
+    w.data
+}
+
+fn gvar(
+    glyphs: &[VarGlyph],
+    variations: &[Variation],
+    default: usize,
+    axis_count: usize,
+) -> Vec<u8> {
+    let mut shared = Writer::new();
+    for variation in variations {
+        for &(_, peak, _) in &variation.tent {
+            shared.f2dot14(peak);
+        }
+    }
+    let mut data = Writer::new();
+    let mut offsets = Vec::new();
+    for glyph in glyphs {
+        offsets.push(data.len() as u32);
+        data.bytes(&glyph_variations(glyph, variations, &glyph.points[default]));
+        data.align(2);
+    }
+    offsets.push(data.len() as u32);
+    let shared_offset = 20 + 4 * offsets.len();
+    let mut w = Writer::new();
+    w.u16(1)
+        .u16(0)
+        .u16(axis_count as u16)
+        .u16(variations.len() as u16)
+        .u32(shared_offset as u32)
+        .u16(glyphs.len() as u16)
+        // Long offsets.
+        .u16(1)
+        .u32((shared_offset + shared.len()) as u32);
+    for offset in offsets {
+        w.u32(offset);
+    }
+    w.bytes(&shared.data).bytes(&data.data);
+    w.data
+}
+
+/// An item variation store with one item variation data, holding the rows
+/// of deltas, one delta for each variation.
+fn item_variation_store(rows: &[Vec<i16>], variations: &[Variation], axis_count: usize) -> Vec<u8> {
+    // Columns for the variations with some delta, those needing words
+    // first.
+    let mut columns: Vec<(bool, usize)> = (0..variations.len())
+        .filter(|&v| rows.iter().any(|d| d[v] != 0))
+        .map(|v| {
+            let words = rows.iter().any(|d| !(-128..=127).contains(&d[v]));
+            (!words, v)
+        })
+        .collect();
+    columns.sort();
+    let word_count = columns.iter().filter(|c| !c.0).count();
+
+    let mut regions = Writer::new();
+    regions.u16(axis_count as u16).u16(columns.len() as u16);
+    for &(_, v) in &columns {
+        for &(start, peak, end) in &variations[v].tent {
+            regions.f2dot14(start).f2dot14(peak).f2dot14(end);
+        }
+    }
+    let mut items = Writer::new();
+    items
+        .u16(rows.len() as u16)
+        .u16(word_count as u16)
+        .u16(columns.len() as u16);
+    for i in 0..columns.len() {
+        items.u16(i as u16);
+    }
+    for row in rows {
+        for (i, &(_, v)) in columns.iter().enumerate() {
+            if i < word_count {
+                items.i16(row[v]);
+            } else {
+                items.u8(row[v] as i8 as u8);
+            }
+        }
+    }
+    let mut store = Writer::new();
+    store
+        .u16(1)
+        .u32(12)
+        .u16(1)
+        .u32((12 + regions.len()) as u32)
+        .bytes(&regions.data)
+        .bytes(&items.data);
+    store.data
+}
+
+/// The `HVAR` table: advance width deltas in one item variation data,
+/// indexed by glyph ID.
+fn hvar(glyphs: &[VarGlyph], variations: &[Variation], axis_count: usize) -> Vec<u8> {
+    let deltas: Vec<Vec<i16>> = glyphs
+        .iter()
+        .map(|g| {
+            let pp2 = g.points[0].len() - 3;
+            variations
+                .iter()
+                .map(|v| glyph_deltas(g, v)[pp2].0 as i16)
+                .collect()
+        })
+        .collect();
+    let mut w = Writer::new();
+    // No mappings: glyph IDs index the items directly.
+    w.u16(1).u16(0).u32(20).u32(0).u32(0).u32(0);
+    w.bytes(&item_variation_store(&deltas, variations, axis_count));
+    w.data
+}
+
+/// The anchors for mark positioning, at the default master with deltas for
+/// the others. Deltas go in a variation store for `GDEF`, one row for each
+/// distinct set of deltas. An anchor a master doesn't have stays put there.
+fn var_anchors(
+    font: &Font,
+    masters: &[&FontMaster],
+    default: usize,
+    glyphs: &[&Glyph],
+    variations: &[Variation],
+    axis_count: usize,
+) -> MarkAnchors {
+    let mut rows: Vec<Vec<i16>> = Vec::new();
+    let mut row_index: HashMap<Vec<i16>, u16> = HashMap::new();
+    let mut row = |deltas: Vec<i16>| {
+        *row_index.entry(deltas.clone()).or_insert_with(|| {
+            rows.push(deltas);
+            rows.len() as u16 - 1
+        })
+    };
+    let anchors = glyphs
+        .iter()
+        .map(|glyph| {
+            let anchors: Vec<Vec<Anchor>> = masters
+                .iter()
+                .map(|m| {
+                    glyph
+                        .get_layer(&m.id)
+                        .map_or(Vec::new(), |l| font.anchors(l))
+                })
+                .collect();
+            let position = |m: usize, name: &str| {
+                let anchor = anchors[m].iter().find(|a| a.name == name);
+                let anchor = anchor.or_else(|| anchors[default].iter().find(|a| a.name == name));
+                anchor.unwrap().position
+            };
+            anchors[default]
+                .iter()
+                .filter(|a| is_mark_anchor(&a.name))
+                .map(|a| {
+                    let delta = |coord: fn(Point) -> f64| -> Vec<i16> {
+                        variations
+                            .iter()
+                            .map(|v| {
+                                let terms = v.terms.iter();
+                                let d: f64 = terms
+                                    .map(|&(m, sign)| sign * coord(position(m, &a.name)))
+                                    .sum();
+                                to_i16(d)
+                            })
+                            .collect()
+                    };
+                    let (dx, dy) = (delta(|p| p.x), delta(|p| p.y));
+                    let deltas = if dx.iter().chain(&dy).all(|&d| d == 0) {
+                        None
+                    } else {
+                        Some((row(dx), row(dy)))
+                    };
+                    let (x, y) = (to_i16(a.position.x), to_i16(a.position.y));
+                    (a.name.clone(), AnchorPoint { x, y, deltas })
+                })
+                .collect()
+        })
+        .collect();
+    let store =
+        Some(item_variation_store(&rows, variations, axis_count)).filter(|_| !rows.is_empty());
+    MarkAnchors { anchors, store }
+}
+
+/// Name IDs for strings in the `name` table, from 256 on, shared by equal
+/// strings.
+#[derive(Default)]
+struct NameIds {
+    names: Vec<(u16, String)>,
+}
+
+impl NameIds {
+    fn id(&mut self, s: &str) -> u16 {
+        if let Some((id, _)) = self.names.iter().find(|(_, name)| name == s) {
+            return *id;
+        }
+        let id = 256 + self.names.len() as u16;
+        self.names.push((id, s.to_string()));
+        id
+    }
+}
+
+/// A named instance, at user coordinates on each axis.
+struct NamedInstance<'a> {
+    instance: &'a Instance,
+    coords: Vec<f64>,
+}
+
+fn fvar(axes: &[VarAxis], named: &[NamedInstance], names: &mut NameIds) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.u16(1)
+        .u16(0)
+        .u16(16)
+        .u16(2)
+        .u16(axes.len() as u16)
+        .u16(20)
+        .u16(named.len() as u16)
+        .u16(4 + 4 * axes.len() as u16);
+    for axis in axes {
+        w.tag(&axis.tag)
+            .fixed(axis.to_user(axis.min()))
+            .fixed(axis.to_user(axis.default))
+            .fixed(axis.to_user(axis.max()))
+            .u16(0)
+            .u16(names.id(&axis.name));
+    }
+    for named in named {
+        w.u16(names.id(&named.instance.name)).u16(0);
+        for &c in &named.coords {
+            w.fixed(c);
+        }
+    }
+    w.data
+}
+
+/// The `avar` table, or `None` if every axis maps user values to design
+/// values linearly.
+fn avar(axes: &[VarAxis]) -> Option<Vec<u8>> {
+    let maps: Vec<Vec<(f64, f64)>> = axes.iter().map(VarAxis::avar_pairs).collect();
+    let f2dot14 = |x: f64| (x * 16384.0).round();
+    let identity = maps
+        .iter()
+        .flatten()
+        .all(|&(from, to)| f2dot14(from) == f2dot14(to));
+    if identity {
+        return None;
+    }
+    let mut w = Writer::new();
+    w.u16(1).u16(0).u16(0).u16(axes.len() as u16);
+    for map in &maps {
+        w.u16(map.len() as u16);
+        for &(from, to) in map {
+            w.f2dot14(from).f2dot14(to);
+        }
+    }
+    Some(w.data)
+}
+
+/// The `STAT` table, with a value for each weight and width class of the
+/// named instances. The default values are elidable, and a Regular weight
+/// links to Bold.
+fn stat(axes: &[VarAxis], named: &[NamedInstance], names: &mut NameIds) -> Vec<u8> {
+    let ordering = |tag: &[u8; 4]| {
+        STAT_ORDER
+            .iter()
+            .position(|t| t.as_bytes() == tag)
+            .unwrap_or(STAT_ORDER.len())
+    };
+    let mut order: Vec<usize> = (0..axes.len()).collect();
+    order.sort_by_key(|&a| ordering(&axes[a].tag));
+
+    let mut tables: Vec<Vec<u8>> = Vec::new();
+    for (axis_index, &a) in order.iter().enumerate() {
+        let axis = &axes[a];
+        let class_name = |i: &Instance| match &axis.tag {
+            b"wght" => {
+                let class = ((i.weight_class as usize + 50) / 100).clamp(1, 9);
+                Some(WEIGHT_NAMES[class - 1])
+            }
+            b"wdth" => Some(WIDTH_NAMES[(i.width_class as usize).clamp(1, 9) - 1]),
+            _ => None,
+        };
+        let mut values: Vec<(f64, &str)> = Vec::new();
+        for named in named {
+            if let Some(name) = class_name(named.instance) {
+                if !values.iter().any(|v| v.1 == name) {
+                    values.push((named.coords[a], name));
+                }
+            }
+        }
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let default = axis.to_user(axis.default);
+        let bold = values.iter().find(|v| v.1 == "Bold").map(|v| v.0);
+        for &(value, name) in &values {
+            let flags = if value == default { 2 } else { 0 };
+            let mut w = Writer::new();
+            match bold {
+                Some(bold) if name == "Regular" && axis.tag == *b"wght" => {
+                    w.u16(3)
+                        .u16(axis_index as u16)
+                        .u16(flags)
+                        .u16(names.id(name))
+                        .fixed(value)
+                        .fixed(bold);
+                }
+                _ => {
+                    w.u16(1)
+                        .u16(axis_index as u16)
+                        .u16(flags)
+                        .u16(names.id(name))
+                        .fixed(value);
+                }
+            }
+            tables.push(w.data);
+        }
+    }
+
+    let axes_offset = 20;
+    let values_offset = axes_offset + 8 * axes.len();
+    let mut w = Writer::new();
+    w.u16(1)
+        .u16(1)
+        .u16(8)
+        .u16(axes.len() as u16)
+        .u32(axes_offset as u32)
+        .u16(tables.len() as u16)
+        .u32(values_offset as u32)
+        // Elided names fall back to the subfamily name.
+        .u16(2);
+    for (ordering, &a) in order.iter().enumerate() {
+        w.tag(&axes[a].tag)
+            .u16(names.id(&axes[a].name))
+            .u16(ordering as u16);
+    }
+    let mut offset = 2 * tables.len();
+    for table in &tables {
+        w.u16(offset as u16);
+        offset += table.len();
+    }
+    for table in &tables {
+        w.bytes(table);
+    }
+    w.data
+}
+
+/// Compile the masters of the font into a TrueType variable font.
+pub fn compile_variable(font: &Font) -> Result<Vec<u8>, String> {
+    let axes = var_axes(font)?;
+    let mut source = font.clone();
+    notdef_first(&mut source);
+    update_automatic_classes(&mut source);
+    let default_master = origin(&source).unwrap();
+    let masters: Vec<&FontMaster> = source.font_master.iter().collect();
+    let default = masters
+        .iter()
+        .position(|m| m.id == default_master.id)
+        .unwrap();
+    let locations: Vec<Vec<f64>> = masters
+        .iter()
+        .map(|m| location(&source, m, &axes))
+        .collect();
+    let cells: usize = axes.iter().map(|a| a.values.len()).product();
+    let mut distinct = locations.clone();
+    distinct.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distinct.dedup();
+    if distinct.len() != locations.len() || cells != locations.len() {
+        return Err("the masters must form a full grid, with one master at each point".to_string());
+    }
+    let variations = variations(&locations, default, &axes);
+
+    let glyphs = glyph_order(&source)?;
+    let index = glyph_index(&glyphs);
+    let var_glyphs: Vec<VarGlyph> = glyphs
+        .iter()
+        .map(|g| var_glyph(&source, &masters, default, g, &index))
+        .collect::<Result<_, _>>()?;
+    let outlines: Vec<GlyphOutline> = var_glyphs.iter().map(|g| g.outline.clone()).collect();
+    let glyf = build_glyf(&outlines);
+
+    let all_instances = instances(font);
+    let named: Vec<NamedInstance> = all_instances
+        .iter()
+        .filter_map(|i| {
+            let design = &i.location;
+            let in_range = axes.iter().all(|a| {
+                let v = design[a.index].1;
+                a.min() <= v && v <= a.max()
+            });
+            Some(NamedInstance {
+                instance: i,
+                coords: axes.iter().map(|a| a.to_user(design[a.index].1)).collect(),
+            })
+            .filter(|_| in_range)
+        })
+        .collect();
+    let default_location = master_location(&source, &default_master.id).unwrap();
+    let regular = Instance {
+        name: "Regular".to_string(),
+        location: default_location.clone(),
+        weight_class: 400,
+        width_class: 5,
+    };
+    let default_instance = all_instances
+        .iter()
+        .find(|i| i.location == default_location)
+        .unwrap_or(&regular);
+
+    let mut names = NameIds::default();
+    for axis in &axes {
+        names.id(&axis.name);
+    }
+    let fvar = fvar(&axes, &named, &mut names);
+    let stat = stat(&axes, &named, &mut names);
+    let family = source
+        .other_stuff
+        .get("familyName")
+        .and_then(Plist::as_str)
+        .unwrap_or("Untitled");
+    let mut extra_names = vec![(25, family.replace(' ', ""))];
+    extra_names.extend(names.names);
+
+    let mut tables = vec![
+        (
+            *b"gvar",
+            gvar(&var_glyphs, &variations, default, axes.len()),
+        ),
+        (*b"HVAR", hvar(&var_glyphs, &variations, axes.len())),
+        (*b"fvar", fvar),
+        (*b"STAT", stat),
+        (*b"glyf", glyf.glyf),
+        (*b"loca", glyf.loca),
+    ];
+    if let Some(avar) = avar(&axes) {
+        tables.push((*b"avar", avar));
+    }
+    let anchors = var_anchors(&source, &masters, default, &glyphs, &variations, axes.len());
+    tables.extend(layout_tables(&source, &glyphs, &anchors)?);
+    let cx = Context::new(
+        &source,
+        default_master,
+        default_instance,
+        glyphs,
+        glyf.bounds,
+    );
+    tables.extend(common_tables(
+        &cx,
+        Outlines::TrueType,
+        Some(glyf.stats),
+        &extra_names,
+    ));
+    Ok(font_file(0x0001_0000, tables))
+}
+
+/// The file name for the variable font, like `Inconsolata[wdth,wght].ttf`,
+/// with the axis tags in alphabetical order.
+pub fn variable_file_name(font: &Font) -> String {
+    let family = font
+        .other_stuff
+        .get("familyName")
+        .and_then(Plist::as_str)
+        .unwrap_or("Untitled");
+    let mut tags: Vec<String> = var_axes(font)
+        .unwrap_or_default()
+        .iter()
+        .map(|a| String::from_utf8_lossy(&a.tag).trim_end().to_string())
+        .collect();
+    tags.sort();
+    format!("{}[{}].ttf", family.replace(' ', ""), tags.join(","))
+}
@@ -0,0 +1,369 @@
+//! Variant families, built from the Inconsolata source.
+//!
+//! A variant is a transformation of the whole font, so its source can be
+//! regenerated whenever Inconsolata changes rather than edited by hand. The
+//! building blocks, renaming glyphs, features and the family, and baking
+//! stylistic alternates into the default glyphs, are useful on their own.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::fea::{
+    parse_features, rename_glyphs_in_code, Block, FeatureFile, LookupBlock, Statement, SubAction,
+    Substitution,
+};
+use crate::font::Font;
+use crate::plist::Plist;
+
+/// A family built from the Inconsolata source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variant {
+    /// The operator ligatures on by default, for text editors.
+    Ligconsolata,
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Variant, String> {
+        match s {
+            "ligconsolata" => Ok(Variant::Ligconsolata),
+            _ => Err(format!("unknown variant {}", s)),
+        }
+    }
+}
+
+/// Custom parameters, of the font or an instance, that contain the family
+/// name.
+const NAMING_PARAMETERS: &[&str] = &[
+    "familyName",
+    "familyNames",
+    "preferredFamilyName",
+    "preferredFamilyNames",
+    "compatibleFullName",
+    "compatibleFullNames",
+    "postscriptFontName",
+    "postscriptFullName",
+    "postscriptFullNames",
+    "uniqueID",
+    "fileName",
+    "Variable Font File Name",
+    "variableFileName",
+    "Name Table Entry",
+];
+
+/// Turn the font into a variant family.
+pub fn make_variant(font: &mut Font, variant: Variant) {
+    match variant {
+        Variant::Ligconsolata => ligconsolata(font),
+    }
+}
+
+/// Ligconsolata exposes the `dlig` ligatures as `liga`. The ligature glyphs
+/// get the `.liga` suffix too, so features that Glyphs generates from the
+/// suffixes come out the same.
+pub fn ligconsolata(font: &mut Font) {
+    let renames: HashMap<String, String> = font
+        .glyphs
+        .iter()
+        .filter_map(|glyph| {
+            let base = glyph.glyphname.strip_suffix(".dlig")?;
+            Some((glyph.glyphname.clone(), format!("{}.liga", base)))
+        })
+        .filter(|(_, new_name)| font.get_glyph(new_name).is_none())
+        .collect();
+    rename_glyphs(font, &renames);
+    rename_feature(font, "dlig", "liga");
+    rename_family(font, "Ligconsolata");
+}
+
+/// Rename glyphs, along with the components, classes, feature code and
+/// kerning that refer to them.
+pub fn rename_glyphs(font: &mut Font, renames: &HashMap<String, String>) {
+    for glyph in &mut font.glyphs {
+        if let Some(new_name) = renames.get(&glyph.glyphname) {
+            glyph.glyphname = new_name.clone();
+        }
+    }
+    rename_components(font, renames);
+    for class in font.classes.iter_mut().flatten() {
+        class.code = rename_glyphs_in_code(&class.code, renames);
+    }
+    let code = font.feature_prefixes.iter_mut().chain(&mut font.features);
+    for feature in code.flatten() {
+        feature.code = rename_glyphs_in_code(&feature.code, renames);
+    }
+    rename_kerning(font, renames);
+}
+
+/// Point components, also those in backgrounds, at renamed glyphs.
+fn rename_components(font: &mut Font, renames: &HashMap<String, String>) {
+    let rename = |name: &mut String| {
+        if let Some(new_name) = renames.get(name.as_str()) {
+            *name = new_name.clone();
+        }
+    };
+    for glyph in &mut font.glyphs {
+        for layer in &mut glyph.layers {
+            for component in layer.components.iter_mut().flatten() {
+                rename(&mut component.name);
+            }
+            let background = layer.other_stuff.get_mut("background");
+            if let Some(Plist::Dictionary(background)) = background {
+                if let Some(Plist::Array(components)) = background.get_mut("components") {
+                    for component in components {
+                        if let Some(Plist::String(name)) = component_name(component) {
+                            rename(name);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rename the glyphs in kerning, which is keyed by master, then left and
+/// right glyph or group.
+fn rename_kerning(font: &mut Font, renames: &HashMap<String, String>) {
+    if let Some(Plist::Dictionary(kerning)) = font.other_stuff.get_mut("kerning") {
+        for master in kerning.values_mut() {
+            if let Plist::Dictionary(pairs) = master {
+                *pairs = rename_keys(std::mem::take(pairs), renames);
+                for right in pairs.values_mut() {
+                    if let Plist::Dictionary(right) = right {
+                        *right = rename_keys(std::mem::take(right), renames);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn component_name(component: &mut Plist) -> Option<&mut Plist> {
+    match component {
+        Plist::Dictionary(dict) => dict.get_mut("name"),
+        _ => None,
+    }
+}
+
+fn rename_keys(
+    dict: HashMap<String, Plist>,
+    renames: &HashMap<String, String>,
+) -> HashMap<String, Plist> {
+    dict.into_iter()
+        .map(|(key, value)| (renames.get(&key).cloned().unwrap_or(key), value))
+        .collect()
+}
+
+/// Move the code of a feature to another tag, and update the `feature`
+/// references to it, as in `aalt`. If the other feature exists, the code is
+/// appended to it, and it's no longer automatic, so Glyphs keeps the code.
+pub fn rename_feature(font: &mut Font, from: &str, to: &str) {
+    let features = match &mut font.features {
+        Some(features) => features,
+        None => return,
+    };
+    if let Some(i) = features.iter().position(|f| f.name == from) {
+        let mut moved = features.remove(i);
+        match features.iter_mut().find(|f| f.name == to) {
+            Some(existing) => {
+                existing.code = format!("{}\n{}", existing.code.trim_end(), moved.code);
+                existing.other_stuff.remove("automatic");
+            }
+            None => {
+                moved.name = to.to_string();
+                features.insert(i, moved);
+            }
+        }
+    }
+    let (old_ref, new_ref) = (format!("feature {};", from), format!("feature {};", to));
+    for feature in features {
+        feature.code = feature.code.replace(&old_ref, &new_ref);
+    }
+}
+
+/// Remove a feature and the `feature` references to it.
+pub fn remove_feature(font: &mut Font, tag: &str) {
+    if let Some(features) = &mut font.features {
+        features.retain(|f| f.name != tag);
+        let reference = format!("feature {};", tag);
+        for feature in features {
+            let lines: Vec<&str> = feature
+                .code
+                .split_inclusive('\n')
+                .filter(|line| line.trim() != reference)
+                .collect();
+            feature.code = lines.concat();
+        }
+    }
+}
+
+/// Make the alternates of single substitution features the defaults. Each
+/// glyph the features substitute swaps its layers and kerning with its
+/// alternate, and components are updated to keep their shapes, so the
+/// default names, with their code points and the other features using them,
+/// get the alternate shapes. The features are removed, as they would now
+/// undo the swap. Returns the swapped pairs of default and alternate.
+pub fn bake_features(font: &mut Font, tags: &[&str]) -> Result<Vec<(String, String)>, String> {
+    let fea = parse_features(font).map_err(|errors| errors.join("\n"))?;
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    let mut swaps = HashMap::new();
+    for &tag in tags {
+        let block = fea
+            .blocks
+            .iter()
+            .find_map(|block| match block {
+                Block::Feature(f) if f.tag == tag => Some(f),
+                _ => None,
+            })
+            .ok_or_else(|| format!("no feature {}", tag))?;
+        let mut substitutions = Vec::new();
+        collect_substitutions(&fea, &block.statements, &mut substitutions);
+        for sub in substitutions {
+            let output = match &sub.action {
+                SubAction::Single(output) if !sub.contextual && sub.input.len() == 1 => output,
+                _ => {
+                    return Err(format!(
+                        "feature {}: only single substitutions can be baked",
+                        tag
+                    ))
+                }
+            };
+            let input = &sub.input[0];
+            for (i, default) in input.iter().enumerate() {
+                let alternate = &output[if output.len() == 1 { 0 } else { i }];
+                if swaps.get(default) == Some(alternate) {
+                    continue;
+                }
+                for name in [default, alternate] {
+                    if swaps.contains_key(name) {
+                        return Err(format!("{} is substituted more than once", name));
+                    }
+                }
+                swaps.insert(default.clone(), alternate.clone());
+                swaps.insert(alternate.clone(), default.clone());
+                pairs.push((default.clone(), alternate.clone()));
+            }
+        }
+    }
+    let index: HashMap<String, usize> = font
+        .glyphs
+        .iter()
+        .enumerate()
+        .map(|(i, glyph)| (glyph.glyphname.clone(), i))
+        .collect();
+    for (default, alternate) in &pairs {
+        let (i, j) = (index[default], index[alternate]);
+        let layers = std::mem::take(&mut font.glyphs[i].layers);
+        font.glyphs[i].layers = std::mem::replace(&mut font.glyphs[j].layers, layers);
+        for key in ["leftKerningGroup", "rightKerningGroup"] {
+            let group = font.glyphs[i].other_stuff.remove(key);
+            if let Some(other) = font.glyphs[j].other_stuff.remove(key) {
+                font.glyphs[i].other_stuff.insert(key.to_string(), other);
+            }
+            if let Some(group) = group {
+                font.glyphs[j].other_stuff.insert(key.to_string(), group);
+            }
+        }
+    }
+    rename_components(font, &swaps);
+    rename_kerning(font, &swaps);
+    for &tag in tags {
+        remove_feature(font, tag);
+    }
+    Ok(pairs)
+}
+
+/// The substitutions of a feature, including those of the lookups it uses.
+fn collect_substitutions<'a>(
+    fea: &'a FeatureFile,
+    statements: &'a [Statement],
+    result: &mut Vec<&'a Substitution>,
+) {
+    for statement in statements {
+        match statement {
+            Statement::Substitute(sub) => result.push(sub),
+            Statement::Lookup(lookup) => collect_substitutions(fea, &lookup.statements, result),
+            Statement::LookupRef(name) => {
+                if let Some(lookup) = find_lookup(&fea.blocks, name) {
+                    collect_substitutions(fea, &lookup.statements, result);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A named lookup, at the top level or in a feature.
+fn find_lookup<'a>(blocks: &'a [Block], name: &str) -> Option<&'a LookupBlock> {
+    blocks.iter().find_map(|block| match block {
+        Block::Lookup(lookup) if lookup.name == name => Some(lookup),
+        Block::Lookup(_) => None,
+        Block::Feature(feature) => feature.statements.iter().find_map(|s| match s {
+            Statement::Lookup(lookup) if lookup.name == name => Some(lookup),
+            _ => None,
+        }),
+    })
+}
+
+/// Rename the family, in the font info and in the naming parameters of the
+/// font and its instances.
+pub fn rename_family(font: &mut Font, name: &str) {
+    let old = font
+        .other_stuff
+        .insert("familyName".to_string(), name.to_string().into())
+        .and_then(|old| old.as_str().map(str::to_string));
+    let old = match old {
+        Some(old) if old != name => old,
+        _ => return,
+    };
+    rename_in_parameters(&mut font.other_stuff, &old, name);
+    if let Some(Plist::Array(instances)) = font.other_stuff.get_mut("instances") {
+        for instance in instances {
+            if let Plist::Dictionary(instance) = instance {
+                rename_in_parameters(instance, &old, name);
+            }
+        }
+    }
+}
+
+fn rename_in_parameters(stuff: &mut HashMap<String, Plist>, old: &str, new: &str) {
+    if let Some(Plist::Array(params)) = stuff.get_mut("customParameters") {
+        for param in params {
+            if let Plist::Dictionary(param) = param {
+                let naming = param
+                    .get("name")
+                    .and_then(Plist::as_str)
+                    .is_some_and(|name| NAMING_PARAMETERS.contains(&name));
+                if let Some(value) = param.get_mut("value").filter(|_| naming) {
+                    replace_strings(value, old, new);
+                }
+            }
+        }
+    }
+}
+
+/// Replace the family name in strings, also in its PostScript form without
+/// spaces.
+fn replace_strings(plist: &mut Plist, old: &str, new: &str) {
+    match plist {
+        Plist::String(s) => {
+            let mut replaced = s.replace(old, new);
+            if old.contains(' ') {
+                replaced = replaced.replace(&old.replace(' ', ""), &new.replace(' ', ""));
+            }
+            *s = replaced;
+        }
+        Plist::Array(items) => {
+            for item in items {
+                replace_strings(item, old, new);
+            }
+        }
+        Plist::Dictionary(dict) => {
+            for value in dict.values_mut() {
+                replace_strings(value, old, new);
+            }
+        }
+        _ => (),
+    }
+}
@@ -0,0 +1,368 @@
+//! A minimal XML element tree, enough for the property lists, glyph files
+//! and designspace documents of UFO sources.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::plist::Plist;
+
+/// An XML element with its attributes, child elements and text.
+///
+/// Mixed content isn't supported: an element has either text or children.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Element {
+        Element {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// An element containing only text.
+    pub fn text(name: &str, text: impl Into<String>) -> Element {
+        Element {
+            name: name.to_string(),
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn attr(mut self, name: &str, value: impl ToString) -> Element {
+        self.attrs.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn child(mut self, child: Element) -> Element {
+        self.children.push(child);
+        self
+    }
+
+    pub fn push(&mut self, child: Element) {
+        self.children.push(child);
+    }
+
+    /// Parse a document, skipping the declaration, comments and doctype.
+    pub fn parse(s: &str) -> Result<Element, String> {
+        let mut parser = Parser { s, ix: 0 };
+        parser.skip_misc()?;
+        let root = parser.element()?;
+        parser.skip_misc()?;
+        if parser.ix < s.len() {
+            return Err(parser.error("content after the root element"));
+        }
+        Ok(root)
+    }
+
+    /// The value of an attribute.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The child elements with the given name.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    /// The first child element with the given name.
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// The document, with an XML declaration, indented by two spaces.
+    pub fn to_document(&self) -> String {
+        let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        self.write(&mut s, 0);
+        s
+    }
+
+    fn write(&self, s: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        write!(s, "{}<{}", indent, self.name).unwrap();
+        for (name, value) in &self.attrs {
+            write!(s, " {}=\"{}\"", name, escape(value)).unwrap();
+        }
+        if !self.children.is_empty() {
+            s.push_str(">\n");
+            for child in &self.children {
+                child.write(s, depth + 1);
+            }
+            writeln!(s, "{}</{}>", indent, self.name).unwrap();
+        } else if !self.text.is_empty() {
+            writeln!(s, ">{}</{}>", escape(&self.text), self.name).unwrap();
+        } else {
+            s.push_str("/>\n");
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    ix: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.ix..]
+    }
+
+    fn error(&self, msg: &str) -> String {
+        let line = self.s[..self.ix].matches('\n').count() + 1;
+        format!("XML error at line {}: {}", line, msg)
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.ix += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skip past the next occurrence of `end`.
+    fn skip_past(&mut self, end: &str) -> Result<(), String> {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.ix += i + end.len();
+                Ok(())
+            }
+            None => Err(self.error(&format!("expected {}", end))),
+        }
+    }
+
+    /// Skip whitespace, processing instructions, comments and doctypes.
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '=')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.ix += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected an element"));
+        }
+        self.ix += 1;
+        let mut element = Element::new(self.name()?);
+        loop {
+            self.skip_ws();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.ix += 2;
+                return Ok(element);
+            } else if rest.starts_with('>') {
+                self.ix += 1;
+                break;
+            }
+            let name = self.name()?;
+            self.skip_ws();
+            if !self.rest().starts_with('=') {
+                return Err(self.error("expected ="));
+            }
+            self.ix += 1;
+            self.skip_ws();
+            let quote = match self.rest().chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => return Err(self.error("expected a quoted value")),
+            };
+            self.ix += 1;
+            let len = self
+                .rest()
+                .find(quote)
+                .ok_or_else(|| self.error("unclosed attribute value"))?;
+            let value = unescape(&self.rest()[..len]).map_err(|e| self.error(&e))?;
+            self.ix += len + 1;
+            element.attrs.push((name.to_string(), value));
+        }
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.ix += 2;
+                let name = self.name()?;
+                if name != element.name {
+                    return Err(self.error(&format!("mismatched </{}>", name)));
+                }
+                self.skip_ws();
+                self.skip_past(">")?;
+                // Drop the indentation between child elements.
+                if !element.children.is_empty() && element.text.trim().is_empty() {
+                    element.text.clear();
+                }
+                return Ok(element);
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let len = cdata
+                    .find("]]>")
+                    .ok_or_else(|| self.error("unclosed CDATA"))?;
+                element.text.push_str(&cdata[..len]);
+                self.ix += "<![CDATA[".len() + len + 3;
+            } else if rest.starts_with('<') {
+                let child = self.element()?;
+                element.children.push(child);
+            } else if rest.is_empty() {
+                return Err(self.error(&format!("unclosed <{}>", element.name)));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                let text = unescape(&rest[..len]).map_err(|e| self.error(&e))?;
+                element.text.push_str(&text);
+                self.ix += len;
+            }
+        }
+    }
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        result.push_str(&rest[..i]);
+        let end = rest[i..]
+            .find(';')
+            .ok_or_else(|| "unterminated entity".to_string())?;
+        let entity = &rest[i + 1..i + end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(std::char::from_u32)
+            }
+        };
+        result.push(c.ok_or_else(|| format!("unknown entity &{};", entity))?);
+        rest = &rest[i + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// A number as an XML plist `integer` or `real`.
+pub fn number(x: f64) -> Element {
+    if x.fract() == 0.0 && x.abs() < 1e15 {
+        Element::text("integer", (x as i64).to_string())
+    } else {
+        Element::text("real", x.to_string())
+    }
+}
+
+pub fn boolean(b: bool) -> Element {
+    Element::new(if b { "true" } else { "false" })
+}
+
+/// A plist `dict` with the entries in the given order.
+pub fn dict(entries: Vec<(String, Element)>) -> Element {
+    let mut result = Element::new("dict");
+    for (key, value) in entries {
+        result.push(Element::text("key", key));
+        result.push(value);
+    }
+    result
+}
+
+/// A complete XML property list document.
+pub fn plist_document(value: Element) -> String {
+    let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    s.push_str(
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+    );
+    Element::new("plist")
+        .attr("version", "1.0")
+        .child(value)
+        .write(&mut s, 0);
+    s
+}
+
+/// Parse a plist value element into a plist. Booleans become 1 and 0, as
+/// in Glyphs files, and dates and data are kept as strings.
+pub fn parse_plist(element: &Element) -> Result<Plist, String> {
+    match element.name.as_str() {
+        "plist" => match element.children.first() {
+            Some(value) => parse_plist(value),
+            None => Err("empty plist".to_string()),
+        },
+        "dict" => {
+            let mut dict = HashMap::new();
+            for pair in element.children.chunks(2) {
+                if pair.len() != 2 || pair[0].name != "key" {
+                    return Err("malformed plist dict".to_string());
+                }
+                dict.insert(pair[0].text.clone(), parse_plist(&pair[1])?);
+            }
+            Ok(Plist::Dictionary(dict))
+        }
+        "array" => Ok(Plist::Array(
+            element
+                .children
+                .iter()
+                .map(parse_plist)
+                .collect::<Result<_, _>>()?,
+        )),
+        "string" | "date" | "data" => Ok(Plist::String(element.text.clone())),
+        "integer" => element
+            .text
+            .trim()
+            .parse()
+            .map(Plist::Integer)
+            .map_err(|_| format!("bad integer {:?}", element.text)),
+        "real" => element
+            .text
+            .trim()
+            .parse()
+            .map(Plist::Float)
+            .map_err(|_| format!("bad real {:?}", element.text)),
+        "true" => Ok(Plist::Integer(1)),
+        "false" => Ok(Plist::Integer(0)),
+        name => Err(format!("unknown plist element {}", name)),
+    }
+}